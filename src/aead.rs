@@ -0,0 +1,274 @@
+//! Authenticated encryption with associated data.
+//!
+//! [`seal`](fn.seal.html) encrypts a message and binds it to associated
+//! data, such as headers or routing information, that is transmitted in
+//! the clear. [`open`](fn.open.html) only releases the plaintext if
+//! neither the ciphertext nor the associated data was modified.
+//!
+//! The construction is EAX: the plaintext is encrypted in counter mode
+//! and the nonce, the associated data and the ciphertext are each
+//! authenticated with a domain-separated CMAC under the same key. The
+//! output is the ciphertext followed by a 64-bit tag, or by a truncated
+//! tag with [`seal_with_tag_len`](fn.seal_with_tag_len.html). Nonces may
+//! have any length, but must never repeat for the same key.
+//!
+//! [`Eax`](struct.Eax.html) holds the key schedule and the tag length,
+//! for encrypting many messages with the same key.
+//!
+//! # Examples
+//!
+//! ```
+//! use present::Key128Bit;
+//! use present::aead;
+//!
+//! let key = Key128Bit::new([0x42; 16]);
+//! let sealed = aead::seal(&key, b"nonce 1", b"v1;to=gw", b"payload");
+//! assert_eq!(aead::open(&key, b"nonce 1", b"v1;to=gw", &sealed).unwrap(), b"payload");
+//! assert!(aead::open(&key, b"nonce 1", b"v1;to=xx", &sealed).is_err());
+//! ```
+
+use block::encrypt_block8;
+use errors::AeadError;
+use keys::{wipe, Key, RoundKeys};
+use mac::Cmac;
+use policy;
+use tag::{tags_equal, TagLen};
+
+/// Length of the authentication tag of [`seal`](fn.seal.html) and
+/// [`open`](fn.open.html) in bytes.
+pub const TAG_LEN: usize = 8;
+
+/// Encrypts and authenticates a message.
+///
+/// Returns the ciphertext with the tag appended.
+pub fn seal<K: Key>(key: &K, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    seal_with_tag_len(key, nonce, aad, plaintext, TagLen::FULL)
+}
+
+/// Encrypts and authenticates a message with a tag of the given length.
+///
+/// Works like [`seal`](fn.seal.html), but only appends the first
+/// `tag_len` bytes of the tag. As for CCM and OCB, tags shorter than
+/// `TagLen::FULL` are reported to the security policy.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Key128Bit, TagLen};
+/// use present::aead;
+///
+/// let key = Key128Bit::new([0x42; 16]);
+/// let tag_len = TagLen::new(4).unwrap();
+/// let sealed = aead::seal_with_tag_len(&key, b"nonce 1", b"", b"payload", tag_len);
+/// assert_eq!(sealed.len(), 7 + 4);
+/// assert_eq!(aead::open_with_tag_len(&key, b"nonce 1", b"", &sealed, tag_len).unwrap(), b"payload");
+/// ```
+pub fn seal_with_tag_len<K: Key>(key: &K, nonce: &[u8], aad: &[u8], plaintext: &[u8], tag_len: TagLen) -> Vec<u8> {
+    policy::check_tag_len(tag_len);
+    let mut round_keys = key.generate_round_keys();
+    let output = seal_with_round_keys(&round_keys, nonce, aad, plaintext, tag_len);
+    wipe(&mut round_keys);
+    output
+}
+
+/// Verifies and decrypts a message produced by [`seal`](fn.seal.html).
+///
+/// # Errors
+///
+/// Returns `AeadError::CiphertextTooShort` if the input is shorter than
+/// the tag, and `AeadError::AuthenticationFailed` if the tag does not
+/// match.
+pub fn open<K: Key>(key: &K, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AeadError> {
+    open_with_tag_len(key, nonce, aad, ciphertext, TagLen::FULL)
+}
+
+/// Verifies and decrypts a message produced by
+/// [`seal_with_tag_len`](fn.seal_with_tag_len.html) with the same tag
+/// length.
+///
+/// # Errors
+///
+/// Returns `AeadError::CiphertextTooShort` if the input is shorter than
+/// the tag, and `AeadError::AuthenticationFailed` if the tag does not
+/// match.
+pub fn open_with_tag_len<K: Key>(key: &K, nonce: &[u8], aad: &[u8], ciphertext: &[u8], tag_len: TagLen) -> Result<Vec<u8>, AeadError> {
+    policy::check_tag_len(tag_len);
+    let mut round_keys = key.generate_round_keys();
+    let plaintext = open_with_round_keys(&round_keys, nonce, aad, ciphertext, tag_len);
+    wipe(&mut round_keys);
+    plaintext
+}
+
+/// Encrypts and authenticates a message, without reporting the tag
+/// length to the security policy.
+fn seal_with_round_keys(round_keys: &RoundKeys, nonce: &[u8], aad: &[u8], plaintext: &[u8], tag_len: TagLen) -> Vec<u8> {
+    let mac = Cmac::from_round_keys(*round_keys);
+
+    let n = omac(&mac, 0, nonce);
+    let mut output = ctr(round_keys, n, plaintext);
+    let tag = n ^ omac(&mac, 1, aad) ^ omac(&mac, 2, &output);
+    output.extend_from_slice(&tag.to_be_bytes()[..tag_len.bytes()]);
+    output
+}
+
+/// Verifies and decrypts a message, without reporting the tag length
+/// to the security policy.
+fn open_with_round_keys(round_keys: &RoundKeys, nonce: &[u8], aad: &[u8], ciphertext: &[u8], tag_len: TagLen) -> Result<Vec<u8>, AeadError> {
+    if ciphertext.len() < tag_len.bytes() {
+        return Err(AeadError::CiphertextTooShort(ciphertext.len()));
+    }
+    let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - tag_len.bytes());
+
+    let mac = Cmac::from_round_keys(*round_keys);
+
+    let n = omac(&mac, 0, nonce);
+    let expected = n ^ omac(&mac, 1, aad) ^ omac(&mac, 2, ciphertext);
+    if !tags_equal(&expected.to_be_bytes()[..tag.len()], tag) {
+        return Err(AeadError::AuthenticationFailed);
+    }
+
+    Ok(ctr(round_keys, n, ciphertext))
+}
+
+/// CMAC of the data, prefixed with a block holding the domain `tweak`.
+fn omac(mac: &Cmac, tweak: u64, data: &[u8]) -> u64 {
+    let mut mac = mac.clone();
+    mac.update(&tweak.to_be_bytes());
+    mac.update(data);
+    u64::from_be_bytes(mac.finalize())
+}
+
+fn ctr(round_keys: &RoundKeys, counter: u64, input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() + TAG_LEN);
+    for (i, chunk) in input.chunks(8).enumerate() {
+        let mut keystream = counter.wrapping_add(i as u64).to_be_bytes();
+        encrypt_block8(&mut keystream, round_keys);
+        output.extend(chunk.iter().zip(keystream.iter()).map(|(x, k)| x ^ k));
+    }
+    output
+}
+
+/// EAX with a fixed key and tag length.
+///
+/// Produces the same output as [`seal_with_tag_len`](fn.seal_with_tag_len.html),
+/// but expands the key only once.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Key128Bit, TagLen};
+/// use present::aead::Eax;
+///
+/// let eax = Eax::new(&Key128Bit::new([0x42; 16]), TagLen::FULL);
+/// let sealed = eax.encrypt(b"nonce 1", b"v1;to=gw", b"payload");
+/// assert_eq!(eax.decrypt(b"nonce 1", b"v1;to=gw", &sealed).unwrap(), b"payload");
+/// ```
+pub struct Eax {
+    round_keys: RoundKeys,
+    tag_len: TagLen,
+}
+
+impl Eax {
+    /// Constructs a new EAX instance. Tags shorter than `TagLen::FULL`
+    /// are reported to the security policy here, once for all messages.
+    pub fn new<K: Key>(key: &K, tag_len: TagLen) -> Self {
+        policy::check_tag_len(tag_len);
+        Eax { round_keys: key.generate_round_keys(), tag_len }
+    }
+
+    /// Encrypts and authenticates a message. See
+    /// [`seal_with_tag_len`](fn.seal_with_tag_len.html).
+    pub fn encrypt(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        seal_with_round_keys(&self.round_keys, nonce, aad, plaintext, self.tag_len)
+    }
+
+    /// Verifies and decrypts a message produced by
+    /// [`encrypt()`](#method.encrypt). See
+    /// [`open_with_tag_len`](fn.open_with_tag_len.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns `AeadError::CiphertextTooShort` if the input is shorter than
+    /// the tag, and `AeadError::AuthenticationFailed` if the tag does not
+    /// match.
+    pub fn decrypt(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AeadError> {
+        open_with_round_keys(&self.round_keys, nonce, aad, ciphertext, self.tag_len)
+    }
+
+    /// Returns the configured tag length.
+    pub fn tag_len(&self) -> TagLen {
+        self.tag_len
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Eax {
+    fn drop(&mut self) {
+        wipe(&mut self.round_keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key80Bit;
+    use mac::cmac;
+
+    #[test]
+    fn test_tag_matches_definition() {
+        let key = Key80Bit::new([0x6B; 10]);
+        let sealed = seal(&key, b"nonce", b"header", b"");
+        let omac = |tweak: u8, data: &[u8]| {
+            let mut input = vec![0, 0, 0, 0, 0, 0, 0, tweak];
+            input.extend_from_slice(data);
+            u64::from_be_bytes(cmac(&key, &input))
+        };
+        let tag = omac(0, b"nonce") ^ omac(1, b"header") ^ omac(2, b"");
+        assert_eq!(sealed, tag.to_be_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_roundtrip_and_tampering() {
+        let key = Key80Bit::new([0x6B; 10]);
+        for len in 0..24 {
+            let plaintext: Vec<u8> = (0..len as u8).collect();
+            let mut sealed = seal(&key, b"n", b"header", &plaintext);
+            assert_eq!(sealed.len(), len + TAG_LEN);
+            assert_eq!(open(&key, b"n", b"header", &sealed).unwrap(), plaintext);
+
+            assert_eq!(open(&key, b"m", b"header", &sealed), Err(AeadError::AuthenticationFailed));
+            assert_eq!(open(&key, b"n", b"Header", &sealed), Err(AeadError::AuthenticationFailed));
+            assert_eq!(open(&Key80Bit::new([0x6C; 10]), b"n", b"header", &sealed), Err(AeadError::AuthenticationFailed));
+            sealed[0] ^= 0x10;
+            assert_eq!(open(&key, b"n", b"header", &sealed), Err(AeadError::AuthenticationFailed));
+        }
+        assert_eq!(open(&key, b"n", b"", &[0; 7]), Err(AeadError::CiphertextTooShort(7)));
+    }
+
+    #[test]
+    fn test_eax_matches_functions() {
+        let key = Key80Bit::new([0x6B; 10]);
+        let tag_len = TagLen::new(6).unwrap();
+        let eax = Eax::new(&key, tag_len);
+        let sealed = eax.encrypt(b"n", b"header", b"payload");
+        assert_eq!(sealed, seal_with_tag_len(&key, b"n", b"header", b"payload", tag_len));
+        assert_eq!(eax.decrypt(b"n", b"header", &sealed).unwrap(), b"payload");
+        assert_eq!(eax.decrypt(b"n", b"Header", &sealed), Err(AeadError::AuthenticationFailed));
+    }
+
+    #[test]
+    fn test_truncated_tags() {
+        let key = Key80Bit::new([0x6B; 10]);
+        let full = seal(&key, b"n", b"header", b"payload");
+        for bytes in 4..=8 {
+            let tag_len = TagLen::new(bytes).unwrap();
+            let mut sealed = seal_with_tag_len(&key, b"n", b"header", b"payload", tag_len);
+            assert_eq!(&sealed[..], &full[..7 + bytes]);
+            assert_eq!(open_with_tag_len(&key, b"n", b"header", &sealed, tag_len).unwrap(), b"payload");
+
+            sealed[7] ^= 0x01;
+            assert_eq!(open_with_tag_len(&key, b"n", b"header", &sealed, tag_len), Err(AeadError::AuthenticationFailed));
+        }
+        assert_eq!(open_with_tag_len(&key, b"n", b"", &[0; 3], TagLen::MIN), Err(AeadError::CiphertextTooShort(3)));
+    }
+}
@@ -0,0 +1,168 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::RngCore;
+
+use errors::{KdfError, RngError};
+use keys::{Key80Bit, Key128Bit};
+
+/// Parameters for deriving keys from passphrases with Argon2id.
+///
+/// Argon2id is memory-hard, so guessing passphrases on GPUs or ASICs is
+/// far more expensive than with [`pbkdf2`](fn.pbkdf2.html). The
+/// parameters, including the salt, are needed again for decryption, so
+/// they can be encoded into 28 bytes with
+/// [`to_bytes()`](#method.to_bytes) and stored in the header of the
+/// ciphertext.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate present;
+/// # extern crate rand;
+/// # fn main() {
+/// use present::{Argon2Params, Block};
+/// use rand::rngs::OsRng;
+///
+/// // Low costs to keep the example fast
+/// let params = Argon2Params { memory_kib: 256, iterations: 1, ..Argon2Params::new(&mut OsRng).unwrap() };
+/// let key = params.derive_key128(b"correct horse battery staple").unwrap();
+/// let header = params.to_bytes();
+///
+/// // Decryption re-derives the key from the header
+/// let params = Argon2Params::from_bytes(&header).unwrap();
+/// assert!(params.derive_key128(b"correct horse battery staple").unwrap() == key);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Params {
+    /// Memory cost in KiB.
+    pub memory_kib: u32,
+    /// Number of passes over the memory.
+    pub iterations: u32,
+    /// Number of lanes that can be computed in parallel.
+    pub parallelism: u32,
+    /// Random salt, unique for every derived key.
+    pub salt: [u8; 16],
+}
+
+impl Argon2Params {
+    /// Length of the encoded parameters in bytes.
+    pub const ENCODED_LEN: usize = 28;
+
+    /// Creates parameters with a random salt and the default costs of
+    /// the `argon2` crate (19 MiB of memory, 2 iterations, 1 lane), as
+    /// recommended by OWASP.
+    pub fn new<R: RngCore + ?Sized>(rng: &mut R) -> Result<Self, RngError> {
+        let mut salt = [0u8; 16];
+        rng.try_fill_bytes(&mut salt)?;
+        Ok(Argon2Params {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            salt,
+        })
+    }
+
+    /// Encodes the parameters: the three costs as big-endian 32-bit
+    /// integers, followed by the salt.
+    pub fn to_bytes(&self) -> [u8; 28] {
+        let mut bytes = [0u8; 28];
+        bytes[..4].copy_from_slice(&self.memory_kib.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.iterations.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.parallelism.to_be_bytes());
+        bytes[12..].copy_from_slice(&self.salt);
+        bytes
+    }
+
+    /// Decodes parameters encoded with [`to_bytes()`](#method.to_bytes).
+    ///
+    /// The costs are checked against the limits of Argon2, but not
+    /// against the resources of the machine. If the header comes from an
+    /// untrusted source, check `memory_kib` and `iterations` before
+    /// deriving a key, or an attacker can make decryption arbitrarily
+    /// expensive.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KdfError::InvalidLength` if `bytes` is not 28 bytes
+    /// long and `KdfError::InvalidParams` if the costs are out of range.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KdfError> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return Err(KdfError::InvalidLength(bytes.len()));
+        }
+
+        let mut salt = [0u8; 16];
+        salt.copy_from_slice(&bytes[12..]);
+        let params = Argon2Params {
+            memory_kib: read_u32(&bytes[..4]),
+            iterations: read_u32(&bytes[4..8]),
+            parallelism: read_u32(&bytes[8..12]),
+            salt,
+        };
+        params.argon2(10)?;
+        Ok(params)
+    }
+
+    /// Derives an 80-bit key from the passphrase.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KdfError::InvalidParams` if the costs are out of range.
+    pub fn derive_key80(&self, passphrase: &[u8]) -> Result<Key80Bit, KdfError> {
+        let mut value = [0u8; 10];
+        self.argon2(value.len())?.hash_password_into(passphrase, &self.salt, &mut value)?;
+        Ok(Key80Bit::new(value))
+    }
+
+    /// Derives a 128-bit key from the passphrase.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KdfError::InvalidParams` if the costs are out of range.
+    pub fn derive_key128(&self, passphrase: &[u8]) -> Result<Key128Bit, KdfError> {
+        let mut value = [0u8; 16];
+        self.argon2(value.len())?.hash_password_into(passphrase, &self.salt, &mut value)?;
+        Ok(Key128Bit::new(value))
+    }
+
+    fn argon2(&self, output_len: usize) -> Result<Argon2<'static>, KdfError> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(output_len))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut value = [0u8; 4];
+    value.copy_from_slice(bytes);
+    u32::from_be_bytes(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> Argon2Params {
+        Argon2Params { memory_kib: 64, iterations: 1, parallelism: 1, salt: [0x5A; 16] }
+    }
+
+    #[test]
+    fn test_params_roundtrip() {
+        let params = Argon2Params { parallelism: 2, ..params() };
+        let bytes = params.to_bytes();
+        assert_eq!(&bytes[..12], &[0, 0, 0, 64, 0, 0, 0, 1, 0, 0, 0, 2]);
+        assert_eq!(Argon2Params::from_bytes(&bytes), Ok(params));
+
+        assert_eq!(Argon2Params::from_bytes(&bytes[..27]), Err(KdfError::InvalidLength(27)));
+        let mut invalid = bytes;
+        invalid[7] = 0;
+        assert!(matches!(Argon2Params::from_bytes(&invalid), Err(KdfError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn test_derived_keys_depend_on_passphrase_and_salt() {
+        let key = params().derive_key128(b"passphrase").unwrap();
+        assert!(key == params().derive_key128(b"passphrase").unwrap());
+        assert!(key != params().derive_key128(b"passphrasf").unwrap());
+        assert!(key != Argon2Params { salt: [0xA5; 16], ..params() }.derive_key128(b"passphrase").unwrap());
+        assert!(params().derive_key80(b"passphrase").is_ok());
+    }
+}
@@ -0,0 +1,232 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use block::Block;
+use keys::Key;
+use modes::OpMode;
+use stream::{invalid_data, StreamState, BUFFER_SIZE};
+
+/// Asynchronous writer that encrypts all data written to it.
+///
+/// This is the tokio counterpart of
+/// [`EncryptWriter`](struct.EncryptWriter.html) and produces the same
+/// ciphertext. The final block with the padding is written on
+/// `shutdown()`, so it must be called after the last write. Memory use
+/// is fixed, independent of the amount of data.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate present;
+/// # extern crate tokio;
+/// use present::{AsyncDecryptReader, AsyncEncryptWriter, Block, Key80Bit, OpMode};
+/// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+///
+/// # fn main() {
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let key = Key80Bit::new([0x42; 10]);
+/// let iv = Block::new(0x0123456789ABCDEF);
+///
+/// let mut writer = AsyncEncryptWriter::new(Vec::new(), &key, &OpMode::CBC, &iv);
+/// runtime.block_on(writer.write_all(b"sent over the network")).unwrap();
+/// runtime.block_on(writer.shutdown()).unwrap();
+/// let ciphertext = writer.into_inner();
+///
+/// let mut reader = AsyncDecryptReader::new(&ciphertext[..], &key, &OpMode::CBC, &iv);
+/// let mut plaintext = Vec::new();
+/// runtime.block_on(reader.read_to_end(&mut plaintext)).unwrap();
+/// assert_eq!(plaintext, b"sent over the network");
+/// # }
+/// ```
+pub struct AsyncEncryptWriter<W> {
+    inner: W,
+    state: StreamState,
+    /// Range of encrypted bytes in the buffer that were not written yet.
+    start: usize,
+    end: usize,
+    finished: bool,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncryptWriter<W> {
+    /// Constructs a new writer that encrypts into the given writer.
+    pub fn new<K: Key>(inner: W, key: &K, mode: &OpMode, init_vec: &Block) -> Self {
+        AsyncEncryptWriter { inner, state: StreamState::for_encryption(key, mode, init_vec), start: 0, end: 0, finished: false }
+    }
+
+    /// Returns the inner writer. Any data that has not been encrypted
+    /// and written yet is lost, so call `shutdown()` first.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Writes the encrypted bytes in the buffer to the inner writer.
+    fn poll_write_buffer(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        while self.start < self.end {
+            let written = match Pin::new(&mut self.inner).poll_write(cx, &self.state.buffer[self.start..self.end]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            self.start += written;
+        }
+
+        self.state.consume(self.end);
+        self.start = 0;
+        self.end = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncEncryptWriter<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(Err(io::Error::other("Writer was already shut down")));
+        }
+        match this.poll_write_buffer(cx) {
+            Poll::Ready(Ok(())) => {},
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+
+        let len = buf.len().min(BUFFER_SIZE - 8);
+        this.end = this.state.encrypt_update(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_write_buffer(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            match this.poll_write_buffer(cx) {
+                Poll::Ready(Ok(())) => {},
+                other => return other,
+            }
+            this.end = this.state.encrypt_final();
+            this.finished = true;
+        }
+
+        match this.poll_write_buffer(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_shutdown(cx),
+            other => other,
+        }
+    }
+}
+
+/// Asynchronous reader that decrypts all data read through it.
+///
+/// This is the tokio counterpart of
+/// [`DecryptReader`](struct.DecryptReader.html), with the same
+/// handling of padding and errors.
+pub struct AsyncDecryptReader<R> {
+    inner: R,
+    state: StreamState,
+    /// Range of decrypted bytes in the buffer that were not read yet.
+    start: usize,
+    end: usize,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecryptReader<R> {
+    /// Constructs a new reader that decrypts the data of the given reader.
+    pub fn new<K: Key>(inner: R, key: &K, mode: &OpMode, init_vec: &Block) -> Self {
+        AsyncDecryptReader { inner, state: StreamState::new(key, mode, init_vec), start: 0, end: 0, eof: false }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads and decrypts more data, until at least one byte of
+    /// plaintext is available or the end of the input is reached.
+    fn poll_fill(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.state.consume(self.end);
+        self.start = 0;
+        self.end = 0;
+
+        while self.end == 0 && !self.eof {
+            let pending = self.state.pending;
+            let mut read_buf = ReadBuf::new(&mut self.state.buffer[pending..]);
+            match Pin::new(&mut self.inner).poll_read(cx, &mut read_buf) {
+                Poll::Ready(Ok(())) => {},
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+
+            let read = read_buf.filled().len();
+            if read == 0 {
+                self.eof = true;
+                self.end = self.state.decrypt_final().map_err(invalid_data)?;
+            } else {
+                self.end = self.state.decrypt_update(read);
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecryptReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.start == this.end {
+            match this.poll_fill(cx) {
+                Poll::Ready(Ok(())) => {},
+                other => return other,
+            }
+        }
+
+        let len = buf.remaining().min(this.end - this.start);
+        buf.put_slice(&this.state.buffer[this.start..(this.start + len)]);
+        this.start += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modes::all_modes;
+    use keys::Key80Bit;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::runtime::Builder;
+
+    #[test]
+    fn test_matches_allocating_functions() {
+        let key = Key80Bit::new([0x42; 10]);
+        let iv = Block::new(0x0123456789ABCDEF);
+        let plaintext: Vec<u8> = (0..10000u32).map(|i| i as u8).collect();
+
+        let runtime = Builder::new_current_thread().build().unwrap();
+        for mode in all_modes().iter() {
+            let expected = ::try_encrypt_bytes_with_iv(&plaintext, &key, mode, &iv).unwrap();
+
+            let mut writer = AsyncEncryptWriter::new(Vec::new(), &key, mode, &iv);
+            for piece in plaintext.chunks(13) {
+                runtime.block_on(writer.write_all(piece)).unwrap();
+            }
+            runtime.block_on(writer.shutdown()).unwrap();
+            assert_eq!(writer.into_inner(), expected);
+
+            let mut decrypted = Vec::new();
+            let mut reader = AsyncDecryptReader::new(&expected[..], &key, mode, &iv);
+            runtime.block_on(reader.read_to_end(&mut decrypted)).unwrap();
+            assert_eq!(decrypted, plaintext);
+        }
+
+        let mut reader = AsyncDecryptReader::new(&[0u8; 12][..], &key, &OpMode::CBC, &iv);
+        let err = runtime.block_on(reader.read_to_end(&mut Vec::new())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
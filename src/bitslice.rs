@@ -0,0 +1,217 @@
+use std::ops::{BitAnd, BitXor, Not};
+
+use keys::RoundKeys;
+
+/// A word holding one state bit of many blocks.
+///
+/// The bitsliced rounds only need AND, XOR and NOT, so they can run on
+/// plain `u64` words as well as on SIMD registers.
+pub trait Word: Copy + BitAnd<Output = Self> + BitXor<Output = Self> + Not<Output = Self> {
+    /// A word with all bits set to the lowest bit of `bit`.
+    fn splat(bit: u64) -> Self;
+}
+
+impl Word for u64 {
+    fn splat(bit: u64) -> Self {
+        0u64.wrapping_sub(bit & 1)
+    }
+}
+
+/// Encrypts many 8-byte blocks in place using a bitsliced implementation.
+///
+/// The blocks are processed in batches of 64. Within a batch, each
+/// 64-bit word holds the same state bit of every block, so the S-Box
+/// becomes a handful of AND and XOR operations on whole words and the
+/// permutation only reorders words. There are no table lookups, so the
+/// running time does not depend on the data or the key, and for longer
+/// inputs the throughput is much higher than that of
+/// [`encrypt_block8()`](fn.encrypt_block8.html). It is well suited for
+/// ECB or for generating a counter mode keystream, and
+/// [`Ctr`](struct.Ctr.html) and the `encrypt_blocks()` methods of
+/// [`Present80`](struct.Present80.html) and
+/// [`Present128`](struct.Present128.html) use it for every full batch.
+/// Batches that are not full still cost as much as full ones.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_blocks_bitsliced, decrypt_blocks_bitsliced, Key, Key80Bit};
+/// let round_keys = Key80Bit::new([0u8; 10]).generate_round_keys();
+/// let mut blocks = vec![[0u8; 8]; 100];
+/// encrypt_blocks_bitsliced(&mut blocks, &round_keys);
+/// assert_eq!(blocks[99], [0x55, 0x79, 0xC1, 0x38, 0x7B, 0x22, 0x84, 0x45]);
+/// decrypt_blocks_bitsliced(&mut blocks, &round_keys);
+/// assert_eq!(blocks[99], [0u8; 8]);
+/// ```
+pub fn encrypt_blocks_bitsliced(blocks: &mut [[u8; 8]], round_keys: &RoundKeys) {
+    for batch in blocks.chunks_mut(64) {
+        let mut slices = load(batch);
+        encrypt_slices(&mut slices, round_keys);
+        store(slices, batch);
+    }
+}
+
+/// Decrypts many 8-byte blocks in place using a bitsliced implementation.
+///
+/// This is the inverse of
+/// [`encrypt_blocks_bitsliced()`](fn.encrypt_blocks_bitsliced.html).
+pub fn decrypt_blocks_bitsliced(blocks: &mut [[u8; 8]], round_keys: &RoundKeys) {
+    for batch in blocks.chunks_mut(64) {
+        let mut slices = load(batch);
+        decrypt_slices(&mut slices, round_keys);
+        store(slices, batch);
+    }
+}
+
+/// Runs all encryption rounds on bit slices.
+pub fn encrypt_slices<W: Word>(slices: &mut [W; 64], round_keys: &RoundKeys) {
+    for round_key in round_keys.iter().take(31) {
+        add_round_key(slices, round_key.value);
+        for nibble in slices.chunks_mut(4) {
+            substitute_enc(nibble);
+        }
+        *slices = permute(slices, |bit| (bit % 4) * 16 + bit / 4);
+    }
+    add_round_key(slices, round_keys[31].value);
+}
+
+/// Runs all decryption rounds on bit slices.
+pub fn decrypt_slices<W: Word>(slices: &mut [W; 64], round_keys: &RoundKeys) {
+    for round in (1..32).rev() {
+        add_round_key(slices, round_keys[round].value);
+        *slices = permute(slices, |bit| bit / 16 + (bit % 16) * 4);
+        for nibble in slices.chunks_mut(4) {
+            substitute_dec(nibble);
+        }
+    }
+    add_round_key(slices, round_keys[0].value);
+}
+
+/// Converts up to 64 blocks into bit slices, where word `i` holds bit
+/// `i` of the state of every block.
+pub fn load(batch: &[[u8; 8]]) -> [u64; 64] {
+    let mut slices = [0u64; 64];
+    for (word, bytes) in slices.iter_mut().zip(batch.iter()) {
+        *word = u64::from_be_bytes(*bytes);
+    }
+    transpose(&mut slices);
+    slices.reverse();
+    slices
+}
+
+/// Converts bit slices back into blocks. This is the inverse of `load()`.
+pub fn store(mut slices: [u64; 64], batch: &mut [[u8; 8]]) {
+    slices.reverse();
+    transpose(&mut slices);
+    for (bytes, word) in batch.iter_mut().zip(slices.iter()) {
+        *bytes = word.to_be_bytes();
+    }
+}
+
+/// Transposes a 64x64 bit matrix in place, with the most significant
+/// bit of each word being the first column.
+fn transpose(matrix: &mut [u64; 64]) {
+    let mut width = 32;
+    let mut mask = 0x00000000FFFFFFFF_u64;
+    while width != 0 {
+        let mut row = 0;
+        while row < 64 {
+            let swap = (matrix[row] ^ (matrix[row + width] >> width)) & mask;
+            matrix[row] ^= swap;
+            matrix[row + width] ^= swap << width;
+            row = (row + width + 1) & !width;
+        }
+        width >>= 1;
+        mask ^= mask << width;
+    }
+}
+
+fn add_round_key<W: Word>(slices: &mut [W; 64], round_key: u64) {
+    for (bit, word) in slices.iter_mut().enumerate() {
+        *word = *word ^ W::splat(round_key >> bit);
+    }
+}
+
+/// The S-Box in algebraic normal form, with `x[0]` being the least
+/// significant bit of the nibble.
+pub fn substitute_enc<W: Word>(x: &mut [W]) {
+    let (x0, x1, x2, x3) = (x[0], x[1], x[2], x[3]);
+    let (x01, x02, x12, x13) = (x0 & x1, x0 & x2, x1 & x2, x1 & x3);
+    let x013 = x01 & x3;
+    let x023 = x02 & x3;
+
+    x[0] = x0 ^ x2 ^ x12 ^ x3;
+    x[1] = x1 ^ (x01 & x2) ^ x3 ^ x13 ^ x013 ^ (x2 & x3) ^ x023;
+    x[2] = !(x01 ^ x2 ^ x3 ^ (x0 & x3) ^ x13 ^ x013 ^ x023);
+    x[3] = !(x0 ^ x1 ^ x12 ^ (x01 & x2) ^ x3 ^ x013 ^ x023);
+}
+
+/// The inverse S-Box in algebraic normal form.
+pub fn substitute_dec<W: Word>(x: &mut [W]) {
+    let (x0, x1, x2, x3) = (x[0], x[1], x[2], x[3]);
+    let (x01, x02, x12, x13, x23) = (x0 & x1, x0 & x2, x1 & x2, x1 & x3, x2 & x3);
+    let x012 = x01 & x2;
+    let x013 = x01 & x3;
+    let x023 = x02 & x3;
+
+    x[0] = !(x0 ^ x2 ^ x13);
+    x[1] = x0 ^ x1 ^ x02 ^ x012 ^ x3 ^ x13 ^ x013 ^ x23 ^ x023;
+    x[2] = !(x01 ^ x02 ^ x12 ^ x012 ^ x3 ^ (x0 & x3) ^ x13 ^ x013 ^ x023);
+    x[3] = x0 ^ x1 ^ x01 ^ x2 ^ x012 ^ x3 ^ x023;
+}
+
+/// Moves the slice of bit `i` to position `position(i)`.
+fn permute<W: Word, F: Fn(usize) -> usize>(slices: &[W; 64], position: F) -> [W; 64] {
+    let mut output = [W::splat(0); 64];
+    for (bit, word) in slices.iter().enumerate() {
+        output[position(bit)] = *word;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::{encrypt_block8, decrypt_block8};
+    use keys::{Key, Key80Bit, Key128Bit};
+    use testing::{blocks, states};
+
+    #[test]
+    fn test_matches_block_encryption() {
+        let round_keys = [
+            Key80Bit::new([0xFF; 10]).generate_round_keys(),
+            Key128Bit::new([0x3C; 16]).generate_round_keys(),
+        ];
+
+        for round_keys in round_keys.iter() {
+            // Not a multiple of the batch size, to cover partial batches
+            let original = blocks(150);
+            let mut data = original.clone();
+            encrypt_blocks_bitsliced(&mut data, round_keys);
+
+            for (block, plain) in data.iter().zip(original.iter()) {
+                let mut expected = *plain;
+                encrypt_block8(&mut expected, round_keys);
+                assert_eq!(*block, expected);
+
+                decrypt_block8(&mut expected, round_keys);
+                assert_eq!(expected, *plain);
+            }
+
+            decrypt_blocks_bitsliced(&mut data, round_keys);
+            assert_eq!(data, original);
+        }
+    }
+
+    #[test]
+    fn test_transpose_is_an_involution() {
+        let mut matrix = [0u64; 64];
+        matrix.copy_from_slice(&states(64));
+        let original = matrix;
+        transpose(&mut matrix);
+        assert_eq!(matrix[0] >> 63, original[0] >> 63);
+        assert!(matrix[..] != original[..]);
+        transpose(&mut matrix);
+        assert_eq!(matrix[..], original[..]);
+    }
+}
@@ -1,10 +1,20 @@
-use std::ops::BitXorAssign;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Not};
+use std::str::FromStr;
 
-use keys::{Key, RoundKey};
-use sbox::S_BOX;
-use pbox::P_BOX;
+use errors::ParseError;
+use keys::{wipe, Key, RoundKey, RoundKeys};
+use pair;
+use profile::{self, Stage};
+use sbox;
+use pbox;
+#[cfg(feature = "small-state")]
+use state16;
+#[cfg(all(feature = "tables", not(feature = "ct-sbox")))]
+use tables;
 
 /// A single 64-bit block used for encryption/decryption.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct Block {
     /// The current state/value of the block.
     state: u64, // PRESENT block size is fixed to 64 bit
@@ -13,7 +23,7 @@ pub struct Block {
 impl Block {
     /// Constructs a new Block with the given state.
     pub fn new(state: u64) -> Self {
-        Block { state: state }
+        Block { state }
     }
 
     /// Constructs a new Block from the given bytes.
@@ -35,7 +45,7 @@ impl Block {
         for (i, byte) in bytes.iter().rev().enumerate() {
             state += (*byte as u64) << (i * 8);
         }
-        Block { state: state }
+        Block { state }
     }
 
     /// Returns the current state of the block as a byte array.
@@ -59,51 +69,92 @@ impl Block {
         ret
     }
 
+    /// Constructs a new Block from the given bytes in little-endian
+    /// order, i.e. with the least significant bits being at the
+    /// beginning of the array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::Block;
+    /// let bytes = [0xFC, 0x90, 0x55, 0xAF, 0xD9, 0x52, 0x34, 0x10];
+    /// let block = Block::from_le_bytes(&bytes);
+    /// assert_eq!(block.get_state(), 0x103452D9AF5590FC);
+    /// ```
+    pub fn from_le_bytes(bytes: &[u8; 8]) -> Self {
+        Block { state: u64::from_le_bytes(*bytes) }
+    }
+
+    /// Returns the current state of the block as a byte array in
+    /// little-endian order, with the least significant bits of the
+    /// state at the beginning.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::Block;
+    /// let block = Block::new(0x9F238517AB6CD820);
+    /// let bytes = block.to_le_bytes();
+    /// assert_eq!(bytes, [0x20, 0xD8, 0x6C, 0xAB, 0x17, 0x85, 0x23, 0x9F]);
+    /// ```
+    pub fn to_le_bytes(&self) -> [u8; 8] {
+        self.state.to_le_bytes()
+    }
+
     /// Adds a round key to the current state (bitwise XOR).
     fn add_round_key(&mut self, round_key: &RoundKey) {
         self.state ^= round_key.value;
     }
 
-    /// Apply PRESENT's S-Box to the current state.
+    /// Applies PRESENT's substitution layer to the block.
     ///
     /// This splits the current state into sixteen 4-bit nibbles
-    /// and sends each one independently through the S-Box.
-    fn apply_substitution_enc(&mut self) {
-        // Split the 64 bit state into sixteen 4 bit nibbles
-        // Apply the S-Box to each of them independently
-        let mut new_state = 0u64;
-        for split in 0..16 {
-            let shift = 4 * split;
-            new_state += (S_BOX.apply_enc(((self.state >> shift) as u8) % 16) as u64) << shift;
-        }
-        self.state = new_state;
+    /// and sends each one independently through the S-Box. Together
+    /// with [`permute()`](#method.permute) and a round key, this makes
+    /// up one round of encryption.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::Block;
+    /// let mut block = Block::new(0x0123456789ABCDEF);
+    /// block.substitute();
+    /// assert_eq!(block.get_state(), 0xC56B90AD3EF84712);
+    /// block.inverse_substitute();
+    /// assert_eq!(block.get_state(), 0x0123456789ABCDEF);
+    /// ```
+    pub fn substitute(&mut self) {
+        self.state = sbox::apply_enc_state(self.state);
     }
 
-    /// Apply PRESENT's permutation function to the current state.
-    fn apply_permutation_enc(&mut self) {
-        // Send the current state through the P-Box
-        self.state = P_BOX.apply_enc(self.state);
+    /// Applies PRESENT's permutation layer (P-Box) to the block, which
+    /// moves bit `i` to position `16 * i mod 63` for `i < 63`. Bit 63
+    /// is fixed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::Block;
+    /// let mut block = Block::new(0b10);
+    /// block.permute();
+    /// assert_eq!(block.get_state(), 1 << 16);
+    /// block.inverse_permute();
+    /// assert_eq!(block.get_state(), 0b10);
+    /// ```
+    pub fn permute(&mut self) {
+        self.state = pbox::apply_enc(self.state);
     }
 
-    /// Apply the inverse of PRESENT's S-Box to the current state.
-    ///
-    /// This splits the current state into sixteen 4-bit nibbles
-    /// and sends each one independently through the inverse S-Box.
-    /// The inverse substitution is required for decryption.
-    fn apply_substitution_dec(&mut self) {
-        let mut new_state = 0u64;
-        for split in 0..16 {
-            let shift = 4 * split;
-            new_state += (S_BOX.apply_dec(((self.state >> shift) as u8) % 16) as u64) << shift;
-        }
-        self.state = new_state;
+    /// Applies the inverse of PRESENT's substitution layer to the block,
+    /// as done during decryption.
+    pub fn inverse_substitute(&mut self) {
+        self.state = sbox::apply_dec_state(self.state);
     }
 
-    /// Apply the inverse of PRESENT's permutation function to the current state.
-    ///
-    /// The inverse permutation is required for decryption.
-    fn apply_permutation_dec(&mut self) {
-        self.state = P_BOX.apply_dec(self.state);
+    /// Applies the inverse of PRESENT's permutation layer to the block,
+    /// as done during decryption.
+    pub fn inverse_permute(&mut self) {
+        self.state = pbox::apply_dec(self.state);
     }
 
     /// Encrypts this block using the given key.
@@ -114,13 +165,41 @@ impl Block {
     /// for details.
     pub fn encrypt<K: Key>(&mut self, key: &K) {
         // Generate round keys
-        let round_keys = key.generate_round_keys();
+        let mut round_keys = profile::measure(Stage::KeySchedule, || key.generate_round_keys());
+        self.encrypt_round_keys(&round_keys);
+        wipe(&mut round_keys);
+    }
+
+    /// Encrypts this block using precomputed round keys.
+    pub(crate) fn encrypt_round_keys(&mut self, round_keys: &RoundKeys) {
+        profile::count_block();
 
+        // The rounds are done on 16-bit words, only the conversion of
+        // the state and round keys uses 64-bit values
+        #[cfg(feature = "small-state")]
+        {
+            self.state = state16::encrypt_state(self.state, round_keys);
+        }
+        #[cfg(not(feature = "small-state"))]
+        self.encrypt_rounds(round_keys);
+    }
+
+    /// Applies the rounds of encryption with 64-bit operations.
+    #[cfg_attr(all(feature = "small-state", not(debug_assertions)), allow(dead_code))]
+    pub(crate) fn encrypt_rounds(&mut self, round_keys: &RoundKeys) {
         // Iterate over rounds
-        for round in 0..31 {
-            *self ^= &round_keys[round];
-            self.apply_substitution_enc();
-            self.apply_permutation_enc();
+        for round_key in round_keys.iter().take(31) {
+            *self ^= round_key;
+
+            // The fused tables do both layers at once, so the time is
+            // attributed to the substitution
+            #[cfg(all(feature = "tables", not(feature = "ct-sbox")))]
+            profile::measure(Stage::Substitution, || self.state = tables::substitute_permute(self.state));
+            #[cfg(any(not(feature = "tables"), feature = "ct-sbox"))]
+            {
+                profile::measure(Stage::Substitution, || self.substitute());
+                profile::measure(Stage::Permutation, || self.permute());
+            }
         }
 
         // Add final round key
@@ -134,13 +213,31 @@ impl Block {
     /// and permutation functions.
     pub fn decrypt<K: Key>(&mut self, key: &K) {
         // Generate round keys
-        let round_keys = key.generate_round_keys();
+        let mut round_keys = profile::measure(Stage::KeySchedule, || key.generate_round_keys());
+        self.decrypt_round_keys(&round_keys);
+        wipe(&mut round_keys);
+    }
+
+    /// Decrypts this block using precomputed round keys.
+    pub(crate) fn decrypt_round_keys(&mut self, round_keys: &RoundKeys) {
+        profile::count_block();
+
+        #[cfg(feature = "small-state")]
+        {
+            self.state = state16::decrypt_state(self.state, round_keys);
+        }
+        #[cfg(not(feature = "small-state"))]
+        self.decrypt_rounds(round_keys);
+    }
 
+    /// Applies the rounds of decryption with 64-bit operations.
+    #[cfg_attr(all(feature = "small-state", not(debug_assertions)), allow(dead_code))]
+    pub(crate) fn decrypt_rounds(&mut self, round_keys: &RoundKeys) {
         // Iterate over rounds in reverse order
         for round in (1..32).rev() {
             *self ^= &round_keys[round];
-            self.apply_permutation_dec();
-            self.apply_substitution_dec();
+            profile::measure(Stage::Permutation, || self.inverse_permute());
+            profile::measure(Stage::Substitution, || self.inverse_substitute());
         }
 
         // Add final (first) round key
@@ -157,14 +254,83 @@ impl Block {
     }
 }
 
-impl<'a> BitXorAssign<&'a RoundKey> for Block {
+/// Encrypts an 8-byte array in place using precomputed round keys.
+///
+/// This is useful for protocol code that already holds its data in
+/// 8-byte buffers, as it neither requires constructing a
+/// [`Block`](struct.Block.html) nor regenerating the round keys for
+/// every call. The bytes are interpreted with the most significant
+/// bits at the beginning of the array, like in
+/// [`Block::from_bytes()`](struct.Block.html#method.from_bytes).
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_block8, decrypt_block8, Key, Key80Bit};
+/// let round_keys = Key80Bit::new([0u8; 10]).generate_round_keys();
+/// let mut bytes = [0u8; 8];
+/// encrypt_block8(&mut bytes, &round_keys);
+/// assert_eq!(bytes, [0x55, 0x79, 0xC1, 0x38, 0x7B, 0x22, 0x84, 0x45]);
+/// decrypt_block8(&mut bytes, &round_keys);
+/// assert_eq!(bytes, [0u8; 8]);
+/// ```
+pub fn encrypt_block8(bytes: &mut [u8; 8], round_keys: &RoundKeys) {
+    let mut block = Block { state: u64::from_be_bytes(*bytes) };
+    block.encrypt_round_keys(round_keys);
+    *bytes = block.state.to_be_bytes();
+}
+
+/// Decrypts an 8-byte array in place using precomputed round keys.
+///
+/// This is the inverse of [`encrypt_block8()`](fn.encrypt_block8.html).
+pub fn decrypt_block8(bytes: &mut [u8; 8], round_keys: &RoundKeys) {
+    let mut block = Block { state: u64::from_be_bytes(*bytes) };
+    block.decrypt_round_keys(round_keys);
+    *bytes = block.state.to_be_bytes();
+}
+
+/// Encrypts a batch of independent 8-byte arrays in place using
+/// precomputed round keys.
+///
+/// The result is the same as calling
+/// [`encrypt_block8()`](fn.encrypt_block8.html) on every array, i.e.
+/// ECB without padding. The arrays are processed two at a time in
+/// lockstep, so the processor can overlap the work on both, which is
+/// faster than encrypting them one by one. For large batches,
+/// [`encrypt_blocks_bitsliced()`](fn.encrypt_blocks_bitsliced.html) may
+/// be faster still.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_blocks8, decrypt_blocks8, Key, Key80Bit};
+/// let round_keys = Key80Bit::new([0u8; 10]).generate_round_keys();
+/// let mut blocks = [[0u8; 8]; 3];
+/// encrypt_blocks8(&mut blocks, &round_keys);
+/// assert_eq!(blocks[2], [0x55, 0x79, 0xC1, 0x38, 0x7B, 0x22, 0x84, 0x45]);
+/// decrypt_blocks8(&mut blocks, &round_keys);
+/// assert_eq!(blocks, [[0u8; 8]; 3]);
+/// ```
+pub fn encrypt_blocks8(blocks: &mut [[u8; 8]], round_keys: &RoundKeys) {
+    pair::encrypt_blocks(blocks.as_flattened_mut(), round_keys);
+}
+
+/// Decrypts a batch of independent 8-byte arrays in place using
+/// precomputed round keys.
+///
+/// This is the inverse of [`encrypt_blocks8()`](fn.encrypt_blocks8.html).
+pub fn decrypt_blocks8(blocks: &mut [[u8; 8]], round_keys: &RoundKeys) {
+    pair::decrypt_blocks(blocks.as_flattened_mut(), round_keys);
+}
+
+impl BitXorAssign<&RoundKey> for Block {
     /// Add a round key to the block (bitwise XOR with the current state).
     fn bitxor_assign(&mut self, rhs: &RoundKey) {
         self.add_round_key(rhs);
     }
 }
 
-impl<'a> BitXorAssign<&'a Block> for Block {
+impl BitXorAssign<&Block> for Block {
     /// Add another block to this block (bitwise XOR).
     ///
     /// This is useful e.g. for initialization vectors.
@@ -173,11 +339,202 @@ impl<'a> BitXorAssign<&'a Block> for Block {
     }
 }
 
+impl BitXor for Block {
+    type Output = Block;
+
+    /// Combines two blocks with a bitwise XOR.
+    fn bitxor(self, rhs: Block) -> Block {
+        Block::new(self.state ^ rhs.state)
+    }
+}
+
+impl<'a> BitXor<&'a Block> for &'a Block {
+    type Output = Block;
+
+    fn bitxor(self, rhs: &Block) -> Block {
+        *self ^ *rhs
+    }
+}
+
+impl BitAnd for Block {
+    type Output = Block;
+
+    /// Combines two blocks with a bitwise AND, e.g. to mask parts of
+    /// the state.
+    fn bitand(self, rhs: Block) -> Block {
+        Block::new(self.state & rhs.state)
+    }
+}
+
+impl<'a> BitAnd<&'a Block> for &'a Block {
+    type Output = Block;
+
+    fn bitand(self, rhs: &Block) -> Block {
+        *self & *rhs
+    }
+}
+
+impl BitOr for Block {
+    type Output = Block;
+
+    /// Combines two blocks with a bitwise OR.
+    fn bitor(self, rhs: Block) -> Block {
+        Block::new(self.state | rhs.state)
+    }
+}
+
+impl<'a> BitOr<&'a Block> for &'a Block {
+    type Output = Block;
+
+    fn bitor(self, rhs: &Block) -> Block {
+        *self | *rhs
+    }
+}
+
+impl Not for Block {
+    type Output = Block;
+
+    /// Inverts all bits of the block.
+    fn not(self) -> Block {
+        Block::new(!self.state)
+    }
+}
+
+impl Not for &Block {
+    type Output = Block;
+
+    fn not(self) -> Block {
+        !*self
+    }
+}
+
+impl fmt::Display for Block {
+    /// Formats the state as 16 lowercase hexadecimal digits.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.state)
+    }
+}
+
+impl fmt::LowerHex for Block {
+    /// Formats the state in lowercase hexadecimal, honouring the width
+    /// and flags, so `{:016x}` prints all 16 digits.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.state, f)
+    }
+}
+
+impl fmt::UpperHex for Block {
+    /// Formats the state in uppercase hexadecimal, see `LowerHex`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.state, f)
+    }
+}
+
+impl fmt::Binary for Block {
+    /// Formats the state in binary, so `{:064b}` prints all 64 bits.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Binary::fmt(&self.state, f)
+    }
+}
+
+impl fmt::Debug for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Block({})", self)
+    }
+}
+
+impl FromStr for Block {
+    type Err = ParseError;
+
+    /// Parses a block from 16 hexadecimal digits, e.g. an IV from a
+    /// configuration file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::InvalidLength` if the string does not have
+    /// 16 characters and `ParseError::InvalidHex` if any of them is not
+    /// a hexadecimal digit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::Block;
+    /// let block: Block = "0123456789ABCDEF".parse().unwrap();
+    /// assert_eq!(block.get_state(), 0x0123456789ABCDEF);
+    /// assert_eq!(block.to_string(), "0123456789abcdef");
+    /// ```
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        if hex.len() != 16 {
+            return Err(ParseError::InvalidLength(hex.len()));
+        }
+        if !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(ParseError::InvalidHex);
+        }
+        u64::from_str_radix(hex, 16).map(Block::new).map_err(|_| ParseError::InvalidHex)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use keys::Key80Bit;
 
+    #[test]
+    fn test_block_hex() {
+        assert_eq!(Block::new(0xAB).to_string(), "00000000000000ab");
+        assert_eq!("00000000000000aB".parse::<Block>().map(|b| b.get_state()), Ok(0xAB));
+        assert_eq!("+0000000000000ab".parse::<Block>().err(), Some(ParseError::InvalidHex));
+        assert_eq!("0123".parse::<Block>().err(), Some(ParseError::InvalidLength(4)));
+    }
+
+    #[test]
+    fn test_block_derives() {
+        use std::collections::HashSet;
+
+        let block = Block::new(0x0123456789ABCDEF);
+        let copy = block;
+        assert_eq!(block, copy);
+        assert!(block != Block::default());
+        assert_eq!(format!("{:?}", block), "Block(0123456789abcdef)");
+
+        let set: HashSet<Block> = [block, copy, Block::new(0)].iter().cloned().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    #[allow(clippy::op_ref)]
+    fn test_block_bit_operators() {
+        let a = Block::new(0xFF00FF00FF00FF00);
+        let b = Block::new(0x0F0F0F0F0F0F0F0F);
+        assert_eq!(a ^ b, Block::new(0xF00FF00FF00FF00F));
+        assert_eq!(&a ^ &b, a ^ b);
+        assert_eq!(a & b, Block::new(0x0F000F000F000F00));
+        assert_eq!(&a & &b, a & b);
+        assert_eq!(a | b, Block::new(0xFF0FFF0FFF0FFF0F));
+        assert_eq!(&a | &b, a | b);
+        assert_eq!(!a, Block::new(0x00FF00FF00FF00FF));
+        assert_eq!(!&a, !a);
+    }
+
+    #[test]
+    fn test_block_radix_formatting() {
+        let block = Block::new(0xAB);
+        assert_eq!(format!("{:x}", block), "ab");
+        assert_eq!(format!("{:016x}", block), "00000000000000ab");
+        assert_eq!(format!("{:#X}", block), "0xAB");
+        assert_eq!(format!("{:b}", block), "10101011");
+        assert_eq!(format!("{:064b}", Block::new(1)).len(), 64);
+    }
+
+    #[test]
+    fn test_block_le_bytes() {
+        let bytes = [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+        let block = Block::from_le_bytes(&bytes);
+        assert_eq!(block.get_state(), 0xEFCDAB8967452301);
+        assert_eq!(block.to_le_bytes(), bytes);
+        assert_eq!(Block::from_bytes(&bytes).to_le_bytes(), [0xEF, 0xCD, 0xAB, 0x89, 0x67, 0x45, 0x23, 0x01]);
+    }
+
     #[test]
     fn test_block_init_from_bytes() {
         let block = Block::from_bytes(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF]);
@@ -193,30 +550,30 @@ mod tests {
     #[test]
     fn test_correct_sbox_application() {
         let mut block = Block { state: 0u64 };
-        block.apply_substitution_enc();
+        block.substitute();
         assert_eq!(block.get_state(), 0xCCCCCCCCCCCCCCCC_u64);
-        block.apply_substitution_dec();
+        block.inverse_substitute();
         assert_eq!(block.get_state(), 0u64);
 
         let mut block = Block { state: 0x0123456789ABCDEF_u64 };
-        block.apply_substitution_enc();
+        block.substitute();
         assert_eq!(block.get_state(), 0xC56B90AD3EF84712_u64);
-        block.apply_substitution_dec();
+        block.inverse_substitute();
         assert_eq!(block.get_state(), 0x0123456789ABCDEF_u64);
     }
 
     #[test]
     fn test_correct_pbox_application() {
         let mut block = Block { state: 0u64 };
-        block.apply_permutation_enc();
+        block.permute();
         assert_eq!(block.get_state(), 0u64);
-        block.apply_permutation_dec();
+        block.inverse_permute();
         assert_eq!(block.get_state(), 0u64);
 
         let mut block = Block { state: 0x0123456789ABCDEF };
-        block.apply_permutation_enc();
+        block.permute();
         assert_eq!(block.get_state(), 0x00FF0F0F33335555);
-        block.apply_permutation_dec();
+        block.inverse_permute();
         assert_eq!(block.get_state(), 0x0123456789ABCDEF);
     }
 
@@ -251,4 +608,16 @@ mod tests {
         block.decrypt(&key);
         assert_eq!(block.get_state(), 0xFFFFFFFFFFFFFFFF_u64);
     }
+
+    #[test]
+    fn test_block8_matches_block_encryption() {
+        let key = Key80Bit { value: [0xFF_u8; 10] };
+        let round_keys = key.generate_round_keys();
+
+        let mut bytes = [0xFF_u8; 8];
+        encrypt_block8(&mut bytes, &round_keys);
+        assert_eq!(bytes, Block::new(0x3333DCD3213210D2_u64).to_bytes());
+        decrypt_block8(&mut bytes, &round_keys);
+        assert_eq!(bytes, [0xFF_u8; 8]);
+    }
 }
@@ -0,0 +1,229 @@
+use std::marker::PhantomData;
+
+use ctr::Ctr;
+use errors::{DecryptError, EncryptError};
+use iv::Iv;
+use keys::Key;
+use modes::Mode;
+use {decrypt_bytes_with_mode, encrypt_bytes_with_mode};
+
+/// Selects the mode of operation for a [`Cipher`](struct.Cipher.html).
+///
+/// The modes that need an IV are chosen directly, while ECB has to be
+/// chosen with [`allow_insecure_ecb()`](#method.allow_insecure_ecb), so
+/// it cannot be picked by accident.
+///
+/// # Examples
+///
+/// ```
+/// use present::{CipherBuilder, Key80Bit};
+/// let cipher = CipherBuilder::new(Key80Bit::new([0x42; 10])).cbc();
+///
+/// let (iv, ciphertext) = cipher.encrypt(b"Hello, world!").unwrap();
+/// assert_eq!(cipher.decrypt(&ciphertext, &iv).unwrap(), b"Hello, world!");
+/// ```
+///
+/// Decrypting without the IV does not compile:
+///
+/// ```compile_fail
+/// use present::{CipherBuilder, Key80Bit};
+/// let cipher = CipherBuilder::new(Key80Bit::new([0x42; 10])).cbc();
+/// let (_iv, ciphertext) = cipher.encrypt(b"Hello, world!").unwrap();
+/// cipher.decrypt(&ciphertext);
+/// ```
+pub struct CipherBuilder<K: Key> {
+    key: K,
+}
+
+impl<K: Key> CipherBuilder<K> {
+    /// Starts building a cipher with the given key.
+    pub fn new(key: K) -> Self {
+        CipherBuilder { key }
+    }
+
+    /// Selects Cipher Block Chaining.
+    pub fn cbc(self) -> Cipher<K, Cbc> {
+        Cipher::new(self.key)
+    }
+
+    /// Selects Propagating Cipher Block Chaining.
+    pub fn pcbc(self) -> Cipher<K, Pcbc> {
+        Cipher::new(self.key)
+    }
+
+    /// Selects Cipher Feedback.
+    pub fn cfb(self) -> Cipher<K, Cfb> {
+        Cipher::new(self.key)
+    }
+
+    /// Selects counter mode with the given nonce, see
+    /// [`Ctr`](struct.Ctr.html).
+    pub fn ctr(self, nonce: &Iv) -> Ctr {
+        Ctr::new(&self.key, &nonce.to_block())
+    }
+
+    /// Selects Electronic Code Book. ECB encrypts equal blocks to equal
+    /// ciphertexts and so reveals patterns in the plaintext. Only use it
+    /// for compatibility with existing data. Only available with the
+    /// `insecure-modes` feature.
+    #[cfg(feature = "insecure-modes")]
+    pub fn allow_insecure_ecb(self) -> Cipher<K, Ecb> {
+        Cipher::new(self.key)
+    }
+}
+
+/// Marker for Electronic Code Book in a [`Cipher`](struct.Cipher.html).
+#[cfg(feature = "insecure-modes")]
+pub enum Ecb {}
+
+/// Marker for Cipher Block Chaining in a [`Cipher`](struct.Cipher.html).
+pub enum Cbc {}
+
+/// Marker for Propagating Cipher Block Chaining in a
+/// [`Cipher`](struct.Cipher.html).
+pub enum Pcbc {}
+
+/// Marker for Cipher Feedback in a [`Cipher`](struct.Cipher.html).
+pub enum Cfb {}
+
+/// The modes of operation that need an IV. Not exported, so no other
+/// modes can be added outside of this crate.
+pub trait IvMode {
+    fn mode(iv: Iv) -> Mode;
+}
+
+impl IvMode for Cbc {
+    fn mode(iv: Iv) -> Mode {
+        Mode::CBC { iv }
+    }
+}
+
+impl IvMode for Pcbc {
+    fn mode(iv: Iv) -> Mode {
+        Mode::PCBC { iv }
+    }
+}
+
+impl IvMode for Cfb {
+    fn mode(iv: Iv) -> Mode {
+        Mode::CFB { iv }
+    }
+}
+
+/// A key bound to a mode of operation, built with a
+/// [`CipherBuilder`](struct.CipherBuilder.html).
+///
+/// The mode is part of the type, so the IV is a required argument for
+/// decryption in the modes that need one, and cannot be given to ECB.
+pub struct Cipher<K: Key, M> {
+    key: K,
+    mode: PhantomData<M>,
+}
+
+impl<K: Key, M> Cipher<K, M> {
+    fn new(key: K) -> Self {
+        Cipher { key, mode: PhantomData }
+    }
+}
+
+impl<K: Key, M: IvMode> Cipher<K, M> {
+    /// Encrypts data with a random IV, which is returned along with the
+    /// ciphertext.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncryptError::Rng` if the IV cannot be generated.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<(Iv, Vec<u8>), EncryptError> {
+        let iv = Iv::random()?;
+        Ok((iv, self.try_encrypt_with_iv(plaintext, &iv)?))
+    }
+
+    /// Encrypts data with the given IV. Never use the same IV twice
+    /// with the same key.
+    ///
+    /// # Errors
+    ///
+    /// None of the current modes of operation can fail with a given IV,
+    /// but future modes may return an `EncryptError`.
+    pub fn try_encrypt_with_iv(&self, plaintext: &[u8], iv: &Iv) -> Result<Vec<u8>, EncryptError> {
+        encrypt_bytes_with_mode(plaintext, &self.key, &M::mode(*iv)).map(|ciphertext| ciphertext.into_bytes())
+    }
+
+    /// Decrypts data encrypted with the given IV.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `DecryptError` if an error occurred during
+    /// decryption.
+    pub fn decrypt(&self, ciphertext: &[u8], iv: &Iv) -> Result<Vec<u8>, DecryptError> {
+        decrypt_bytes_with_mode(ciphertext, &self.key, &M::mode(*iv))
+    }
+}
+
+#[cfg(feature = "insecure-modes")]
+#[allow(deprecated)]
+impl<K: Key> Cipher<K, Ecb> {
+    /// Encrypts data in ECB mode.
+    ///
+    /// # Errors
+    ///
+    /// ECB cannot fail at the moment, but future versions may return an
+    /// `EncryptError`.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, EncryptError> {
+        encrypt_bytes_with_mode(plaintext, &self.key, &Mode::ECB).map(|ciphertext| ciphertext.into_bytes())
+    }
+
+    /// Decrypts data in ECB mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `DecryptError` if an error occurred during
+    /// decryption.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        decrypt_bytes_with_mode(ciphertext, &self.key, &Mode::ECB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key80Bit;
+    use modes::OpMode;
+    use decrypt_bytes;
+
+    #[test]
+    fn test_iv_modes() {
+        let iv = Iv::from_bytes(&[0x17; 8]);
+        let cbc = CipherBuilder::new(Key80Bit::new([0x42; 10])).cbc();
+        let ciphertext = cbc.try_encrypt_with_iv(b"typestate", &iv).unwrap();
+        let expected = decrypt_bytes(&ciphertext, &Key80Bit::new([0x42; 10]), &OpMode::CBC, Some(iv.to_block()));
+        assert_eq!(expected.unwrap(), b"typestate");
+
+        let cfb = CipherBuilder::new(Key80Bit::new([0x42; 10])).cfb();
+        let (iv, ciphertext) = cfb.encrypt(b"typestate").unwrap();
+        assert_eq!(ciphertext.len(), 9);
+        assert_eq!(cfb.decrypt(&ciphertext, &iv).unwrap(), b"typestate");
+    }
+
+    #[cfg(feature = "insecure-modes")]
+    #[allow(deprecated)]
+    #[test]
+    fn test_ecb() {
+        use encrypt_bytes;
+
+        let ecb = CipherBuilder::new(Key80Bit::new([0x42; 10])).allow_insecure_ecb();
+        let ciphertext = ecb.encrypt(b"typestate").unwrap();
+        let expected = encrypt_bytes(b"typestate", &Key80Bit::new([0x42; 10]), &OpMode::ECB).unwrap();
+        assert_eq!(ciphertext, expected.as_bytes());
+        assert_eq!(ecb.decrypt(&ciphertext).unwrap(), b"typestate");
+    }
+
+    #[test]
+    fn test_ctr() {
+        let nonce = Iv::from_bytes(&[0; 8]);
+        let mut data = *b"typestate";
+        CipherBuilder::new(Key80Bit::new([0x42; 10])).ctr(&nonce).apply_keystream(&mut data);
+        CipherBuilder::new(Key80Bit::new([0x42; 10])).ctr(&nonce).apply_keystream(&mut data);
+        assert_eq!(&data, b"typestate");
+    }
+}
@@ -0,0 +1,162 @@
+use block::Block;
+use ciphertext::Ciphertext;
+use errors::{DecryptError, EncryptError};
+use keys::Key;
+use modes::OpMode;
+use {decrypt_str, encrypt_str, try_encrypt_str_with_iv};
+
+/// A key that can only be used for encryption.
+///
+/// The wrapped key cannot be retrieved again, and `EncryptKey` does
+/// not implement [`Key`](trait.Key.html), so it cannot be passed to
+/// any decryption function. Hand this to components that only need to
+/// produce ciphertexts (e.g. log shippers), so that they are unable
+/// to read existing data.
+///
+/// Raw block encryption is not offered, as it is all that is needed
+/// to decrypt modes built on the forward cipher alone. For the same
+/// reason, such modes (CFB and CTR) are rejected with
+/// [`EncryptError::ModeNotAllowed`](enum.EncryptError.html#variant.ModeNotAllowed).
+///
+/// # Examples
+///
+/// ```
+/// use present::{EncryptKey, DecryptKey, Key80Bit, OpMode};
+/// let encrypt_key = EncryptKey::new(Key80Bit::new([0x42; 10]));
+/// let decrypt_key = DecryptKey::new(Key80Bit::new([0x42; 10]));
+///
+/// let (ciphertext, iv) = encrypt_key.encrypt_str("Hello, world!", &OpMode::CBC).unwrap().into_parts();
+/// let decrypt_result = decrypt_key.decrypt_str(&ciphertext, &OpMode::CBC, iv);
+/// assert_eq!(decrypt_result.unwrap(), "Hello, world!");
+/// ```
+pub struct EncryptKey<K: Key> {
+    key: K,
+}
+
+impl<K: Key> EncryptKey<K> {
+    /// Wraps the given key, restricting it to encryption.
+    pub fn new(key: K) -> Self {
+        EncryptKey { key }
+    }
+
+    /// Encrypts a string. See [`encrypt_str`](fn.encrypt_str.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncryptError::ModeNotAllowed` for modes that can be
+    /// decrypted with the forward cipher alone.
+    pub fn encrypt_str(&self, text: &str, mode: &OpMode) -> Result<Ciphertext, EncryptError> {
+        check_mode(mode)?;
+        encrypt_str(text, &self.key, mode)
+    }
+
+    /// Encrypts a string with a given IV. See
+    /// [`try_encrypt_str_with_iv`](fn.try_encrypt_str_with_iv.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncryptError::ModeNotAllowed` for modes that can be
+    /// decrypted with the forward cipher alone.
+    pub fn try_encrypt_str_with_iv(&self, text: &str, mode: &OpMode, init_vec: &Block) -> Result<Vec<u8>, EncryptError> {
+        check_mode(mode)?;
+        try_encrypt_str_with_iv(text, &self.key, mode, init_vec)
+    }
+
+    /// Encrypts a string with a given IV, panicking if encryption
+    /// fails. See
+    /// [`try_encrypt_str_with_iv`](#method.try_encrypt_str_with_iv).
+    #[deprecated(note = "use `try_encrypt_str_with_iv()`, which returns a `Result`")]
+    pub fn encrypt_str_with_iv(&self, text: &str, mode: &OpMode, init_vec: &Block) -> Vec<u8> {
+        self.try_encrypt_str_with_iv(text, mode, init_vec).expect("Encryption failed")
+    }
+}
+
+/// Rejects the modes whose decryption only uses the forward cipher,
+/// as an encrypt-only key would be able to read them.
+fn check_mode(mode: &OpMode) -> Result<(), EncryptError> {
+    match *mode {
+        OpMode::CFB | OpMode::CTR => Err(EncryptError::ModeNotAllowed),
+        _ => Ok(()),
+    }
+}
+
+/// A key that can only be used for decryption.
+///
+/// This is the counterpart to [`EncryptKey`](struct.EncryptKey.html).
+/// Components holding a `DecryptKey` can read data, but cannot produce
+/// new ciphertexts under the wrapped key.
+pub struct DecryptKey<K: Key> {
+    key: K,
+}
+
+impl<K: Key> DecryptKey<K> {
+    /// Wraps the given key, restricting it to decryption.
+    pub fn new(key: K) -> Self {
+        DecryptKey { key }
+    }
+
+    /// Decrypts a single block. See [`Block::decrypt()`](struct.Block.html#method.decrypt).
+    pub fn decrypt_block(&self, block: &mut Block) {
+        block.decrypt(&self.key);
+    }
+
+    /// Decrypts a string. See [`decrypt_str`](fn.decrypt_str.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `DecryptError` if an error occurred during decryption.
+    pub fn decrypt_str(&self, ciphertext: &[u8], mode: &OpMode, init_vec: Option<Block>) -> Result<String, DecryptError> {
+        decrypt_str(ciphertext, &self.key, mode, init_vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key128Bit;
+
+    #[test]
+    fn test_decrypt_block() {
+        let decrypt_key = DecryptKey::new(Key128Bit::new([0x99; 16]));
+
+        let mut block = Block::new(0x0123456789ABCDEF);
+        block.encrypt(&Key128Bit::new([0x99; 16]));
+        assert!(block.get_state() != 0x0123456789ABCDEF);
+        decrypt_key.decrypt_block(&mut block);
+        assert_eq!(block.get_state(), 0x0123456789ABCDEF);
+    }
+
+    #[test]
+    fn test_string_roundtrip_with_split_keys() {
+        let encrypt_key = EncryptKey::new(Key128Bit::new([0x99; 16]));
+        let decrypt_key = DecryptKey::new(Key128Bit::new([0x99; 16]));
+
+        let ciphertext = encrypt_key.try_encrypt_str_with_iv("split", &OpMode::CBC, &Block::new(7)).unwrap();
+        let decrypt_result = decrypt_key.decrypt_str(&ciphertext, &OpMode::CBC, Some(Block::new(7)));
+        assert_eq!(decrypt_result.unwrap(), "split");
+    }
+
+    #[test]
+    fn test_encrypt_key_cannot_decrypt_cfb() {
+        let iv = Block::new(7);
+        let ciphertext = try_encrypt_str_with_iv("secret", &Key128Bit::new([0x99; 16]), &OpMode::CFB, &iv).unwrap();
+
+        // Encrypting zeros under the same IV would yield the keystream
+        // that decrypts the ciphertext
+        let encrypt_key = EncryptKey::new(Key128Bit::new([0x99; 16]));
+        let zeros = "\0".repeat(ciphertext.len());
+        assert!(matches!(encrypt_key.try_encrypt_str_with_iv(&zeros, &OpMode::CFB, &iv), Err(EncryptError::ModeNotAllowed)));
+        assert!(matches!(encrypt_key.encrypt_str(&zeros, &OpMode::CFB), Err(EncryptError::ModeNotAllowed)));
+    }
+
+    #[test]
+    fn test_encrypt_key_cannot_decrypt_ctr() {
+        let nonce = Block::new(7);
+        let ciphertext = try_encrypt_str_with_iv("secret", &Key128Bit::new([0x99; 16]), &OpMode::CTR, &nonce).unwrap();
+
+        let encrypt_key = EncryptKey::new(Key128Bit::new([0x99; 16]));
+        let zeros = "\0".repeat(ciphertext.len());
+        assert!(matches!(encrypt_key.try_encrypt_str_with_iv(&zeros, &OpMode::CTR, &nonce), Err(EncryptError::ModeNotAllowed)));
+        assert!(matches!(encrypt_key.encrypt_str(&zeros, &OpMode::CTR), Err(EncryptError::ModeNotAllowed)));
+    }
+}
@@ -0,0 +1,268 @@
+use block::encrypt_block8;
+use errors::AeadError;
+use keys::{Key, RoundKeys};
+use policy;
+use tag::{tags_equal, TagLen};
+#[cfg(feature = "zeroize")]
+use keys::wipe;
+
+/// CCM authenticated encryption (counter mode with CBC-MAC).
+///
+/// CCM (RFC 3610) encrypts the plaintext in counter mode and
+/// authenticates the nonce, the associated data and the plaintext with
+/// a CBC-MAC. The associated data is authenticated but not encrypted,
+/// which is useful for frame headers that have to stay readable.
+///
+/// With PRESENT's 64-bit block, the nonce and the message length field
+/// share 7 bytes of the first block. Longer nonces therefore limit the
+/// maximum message length: nonces of 3, 4 or 5 bytes allow messages of
+/// up to 2^32 - 1, 2^24 - 1 and 2^16 - 1 bytes respectively. Tags must
+/// have an even length.
+///
+/// Never encrypt two messages with the same nonce and key.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Ccm, Key128Bit, TagLen};
+/// let ccm = Ccm::new(&Key128Bit::new([0x42; 16]), 5, TagLen::new(4).unwrap()).unwrap();
+/// let nonce = [0x00, 0x00, 0x00, 0x01, 0x07];
+///
+/// let sealed = ccm.encrypt(&nonce, b"header", b"sensor reading").unwrap();
+/// assert_eq!(sealed.len(), 14 + 4);
+/// assert_eq!(ccm.decrypt(&nonce, b"header", &sealed).unwrap(), b"sensor reading");
+/// ```
+pub struct Ccm {
+    round_keys: RoundKeys,
+    nonce_len: usize,
+    tag_len: TagLen,
+}
+
+impl Ccm {
+    /// Constructs a new CCM instance.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AeadError::InvalidNonceLength` if `nonce_len` is not
+    /// between 3 and 5, and `AeadError::InvalidTagLength` if the tag
+    /// length is odd.
+    pub fn new<K: Key>(key: &K, nonce_len: usize, tag_len: TagLen) -> Result<Self, AeadError> {
+        if !(3..=5).contains(&nonce_len) {
+            return Err(AeadError::InvalidNonceLength(nonce_len));
+        }
+        if !tag_len.bytes().is_multiple_of(2) {
+            return Err(AeadError::InvalidTagLength(tag_len.bytes()));
+        }
+
+        policy::check_tag_len(tag_len);
+        Ok(Ccm { round_keys: key.generate_round_keys(), nonce_len, tag_len })
+    }
+
+    /// Encrypts and authenticates a message.
+    ///
+    /// Returns the ciphertext with the tag appended.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AeadError::InvalidNonceLength` if the nonce does not
+    /// have the configured length, and `AeadError::MessageTooLong` if
+    /// the plaintext does not fit the length field.
+    pub fn encrypt(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AeadError> {
+        self.check_params(nonce, plaintext.len())?;
+
+        let tag = self.cbc_mac(nonce, aad, plaintext);
+        let mut output = self.ctr(nonce, plaintext);
+        output.extend_from_slice(&self.encrypt_tag(nonce, &tag));
+        Ok(output)
+    }
+
+    /// Verifies and decrypts a message produced by
+    /// [`encrypt()`](#method.encrypt).
+    ///
+    /// # Errors
+    ///
+    /// Returns `AeadError::AuthenticationFailed` if the tag does not
+    /// match, plus the errors of `encrypt()` for invalid parameters.
+    pub fn decrypt(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AeadError> {
+        let tag_len = self.tag_len.bytes();
+        if ciphertext.len() < tag_len {
+            return Err(AeadError::CiphertextTooShort(ciphertext.len()));
+        }
+        let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - tag_len);
+        self.check_params(nonce, ciphertext.len())?;
+
+        let plaintext = self.ctr(nonce, ciphertext);
+        let expected = self.encrypt_tag(nonce, &self.cbc_mac(nonce, aad, &plaintext));
+        if !tags_equal(&expected, tag) {
+            return Err(AeadError::AuthenticationFailed);
+        }
+
+        Ok(plaintext)
+    }
+
+    /// Returns the configured nonce length in bytes.
+    pub fn nonce_len(&self) -> usize {
+        self.nonce_len
+    }
+
+    /// Returns the configured tag length.
+    pub fn tag_len(&self) -> TagLen {
+        self.tag_len
+    }
+
+    /// Size of the length field in bytes ("L" in RFC 3610).
+    fn length_size(&self) -> usize {
+        7 - self.nonce_len
+    }
+
+    fn check_params(&self, nonce: &[u8], len: usize) -> Result<(), AeadError> {
+        if nonce.len() != self.nonce_len {
+            return Err(AeadError::InvalidNonceLength(nonce.len()));
+        }
+        if (len as u64) >> (8 * self.length_size()) != 0 {
+            return Err(AeadError::MessageTooLong(len));
+        }
+        Ok(())
+    }
+
+    /// Formats a block of flags, nonce and a big-endian number.
+    fn format_block(&self, flags: u8, nonce: &[u8], number: u64) -> [u8; 8] {
+        let mut block = [0u8; 8];
+        block[0] = flags;
+        block[1..=self.nonce_len].copy_from_slice(nonce);
+        block[(1 + self.nonce_len)..].copy_from_slice(&number.to_be_bytes()[(8 - self.length_size())..]);
+        block
+    }
+
+    fn cbc_mac(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> [u8; 8] {
+        let adata = if aad.is_empty() { 0 } else { 0x40 };
+        let flags = adata | (((self.tag_len.bytes() as u8 - 2) / 2) << 3) | (self.length_size() as u8 - 1);
+
+        let mut input = self.format_block(flags, nonce, plaintext.len() as u64).to_vec();
+        if !aad.is_empty() {
+            let len = aad.len() as u64;
+            if len < 0xFF00 {
+                input.extend_from_slice(&(len as u16).to_be_bytes());
+            } else if len >> 32 == 0 {
+                input.extend_from_slice(&[0xFF, 0xFE]);
+                input.extend_from_slice(&(len as u32).to_be_bytes());
+            } else {
+                input.extend_from_slice(&[0xFF, 0xFF]);
+                input.extend_from_slice(&len.to_be_bytes());
+            }
+            input.extend_from_slice(aad);
+            pad_to_block(&mut input);
+        }
+        input.extend_from_slice(plaintext);
+        pad_to_block(&mut input);
+
+        let mut state = [0u8; 8];
+        for chunk in input.chunks(8) {
+            for (s, byte) in state.iter_mut().zip(chunk.iter()) {
+                *s ^= byte;
+            }
+            encrypt_block8(&mut state, &self.round_keys);
+        }
+        state
+    }
+
+    fn keystream_block(&self, nonce: &[u8], counter: u64) -> [u8; 8] {
+        let mut block = self.format_block(self.length_size() as u8 - 1, nonce, counter);
+        encrypt_block8(&mut block, &self.round_keys);
+        block
+    }
+
+    /// Applies counter mode, starting with counter 1.
+    fn ctr(&self, nonce: &[u8], input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(input.len());
+        for (i, chunk) in input.chunks(8).enumerate() {
+            let keystream = self.keystream_block(nonce, i as u64 + 1);
+            output.extend(chunk.iter().zip(keystream.iter()).map(|(x, k)| x ^ k));
+        }
+        output
+    }
+
+    /// Encrypts and truncates the CBC-MAC with the first counter block.
+    fn encrypt_tag(&self, nonce: &[u8], mac: &[u8; 8]) -> Vec<u8> {
+        let keystream = self.keystream_block(nonce, 0);
+        mac.iter().zip(keystream.iter()).take(self.tag_len.bytes()).map(|(x, k)| x ^ k).collect()
+    }
+}
+
+fn pad_to_block(data: &mut Vec<u8>) {
+    let len = data.len().div_ceil(8) * 8;
+    data.resize(len, 0);
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Ccm {
+    fn drop(&mut self) {
+        wipe(&mut self.round_keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::Block;
+    use keys::Key80Bit;
+
+    fn ccm(nonce_len: usize, tag_len: usize) -> Ccm {
+        Ccm::new(&Key80Bit::new([0x3A; 10]), nonce_len, TagLen::new(tag_len).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parameter_validation() {
+        let key = Key80Bit::new([0x3A; 10]);
+        assert!(Ccm::new(&key, 2, TagLen::FULL).is_err());
+        assert!(Ccm::new(&key, 6, TagLen::FULL).is_err());
+        assert_eq!(Ccm::new(&key, 5, TagLen::new(5).unwrap()).err(), Some(AeadError::InvalidTagLength(5)));
+
+        let ccm = ccm(5, 8);
+        assert_eq!(ccm.encrypt(&[0; 4], b"", b"").err(), Some(AeadError::InvalidNonceLength(4)));
+        assert_eq!(ccm.encrypt(&[0; 5], b"", &[0; 65536]).err(), Some(AeadError::MessageTooLong(65536)));
+        assert_eq!(ccm.decrypt(&[0; 5], b"", &[0; 7]).err(), Some(AeadError::CiphertextTooShort(7)));
+    }
+
+    #[test]
+    fn test_matches_definition() {
+        let ccm = ccm(5, 8);
+        let nonce = [1, 2, 3, 4, 5];
+        let sealed = ccm.encrypt(&nonce, b"", b"8 bytes!").unwrap();
+        let key = Key80Bit::new([0x3A; 10]);
+
+        // CBC-MAC over B0 and the plaintext block
+        let mut mac = Block::new(0x1901020304050008);
+        mac.encrypt(&key);
+        let mut mac = Block::new(mac.get_state() ^ u64::from_be_bytes(*b"8 bytes!"));
+        mac.encrypt(&key);
+
+        let mut s0 = Block::new(0x0101020304050000);
+        s0.encrypt(&key);
+        let mut s1 = Block::new(0x0101020304050001);
+        s1.encrypt(&key);
+
+        assert_eq!(&sealed[..8], &(u64::from_be_bytes(*b"8 bytes!") ^ s1.get_state()).to_be_bytes());
+        assert_eq!(&sealed[8..], &(mac.get_state() ^ s0.get_state()).to_be_bytes());
+    }
+
+    #[test]
+    fn test_roundtrip_and_tampering() {
+        let nonce = [9, 8, 7, 6];
+        let aad = vec![0xAA; 300];
+        for &tag_len in [4, 6, 8].iter() {
+            let ccm = ccm(4, tag_len);
+            for len in 0..20 {
+                let plaintext: Vec<u8> = (0..len as u8).collect();
+                let mut sealed = ccm.encrypt(&nonce, &aad, &plaintext).unwrap();
+                assert_eq!(sealed.len(), len + tag_len);
+                assert_eq!(ccm.decrypt(&nonce, &aad, &sealed).unwrap(), plaintext);
+
+                assert_eq!(ccm.decrypt(&nonce, &aad[1..], &sealed), Err(AeadError::AuthenticationFailed));
+                assert_eq!(ccm.decrypt(&[9, 8, 7, 7], &aad, &sealed), Err(AeadError::AuthenticationFailed));
+                sealed[0] ^= 0x80;
+                assert_eq!(ccm.decrypt(&nonce, &aad, &sealed), Err(AeadError::AuthenticationFailed));
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "base64")]
+use base64ct::{Base64, Encoding};
+
+use block::Block;
+use errors::{DecryptError, ParseError};
+use keys::Key;
+use modes::OpMode;
+use {decrypt_bytes, decrypt_str, decrypt_str_lossy};
+
+/// The result of an encryption: the ciphertext together with the mode
+/// of operation and the IV it was encrypted with.
+///
+/// Keeping the three together means the IV cannot be dropped or paired
+/// with the wrong ciphertext by accident. To store or transmit the
+/// ciphertext, take it apart with [`into_parts()`](#method.into_parts),
+/// or use an [`Envelope`](struct.Envelope.html), which also encodes the
+/// mode.
+///
+/// As text, a ciphertext is formatted as the mode, the IV and the bytes
+/// separated by colons, e.g. `cbc:0123456789abcdef:5a3c...`, with an
+/// empty IV for ECB. It can be parsed back with `str::parse()`.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_str, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let ciphertext = encrypt_str("Hello, world!", &key, &OpMode::CBC).unwrap();
+/// assert!(ciphertext.init_vec().is_some());
+/// assert_eq!(ciphertext.decrypt_str(&key).unwrap(), "Hello, world!");
+/// ```
+pub struct Ciphertext {
+    bytes: Vec<u8>,
+    mode: OpMode,
+    init_vec: Option<Block>,
+}
+
+impl Ciphertext {
+    /// Bundles a ciphertext with the mode of operation and the IV it was
+    /// encrypted with, e.g. after receiving them separately.
+    pub fn new(bytes: Vec<u8>, mode: OpMode, init_vec: Option<Block>) -> Self {
+        Ciphertext { bytes, mode, init_vec }
+    }
+
+    /// Returns the encrypted bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the mode of operation.
+    pub fn mode(&self) -> OpMode {
+        self.mode
+    }
+
+    /// Returns the IV, or `None` if the mode of operation does not use
+    /// one.
+    pub fn init_vec(&self) -> Option<&Block> {
+        self.init_vec.as_ref()
+    }
+
+    /// Decrypts the ciphertext. See [`decrypt_bytes`](fn.decrypt_bytes.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `DecryptError` if an error occurred during
+    /// decryption, e.g. because the wrong key was used.
+    pub fn decrypt<K: Key>(&self, key: &K) -> Result<Vec<u8>, DecryptError> {
+        decrypt_bytes(&self.bytes, key, &self.mode, self.copy_init_vec())
+    }
+
+    /// Decrypts the ciphertext to a string. See
+    /// [`decrypt_str`](fn.decrypt_str.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `DecryptError` if an error occurred during
+    /// decryption or the plaintext is not valid UTF-8.
+    pub fn decrypt_str<K: Key>(&self, key: &K) -> Result<String, DecryptError> {
+        decrypt_str(&self.bytes, key, &self.mode, self.copy_init_vec())
+    }
+
+    /// Decrypts the ciphertext to a string, replacing invalid UTF-8. See
+    /// [`decrypt_str_lossy`](fn.decrypt_str_lossy.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `DecryptError` if an error occurred during
+    /// decryption.
+    pub fn decrypt_str_lossy<K: Key>(&self, key: &K) -> Result<String, DecryptError> {
+        decrypt_str_lossy(&self.bytes, key, &self.mode, self.copy_init_vec())
+    }
+
+    /// Returns the encrypted bytes, discarding the IV.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Splits the ciphertext into the encrypted bytes and the IV, in
+    /// the form the decryption functions take them.
+    pub fn into_parts(self) -> (Vec<u8>, Option<Block>) {
+        (self.bytes, self.init_vec)
+    }
+
+    /// Encodes the IV, if any, followed by the encrypted bytes as padded
+    /// Base64 in the standard alphabet, e.g. for JSON or HTTP headers.
+    /// The mode is not encoded.
+    #[cfg(feature = "base64")]
+    pub fn to_base64(&self) -> String {
+        let mut bytes = Vec::with_capacity(8 + self.bytes.len());
+        if let Some(ref init_vec) = self.init_vec {
+            bytes.extend_from_slice(&init_vec.to_bytes());
+        }
+        bytes.extend_from_slice(&self.bytes);
+        Base64::encode_string(&bytes)
+    }
+
+    /// Decodes a ciphertext encoded with [`to_base64()`](#method.to_base64).
+    /// The mode must be the one the ciphertext was encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::InvalidBase64` if the string is not valid
+    /// Base64 and `ParseError::InvalidFormat` if it is too short to hold
+    /// the IV of the mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{encrypt_str, Ciphertext, Key80Bit, OpMode};
+    /// let key = Key80Bit::new([0xFF; 10]);
+    /// let encoded = encrypt_str("Hello, world!", &key, &OpMode::CBC).unwrap().to_base64();
+    ///
+    /// let ciphertext = Ciphertext::from_base64(&encoded, OpMode::CBC).unwrap();
+    /// assert_eq!(ciphertext.decrypt_str(&key).unwrap(), "Hello, world!");
+    /// ```
+    #[cfg(feature = "base64")]
+    pub fn from_base64(encoded: &str, mode: OpMode) -> Result<Self, ParseError> {
+        let mut bytes = Base64::decode_vec(encoded).map_err(|_| ParseError::InvalidBase64)?;
+        let init_vec = match mode {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            OpMode::ECB => None,
+            OpMode::CBC | OpMode::PCBC | OpMode::CFB | OpMode::CTR => {
+                if bytes.len() < 8 {
+                    return Err(ParseError::InvalidFormat);
+                }
+                let mut init_vec = [0u8; 8];
+                init_vec.copy_from_slice(&bytes[..8]);
+                bytes.drain(..8);
+                Some(Block::from_bytes(&init_vec))
+            },
+        };
+        Ok(Ciphertext { bytes, mode, init_vec })
+    }
+
+    fn copy_init_vec(&self) -> Option<Block> {
+        self.init_vec.as_ref().map(|init_vec| Block::new(init_vec.get_state()))
+    }
+}
+
+impl fmt::Display for Ciphertext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", mode_name(&self.mode))?;
+        if let Some(ref init_vec) = self.init_vec {
+            write!(f, "{}", init_vec)?;
+        }
+        f.write_str(":")?;
+        for byte in self.bytes.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Ciphertext {
+    type Err = ParseError;
+
+    /// Parses a ciphertext formatted with `Display`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::InvalidFormat` if a field is missing, the
+    /// mode is unknown or the IV does not match the mode, and the other
+    /// variants of `ParseError` if a field is not valid hexadecimal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{encrypt_str, Ciphertext, Key80Bit, OpMode};
+    /// let key = Key80Bit::new([0xFF; 10]);
+    /// let text = encrypt_str("Hello, world!", &key, &OpMode::CBC).unwrap().to_string();
+    ///
+    /// let ciphertext: Ciphertext = text.parse().unwrap();
+    /// assert_eq!(ciphertext.decrypt_str(&key).unwrap(), "Hello, world!");
+    /// ```
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut fields = text.splitn(3, ':');
+        let (mode, init_vec, bytes) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(mode), Some(init_vec), Some(bytes)) => (mode, init_vec, bytes),
+            _ => return Err(ParseError::InvalidFormat),
+        };
+
+        let mode = mode_from_name(mode).ok_or(ParseError::InvalidFormat)?;
+        let init_vec = match (mode, init_vec.is_empty()) {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            (OpMode::ECB, true) => None,
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            (OpMode::ECB, false) => return Err(ParseError::InvalidFormat),
+            (_, true) => return Err(ParseError::InvalidFormat),
+            (_, false) => Some(init_vec.parse()?),
+        };
+        Ok(Ciphertext { bytes: decode_hex(bytes)?, mode, init_vec })
+    }
+}
+
+fn mode_name(mode: &OpMode) -> &'static str {
+    match *mode {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        OpMode::ECB => "ecb",
+        OpMode::CBC => "cbc",
+        OpMode::PCBC => "pcbc",
+        OpMode::CFB => "cfb",
+        OpMode::CTR => "ctr",
+    }
+}
+
+fn mode_from_name(name: &str) -> Option<OpMode> {
+    match name {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        "ecb" => Some(OpMode::ECB),
+        "cbc" => Some(OpMode::CBC),
+        "pcbc" => Some(OpMode::PCBC),
+        "cfb" => Some(OpMode::CFB),
+        "ctr" => Some(OpMode::CTR),
+        _ => None,
+    }
+}
+
+/// Decodes hexadecimal digits of either case into bytes.
+fn decode_hex(hex: &str) -> Result<Vec<u8>, ParseError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(ParseError::InvalidLength(hex.len()));
+    }
+    if !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(ParseError::InvalidHex);
+    }
+    Ok((0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modes::all_modes;
+    use keys::Key80Bit;
+    use encrypt_bytes;
+
+    #[test]
+    fn test_ciphertext_decrypts_itself() {
+        let key = Key80Bit::new([0x42; 10]);
+        let ciphertext = encrypt_bytes(b"sixteen bytes!!!", &key, &OpMode::PCBC).unwrap();
+        assert_eq!(ciphertext.as_bytes().len(), 24);
+        assert_eq!(ciphertext.decrypt(&key).unwrap(), b"sixteen bytes!!!");
+        assert_ne!(
+            ciphertext.decrypt(&Key80Bit::new([0x43; 10])).ok(),
+            Some(b"sixteen bytes!!!".to_vec())
+        );
+
+        let (bytes, init_vec) = ciphertext.into_parts();
+        let rebuilt = Ciphertext::new(bytes, OpMode::PCBC, init_vec);
+        assert_eq!(rebuilt.decrypt(&key).unwrap(), b"sixteen bytes!!!");
+    }
+
+    #[test]
+    fn test_decrypt_str_lossy() {
+        let key = Key80Bit::new([0x42; 10]);
+        let ciphertext = encrypt_bytes(b"caf\xC3 au lait", &key, &OpMode::CFB).unwrap();
+        assert!(ciphertext.decrypt_str(&key).is_err());
+        assert_eq!(ciphertext.decrypt_str_lossy(&key).unwrap(), "caf\u{FFFD} au lait");
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let key = Key80Bit::new([0x42; 10]);
+        for mode in all_modes().iter() {
+            let text = encrypt_bytes(b"hex armor", &key, mode).unwrap().to_string();
+            let parsed: Ciphertext = text.parse().unwrap();
+            assert_eq!(parsed.to_string(), text);
+            assert_eq!(parsed.decrypt(&key).unwrap(), b"hex armor");
+        }
+
+        assert_eq!("cbc::00".parse::<Ciphertext>().err(), Some(ParseError::InvalidFormat));
+        assert_eq!("ofb::00".parse::<Ciphertext>().err(), Some(ParseError::InvalidFormat));
+        assert_eq!("cbc:00".parse::<Ciphertext>().err(), Some(ParseError::InvalidFormat));
+        assert_eq!("cbc:0000000000000000:0g".parse::<Ciphertext>().err(), Some(ParseError::InvalidHex));
+        assert_eq!("cbc:0000000000000000:000".parse::<Ciphertext>().err(), Some(ParseError::InvalidLength(3)));
+    }
+
+    #[cfg(feature = "insecure-modes")]
+    #[allow(deprecated)]
+    #[test]
+    fn test_ecb_hex_form() {
+        let ecb = Ciphertext::new(vec![0x00, 0xAB], OpMode::ECB, None);
+        assert_eq!(ecb.to_string(), "ecb::00ab");
+        assert_eq!("ecb:0000000000000000:00".parse::<Ciphertext>().err(), Some(ParseError::InvalidFormat));
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_base64_roundtrip() {
+        let key = Key80Bit::new([0x42; 10]);
+        for mode in all_modes().iter() {
+            let ciphertext = encrypt_bytes(b"base64 armor", &key, mode).unwrap();
+            let decoded = Ciphertext::from_base64(&ciphertext.to_base64(), *mode).unwrap();
+            assert_eq!(decoded.to_string(), ciphertext.to_string());
+        }
+
+        assert_eq!(Ciphertext::from_base64("AAAA", OpMode::CBC).err(), Some(ParseError::InvalidFormat));
+        assert_eq!(Ciphertext::from_base64("AAA*", OpMode::CBC).err(), Some(ParseError::InvalidBase64));
+    }
+}
@@ -0,0 +1,370 @@
+//! Conformance checks for custom `Key` implementations.
+//!
+//! Implementations of the [`Key`](../trait.Key.html) trait that are
+//! backed by hardware, an HSM or a port of the key schedule can be
+//! checked against the official test vectors and this crate's own
+//! implementation with [`run`](fn.run.html).
+//!
+//! # Examples
+//!
+//! ```
+//! use present::Key80Bit;
+//! use present::conformance;
+//!
+//! let mut value = [0u8; 10];
+//! let kats = conformance::official_kats_80bit(|bytes| {
+//!     value.copy_from_slice(bytes);
+//!     Key80Bit::new(value)
+//! });
+//! assert!(conformance::run(&kats).passed());
+//! ```
+
+use std::fmt::Write;
+
+use block::Block;
+use errors::KeyParseError;
+use keys::{Key, Key80Bit, Key128Bit, RoundKeys};
+use modes::{self, OpMode};
+use {decrypt_str, encrypt_with_mode, encrypt_str};
+
+/// Test vectors for 80-bit keys from the paper, as (key, plaintext, ciphertext).
+pub const VECTORS_80BIT: [([u8; 10], u64, u64); 4] = [
+    ([0x00; 10], 0x0000000000000000, 0x5579C1387B228445),
+    ([0xFF; 10], 0x0000000000000000, 0xE72C46C0F5945049),
+    ([0x00; 10], 0xFFFFFFFFFFFFFFFF, 0xA112FFC72F68417B),
+    ([0xFF; 10], 0xFFFFFFFFFFFFFFFF, 0x3333DCD3213210D2),
+];
+
+/// Test vectors for 128-bit keys, as (key, plaintext, ciphertext).
+pub const VECTORS_128BIT: [([u8; 16], u64, u64); 4] = [
+    ([0x00; 16], 0x0000000000000000, 0x96DB702A2E6900AF),
+    ([0xFF; 16], 0x0000000000000000, 0x13238C710272A5D8),
+    ([0x00; 16], 0xFFFFFFFFFFFFFFFF, 0x3C6019E5E5EDD563),
+    ([0xFF; 16], 0xFFFFFFFFFFFFFFFF, 0x628D9FBD4218E5B4),
+];
+
+/// Plaintexts used for the mode round trips, chosen to cover empty
+/// input, partial blocks and exact block boundaries.
+const ROUND_TRIP_TEXTS: [&str; 5] = ["", "a", "exactly8", "nine byte", "a somewhat longer text →"];
+
+/// A single known-answer test.
+pub struct Kat<K: Key> {
+    /// The key under test.
+    pub key: K,
+    /// The raw bytes of the key, used to compare the key schedule
+    /// against this crate's implementation. Must be 10 or 16 bytes
+    /// long for the comparison to take place.
+    pub key_bytes: Vec<u8>,
+    /// The plaintext block.
+    pub plaintext: u64,
+    /// The expected ciphertext block.
+    pub ciphertext: u64,
+}
+
+/// A single failed check.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Failure {
+    /// Encrypting the plaintext of the KAT with the given index did not
+    /// produce the expected ciphertext.
+    Encrypt { kat: usize, expected: u64, actual: u64 },
+    /// Decrypting the ciphertext of the KAT with the given index did not
+    /// produce the expected plaintext.
+    Decrypt { kat: usize, expected: u64, actual: u64 },
+    /// The key schedule returned different round keys on repeated calls.
+    NonDeterministicSchedule { kat: usize },
+    /// A round key differs from the reference key schedule.
+    RoundKey { kat: usize, round: usize, expected: u64, actual: u64 },
+    /// Encrypting and decrypting a string in the given mode did not
+    /// return the original string.
+    RoundTrip { kat: usize, mode: &'static str, text_len: usize },
+}
+
+/// Summary of a conformance run.
+#[derive(Debug, Default)]
+pub struct ConformanceReport {
+    /// Number of checks that were performed.
+    pub checks: usize,
+    /// All checks that failed.
+    pub failures: Vec<Failure>,
+}
+
+impl ConformanceReport {
+    /// Returns `true` if all checks passed.
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn check(&mut self, ok: bool, failure: Failure) {
+        self.checks += 1;
+        if !ok {
+            self.failures.push(failure);
+        }
+    }
+}
+
+/// Builds known-answer tests from the 80-bit test vectors.
+///
+/// `make_key` receives the 10 key bytes of each vector and must return
+/// the corresponding key of the implementation under test.
+pub fn official_kats_80bit<K: Key, F: FnMut(&[u8]) -> K>(mut make_key: F) -> Vec<Kat<K>> {
+    VECTORS_80BIT.iter().map(|&(ref key, plaintext, ciphertext)| Kat {
+        key: make_key(key),
+        key_bytes: key.to_vec(),
+        plaintext,
+        ciphertext,
+    }).collect()
+}
+
+/// Builds known-answer tests from the 128-bit test vectors.
+///
+/// `make_key` receives the 16 key bytes of each vector and must return
+/// the corresponding key of the implementation under test.
+pub fn official_kats_128bit<K: Key, F: FnMut(&[u8]) -> K>(mut make_key: F) -> Vec<Kat<K>> {
+    VECTORS_128BIT.iter().map(|&(ref key, plaintext, ciphertext)| Kat {
+        key: make_key(key),
+        key_bytes: key.to_vec(),
+        plaintext,
+        ciphertext,
+    }).collect()
+}
+
+/// Runs the conformance checks for all given known-answer tests.
+///
+/// For each KAT, this checks single block encryption and decryption,
+/// that the key schedule is deterministic and (if the key bytes have a
+/// supported length) identical to this crate's key schedule, and that
+/// strings of various lengths survive a round trip in every mode.
+pub fn run<K: Key>(kats: &[Kat<K>]) -> ConformanceReport {
+    let mut report = ConformanceReport::default();
+
+    for (index, kat) in kats.iter().enumerate() {
+        let mut block = Block::new(kat.plaintext);
+        block.encrypt(&kat.key);
+        report.check(block.get_state() == kat.ciphertext, Failure::Encrypt {
+            kat: index,
+            expected: kat.ciphertext,
+            actual: block.get_state(),
+        });
+
+        let mut block = Block::new(kat.ciphertext);
+        block.decrypt(&kat.key);
+        report.check(block.get_state() == kat.plaintext, Failure::Decrypt {
+            kat: index,
+            expected: kat.plaintext,
+            actual: block.get_state(),
+        });
+
+        check_schedule(&mut report, index, kat);
+
+        for text in ROUND_TRIP_TEXTS.iter() {
+            for mode in modes::all_modes().iter() {
+                let name = mode_name(mode);
+                let (ciphertext, iv) = encrypt_str(text, &kat.key, mode).unwrap().into_parts();
+                let ok = match decrypt_str(&ciphertext, &kat.key, mode, iv) {
+                    Ok(ref decrypted) => decrypted == text,
+                    Err(_) => false,
+                };
+                report.check(ok, Failure::RoundTrip { kat: index, mode: name, text_len: text.len() });
+            }
+        }
+    }
+
+    report
+}
+
+/// Generates known-answer vectors as a JSON array.
+///
+/// One vector is generated for every combination of the given keys,
+/// plaintexts and modes. Keys must be 10 or 16 bytes long. Modes that
+/// need an IV get a fresh random one for every vector. Each vector is
+/// an object with the hex-encoded `key`, `plaintext`, `iv` (or `null`)
+/// and `ciphertext`, plus the `key_bits` and the `mode` name. This lets
+/// teams porting PRESENT to other platforms produce tailored
+/// conformance data from this implementation.
+///
+/// # Errors
+///
+/// Returns `KeyParseError::InvalidLength` if a key has an unsupported length.
+///
+/// # Examples
+///
+/// ```
+/// use present::OpMode;
+/// use present::conformance::generate_vectors_json;
+///
+/// # #[cfg(feature = "insecure-modes")]
+/// # #[allow(deprecated)]
+/// # fn main() {
+/// let json = generate_vectors_json(&[&[0u8; 10]], &[b""], &[OpMode::ECB]).unwrap();
+/// assert!(json.contains("\"ciphertext\": \"65585a6ce7312131\""));
+/// # }
+/// # #[cfg(not(feature = "insecure-modes"))]
+/// # fn main() {}
+/// ```
+pub fn generate_vectors_json(keys: &[&[u8]], plaintexts: &[&[u8]], modes: &[OpMode]) -> Result<String, KeyParseError> {
+    let mut json = String::from("[");
+
+    for key in keys.iter() {
+        for plaintext in plaintexts.iter() {
+            for mode in modes.iter() {
+                let iv = match *mode {
+                    #[cfg(feature = "insecure-modes")]
+                    #[allow(deprecated)]
+                    OpMode::ECB => None,
+                    OpMode::CBC | OpMode::PCBC | OpMode::CFB | OpMode::CTR => Some(modes::random_iv().expect("Unable to obtain RNG from OS")),
+                };
+
+                let ciphertext = match key.len() {
+                    10 => {
+                        let mut value = [0u8; 10];
+                        value.copy_from_slice(key);
+                        encrypt_with_mode(plaintext, &Key80Bit::new(value), mode, iv.as_ref())
+                    },
+                    16 => {
+                        let mut value = [0u8; 16];
+                        value.copy_from_slice(key);
+                        encrypt_with_mode(plaintext, &Key128Bit::new(value), mode, iv.as_ref())
+                    },
+                    len => return Err(KeyParseError::InvalidLength(len)),
+                };
+
+                if json.len() > 1 {
+                    json.push(',');
+                }
+                json.push_str("\n  {");
+                write!(json, "\"key_bits\": {}, ", key.len() * 8).unwrap();
+                write!(json, "\"key\": \"{}\", ", to_hex(key)).unwrap();
+                write!(json, "\"mode\": \"{}\", ", mode_name(mode)).unwrap();
+                match iv {
+                    Some(ref iv) => write!(json, "\"iv\": \"{}\", ", to_hex(&iv.to_bytes())).unwrap(),
+                    None => json.push_str("\"iv\": null, "),
+                }
+                write!(json, "\"plaintext\": \"{}\", ", to_hex(plaintext)).unwrap();
+                write!(json, "\"ciphertext\": \"{}\"", to_hex(&ciphertext)).unwrap();
+                json.push('}');
+            }
+        }
+    }
+
+    json.push_str("\n]\n");
+    Ok(json)
+}
+
+fn mode_name(mode: &OpMode) -> &'static str {
+    match *mode {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        OpMode::ECB => "ECB",
+        OpMode::CBC => "CBC",
+        OpMode::PCBC => "PCBC",
+        OpMode::CFB => "CFB",
+        OpMode::CTR => "CTR",
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes.iter() {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+fn check_schedule<K: Key>(report: &mut ConformanceReport, index: usize, kat: &Kat<K>) {
+    let round_keys = kat.key.generate_round_keys();
+    let repeated = kat.key.generate_round_keys();
+    let deterministic = round_keys == repeated;
+    report.check(deterministic, Failure::NonDeterministicSchedule { kat: index });
+
+    let reference = match reference_round_keys(&kat.key_bytes) {
+        Some(r) => r,
+        None => return,
+    };
+
+    for (round, (expected, actual)) in reference.iter().zip(round_keys.iter()).enumerate() {
+        report.check(expected.value == actual.value, Failure::RoundKey {
+            kat: index,
+            round,
+            expected: expected.value,
+            actual: actual.value,
+        });
+    }
+}
+
+fn reference_round_keys(key_bytes: &[u8]) -> Option<RoundKeys> {
+    match key_bytes.len() {
+        10 => {
+            let mut value = [0u8; 10];
+            value.copy_from_slice(key_bytes);
+            Some(Key80Bit::new(value).generate_round_keys())
+        },
+        16 => {
+            let mut value = [0u8; 16];
+            value.copy_from_slice(key_bytes);
+            Some(Key128Bit::new(value).generate_round_keys())
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::RoundKey;
+
+    /// A key whose schedule is off by one bit in the last round.
+    struct BrokenKey(Key80Bit);
+
+    impl Key for BrokenKey {
+        fn generate_round_keys(&self) -> RoundKeys {
+            let mut round_keys = self.0.generate_round_keys();
+            round_keys[31] = RoundKey { value: round_keys[31].value ^ 1 };
+            round_keys
+        }
+    }
+
+    #[test]
+    fn test_builtin_keys_pass() {
+        let kats = official_kats_128bit(|bytes| {
+            let mut value = [0u8; 16];
+            value.copy_from_slice(bytes);
+            Key128Bit::new(value)
+        });
+        let report = run(&kats);
+        assert!(report.passed());
+        assert_eq!(report.checks, 4 * (2 + 1 + 32 + ROUND_TRIP_TEXTS.len() * modes::all_modes().len()));
+    }
+
+    #[cfg(feature = "insecure-modes")]
+    #[allow(deprecated)]
+    #[test]
+    fn test_generated_vectors_match_encryption() {
+        let json = generate_vectors_json(&[&[0xFF; 16]], &[b"vector", b""], &[OpMode::ECB, OpMode::CBC]).unwrap();
+        assert_eq!(json.matches("\"key_bits\": 128").count(), 4);
+        assert_eq!(json.matches("\"iv\": null").count(), 2);
+        assert_eq!(json.matches("\"mode\": \"CBC\"").count(), 2);
+
+        let (ciphertext, _) = encrypt_str("vector", &Key128Bit::new([0xFF; 16]), &OpMode::ECB).unwrap().into_parts();
+        assert!(json.contains(&format!("\"plaintext\": \"766563746f72\", \"ciphertext\": \"{}\"", to_hex(&ciphertext))));
+    }
+
+    #[test]
+    fn test_generate_vectors_rejects_invalid_keys() {
+        let result = generate_vectors_json(&[&[0u8; 12]], &[b""], &[OpMode::CBC]);
+        assert_eq!(result.err(), Some(KeyParseError::InvalidLength(12)));
+    }
+
+    #[test]
+    fn test_broken_key_is_reported() {
+        let kats = official_kats_80bit(|bytes| {
+            let mut value = [0u8; 10];
+            value.copy_from_slice(bytes);
+            BrokenKey(Key80Bit::new(value))
+        });
+        let report = run(&kats[..1]);
+        assert!(!report.passed());
+        assert!(report.failures.contains(&Failure::Encrypt { kat: 0, expected: 0x5579C1387B228445, actual: 0x5579C1387B228444 }));
+        assert!(report.failures.iter().any(|f| matches!(*f, Failure::RoundKey { round: 31, .. })));
+    }
+}
@@ -0,0 +1,789 @@
+use rand::RngCore;
+
+#[cfg(not(feature = "simd"))]
+use bitslice::{encrypt_blocks_bitsliced as encrypt_batches, decrypt_blocks_bitsliced as decrypt_batches};
+use block::Block;
+use errors::{BufferError, DecryptError, FaultError, RngError};
+use keys::{Key, Key80Bit, Key128Bit, RoundKeys};
+#[cfg(feature = "zeroize")]
+use keys::wipe;
+use masked::MaskedSchedule;
+use modes::{ModeOfOperation, OpMode};
+use shuffled::ShuffledSchedule;
+#[cfg(feature = "simd")]
+use simd::{encrypt_blocks_simd as encrypt_batches, decrypt_blocks_simd as decrypt_batches};
+use pair;
+use profile::{self, Stage};
+use {check_ciphertext_len, check_padding, final_block, report_encryption, report_mode};
+
+/// Number of blocks the bitsliced backend processes at once.
+#[cfg(not(feature = "simd"))]
+const BATCH_BLOCKS: usize = 64;
+
+/// Number of blocks the SIMD backend processes at once.
+#[cfg(feature = "simd")]
+const BATCH_BLOCKS: usize = 128;
+
+/// Implements the methods `Present80` and `Present128` share, along with
+/// `Key` and `BlockCipher`. All of them delegate to the cached schedule.
+macro_rules! impl_cipher {
+    ($cipher:ident) => {
+        impl $cipher {
+            /// Encrypts a single block.
+            pub fn encrypt_block(&self, block: &mut Block) {
+                self.expanded.encrypt_block(block);
+            }
+
+            /// Decrypts a single block.
+            pub fn decrypt_block(&self, block: &mut Block) {
+                self.expanded.decrypt_block(block);
+            }
+
+            /// Encrypts a single block and verifies the result.
+            ///
+            /// The ciphertext is decrypted again and compared with the
+            /// plaintext, so a fault injected into either computation, e.g.
+            /// by glitching the supply voltage or clock of a microcontroller,
+            /// is detected instead of releasing a faulty ciphertext that could
+            /// be used for differential fault analysis. This takes twice as
+            /// long as [`encrypt_block()`](#method.encrypt_block).
+            ///
+            /// # Errors
+            ///
+            /// Returns `FaultError::Detected` if the verification fails. The
+            /// block is set to zero in this case.
+            pub fn encrypt_block_checked(&self, block: &mut Block) -> Result<(), FaultError> {
+                self.expanded.encrypt_block_checked(block)
+            }
+
+            /// Decrypts a single block and verifies the result by encrypting
+            /// it again. See
+            /// [`encrypt_block_checked()`](#method.encrypt_block_checked).
+            ///
+            /// # Errors
+            ///
+            /// Returns `FaultError::Detected` if the verification fails. The
+            /// block is set to zero in this case.
+            pub fn decrypt_block_checked(&self, block: &mut Block) -> Result<(), FaultError> {
+                self.expanded.decrypt_block_checked(block)
+            }
+
+            /// Encrypts a batch of independent 8-byte blocks in place.
+            ///
+            /// See [`encrypt_blocks8()`](fn.encrypt_blocks8.html) for details.
+            /// Every full batch of 64 blocks is processed with
+            /// [`encrypt_blocks_bitsliced()`](fn.encrypt_blocks_bitsliced.html)
+            /// instead, or of 128 blocks with
+            /// [`encrypt_blocks_simd()`](fn.encrypt_blocks_simd.html) if the
+            /// `simd` feature is enabled.
+            pub fn encrypt_blocks(&self, blocks: &mut [[u8; 8]]) {
+                self.expanded.encrypt_blocks(blocks.as_flattened_mut());
+            }
+
+            /// Decrypts a batch of independent 8-byte blocks in place.
+            pub fn decrypt_blocks(&self, blocks: &mut [[u8; 8]]) {
+                self.expanded.decrypt_blocks(blocks.as_flattened_mut());
+            }
+
+            /// Encrypts data in place, without allocating.
+            ///
+            /// No padding is added, so for ECB, CBC and PCBC the length of the
+            /// buffer must be a multiple of 8 bytes. CFB accepts any length.
+            /// The IV is ignored by ECB.
+            ///
+            /// # Errors
+            ///
+            /// Returns `BufferError::NotAligned` if the mode requires whole
+            /// blocks and the buffer length is not a multiple of 8 bytes.
+            pub fn encrypt_in_place(&self, buffer: &mut [u8], mode: &OpMode, init_vec: &Block) -> Result<(), BufferError> {
+                self.expanded.encrypt_in_place(buffer, mode, init_vec)
+            }
+
+            /// Decrypts data in place, without allocating.
+            ///
+            /// This is the inverse of
+            /// [`encrypt_in_place()`](#method.encrypt_in_place). No padding is
+            /// checked or removed.
+            ///
+            /// # Errors
+            ///
+            /// Returns `BufferError::NotAligned` if the mode requires whole
+            /// blocks and the buffer length is not a multiple of 8 bytes.
+            pub fn decrypt_in_place(&self, buffer: &mut [u8], mode: &OpMode, init_vec: &Block) -> Result<(), BufferError> {
+                self.expanded.decrypt_in_place(buffer, mode, init_vec)
+            }
+
+            /// Encrypts data into a caller-provided buffer, without allocating.
+            ///
+            /// The ciphertext is the same as the one returned by
+            /// [`try_encrypt_bytes_with_iv()`](fn.try_encrypt_bytes_with_iv.html),
+            /// including the padding. For ECB, CBC and PCBC the output needs
+            /// room for the input rounded up to the next multiple of 8 bytes
+            /// (a full block of padding is added to aligned input), for CFB it
+            /// needs as many bytes as the input. Returns the number of bytes
+            /// written.
+            ///
+            /// # Errors
+            ///
+            /// Returns `BufferError::OutputTooSmall` if the ciphertext does not
+            /// fit into the output buffer.
+            pub fn encrypt_to(&self, input: &[u8], output: &mut [u8], mode: &OpMode, init_vec: &Block) -> Result<usize, BufferError> {
+                self.expanded.encrypt_to(input, output, mode, init_vec)
+            }
+
+            /// Decrypts data into a caller-provided buffer, without allocating.
+            ///
+            /// This is the inverse of [`encrypt_to()`](#method.encrypt_to). The
+            /// padding is only known after decryption, so the output needs room
+            /// for as many bytes as the input. Returns the length of the
+            /// plaintext.
+            ///
+            /// # Errors
+            ///
+            /// Returns `Err` with a `DecryptError` if the ciphertext or its
+            /// padding is invalid, or `DecryptError::OutputTooSmall` if the
+            /// output buffer is shorter than the input.
+            pub fn decrypt_to(&self, input: &[u8], output: &mut [u8], mode: &OpMode, init_vec: &Block) -> Result<usize, DecryptError> {
+                self.expanded.decrypt_to(input, output, mode, init_vec)
+            }
+        }
+
+        impl Key for $cipher {
+            /// Returns a copy of the cached round keys.
+            fn generate_round_keys(&self) -> RoundKeys {
+                self.expanded.round_keys()
+            }
+
+            /// Reuses the cached schedule, keeping its countermeasures.
+            fn expand(&self, _: Sealed) -> Expanded {
+                self.expanded.clone()
+            }
+        }
+
+        impl BlockCipher for $cipher {
+            fn encrypt_block(&self, block: &mut Block) {
+                self.expanded.encrypt_block(block);
+            }
+
+            fn decrypt_block(&self, block: &mut Block) {
+                self.expanded.decrypt_block(block);
+            }
+
+            fn encrypt_blocks(&self, blocks: &mut [u8]) {
+                self.expanded.encrypt_blocks(blocks);
+            }
+
+            fn decrypt_blocks(&self, blocks: &mut [u8]) {
+                self.expanded.decrypt_blocks(blocks);
+            }
+        }
+    };
+}
+
+/// PRESENT with an 80-bit key and a cached key schedule.
+///
+/// [`Block::encrypt()`](struct.Block.html#method.encrypt) expands the
+/// key for every block it processes. This struct expands the key once
+/// on construction, so encrypting many blocks with the same key only
+/// pays for the rounds themselves.
+///
+/// It also implements [`Key`](trait.Key.html), so it can be passed to
+/// the high-level functions like any other key.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Block, Key80Bit, Present80};
+/// let cipher = Present80::new(&Key80Bit::new([0u8; 10]));
+///
+/// let mut block = Block::new(0);
+/// cipher.encrypt_block(&mut block);
+/// assert_eq!(block.get_state(), 0x5579C1387B228445);
+/// cipher.decrypt_block(&mut block);
+/// assert_eq!(block.get_state(), 0);
+/// ```
+#[derive(Clone)]
+pub struct Present80 {
+    expanded: Expanded,
+}
+
+impl Present80 {
+    /// Constructs a new cipher by expanding the given key.
+    pub fn new(key: &Key80Bit) -> Self {
+        Present80 { expanded: Expanded::new(key) }
+    }
+
+    /// Constructs a new cipher that is protected against first-order
+    /// power analysis by boolean masking.
+    ///
+    /// The key schedule runs on masked shares of the key register, and
+    /// every block is processed with fresh random masks and an S-Box
+    /// table recomputed for them, so the power consumption of a single
+    /// operation does not correlate with the key. The masks are
+    /// derived from the given RNG. This is several times slower than
+    /// [`new()`](#method.new) and only pays off on devices an attacker
+    /// can measure, such as smartcards.
+    ///
+    /// The masked schedule is kept when the cipher is passed as a
+    /// [`Key`](trait.Key.html) to the functions for the modes of
+    /// [`OpMode`](enum.OpMode.html), the streams,
+    /// [`EncryptBlocks`](struct.EncryptBlocks.html), [`Ctr`](struct.Ctr.html)
+    /// and [`Cmac`](struct.Cmac.html). All other primitives, such as the
+    /// AEAD modes and XTS, work on the plain round keys, which are
+    /// unmasked for them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RngError::Unavailable` if the RNG fails to provide
+    /// randomness.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate present;
+    /// # extern crate rand;
+    /// use present::{Block, Key80Bit, Present80};
+    /// use rand::rngs::OsRng;
+    ///
+    /// # fn main() {
+    /// let cipher = Present80::new_masked(&Key80Bit::new([0u8; 10]), &mut OsRng).unwrap();
+    ///
+    /// let mut block = Block::new(0);
+    /// cipher.encrypt_block(&mut block);
+    /// assert_eq!(block.get_state(), 0x5579C1387B228445);
+    /// # }
+    /// ```
+    pub fn new_masked<R: RngCore + ?Sized>(key: &Key80Bit, rng: &mut R) -> Result<Self, RngError> {
+        Ok(Present80 { expanded: Expanded::masked(MaskedSchedule::new_80bit(&key.value, rng)?) })
+    }
+
+    /// Constructs a new cipher that processes the S-Box nibbles of
+    /// every round in random order.
+    ///
+    /// Shuffling the operations makes it harder to line up power traces
+    /// for simple and differential power analysis on embedded devices.
+    /// The order is drawn from a generator seeded from the given RNG.
+    /// The output is the same as that of [`new()`](#method.new), at a
+    /// considerable cost in speed. As with
+    /// [`new_masked()`](#method.new_masked), only some of the functions
+    /// taking a [`Key`](trait.Key.html) keep the shuffled schedule.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RngError::Unavailable` if the RNG fails to provide
+    /// randomness.
+    pub fn new_shuffled<R: RngCore + ?Sized>(key: &Key80Bit, rng: &mut R) -> Result<Self, RngError> {
+        Ok(Present80 { expanded: Expanded::shuffled(ShuffledSchedule::new(key.generate_round_keys(), rng)?) })
+    }
+}
+
+impl_cipher!(Present80);
+
+/// PRESENT with a 128-bit key and a cached key schedule.
+///
+/// See [`Present80`](struct.Present80.html) for details.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_bytes, decrypt_bytes, Key128Bit, OpMode, Present128};
+/// let cipher = Present128::new(&Key128Bit::new([0x42; 16]));
+///
+/// let (ciphertext, iv) = encrypt_bytes(b"long message", &cipher, &OpMode::CBC).unwrap().into_parts();
+/// assert_eq!(decrypt_bytes(&ciphertext, &cipher, &OpMode::CBC, iv).unwrap(), b"long message");
+/// ```
+#[derive(Clone)]
+pub struct Present128 {
+    expanded: Expanded,
+}
+
+impl Present128 {
+    /// Constructs a new cipher by expanding the given key.
+    pub fn new(key: &Key128Bit) -> Self {
+        Present128 { expanded: Expanded::new(key) }
+    }
+
+    /// Constructs a new cipher that is protected against first-order
+    /// power analysis by boolean masking. See
+    /// [`Present80::new_masked()`](struct.Present80.html#method.new_masked).
+    ///
+    /// # Errors
+    ///
+    /// Returns `RngError::Unavailable` if the RNG fails to provide
+    /// randomness.
+    pub fn new_masked<R: RngCore + ?Sized>(key: &Key128Bit, rng: &mut R) -> Result<Self, RngError> {
+        Ok(Present128 { expanded: Expanded::masked(MaskedSchedule::new_128bit(&key.value, rng)?) })
+    }
+
+    /// Constructs a new cipher that processes the S-Box nibbles of
+    /// every round in random order. See
+    /// [`Present80::new_shuffled()`](struct.Present80.html#method.new_shuffled).
+    ///
+    /// # Errors
+    ///
+    /// Returns `RngError::Unavailable` if the RNG fails to provide
+    /// randomness.
+    pub fn new_shuffled<R: RngCore + ?Sized>(key: &Key128Bit, rng: &mut R) -> Result<Self, RngError> {
+        Ok(Present128 { expanded: Expanded::shuffled(ShuffledSchedule::new(key.generate_round_keys(), rng)?) })
+    }
+}
+
+impl_cipher!(Present128);
+
+/// A keyed instance of PRESENT, as used by a
+/// [`ModeOfOperation`](trait.ModeOfOperation.html).
+///
+/// Implemented by [`Present80`](struct.Present80.html) and
+/// [`Present128`](struct.Present128.html), and by the key schedule the
+/// high-level functions use internally.
+pub trait BlockCipher {
+    /// Encrypts a single block.
+    fn encrypt_block(&self, block: &mut Block);
+
+    /// Decrypts a single block.
+    fn decrypt_block(&self, block: &mut Block);
+
+    /// Encrypts independent blocks in place. The length of the data
+    /// must be a multiple of the block size. The default encrypts one
+    /// block after the other.
+    fn encrypt_blocks(&self, blocks: &mut [u8]) {
+        process_each(blocks, |state| {
+            let mut block = Block::new(state);
+            self.encrypt_block(&mut block);
+            block.get_state()
+        });
+    }
+
+    /// Decrypts independent blocks in place, see
+    /// [`encrypt_blocks()`](#method.encrypt_blocks).
+    fn decrypt_blocks(&self, blocks: &mut [u8]) {
+        process_each(blocks, |state| {
+            let mut block = Block::new(state);
+            self.decrypt_block(&mut block);
+            block.get_state()
+        });
+    }
+}
+
+impl BlockCipher for Expanded {
+    fn encrypt_block(&self, block: &mut Block) {
+        Expanded::encrypt_block(self, block);
+    }
+
+    fn decrypt_block(&self, block: &mut Block) {
+        Expanded::decrypt_block(self, block);
+    }
+
+    fn encrypt_blocks(&self, blocks: &mut [u8]) {
+        Expanded::encrypt_blocks(self, blocks);
+    }
+
+    fn decrypt_blocks(&self, blocks: &mut [u8]) {
+        Expanded::decrypt_blocks(self, blocks);
+    }
+}
+
+/// Argument of the methods of public traits that only this crate may
+/// call. It cannot be named or constructed outside of the crate, so
+/// such methods can neither be called nor overridden there.
+pub struct Sealed(pub(crate) ());
+
+/// The expanded key schedule of any key.
+///
+/// This is what the high-level functions use internally, since they
+/// accept any implementation of `Key`.
+#[derive(Clone)]
+pub struct Expanded {
+    schedule: Schedule,
+}
+
+// The plain round keys stay inline, since they are used for every
+// block of the common case
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+enum Schedule {
+    Plain(RoundKeys),
+    Masked(Box<MaskedSchedule>),
+    Shuffled(Box<ShuffledSchedule>),
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Expanded {
+    fn drop(&mut self) {
+        // The boxed schedules wipe themselves
+        if let Schedule::Plain(ref mut round_keys) = self.schedule {
+            wipe(round_keys);
+        }
+    }
+}
+
+impl Expanded {
+    pub fn new<K: Key + ?Sized>(key: &K) -> Self {
+        profile::measure(Stage::KeySchedule, || key.expand(Sealed(())))
+    }
+
+    pub fn from_round_keys(round_keys: RoundKeys) -> Self {
+        Expanded { schedule: Schedule::Plain(round_keys) }
+    }
+
+    pub fn masked(schedule: MaskedSchedule) -> Self {
+        Expanded { schedule: Schedule::Masked(Box::new(schedule)) }
+    }
+
+    pub fn shuffled(schedule: ShuffledSchedule) -> Self {
+        Expanded { schedule: Schedule::Shuffled(Box::new(schedule)) }
+    }
+
+    pub fn round_keys(&self) -> RoundKeys {
+        match self.schedule {
+            Schedule::Plain(round_keys) => round_keys,
+            Schedule::Masked(ref masked) => masked.round_keys(),
+            Schedule::Shuffled(ref shuffled) => shuffled.round_keys(),
+        }
+    }
+
+    pub fn encrypt_block(&self, block: &mut Block) {
+        match self.schedule {
+            Schedule::Plain(ref round_keys) => block.encrypt_round_keys(round_keys),
+            Schedule::Masked(ref masked) => *block = Block::new(masked.encrypt(block.get_state())),
+            Schedule::Shuffled(ref shuffled) => *block = Block::new(shuffled.encrypt(block.get_state())),
+        }
+    }
+
+    pub fn encrypt_block_checked(&self, block: &mut Block) -> Result<(), FaultError> {
+        checked(block, |b| self.encrypt_block(b), |b| self.decrypt_block(b))
+    }
+
+    pub fn decrypt_block_checked(&self, block: &mut Block) -> Result<(), FaultError> {
+        checked(block, |b| self.decrypt_block(b), |b| self.encrypt_block(b))
+    }
+
+    pub fn decrypt_block(&self, block: &mut Block) {
+        match self.schedule {
+            Schedule::Plain(ref round_keys) => block.decrypt_round_keys(round_keys),
+            Schedule::Masked(ref masked) => *block = Block::new(masked.decrypt(block.get_state())),
+            Schedule::Shuffled(ref shuffled) => *block = Block::new(shuffled.decrypt(block.get_state())),
+        }
+    }
+
+    pub fn encrypt_in_place(&self, buffer: &mut [u8], mode: &OpMode, init_vec: &Block) -> Result<(), BufferError> {
+        check_aligned(buffer, mode)?;
+        report_encryption(mode, self, init_vec, buffer.len().div_ceil(8));
+        mode.encrypt_in_place(buffer, self, &mut Block::new(init_vec.get_state()));
+        Ok(())
+    }
+
+    pub fn decrypt_in_place(&self, buffer: &mut [u8], mode: &OpMode, init_vec: &Block) -> Result<(), BufferError> {
+        check_aligned(buffer, mode)?;
+        report_mode(mode);
+        mode.decrypt_in_place(buffer, self, &mut Block::new(init_vec.get_state()));
+        Ok(())
+    }
+
+    pub fn encrypt_to(&self, input: &[u8], output: &mut [u8], mode: &OpMode, init_vec: &Block) -> Result<usize, BufferError> {
+        let len = if mode.is_padded() { input.len() - input.len() % 8 + 8 } else { input.len() };
+        if output.len() < len {
+            return Err(BufferError::OutputTooSmall { len: output.len(), required: len });
+        }
+
+        let output = &mut output[..len];
+        if mode.is_padded() {
+            let aligned = len - 8;
+            output[..aligned].copy_from_slice(&input[..aligned]);
+            output[aligned..].copy_from_slice(&final_block(input));
+        } else {
+            output.copy_from_slice(input);
+        }
+
+        self.encrypt_in_place(output, mode, init_vec)?;
+        Ok(len)
+    }
+
+    pub fn decrypt_to(&self, input: &[u8], output: &mut [u8], mode: &OpMode, init_vec: &Block) -> Result<usize, DecryptError> {
+        check_ciphertext_len(input, mode)?;
+        if output.len() < input.len() {
+            return Err(DecryptError::OutputTooSmall { len: output.len(), required: input.len() });
+        }
+
+        let output = &mut output[..input.len()];
+        output.copy_from_slice(input);
+        report_mode(mode);
+        mode.decrypt_in_place(output, self, &mut Block::new(init_vec.get_state()));
+
+        if mode.is_padded() {
+            let len = output.len();
+            Ok(len - check_padding(&output[(len - 8)..], len / 8 - 1)?)
+        } else {
+            Ok(output.len())
+        }
+    }
+
+    /// Encrypts independent blocks, full batches with the bitsliced
+    /// backend and the rest two at a time. The length of the data must
+    /// be a multiple of the block size.
+    pub fn encrypt_blocks(&self, blocks: &mut [u8]) {
+        match self.schedule {
+            Schedule::Plain(ref round_keys) => {
+                let (batches, rest) = split_batches(blocks);
+                encrypt_batches(batches, round_keys);
+                pair::encrypt_blocks(rest, round_keys);
+            }
+            Schedule::Masked(ref masked) => process_each(blocks, |state| masked.encrypt(state)),
+            Schedule::Shuffled(ref shuffled) => process_each(blocks, |state| shuffled.encrypt(state)),
+        }
+    }
+
+    /// Decrypts independent blocks, full batches with the bitsliced
+    /// backend and the rest two at a time. The length of the data must
+    /// be a multiple of the block size.
+    pub fn decrypt_blocks(&self, blocks: &mut [u8]) {
+        match self.schedule {
+            Schedule::Plain(ref round_keys) => {
+                let (batches, rest) = split_batches(blocks);
+                decrypt_batches(batches, round_keys);
+                pair::decrypt_blocks(rest, round_keys);
+            }
+            Schedule::Masked(ref masked) => process_each(blocks, |state| masked.decrypt(state)),
+            Schedule::Shuffled(ref shuffled) => process_each(blocks, |state| shuffled.decrypt(state)),
+        }
+    }
+}
+
+/// Applies `forward` to the block and checks that `inverse` restores
+/// the input, clearing the block otherwise.
+fn checked<F, I>(block: &mut Block, forward: F, inverse: I) -> Result<(), FaultError>
+    where F: Fn(&mut Block), I: Fn(&mut Block) {
+
+    let input = block.get_state();
+    forward(block);
+
+    let mut check = Block::new(block.get_state());
+    inverse(&mut check);
+    if check.get_state() != input {
+        *block = Block::new(0);
+        return Err(FaultError::Detected);
+    }
+    Ok(())
+}
+
+/// Splits the blocks into full batches and the rest, which is left to the pair implementation.
+fn split_batches(blocks: &mut [u8]) -> (&mut [[u8; 8]], &mut [u8]) {
+    let split = blocks.len() / (8 * BATCH_BLOCKS) * (8 * BATCH_BLOCKS);
+    let (batches, rest) = blocks.split_at_mut(split);
+    (batches.as_chunks_mut().0, rest)
+}
+
+/// Processes independent blocks one at a time, for the schedules
+/// with side-channel countermeasures.
+fn process_each<F: Fn(u64) -> u64>(blocks: &mut [u8], process: F) {
+    for chunk in blocks.chunks_exact_mut(8) {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(chunk);
+        chunk.copy_from_slice(&process(u64::from_be_bytes(bytes)).to_be_bytes());
+    }
+}
+
+fn check_aligned(buffer: &[u8], mode: &OpMode) -> Result<(), BufferError> {
+    if mode.is_padded() && !buffer.len().is_multiple_of(8) {
+        return Err(BufferError::NotAligned { len: buffer.len(), block_size: 8 });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modes::all_modes;
+    use rand::rngs::mock::StepRng;
+    use testing::blocks;
+
+    #[test]
+    fn test_matches_block_encryption() {
+        let key80 = Key80Bit::new([0xFF; 10]);
+        let key128 = Key128Bit::new([0xFF; 16]);
+        let present80 = Present80::new(&key80);
+        let present128 = Present128::new(&key128);
+
+        for &state in [0u64, 0x0123456789ABCDEF, u64::MAX].iter() {
+            let mut expected = Block::new(state);
+            expected.encrypt(&key80);
+            let mut block = Block::new(state);
+            present80.encrypt_block(&mut block);
+            assert_eq!(block.get_state(), expected.get_state());
+            present80.decrypt_block(&mut block);
+            assert_eq!(block.get_state(), state);
+
+            let mut expected = Block::new(state);
+            expected.encrypt(&key128);
+            let mut block = Block::new(state);
+            present128.encrypt_block(&mut block);
+            assert_eq!(block.get_state(), expected.get_state());
+            present128.decrypt_block(&mut block);
+            assert_eq!(block.get_state(), state);
+        }
+    }
+
+    #[test]
+    fn test_in_place_matches_allocating_functions() {
+        let key = Key80Bit::new([0x42; 10]);
+        let cipher = Present80::new(&key);
+        let iv = Block::new(0x0123456789ABCDEF);
+
+        // Without the padding block, the output matches try_encrypt_bytes_with_iv()
+        let plaintext: Vec<u8> = (0..24u8).collect();
+        for mode in all_modes().iter() {
+            let expected = ::try_encrypt_bytes_with_iv(&plaintext, &key, mode, &iv).unwrap();
+            let mut buffer = plaintext.clone();
+            cipher.encrypt_in_place(&mut buffer, mode, &iv).unwrap();
+            assert_eq!(&buffer[..], &expected[..24]);
+
+            cipher.decrypt_in_place(&mut buffer, mode, &iv).unwrap();
+            assert_eq!(buffer, plaintext);
+        }
+
+        let mut buffer = [0u8; 13];
+        assert_eq!(cipher.encrypt_in_place(&mut buffer, &OpMode::CBC, &iv), Err(BufferError::NotAligned { len: 13, block_size: 8 }));
+        assert_eq!(cipher.decrypt_in_place(&mut buffer, &OpMode::PCBC, &iv), Err(BufferError::NotAligned { len: 13, block_size: 8 }));
+        assert!(cipher.encrypt_in_place(&mut buffer, &OpMode::CFB, &iv).is_ok());
+    }
+
+    #[test]
+    fn test_encrypt_to_matches_allocating_functions() {
+        let key = Key128Bit::new([0x42; 16]);
+        let cipher = Present128::new(&key);
+        let iv = Block::new(0x0123456789ABCDEF);
+        let mut output = [0u8; 32];
+
+        for &len in [0usize, 5, 16].iter() {
+            let plaintext: Vec<u8> = (0..len as u8).collect();
+            for mode in all_modes().iter() {
+                let expected = ::try_encrypt_bytes_with_iv(&plaintext, &key, mode, &iv).unwrap();
+                let written = cipher.encrypt_to(&plaintext, &mut output, mode, &iv).unwrap();
+                assert_eq!(&output[..written], &expected[..]);
+
+                let mut decrypted = [0u8; 32];
+                let plain_len = cipher.decrypt_to(&output[..written], &mut decrypted, mode, &iv).unwrap();
+                assert_eq!(&decrypted[..plain_len], &plaintext[..]);
+            }
+        }
+
+        assert_eq!(cipher.encrypt_to(&[0u8; 8], &mut output[..15], &OpMode::CBC, &iv), Err(BufferError::OutputTooSmall { len: 15, required: 16 }));
+        assert!(matches!(cipher.decrypt_to(&[0u8; 16], &mut output[..8], &OpMode::CBC, &iv), Err(DecryptError::OutputTooSmall { len: 8, required: 16 })));
+        assert!(matches!(cipher.decrypt_to(&[0u8; 12], &mut output, &OpMode::CBC, &iv), Err(DecryptError::CiphertextNotAligned { len: 12, .. })));
+    }
+
+    #[test]
+    fn test_encrypt_blocks_matches_block_encryption() {
+        let cipher = Present80::new(&Key80Bit::new([0xFF; 10]));
+        let original = blocks(300);
+
+        let mut blocks = original.clone();
+        cipher.encrypt_blocks(&mut blocks);
+        for (bytes, plain) in blocks.iter().zip(original.iter()) {
+            let mut block = Block::from_bytes(plain);
+            cipher.encrypt_block(&mut block);
+            assert_eq!(*bytes, block.to_bytes());
+        }
+
+        cipher.decrypt_blocks(&mut blocks);
+        assert_eq!(blocks, original);
+    }
+
+    #[test]
+    fn test_masked_cipher_matches_plain_cipher() {
+        let mut rng = StepRng::new(7, 3);
+        let plain = Present128::new(&Key128Bit::new([0x42; 16]));
+        let masked = Present128::new_masked(&Key128Bit::new([0x42; 16]), &mut rng).unwrap();
+        let iv = Block::new(0x0123456789ABCDEF);
+        let plaintext: Vec<u8> = (0..40u8).collect();
+
+        for mode in all_modes().iter() {
+            let mut expected = [0u8; 48];
+            let len = plain.encrypt_to(&plaintext, &mut expected, mode, &iv).unwrap();
+            let mut output = [0u8; 48];
+            assert_eq!(masked.encrypt_to(&plaintext, &mut output, mode, &iv), Ok(len));
+            assert_eq!(&output[..len], &expected[..len]);
+
+            let mut decrypted = [0u8; 48];
+            let plain_len = masked.decrypt_to(&output[..len], &mut decrypted, mode, &iv).unwrap();
+            assert_eq!(&decrypted[..plain_len], &plaintext[..]);
+        }
+
+        let masked = Present80::new_masked(&Key80Bit::new([0xFF; 10]), &mut rng).unwrap();
+        let mut blocks = [[0xFF; 8]; 3];
+        masked.encrypt_blocks(&mut blocks);
+        assert_eq!(blocks[2], [0x33, 0x33, 0xDC, 0xD3, 0x21, 0x32, 0x10, 0xD2]);
+        masked.decrypt_blocks(&mut blocks);
+        assert_eq!(blocks, [[0xFF; 8]; 3]);
+        assert_eq!(masked.generate_round_keys()[31].value, Key80Bit::new([0xFF; 10]).generate_round_keys()[31].value);
+    }
+
+    #[test]
+    fn test_masked_and_shuffled_schedules_are_kept_as_key() {
+        let mut rng = StepRng::new(7, 3);
+        let masked = Present80::new_masked(&Key80Bit::new([0x42; 10]), &mut rng).unwrap();
+        let shuffled = Present128::new_shuffled(&Key128Bit::new([0x42; 16]), &mut rng).unwrap();
+        assert!(matches!(Expanded::new(&masked).schedule, Schedule::Masked(_)));
+        assert!(matches!(Expanded::new(&shuffled).schedule, Schedule::Shuffled(_)));
+        assert!(matches!(Expanded::new(&Key80Bit::new([0x42; 10])).schedule, Schedule::Plain(_)));
+
+        let ciphertext = ::encrypt_bytes(b"masked", &masked, &OpMode::CBC).unwrap();
+        assert_eq!(ciphertext.decrypt(&Key80Bit::new([0x42; 10])).unwrap(), b"masked");
+        let mut data = *b"masked";
+        ::Ctr::new(&shuffled, &Block::new(0)).apply_keystream(&mut data);
+        ::Ctr::new(&Key128Bit::new([0x42; 16]), &Block::new(0)).apply_keystream(&mut data);
+        assert_eq!(&data, b"masked");
+        assert_eq!(::cmac(&masked, b"masked"), ::cmac(&Key80Bit::new([0x42; 10]), b"masked"));
+    }
+
+    #[test]
+    fn test_shuffled_cipher_matches_plain_cipher() {
+        let mut rng = StepRng::new(7, 3);
+        let plain = Present80::new(&Key80Bit::new([0x42; 10]));
+        let shuffled = Present80::new_shuffled(&Key80Bit::new([0x42; 10]), &mut rng).unwrap();
+        let iv = Block::new(0x0123456789ABCDEF);
+        let plaintext: Vec<u8> = (0..40u8).collect();
+
+        for mode in all_modes().iter() {
+            let mut expected = [0u8; 48];
+            let len = plain.encrypt_to(&plaintext, &mut expected, mode, &iv).unwrap();
+            let mut output = [0u8; 48];
+            assert_eq!(shuffled.encrypt_to(&plaintext, &mut output, mode, &iv), Ok(len));
+            assert_eq!(&output[..len], &expected[..len]);
+
+            let mut decrypted = [0u8; 48];
+            let plain_len = shuffled.decrypt_to(&output[..len], &mut decrypted, mode, &iv).unwrap();
+            assert_eq!(&decrypted[..plain_len], &plaintext[..]);
+        }
+
+        let shuffled = Present128::new_shuffled(&Key128Bit::new([0xFF; 16]), &mut rng).unwrap();
+        let mut blocks = [[0x5A; 8]; 3];
+        shuffled.encrypt_blocks(&mut blocks);
+        let mut expected = [[0x5A; 8]; 3];
+        Present128::new(&Key128Bit::new([0xFF; 16])).encrypt_blocks(&mut expected);
+        assert_eq!(blocks, expected);
+        shuffled.decrypt_blocks(&mut blocks);
+        assert_eq!(blocks, [[0x5A; 8]; 3]);
+    }
+
+    #[test]
+    fn test_checked_block_operations() {
+        let cipher = Present128::new(&Key128Bit::new([0xFF; 16]));
+        let mut block = Block::new(0x0123456789ABCDEF);
+        cipher.encrypt_block_checked(&mut block).unwrap();
+        let mut expected = Block::new(0x0123456789ABCDEF);
+        cipher.encrypt_block(&mut expected);
+        assert_eq!(block.get_state(), expected.get_state());
+        cipher.decrypt_block_checked(&mut block).unwrap();
+        assert_eq!(block.get_state(), 0x0123456789ABCDEF);
+
+        // A fault in the computation is detected and nothing is released
+        let mut block = Block::new(0x0123456789ABCDEF);
+        let faulty = |b: &mut Block| {
+            cipher.encrypt_block(b);
+            *b = Block::new(b.get_state() ^ (1 << 17));
+        };
+        assert_eq!(checked(&mut block, faulty, |b| cipher.decrypt_block(b)), Err(FaultError::Detected));
+        assert_eq!(block.get_state(), 0);
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use block::Block;
+use keys::Key;
+
+/// Persistent storage for the state of a [`CounterIv`](struct.CounterIv.html).
+///
+/// Implement this trait to keep the counter in whatever non-volatile
+/// memory is available on the target, e.g. an EEPROM cell or a flash
+/// page. [`FileCounterStore`](struct.FileCounterStore.html) is provided
+/// for targets with a file system.
+pub trait CounterStore {
+    /// Loads the persisted counter value, or `None` if no value has
+    /// been stored yet.
+    fn load(&mut self) -> io::Result<Option<u64>>;
+
+    /// Persists the given counter value. This must only return `Ok`
+    /// once the value survives a power cycle.
+    fn store(&mut self, value: u64) -> io::Result<()>;
+}
+
+/// A [`CounterStore`](trait.CounterStore.html) that keeps the counter in a file.
+///
+/// The counter is stored as 8 big-endian bytes and synced to disk on
+/// every update.
+pub struct FileCounterStore {
+    path: PathBuf,
+}
+
+impl FileCounterStore {
+    /// Constructs a new store that uses the file at the given path.
+    /// The file is created on the first update if it does not exist.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        FileCounterStore { path: path.as_ref().to_path_buf() }
+    }
+}
+
+impl CounterStore for FileCounterStore {
+    fn load(&mut self) -> io::Result<Option<u64>> {
+        let mut file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut bytes = [0u8; 8];
+        file.read_exact(&mut bytes)?;
+        Ok(Some(u64::from_be_bytes(bytes)))
+    }
+
+    fn store(&mut self, value: u64) -> io::Result<()> {
+        let mut file = OpenOptions::new().write(true).create(true).truncate(true).open(&self.path)?;
+        file.write_all(&value.to_be_bytes())?;
+        file.sync_all()
+    }
+}
+
+/// Generates unique initialization vectors from a persisted counter.
+///
+/// Devices that reboot frequently cannot always rely on their RNG to
+/// produce fresh IVs. This generator instead derives every IV from a
+/// counter that is persisted in a [`CounterStore`](trait.CounterStore.html)
+/// before the IV is handed out, so no counter value is ever used twice,
+/// even across power cycles. To keep the IVs unpredictable (as required
+/// by CBC), each counter value is encrypted with the key before use.
+///
+/// To reduce wear on the storage, counter values can be reserved in
+/// batches. Values reserved but not used before a reboot are skipped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use present::{try_encrypt_str_with_iv, CounterIv, FileCounterStore, Key80Bit, OpMode};
+///
+/// let key = Key80Bit::new([0x42; 10]);
+/// let mut ivs = CounterIv::new(FileCounterStore::new("iv.counter")).unwrap();
+///
+/// let iv = ivs.next_iv(&key).unwrap();
+/// let ciphertext = try_encrypt_str_with_iv("Hello, world!", &key, &OpMode::CBC, &iv).unwrap();
+/// ```
+pub struct CounterIv<S: CounterStore> {
+    store: S,
+    next: u64,
+    reserved: u64,
+    batch: u64,
+}
+
+impl<S: CounterStore> CounterIv<S> {
+    /// Constructs a new generator that persists every counter value
+    /// before using it.
+    pub fn new(store: S) -> io::Result<Self> {
+        CounterIv::with_batch(store, 1)
+    }
+
+    /// Constructs a new generator that reserves `batch` counter values
+    /// at a time, so the store is only updated every `batch` IVs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch` is zero.
+    pub fn with_batch(mut store: S, batch: u64) -> io::Result<Self> {
+        if batch == 0 {
+            panic!("Counter batch size must be at least 1");
+        }
+
+        let next = store.load()?.unwrap_or(0);
+        Ok(CounterIv { store, next, reserved: next, batch })
+    }
+
+    /// Returns the next IV for use with the given key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the counter could not be persisted or if all
+    /// counter values have been used up. No IV is returned in that case.
+    pub fn next_iv<K: Key>(&mut self, key: &K) -> io::Result<Block> {
+        if self.next == self.reserved {
+            let reserved = match self.next.checked_add(self.batch) {
+                Some(r) => r,
+                None => return Err(io::Error::other("IV counter exhausted")),
+            };
+            self.store.store(reserved)?;
+            self.reserved = reserved;
+        }
+
+        let mut iv = Block::new(self.next);
+        self.next += 1;
+        iv.encrypt(key);
+        Ok(iv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key80Bit;
+
+    struct MemoryStore {
+        value: Option<u64>,
+        writes: usize,
+    }
+
+    impl CounterStore for MemoryStore {
+        fn load(&mut self) -> io::Result<Option<u64>> {
+            Ok(self.value)
+        }
+
+        fn store(&mut self, value: u64) -> io::Result<()> {
+            self.value = Some(value);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_counter_is_persisted_before_use() {
+        let key = Key80Bit::new([0u8; 10]);
+        let mut ivs = CounterIv::new(MemoryStore { value: None, writes: 0 }).unwrap();
+
+        let iv = ivs.next_iv(&key).unwrap();
+        assert_eq!(iv.get_state(), 0x5579C1387B228445_u64);
+        assert_eq!(ivs.store.value, Some(1));
+
+        ivs.next_iv(&key).unwrap();
+        assert_eq!(ivs.store.value, Some(2));
+    }
+
+    #[test]
+    fn test_counter_resumes_after_restart() {
+        let key = Key80Bit::new([0x11; 10]);
+        let mut ivs = CounterIv::with_batch(MemoryStore { value: None, writes: 0 }, 10).unwrap();
+        let first = ivs.next_iv(&key).unwrap();
+        ivs.next_iv(&key).unwrap();
+        assert_eq!(ivs.store.writes, 1);
+
+        // Simulate a reboot: the unused rest of the batch is skipped
+        let mut ivs = CounterIv::new(ivs.store).unwrap();
+        let mut expected = Block::new(10);
+        expected.encrypt(&key);
+        let resumed = ivs.next_iv(&key).unwrap();
+        assert_eq!(resumed.get_state(), expected.get_state());
+        assert!(resumed.get_state() != first.get_state());
+    }
+
+    #[test]
+    fn test_exhausted_counter_fails() {
+        let key = Key80Bit::new([0x11; 10]);
+        let mut ivs = CounterIv::new(MemoryStore { value: Some(u64::MAX), writes: 0 }).unwrap();
+        assert!(ivs.next_iv(&key).is_err());
+    }
+
+    #[test]
+    fn test_file_store_roundtrip() {
+        let path = ::std::env::temp_dir().join(format!("present-counter-{}", ::std::process::id()));
+        let mut store = FileCounterStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+        store.store(0x0123456789ABCDEF).unwrap();
+        assert_eq!(FileCounterStore::new(&path).load().unwrap(), Some(0x0123456789ABCDEF));
+        ::std::fs::remove_file(&path).unwrap();
+    }
+}
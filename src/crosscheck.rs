@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use block::Block;
+use keys::RoundKeys;
+
+/// Only one out of this many blocks is checked, to keep debug builds usable.
+const SAMPLE_RATE: usize = 64;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns `true` if the current block should be cross-checked.
+pub fn should_sample() -> bool {
+    COUNTER.fetch_add(1, Ordering::Relaxed).is_multiple_of(SAMPLE_RATE)
+}
+
+/// Compares the output of an alternative backend with the reference
+/// implementation and panics if they disagree.
+pub fn check_block(backend: &str, encrypt: bool, input: &[u8; 8], output: &[u8; 8], round_keys: &RoundKeys) {
+    let mut block = Block::from_bytes(input);
+    if encrypt {
+        block.encrypt_rounds(round_keys);
+    } else {
+        block.decrypt_rounds(round_keys);
+    }
+    let expected = block.to_bytes();
+
+    assert!(expected == *output,
+            "Logic error! The {} backend disagrees with the reference implementation (input {:?}, expected {:?}, got {:?})",
+            backend, input, expected, output);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::{Key, Key80Bit};
+
+    #[test]
+    fn test_matching_output_passes() {
+        let round_keys = Key80Bit::new([0u8; 10]).generate_round_keys();
+        let output = [0x55, 0x79, 0xC1, 0x38, 0x7B, 0x22, 0x84, 0x45];
+        check_block("test", true, &[0u8; 8], &output, &round_keys);
+        check_block("test", false, &output, &[0u8; 8], &round_keys);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mismatching_output_panics() {
+        let round_keys = Key80Bit::new([0u8; 10]).generate_round_keys();
+        check_block("test", true, &[0u8; 8], &[0u8; 8], &round_keys);
+    }
+}
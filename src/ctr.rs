@@ -0,0 +1,263 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use block::Block;
+use context::Expanded;
+use keys::{Key, RoundKeys};
+use keys::wipe;
+
+/// Number of bytes each thread processes at a time.
+#[cfg(feature = "parallel")]
+const BYTES_PER_TASK: usize = 32 * 1024;
+
+/// Number of counter blocks encrypted at once, so that whole batches go
+/// through the bitsliced backend.
+const BATCH_BLOCKS: usize = 128;
+
+/// PRESENT in counter mode, as a seekable keystream.
+///
+/// The keystream consists of the encryptions of the initial counter
+/// block, the block after it and so on, where the counter wraps around
+/// after `2^64 - 1`. Applying the keystream to data encrypts it, and
+/// applying it again decrypts it. The position in the keystream can be
+/// changed freely, so parts of a message can be decrypted without
+/// processing the data before them.
+///
+/// Never use the same initial counter twice with the same key.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Block, Ctr, Key80Bit};
+/// let key = Key80Bit::new([0x42; 10]);
+/// let mut data = *b"counter mode";
+///
+/// Ctr::new(&key, &Block::new(1 << 32)).apply_keystream(&mut data);
+///
+/// let mut ctr = Ctr::new(&key, &Block::new(1 << 32));
+/// ctr.seek(8);
+/// ctr.apply_keystream(&mut data[8..]);
+/// assert_eq!(&data[8..], b"mode");
+/// ```
+pub struct Ctr {
+    cipher: Expanded,
+    counter: u64,
+    pos: u64,
+    keystream: [u8; 8],
+}
+
+impl Ctr {
+    /// Constructs a new keystream starting at the given counter block.
+    pub fn new<K: Key>(key: &K, init_vec: &Block) -> Self {
+        Ctr::with_cipher(Expanded::new(key), init_vec.get_state())
+    }
+
+    /// Constructs a new keystream from precomputed round keys.
+    pub fn from_round_keys(round_keys: RoundKeys, counter: u64) -> Self {
+        Ctr::with_cipher(Expanded::from_round_keys(round_keys), counter)
+    }
+
+    fn with_cipher(cipher: Expanded, counter: u64) -> Self {
+        let mut ctr = Ctr { cipher, counter, pos: 0, keystream: [0u8; 8] };
+        ctr.refill();
+        ctr
+    }
+
+    /// XORs the keystream into the data, advancing the position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position would exceed `2^64 - 1` bytes.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        if self.remaining() < data.len() as u64 {
+            panic!("Keystream exhausted! Cannot process {} more bytes", data.len());
+        }
+
+        // Bytes up to the next block boundary and after the last whole
+        // block use the buffered keystream block
+        let head = ((8 - self.pos % 8) % 8).min(data.len() as u64) as usize;
+        let (head, rest) = data.split_at_mut(head);
+        let (middle, tail) = rest.split_at_mut(rest.len() / 8 * 8);
+        for byte in head.iter_mut() {
+            *byte ^= self.next_byte();
+        }
+
+        if !middle.is_empty() {
+            xor_keystream(&self.cipher, self.counter.wrapping_add(self.pos / 8), middle);
+            let pos = self.pos + middle.len() as u64;
+            self.seek(pos);
+        }
+
+        for byte in tail.iter_mut() {
+            *byte ^= self.next_byte();
+        }
+    }
+
+    /// XORs the keystream into the data on multiple threads.
+    ///
+    /// Works like [`apply_keystream()`](#method.apply_keystream), but
+    /// splits the data across the threads of the rayon thread pool.
+    /// Only pays off for inputs of several hundred kilobytes or more.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position would exceed `2^64 - 1` bytes.
+    #[cfg(feature = "parallel")]
+    pub fn apply_keystream_par(&mut self, data: &mut [u8]) {
+        if self.remaining() < data.len() as u64 {
+            panic!("Keystream exhausted! Cannot process {} more bytes", data.len());
+        }
+
+        // Process bytes up to the next block boundary sequentially
+        let head = ((8 - self.pos % 8) % 8).min(data.len() as u64) as usize;
+        let (head, rest) = data.split_at_mut(head);
+        self.apply_keystream(head);
+
+        let cipher = &self.cipher;
+        let first_block = self.counter.wrapping_add(self.pos / 8);
+        rest.par_chunks_mut(BYTES_PER_TASK).enumerate().for_each(|(task, chunk)| {
+            let task_block = first_block.wrapping_add((task * BYTES_PER_TASK / 8) as u64);
+            xor_keystream(cipher, task_block, chunk);
+        });
+
+        let pos = self.pos + rest.len() as u64;
+        self.seek(pos);
+    }
+
+    /// Returns the current position in the keystream in bytes.
+    pub fn current_pos(&self) -> u64 {
+        self.pos
+    }
+
+    /// Sets the position in the keystream in bytes.
+    pub fn seek(&mut self, pos: u64) {
+        let block_changed = pos / 8 != self.pos / 8;
+        self.pos = pos;
+        if block_changed {
+            self.refill();
+        }
+    }
+
+    /// Returns how many bytes of keystream are left.
+    pub fn remaining(&self) -> u64 {
+        u64::MAX - self.pos
+    }
+
+    /// Returns the next keystream byte, advancing the position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position would exceed `2^64 - 1` bytes.
+    pub fn next_byte(&mut self) -> u8 {
+        let byte = self.keystream[(self.pos % 8) as usize];
+        self.pos = self.pos.checked_add(1).expect("Keystream exhausted! Cannot process another byte");
+        if self.pos.is_multiple_of(8) {
+            self.refill();
+        }
+        byte
+    }
+
+    fn refill(&mut self) {
+        let mut block = Block::new(self.counter.wrapping_add(self.pos / 8));
+        self.cipher.encrypt_block(&mut block);
+        self.keystream = block.to_bytes();
+    }
+}
+
+/// XORs the keystream starting at the given counter block into the data.
+///
+/// The counter blocks are encrypted in batches. The data may end with a
+/// partial block.
+fn xor_keystream(cipher: &Expanded, first_block: u64, data: &mut [u8]) {
+    let mut keystream = [0u8; BATCH_BLOCKS * 8];
+    for (batch, chunk) in data.chunks_mut(BATCH_BLOCKS * 8).enumerate() {
+        let batch_block = first_block.wrapping_add((batch * BATCH_BLOCKS) as u64);
+        let keystream = &mut keystream[..chunk.len().div_ceil(8) * 8];
+        for (i, counter) in keystream.chunks_exact_mut(8).enumerate() {
+            counter.copy_from_slice(&batch_block.wrapping_add(i as u64).to_be_bytes());
+        }
+
+        cipher.encrypt_blocks(keystream);
+        for (byte, k) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= k;
+        }
+    }
+    wipe(&mut keystream);
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Ctr {
+    fn drop(&mut self) {
+        // The cipher wipes itself
+        wipe(&mut self.keystream);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key80Bit;
+
+    #[test]
+    fn test_keystream_matches_definition() {
+        let key = Key80Bit::new([0x42; 10]);
+        let mut data = [0u8; 20];
+        Ctr::new(&key, &Block::new(u64::MAX)).apply_keystream(&mut data);
+
+        for (i, counter) in [u64::MAX, 0, 1].iter().enumerate() {
+            let mut block = Block::new(*counter);
+            block.encrypt(&key);
+            let expected = block.to_bytes();
+            let end = ((i + 1) * 8).min(20);
+            assert_eq!(&data[i * 8..end], &expected[..end - i * 8]);
+        }
+    }
+
+    #[test]
+    fn test_seek_and_chunking() {
+        let key = Key80Bit::new([0x42; 10]);
+        let mut whole = [0x5Au8; 2085];
+        let mut ctr = Ctr::new(&key, &Block::new(u64::MAX - 99));
+        ctr.apply_keystream(&mut whole[..3]);
+        ctr.apply_keystream(&mut whole[3..]);
+
+        let mut chunked = [0x5Au8; 2085];
+        let mut ctr = Ctr::new(&key, &Block::new(u64::MAX - 99));
+        for chunk in chunked.chunks_mut(5) {
+            ctr.apply_keystream(chunk);
+        }
+        assert_eq!(&chunked[..], &whole[..]);
+        assert_eq!(ctr.current_pos(), 2085);
+
+        for &pos in [0u64, 3, 8, 13, 36, 2084].iter() {
+            let mut part = [0x5Au8; 1];
+            ctr.seek(pos);
+            ctr.apply_keystream(&mut part);
+            assert_eq!(part[0], whole[pos as usize]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Keystream exhausted")]
+    fn test_exhausted_keystream() {
+        let mut ctr = Ctr::new(&Key80Bit::new([0; 10]), &Block::new(0));
+        ctr.seek(u64::MAX - 2);
+        ctr.apply_keystream(&mut [0u8; 3]);
+    }
+
+    #[test]
+    fn test_last_keystream_byte() {
+        let mut ctr = Ctr::new(&Key80Bit::new([0; 10]), &Block::new(0));
+        ctr.seek(u64::MAX - 1);
+        ctr.next_byte();
+        assert_eq!(ctr.remaining(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Keystream exhausted")]
+    fn test_exhausted_keystream_byte() {
+        let mut ctr = Ctr::new(&Key80Bit::new([0; 10]), &Block::new(0));
+        ctr.seek(u64::MAX);
+        ctr.next_byte();
+    }
+}
@@ -0,0 +1,585 @@
+use std::convert::TryFrom;
+
+#[cfg(feature = "base64")]
+use base64ct::{Base64, Encoding};
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use block::Block;
+use errors::{EncryptError, EnvelopeError, RngError};
+#[cfg(feature = "base64")]
+use errors::ParseError;
+use kdf::{pbkdf2_key128, Hkdf};
+use keys::{Key, Key128Bit};
+use mac::Cmac;
+use modes::OpMode;
+use rotation::KeyRing;
+use tag::tags_equal;
+use {decrypt_bytes, encrypt_bytes};
+
+/// Magic bytes at the start of every envelope.
+const MAGIC: [u8; 4] = *b"PRSE";
+
+/// Version of the envelope format.
+const VERSION: u8 = 1;
+
+/// Flags announcing the optional fields.
+const FLAG_KEY_ID: u8 = 0x01;
+const FLAG_KDF: u8 = 0x02;
+const FLAG_TAG: u8 = 0x04;
+
+/// Identifier of PBKDF2 with `CmacPrf`, the only KDF so far.
+const KDF_PBKDF2: u8 = 1;
+
+/// Labels for deriving the encryption and MAC subkeys of authenticated
+/// envelopes.
+const ENC_LABEL: &[u8] = b"present envelope enc";
+const MAC_LABEL: &[u8] = b"present envelope mac";
+
+/// A self-describing container for ciphertexts.
+///
+/// An envelope carries everything that is needed for decryption except
+/// the key, so callers no longer have to ship the IV and the mode of
+/// operation next to the ciphertext by hand. It consists of
+///
+/// * the magic bytes `PRSE` and a format version byte,
+/// * a byte for the mode of operation and a byte for the key length,
+/// * a flags byte announcing the optional fields,
+/// * the key ID, as a big-endian 32-bit integer (optional),
+/// * the KDF parameters for passphrases: a byte identifying the KDF,
+///   the iteration count and a 16-byte salt (optional),
+/// * the IV, if the mode of operation needs one,
+/// * the ciphertext,
+/// * an 8-byte tag (optional).
+///
+/// The tag is computed over everything before it, in the same way as
+/// in [`encrypt_then_mac`](fn.encrypt_then_mac.html), so modifications
+/// of the header are detected, too. Associated data, such as routing
+/// information, can be authenticated along with it without being
+/// stored in the envelope: it is passed to both `seal` and `open`, and
+/// opening fails if it differs. Envelopes are authenticated unless
+/// [`without_tag()`](#method.without_tag) is used, and the `open`
+/// functions reject envelopes without a tag, so an attacker cannot strip
+/// it. Envelopes sealed without a tag have to be opened with
+/// [`open_unauthenticated()`](#method.open_unauthenticated).
+///
+/// # Examples
+///
+/// ```
+/// use present::{Envelope, Key80Bit, KeyRing, OpMode};
+///
+/// let mut keys = KeyRing::new();
+/// keys.rotate(1, Key80Bit::new([0x01; 10]));
+///
+/// let (id, key) = keys.current().unwrap();
+/// let sealed = Envelope::new(OpMode::CBC).with_key_id(id).seal(b"Hello, world!", b"inbox", key).unwrap();
+///
+/// // Data sealed before a rotation can still be opened
+/// keys.rotate(2, Key80Bit::new([0x02; 10]));
+/// assert_eq!(Envelope::open_with_key_ring(&sealed, b"inbox", &keys).unwrap(), b"Hello, world!");
+/// assert!(Envelope::open_with_key_ring(&sealed, b"outbox", &keys).is_err());
+/// ```
+#[derive(Clone, Copy)]
+pub struct Envelope {
+    mode: OpMode,
+    key_id: Option<u32>,
+    authenticated: bool,
+}
+
+impl Envelope {
+    /// Highest iteration count that is accepted for passphrases, so
+    /// that a crafted envelope cannot make opening take hours.
+    pub const MAX_ITERATIONS: u32 = 10_000_000;
+
+    /// Constructs an authenticated envelope for the given mode of
+    /// operation, without a key ID.
+    pub fn new(mode: OpMode) -> Self {
+        Envelope { mode, key_id: None, authenticated: true }
+    }
+
+    /// Stores the given key ID in the header, so the key can be looked
+    /// up with [`open_with_key_ring()`](#method.open_with_key_ring).
+    pub fn with_key_id(self, key_id: u32) -> Self {
+        Envelope { key_id: Some(key_id), ..self }
+    }
+
+    /// Omits the tag, so the ciphertext is encrypted with the key
+    /// directly and can also be decrypted without this type. Without
+    /// the tag, modifications of the envelope are not detected, and the
+    /// envelope can only be opened with
+    /// [`open_unauthenticated()`](#method.open_unauthenticated).
+    pub fn without_tag(self) -> Self {
+        Envelope { authenticated: false, ..self }
+    }
+
+    /// Encrypts the plaintext with a random IV and returns the envelope.
+    /// The associated data is authenticated, but not encrypted or stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncryptError::Rng` if the random number generator of
+    /// the operating system fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `aad` is not empty for an envelope without a tag.
+    pub fn seal<K: Key>(&self, plaintext: &[u8], aad: &[u8], key: &K) -> Result<Vec<u8>, EncryptError> {
+        let mut sealed = self.header(key.key_size(), 0);
+        self.seal_body(&mut sealed, plaintext, aad, key)?;
+        Ok(sealed)
+    }
+
+    /// Encrypts the plaintext with a 128-bit key derived from the
+    /// passphrase with [`pbkdf2_key128`](fn.pbkdf2_key128.html). The
+    /// iteration count and a random salt are stored in the header.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncryptError::Rng` if the random number generator of
+    /// the operating system fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `iterations` is zero or greater than
+    /// [`MAX_ITERATIONS`](#associatedconstant.MAX_ITERATIONS), or if
+    /// `aad` is not empty for an envelope without a tag.
+    pub fn seal_with_passphrase(&self, plaintext: &[u8], aad: &[u8], passphrase: &[u8], iterations: u32) -> Result<Vec<u8>, EncryptError> {
+        assert!(iterations <= Envelope::MAX_ITERATIONS, "Iteration count is too high to be opened again");
+        let mut salt = [0u8; 16];
+        OsRng.try_fill_bytes(&mut salt).map_err(RngError::from)?;
+        let key = pbkdf2_key128(passphrase, &salt, iterations);
+
+        let mut sealed = self.header(16, FLAG_KDF);
+        sealed.push(KDF_PBKDF2);
+        sealed.extend_from_slice(&iterations.to_be_bytes());
+        sealed.extend_from_slice(&salt);
+        self.seal_body(&mut sealed, plaintext, aad, &key)?;
+        Ok(sealed)
+    }
+
+    /// Decrypts an envelope sealed with [`seal()`](#method.seal). The
+    /// key ID is ignored, if there is one. The associated data has to
+    /// be the same as when sealing.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with an `EnvelopeError` if the envelope is
+    /// malformed, was sealed with a different kind of key, or the tag
+    /// does not match, e.g. because the associated data differs.
+    /// Returns `EnvelopeError::Unauthenticated` if the envelope has no
+    /// tag.
+    pub fn open<K: Key>(sealed: &[u8], aad: &[u8], key: &K) -> Result<Vec<u8>, EnvelopeError> {
+        Envelope::parse_for_key(sealed, key)?.open(aad, key)
+    }
+
+    /// Decrypts an envelope sealed with [`seal()`](#method.seal), also
+    /// if it has no tag.
+    ///
+    /// Envelopes sealed with [`without_tag()`](#method.without_tag) are
+    /// decrypted without any verification, so modifications go
+    /// unnoticed. Tags that are present are still checked, together
+    /// with the associated data. Only use this for data that is
+    /// authenticated by other means.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with an `EnvelopeError` if the envelope is
+    /// malformed, was sealed with a different kind of key, or has a tag
+    /// that does not match. Returns `EnvelopeError::Unauthenticated` if
+    /// the envelope has no tag but `aad` is not empty, as it cannot be
+    /// checked.
+    pub fn open_unauthenticated<K: Key>(sealed: &[u8], aad: &[u8], key: &K) -> Result<Vec<u8>, EnvelopeError> {
+        let parsed = Envelope::parse_for_key(sealed, key)?;
+        if parsed.tag.is_some() {
+            return parsed.open(aad, key);
+        }
+        if !aad.is_empty() {
+            return Err(EnvelopeError::Unauthenticated);
+        }
+        Ok(decrypt_bytes(parsed.ciphertext, key, &parsed.mode, parsed.init_vec)?)
+    }
+
+    /// Decrypts an envelope sealed with
+    /// [`seal_with_passphrase()`](#method.seal_with_passphrase).
+    ///
+    /// The iteration count is taken from the header. Envelopes with more
+    /// than [`MAX_ITERATIONS`](#associatedconstant.MAX_ITERATIONS) are
+    /// rejected before the key is derived.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with an `EnvelopeError` if the envelope is
+    /// malformed, was not sealed with a passphrase, or the tag does not
+    /// match, e.g. because the passphrase or the associated data is
+    /// wrong. Returns `EnvelopeError::Malformed` if the iteration count
+    /// is too high.
+    pub fn open_with_passphrase(sealed: &[u8], aad: &[u8], passphrase: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+        let parsed = Parsed::parse(sealed)?;
+        let (iterations, salt) = parsed.kdf.ok_or(EnvelopeError::KeyMismatch)?;
+        parsed.open(aad, &pbkdf2_key128(passphrase, salt, iterations))
+    }
+
+    /// Decrypts an envelope sealed with a key ID, looking up the key in
+    /// the key ring.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EnvelopeError::UnknownKeyId` if the key ring has no key
+    /// with the ID from the header, and the errors of
+    /// [`open()`](#method.open) otherwise.
+    pub fn open_with_key_ring<K: Key>(sealed: &[u8], aad: &[u8], keys: &KeyRing<K>) -> Result<Vec<u8>, EnvelopeError> {
+        let key_id = Parsed::parse(sealed)?.key_id.ok_or(EnvelopeError::KeyMismatch)?;
+        let key = keys.get(key_id).ok_or(EnvelopeError::UnknownKeyId(key_id))?;
+        Envelope::open(sealed, aad, key)
+    }
+
+    /// Encodes a sealed envelope as padded Base64 in the standard
+    /// alphabet, so it can be embedded in JSON, HTTP headers or
+    /// environment variables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{Envelope, Key80Bit, OpMode};
+    /// let key = Key80Bit::new([0x42; 10]);
+    /// let sealed = Envelope::new(OpMode::CBC).seal(b"Hello, world!", b"", &key).unwrap();
+    /// let encoded = Envelope::to_base64(&sealed);
+    ///
+    /// let sealed = Envelope::from_base64(&encoded).unwrap();
+    /// assert_eq!(Envelope::open(&sealed, b"", &key).unwrap(), b"Hello, world!");
+    /// ```
+    #[cfg(feature = "base64")]
+    pub fn to_base64(sealed: &[u8]) -> String {
+        Base64::encode_string(sealed)
+    }
+
+    /// Decodes an envelope encoded with [`to_base64()`](#method.to_base64).
+    /// The envelope itself is checked when it is opened.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::InvalidBase64` if the string is not valid
+    /// Base64.
+    #[cfg(feature = "base64")]
+    pub fn from_base64(encoded: &str) -> Result<Vec<u8>, ParseError> {
+        Base64::decode_vec(encoded).map_err(|_| ParseError::InvalidBase64)
+    }
+
+    /// Parses an envelope that is to be opened with the given key.
+    fn parse_for_key<'a, K: Key>(sealed: &'a [u8], key: &K) -> Result<Parsed<'a>, EnvelopeError> {
+        let parsed = Parsed::parse(sealed)?;
+        if parsed.kdf.is_some() || parsed.key_size != key_size_byte(key.key_size()) {
+            return Err(EnvelopeError::KeyMismatch);
+        }
+        Ok(parsed)
+    }
+
+    /// Encodes the header up to the KDF parameters.
+    fn header(&self, key_size: usize, flags: u8) -> Vec<u8> {
+        let mut flags = flags;
+        if self.key_id.is_some() {
+            flags |= FLAG_KEY_ID;
+        }
+        if self.authenticated {
+            flags |= FLAG_TAG;
+        }
+
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&[VERSION, mode_to_byte(&self.mode), key_size_byte(key_size), flags]);
+        if let Some(key_id) = self.key_id {
+            header.extend_from_slice(&key_id.to_be_bytes());
+        }
+        header
+    }
+
+    /// Appends the IV, the ciphertext and the tag.
+    fn seal_body<K: Key>(&self, sealed: &mut Vec<u8>, plaintext: &[u8], aad: &[u8], key: &K) -> Result<(), EncryptError> {
+        if !self.authenticated {
+            assert!(aad.is_empty(), "Associated data needs an envelope with a tag");
+            return append_ciphertext(sealed, plaintext, key, &self.mode);
+        }
+
+        let (enc_key, mac_key) = subkeys(key);
+        append_ciphertext(sealed, plaintext, &enc_key, &self.mode)?;
+        let tag = compute_tag(&mac_key, sealed, aad);
+        sealed.extend_from_slice(&tag);
+        Ok(())
+    }
+}
+
+/// Encodes the key size for the header. Key schedules are 256 bytes
+/// long and are recorded as zero.
+fn key_size_byte(key_size: usize) -> u8 {
+    u8::try_from(key_size).unwrap_or(0)
+}
+
+/// The fields of an envelope.
+struct Parsed<'a> {
+    mode: OpMode,
+    key_size: u8,
+    key_id: Option<u32>,
+    kdf: Option<(u32, &'a [u8])>,
+    init_vec: Option<Block>,
+    /// Everything the tag is computed over.
+    data: &'a [u8],
+    ciphertext: &'a [u8],
+    tag: Option<&'a [u8]>,
+}
+
+impl<'a> Parsed<'a> {
+    fn parse(sealed: &'a [u8]) -> Result<Self, EnvelopeError> {
+        if sealed.len() < 8 || sealed[..4] != MAGIC {
+            return Err(EnvelopeError::Malformed);
+        }
+        if sealed[4] != VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(sealed[4]));
+        }
+        let mode = mode_from_byte(sealed[5]).ok_or(EnvelopeError::Malformed)?;
+        let key_size = sealed[6];
+        let flags = sealed[7];
+        if flags & !(FLAG_KEY_ID | FLAG_KDF | FLAG_TAG) != 0 {
+            return Err(EnvelopeError::Malformed);
+        }
+
+        let (data, tag) = if flags & FLAG_TAG != 0 {
+            if sealed.len() < 16 {
+                return Err(EnvelopeError::Malformed);
+            }
+            let (data, tag) = sealed.split_at(sealed.len() - 8);
+            (data, Some(tag))
+        } else {
+            (sealed, None)
+        };
+
+        let mut rest = &data[8..];
+        let key_id = if flags & FLAG_KEY_ID != 0 {
+            Some(read_u32(take(&mut rest, 4)?))
+        } else {
+            None
+        };
+        let kdf = if flags & FLAG_KDF != 0 {
+            let params = take(&mut rest, 21)?;
+            let iterations = read_u32(&params[1..5]);
+            if params[0] != KDF_PBKDF2 || iterations == 0 || iterations > Envelope::MAX_ITERATIONS {
+                return Err(EnvelopeError::Malformed);
+            }
+            Some((iterations, &params[5..]))
+        } else {
+            None
+        };
+        let init_vec = match mode {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            OpMode::ECB => None,
+            OpMode::CBC | OpMode::PCBC | OpMode::CFB | OpMode::CTR => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(take(&mut rest, 8)?);
+                Some(Block::from_bytes(&bytes))
+            },
+        };
+
+        Ok(Parsed { mode, key_size, key_id, kdf, init_vec, data, ciphertext: rest, tag })
+    }
+
+    /// Verifies the tag and decrypts the ciphertext.
+    fn open<K: Key>(self, aad: &[u8], key: &K) -> Result<Vec<u8>, EnvelopeError> {
+        let tag = self.tag.ok_or(EnvelopeError::Unauthenticated)?;
+
+        let (enc_key, mac_key) = subkeys(key);
+        if !tags_equal(&compute_tag(&mac_key, self.data, aad), tag) {
+            return Err(EnvelopeError::AuthenticationFailed);
+        }
+        Ok(decrypt_bytes(self.ciphertext, &enc_key, &self.mode, self.init_vec)?)
+    }
+}
+
+/// Encrypts the plaintext and appends the IV, if any, and the
+/// ciphertext.
+fn append_ciphertext<K: Key>(sealed: &mut Vec<u8>, plaintext: &[u8], key: &K, mode: &OpMode) -> Result<(), EncryptError> {
+    let (ciphertext, init_vec) = encrypt_bytes(plaintext, key, mode)?.into_parts();
+    if let Some(init_vec) = init_vec {
+        sealed.extend_from_slice(&init_vec.to_bytes());
+    }
+    sealed.extend(ciphertext);
+    Ok(())
+}
+
+/// Computes the tag over the envelope and the associated data. The
+/// length of the associated data comes last, so that bytes cannot be
+/// moved between the two.
+fn compute_tag(mac_key: &Key128Bit, data: &[u8], aad: &[u8]) -> [u8; 8] {
+    let mut mac = Cmac::new(mac_key);
+    mac.update(data);
+    mac.update(aad);
+    mac.update(&(aad.len() as u64).to_be_bytes());
+    mac.finalize()
+}
+
+/// Derives the encryption and the MAC subkey of authenticated envelopes.
+fn subkeys<K: Key>(key: &K) -> (Key128Bit, Key128Bit) {
+    let hkdf = Hkdf::from_prk(key);
+    (hkdf.expand_key128(ENC_LABEL), hkdf.expand_key128(MAC_LABEL))
+}
+
+/// Splits off the next `len` bytes of the header.
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8], EnvelopeError> {
+    if rest.len() < len {
+        return Err(EnvelopeError::Malformed);
+    }
+    let (field, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(field)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut value = [0u8; 4];
+    value.copy_from_slice(bytes);
+    u32::from_be_bytes(value)
+}
+
+fn mode_to_byte(mode: &OpMode) -> u8 {
+    match *mode {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        OpMode::ECB => 0,
+        OpMode::CBC => 1,
+        OpMode::PCBC => 2,
+        OpMode::CFB => 3,
+        OpMode::CTR => 4,
+    }
+}
+
+fn mode_from_byte(byte: u8) -> Option<OpMode> {
+    match byte {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        0 => Some(OpMode::ECB),
+        1 => Some(OpMode::CBC),
+        2 => Some(OpMode::PCBC),
+        3 => Some(OpMode::CFB),
+        4 => Some(OpMode::CTR),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modes::all_modes;
+    use keys::Key80Bit;
+
+    #[test]
+    fn test_seal_and_open() {
+        let key = Key80Bit::new([0x42; 10]);
+        for mode in all_modes().iter() {
+            let sealed = Envelope::new(*mode).seal(b"Hello, world!", b"", &key).unwrap();
+            assert_eq!(&sealed[..8], &[b'P', b'R', b'S', b'E', 1, mode_to_byte(mode), 10, FLAG_TAG]);
+            assert_eq!(Envelope::open(&sealed, b"", &key).unwrap(), b"Hello, world!");
+        }
+
+        assert!(matches!(Envelope::open(&[0u8; 4], b"", &key), Err(EnvelopeError::Malformed)));
+        let sealed = Envelope::new(OpMode::CBC).seal(b"", b"", &key).unwrap();
+        assert!(matches!(Envelope::open(&sealed, b"", &Key128Bit::new([0x42; 16])), Err(EnvelopeError::KeyMismatch)));
+        for i in 0..sealed.len() {
+            let mut tampered = sealed.clone();
+            tampered[i] ^= 0x01;
+            assert!(Envelope::open(&tampered, b"", &key).is_err());
+        }
+        let mut newer = sealed.clone();
+        newer[4] = 2;
+        assert!(matches!(Envelope::open(&newer, b"", &key), Err(EnvelopeError::UnsupportedVersion(2))));
+    }
+
+    #[test]
+    fn test_seal_with_round_keys() {
+        let round_keys = Key80Bit::new([0x42; 10]).generate_round_keys();
+        let sealed = Envelope::new(OpMode::CBC).seal(b"Hello, world!", b"", &round_keys).unwrap();
+        assert_eq!(sealed[6], 0);
+        assert_eq!(Envelope::open(&sealed, b"", &round_keys).unwrap(), b"Hello, world!");
+    }
+
+    #[test]
+    fn test_without_tag_is_plain_ciphertext() {
+        let key = Key80Bit::new([0x42; 10]);
+        let sealed = Envelope::new(OpMode::CBC).without_tag().seal(b"Hello, world!", b"", &key).unwrap();
+        assert_eq!(sealed.len(), 8 + 8 + 16);
+        assert!(matches!(Envelope::open(&sealed, b"", &key), Err(EnvelopeError::Unauthenticated)));
+        assert_eq!(Envelope::open_unauthenticated(&sealed, b"", &key).unwrap(), b"Hello, world!");
+
+        let mut iv = [0u8; 8];
+        iv.copy_from_slice(&sealed[8..16]);
+        let plaintext = decrypt_bytes(&sealed[16..], &key, &OpMode::CBC, Some(Block::from_bytes(&iv)));
+        assert_eq!(plaintext.unwrap(), b"Hello, world!");
+    }
+
+    #[test]
+    fn test_tag_cannot_be_stripped() {
+        let key = Key80Bit::new([0x42; 10]);
+        let mut keys = KeyRing::new();
+        keys.insert(7, Key80Bit::new([0x42; 10]));
+
+        let sealed = Envelope::new(OpMode::CBC).with_key_id(7).seal(b"Hello, world!", b"", &key).unwrap();
+        let mut stripped = sealed[..sealed.len() - 8].to_vec();
+        stripped[7] &= !FLAG_TAG;
+        assert!(matches!(Envelope::open(&stripped, b"", &key), Err(EnvelopeError::Unauthenticated)));
+        assert!(matches!(Envelope::open_with_key_ring(&stripped, b"", &keys), Err(EnvelopeError::Unauthenticated)));
+        assert_eq!(Envelope::open_unauthenticated(&sealed, b"", &key).unwrap(), b"Hello, world!");
+
+        let sealed = Envelope::new(OpMode::CFB).seal_with_passphrase(b"secret", b"", b"passphrase", 10).unwrap();
+        let mut stripped = sealed[..sealed.len() - 8].to_vec();
+        stripped[7] &= !FLAG_TAG;
+        assert!(matches!(Envelope::open_with_passphrase(&stripped, b"", b"passphrase"), Err(EnvelopeError::Unauthenticated)));
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let sealed = Envelope::new(OpMode::CFB).seal_with_passphrase(b"secret", b"", b"passphrase", 10).unwrap();
+        assert_eq!(Envelope::open_with_passphrase(&sealed, b"", b"passphrase").unwrap(), b"secret");
+        assert!(matches!(Envelope::open_with_passphrase(&sealed, b"", b"passphrasf"), Err(EnvelopeError::AuthenticationFailed)));
+        assert!(matches!(Envelope::open(&sealed, b"", &Key128Bit::new([0u8; 16])), Err(EnvelopeError::KeyMismatch)));
+    }
+
+    #[test]
+    fn test_associated_data() {
+        let key = Key80Bit::new([0x42; 10]);
+        let sealed = Envelope::new(OpMode::CBC).seal(b"Hello, world!", b"route 1", &key).unwrap();
+        assert_eq!(Envelope::open(&sealed, b"route 1", &key).unwrap(), b"Hello, world!");
+        assert!(matches!(Envelope::open(&sealed, b"route 2", &key), Err(EnvelopeError::AuthenticationFailed)));
+        assert!(matches!(Envelope::open(&sealed, b"", &key), Err(EnvelopeError::AuthenticationFailed)));
+        assert!(matches!(Envelope::open_unauthenticated(&sealed, b"route 2", &key), Err(EnvelopeError::AuthenticationFailed)));
+
+        let sealed = Envelope::new(OpMode::CFB).seal_with_passphrase(b"secret", b"v2", b"passphrase", 10).unwrap();
+        assert_eq!(Envelope::open_with_passphrase(&sealed, b"v2", b"passphrase").unwrap(), b"secret");
+        assert!(matches!(Envelope::open_with_passphrase(&sealed, b"v3", b"passphrase"), Err(EnvelopeError::AuthenticationFailed)));
+
+        let untagged = Envelope::new(OpMode::CBC).without_tag().seal(b"", b"", &key).unwrap();
+        assert!(matches!(Envelope::open_unauthenticated(&untagged, b"route 1", &key), Err(EnvelopeError::Unauthenticated)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_associated_data_needs_tag() {
+        let _ = Envelope::new(OpMode::CBC).without_tag().seal(b"", b"route 1", &Key80Bit::new([0x42; 10]));
+    }
+
+    #[test]
+    fn test_rejects_excessive_iterations() {
+        let mut sealed = Envelope::new(OpMode::CBC).seal_with_passphrase(b"secret", b"", b"passphrase", 10).unwrap();
+        sealed[9..13].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(Envelope::open_with_passphrase(&sealed, b"", b"passphrase"), Err(EnvelopeError::Malformed)));
+    }
+
+    #[test]
+    fn test_key_ring() {
+        let mut keys = KeyRing::new();
+        keys.insert(7, Key80Bit::new([0x07; 10]));
+        let sealed = Envelope::new(OpMode::CBC).with_key_id(7).seal(b"rotated", b"", keys.get(7).unwrap()).unwrap();
+        assert_eq!(&sealed[8..12], &[0, 0, 0, 7]);
+        assert_eq!(Envelope::open_with_key_ring(&sealed, b"", &keys).unwrap(), b"rotated");
+
+        keys.remove(7);
+        assert!(matches!(Envelope::open_with_key_ring(&sealed, b"", &keys), Err(EnvelopeError::UnknownKeyId(7))));
+        let without_id = Envelope::new(OpMode::CBC).seal(b"", b"", &Key80Bit::new([0x07; 10])).unwrap();
+        assert!(matches!(Envelope::open_with_key_ring(&without_id, b"", &keys), Err(EnvelopeError::KeyMismatch)));
+    }
+}
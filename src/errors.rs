@@ -1,32 +1,794 @@
 use std::convert::From;
+use std::error::Error;
+use std::fmt;
+use std::str::Utf8Error;
 use std::string::FromUtf8Error;
 
+/// Broad category of an error.
+///
+/// Every error type of this crate can be mapped to one of these
+/// kinds via its `kind()` method. Matching on the kind instead of
+/// the individual variants keeps downstream code working when new
+/// modes of operation introduce new failure cases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The input is malformed, e.g. it has an invalid length or
+    /// encoding, or a required parameter is missing.
+    Format,
+    /// An integrity check failed, e.g. the padding is corrupted.
+    Integrity,
+    /// The key is invalid or unsuitable for the operation.
+    Key,
+    /// Reading or writing data failed.
+    Io,
+    /// The random number generator could not provide randomness.
+    Rng,
+}
+
 /// Error type describing string decryption errors.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DecryptError {
     /// Indicates that the decrypted bytes cannot be converted
     /// to a valid UTF-8-encoded string.
-    Utf8Error,
+    Utf8Error {
+        /// Offset of the first invalid byte in the decrypted data.
+        offset: usize,
+        /// The conversion error. Holds no decrypted bytes, so the
+        /// plaintext never ends up in `Debug` output.
+        source: Utf8Error,
+    },
     /// Indicates that the ciphertext length is not a multiple
-    /// of the block size. Includes the length of the given
-    /// ciphertext.
-    CiphertextNotAligned(usize),
+    /// of the block size.
+    CiphertextNotAligned {
+        /// Length of the given ciphertext.
+        len: usize,
+        /// Block size the length must be a multiple of.
+        block_size: usize,
+    },
     /// Indicates that the ciphertext is too short (i.e. less
-    /// than one block). Includes the length of the given
-    /// ciphertext.
-    CiphertextTooShort(usize),
+    /// than one block).
+    CiphertextTooShort {
+        /// Length of the given ciphertext.
+        len: usize,
+        /// Minimum length of a ciphertext.
+        min_len: usize,
+    },
     /// Indicates that the padding bytes at the end of the string
     /// are invalid or corrupted.
-    InvalidPadding,
+    InvalidPadding {
+        /// Index of the block containing the padding.
+        block: usize,
+    },
     /// When using an operation mode that requires an initialization
     /// vector (pretty much all except ECB), this indicates that
     /// the IV was not given in the function arguments.
     InitVecMissing,
+    /// Indicates that the decrypted data does not have the length
+    /// of the requested type.
+    PlaintextLengthMismatch {
+        /// Length of the decrypted data.
+        len: usize,
+        /// Size of the requested type.
+        expected: usize,
+    },
+    /// Indicates that the output buffer is too small to hold the
+    /// decrypted data.
+    OutputTooSmall {
+        /// Length of the given output buffer.
+        len: usize,
+        /// Length the output buffer needs to have.
+        required: usize,
+    },
+    /// Indicates that the padding or the content of the decrypted
+    /// data is invalid, without telling which. Only returned by the
+    /// uniform decryption functions such as
+    /// [`decrypt_bytes_uniform`](fn.decrypt_bytes_uniform.html).
+    Rejected,
+}
+
+impl DecryptError {
+    /// Returns the broad category of this error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{decrypt_str, ErrorKind, Key80Bit, OpMode};
+    /// let key = Key80Bit::new([0xFF; 10]);
+    /// let err = decrypt_str(&[0u8; 5], &key, &OpMode::CBC, None).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::Format);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            DecryptError::Utf8Error { .. } => ErrorKind::Format,
+            DecryptError::CiphertextNotAligned { .. } => ErrorKind::Format,
+            DecryptError::CiphertextTooShort { .. } => ErrorKind::Format,
+            DecryptError::InvalidPadding { .. } => ErrorKind::Integrity,
+            DecryptError::InitVecMissing => ErrorKind::Format,
+            DecryptError::PlaintextLengthMismatch { .. } => ErrorKind::Format,
+            DecryptError::OutputTooSmall { .. } => ErrorKind::Format,
+            DecryptError::Rejected => ErrorKind::Integrity,
+        }
+    }
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecryptError::Utf8Error { offset, .. } =>
+                write!(f, "decrypted data is not valid UTF-8 at byte {}", offset),
+            DecryptError::CiphertextNotAligned { len, block_size } =>
+                write!(f, "ciphertext length of {} bytes is not a multiple of the block size of {} bytes", len, block_size),
+            DecryptError::CiphertextTooShort { len, min_len } =>
+                write!(f, "ciphertext of {} bytes is shorter than the minimum of {} bytes", len, min_len),
+            DecryptError::InvalidPadding { block } => write!(f, "padding in block {} is invalid or corrupted", block),
+            DecryptError::InitVecMissing => write!(f, "mode of operation requires an initialization vector"),
+            DecryptError::PlaintextLengthMismatch { len, expected } =>
+                write!(f, "decrypted data of {} bytes does not match the {} bytes of the requested type", len, expected),
+            DecryptError::OutputTooSmall { len, required } =>
+                write!(f, "output buffer of {} bytes is too small, {} bytes are required", len, required),
+            DecryptError::Rejected => write!(f, "decrypted data is invalid"),
+        }
+    }
+}
+
+impl Error for DecryptError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            DecryptError::Utf8Error { ref source, .. } => Some(source),
+            _ => None,
+        }
+    }
 }
 
 impl From<FromUtf8Error> for DecryptError {
     /// Convert string encoding error to the corresponding DecryptError.
-    fn from(_: FromUtf8Error) -> Self {
-        DecryptError::Utf8Error
+    /// The decrypted bytes held by the error are discarded.
+    fn from(e: FromUtf8Error) -> Self {
+        let source = e.utf8_error();
+        DecryptError::Utf8Error { offset: source.valid_up_to(), source }
+    }
+}
+
+/// Error type describing encryption errors.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EncryptError {
+    /// Indicates that no initialization vector could be generated.
+    /// Includes the error of the random number generator.
+    Rng(RngError),
+    /// Indicates that the mode of operation cannot be used with an
+    /// [`EncryptKey`](struct.EncryptKey.html), because decrypting it
+    /// only needs the forward cipher.
+    ModeNotAllowed,
+}
+
+impl EncryptError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            EncryptError::Rng(ref e) => e.kind(),
+            EncryptError::ModeNotAllowed => ErrorKind::Key,
+        }
+    }
+}
+
+impl From<RngError> for EncryptError {
+    /// Convert random number generator errors to the corresponding
+    /// EncryptError.
+    fn from(e: RngError) -> Self {
+        EncryptError::Rng(e)
+    }
+}
+
+impl fmt::Display for EncryptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncryptError::Rng(_) => write!(f, "no initialization vector could be generated"),
+            EncryptError::ModeNotAllowed => write!(f, "mode of operation cannot be used with an encrypt-only key"),
+        }
+    }
+}
+
+impl Error for EncryptError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EncryptError::Rng(ref e) => Some(e),
+            EncryptError::ModeNotAllowed => None,
+        }
+    }
+}
+
+/// Error type describing failures when parsing keys.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyParseError {
+    /// Indicates that the input does not have the length required for
+    /// the key type. Includes the length of the given input.
+    InvalidLength(usize),
+    /// Indicates that the input contains characters that are not valid
+    /// hexadecimal digits. The position is deliberately not reported,
+    /// as it could leak information about the key.
+    InvalidHex,
+    /// Indicates that the input is not valid padded Base64 in the
+    /// standard alphabet. Like for `InvalidHex`, the position is not
+    /// reported.
+    InvalidBase64,
+}
+
+impl KeyParseError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            KeyParseError::InvalidLength(_) => ErrorKind::Key,
+            KeyParseError::InvalidHex => ErrorKind::Key,
+            KeyParseError::InvalidBase64 => ErrorKind::Key,
+        }
+    }
+}
+
+impl fmt::Display for KeyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyParseError::InvalidLength(len) => write!(f, "key of {} bytes has an unsupported length", len),
+            KeyParseError::InvalidHex => write!(f, "key contains invalid hexadecimal digits"),
+            KeyParseError::InvalidBase64 => write!(f, "key is not valid Base64"),
+        }
+    }
+}
+
+impl Error for KeyParseError {}
+
+/// Error type describing failures to parse blocks and ciphertexts from
+/// their hexadecimal text representation.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// Indicates that a hexadecimal field does not have the required
+    /// length. Includes the length of the field.
+    InvalidLength(usize),
+    /// Indicates that the input contains characters that are not valid
+    /// hexadecimal digits.
+    InvalidHex,
+    /// Indicates that the input does not have the expected structure,
+    /// e.g. a field or the IV is missing, or the mode is unknown.
+    InvalidFormat,
+    /// Indicates that the input is not valid padded Base64 in the
+    /// standard alphabet.
+    InvalidBase64,
+}
+
+impl ParseError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            ParseError::InvalidLength(_) => ErrorKind::Format,
+            ParseError::InvalidHex => ErrorKind::Format,
+            ParseError::InvalidFormat => ErrorKind::Format,
+            ParseError::InvalidBase64 => ErrorKind::Format,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::InvalidLength(len) => write!(f, "hexadecimal field of {} digits has an invalid length", len),
+            ParseError::InvalidHex => write!(f, "input contains invalid hexadecimal digits"),
+            ParseError::InvalidFormat => write!(f, "input does not have the expected format"),
+            ParseError::InvalidBase64 => write!(f, "input is not valid Base64"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Error type describing failures of authenticated encryption modes.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AeadError {
+    /// Indicates that the tag length is not supported by the mode.
+    /// Includes the requested length in bytes.
+    InvalidTagLength(usize),
+    /// Indicates that the nonce does not have a length supported by
+    /// the mode. Includes the length of the given nonce.
+    InvalidNonceLength(usize),
+    /// Indicates that the message is too long to be processed with the
+    /// configured parameters. Includes the length of the message.
+    MessageTooLong(usize),
+    /// Indicates that the ciphertext is shorter than the tag. Includes
+    /// the length of the given ciphertext.
+    CiphertextTooShort(usize),
+    /// Indicates that the tag did not match, i.e. the ciphertext, the
+    /// associated data or the nonce was modified, or the wrong key was
+    /// used. No plaintext is released in this case.
+    AuthenticationFailed,
+}
+
+impl AeadError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            AeadError::InvalidTagLength(_) => ErrorKind::Format,
+            AeadError::InvalidNonceLength(_) => ErrorKind::Format,
+            AeadError::MessageTooLong(_) => ErrorKind::Format,
+            AeadError::CiphertextTooShort(_) => ErrorKind::Format,
+            AeadError::AuthenticationFailed => ErrorKind::Integrity,
+        }
+    }
+}
+
+impl fmt::Display for AeadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AeadError::InvalidTagLength(len) => write!(f, "tag length of {} bytes is not supported", len),
+            AeadError::InvalidNonceLength(len) => write!(f, "nonce length of {} bytes is not supported", len),
+            AeadError::MessageTooLong(len) => write!(f, "message of {} bytes is too long", len),
+            AeadError::CiphertextTooShort(len) => write!(f, "ciphertext of {} bytes is shorter than the tag", len),
+            AeadError::AuthenticationFailed => write!(f, "authentication failed"),
+        }
+    }
+}
+
+impl Error for AeadError {}
+
+/// Error type describing failures when opening an
+/// [`Envelope`](struct.Envelope.html).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EnvelopeError {
+    /// Indicates that the data is not a valid envelope, e.g. because
+    /// it is truncated or the header is corrupted.
+    Malformed,
+    /// Indicates that the envelope was created by a newer version of
+    /// the format. Includes the version found in the header.
+    UnsupportedVersion(u8),
+    /// Indicates that the envelope was sealed with a different kind of
+    /// key, e.g. a key of the other length, a passphrase instead of a
+    /// key, or a key without an ID when opening with a key ring.
+    KeyMismatch,
+    /// Indicates that the key ring has no key with the ID from the
+    /// header. Includes the ID.
+    UnknownKeyId(u32),
+    /// Indicates that the tag did not match, i.e. the envelope was
+    /// modified or the wrong key was used.
+    AuthenticationFailed,
+    /// Indicates that the envelope has no tag. Envelopes sealed
+    /// without a tag can only be opened with
+    /// [`Envelope::open_unauthenticated()`](struct.Envelope.html#method.open_unauthenticated).
+    Unauthenticated,
+    /// Indicates that the ciphertext could not be decrypted. Includes
+    /// the decryption error.
+    Decrypt(DecryptError),
+}
+
+impl EnvelopeError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            EnvelopeError::Malformed => ErrorKind::Format,
+            EnvelopeError::UnsupportedVersion(_) => ErrorKind::Format,
+            EnvelopeError::KeyMismatch => ErrorKind::Key,
+            EnvelopeError::UnknownKeyId(_) => ErrorKind::Key,
+            EnvelopeError::AuthenticationFailed => ErrorKind::Integrity,
+            EnvelopeError::Unauthenticated => ErrorKind::Integrity,
+            EnvelopeError::Decrypt(ref e) => e.kind(),
+        }
+    }
+}
+
+impl From<DecryptError> for EnvelopeError {
+    /// Wrap errors of the decryption of the ciphertext.
+    fn from(e: DecryptError) -> Self {
+        EnvelopeError::Decrypt(e)
+    }
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EnvelopeError::Malformed => write!(f, "data is not a valid envelope"),
+            EnvelopeError::UnsupportedVersion(version) => write!(f, "envelope format version {} is not supported", version),
+            EnvelopeError::KeyMismatch => write!(f, "envelope was sealed with a different kind of key"),
+            EnvelopeError::UnknownKeyId(id) => write!(f, "no key with ID {} in the key ring", id),
+            EnvelopeError::AuthenticationFailed => write!(f, "envelope authentication failed"),
+            EnvelopeError::Unauthenticated => write!(f, "envelope has no tag"),
+            EnvelopeError::Decrypt(_) => write!(f, "envelope could not be decrypted"),
+        }
+    }
+}
+
+impl Error for EnvelopeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EnvelopeError::Decrypt(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error type describing failures when working on caller-provided buffers.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BufferError {
+    /// Indicates that the buffer length is not a multiple of the block
+    /// size, which the operation mode requires because no padding is
+    /// added or removed in place.
+    NotAligned {
+        /// Length of the given buffer.
+        len: usize,
+        /// Block size the length must be a multiple of.
+        block_size: usize,
+    },
+    /// Indicates that the output buffer is too small to hold the
+    /// result.
+    OutputTooSmall {
+        /// Length of the given output buffer.
+        len: usize,
+        /// Length the output buffer needs to have.
+        required: usize,
+    },
+    /// Indicates that the buffer is shorter than the operation mode
+    /// allows, e.g. an XTS sector shorter than one block.
+    TooShort {
+        /// Length of the given buffer.
+        len: usize,
+        /// Minimum length the buffer needs to have.
+        min: usize,
+    },
+}
+
+impl BufferError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            BufferError::NotAligned { .. } => ErrorKind::Format,
+            BufferError::OutputTooSmall { .. } => ErrorKind::Format,
+            BufferError::TooShort { .. } => ErrorKind::Format,
+        }
+    }
+}
+
+impl fmt::Display for BufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BufferError::NotAligned { len, block_size } =>
+                write!(f, "buffer length of {} bytes is not a multiple of the block size of {} bytes", len, block_size),
+            BufferError::OutputTooSmall { len, required } =>
+                write!(f, "output buffer of {} bytes is too small, {} bytes are required", len, required),
+            BufferError::TooShort { len, min } =>
+                write!(f, "buffer of {} bytes is too short, at least {} bytes are required", len, min),
+        }
+    }
+}
+
+impl Error for BufferError {}
+
+/// Error type describing faults detected by the hardened block
+/// operations.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FaultError {
+    /// Indicates that the result did not pass the verification with
+    /// the inverse operation, e.g. because a voltage or clock glitch
+    /// disturbed the computation. The output was cleared.
+    Detected,
+}
+
+impl FaultError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            FaultError::Detected => ErrorKind::Integrity,
+        }
+    }
+}
+
+impl fmt::Display for FaultError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FaultError::Detected => write!(f, "fault detected, the output was cleared"),
+        }
+    }
+}
+
+impl Error for FaultError {}
+
+/// Error type describing failures of the random number generator.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RngError {
+    /// Indicates that the random number generator could not provide
+    /// randomness, e.g. because the operating system RNG is not
+    /// available. Includes the error reported by the generator.
+    Unavailable(::rand::Error),
+}
+
+impl RngError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            RngError::Unavailable(_) => ErrorKind::Rng,
+        }
+    }
+}
+
+impl From<::rand::Error> for RngError {
+    /// Convert errors reported by a random number generator.
+    fn from(e: ::rand::Error) -> Self {
+        RngError::Unavailable(e)
+    }
+}
+
+impl fmt::Display for RngError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RngError::Unavailable(_) => write!(f, "random number generator is unavailable"),
+        }
+    }
+}
+
+impl Error for RngError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            RngError::Unavailable(ref e) => Some(e),
+        }
+    }
+}
+
+/// Error type describing failures when storing or loading keys in
+/// the platform keyring.
+#[cfg(feature = "os-keyring")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum KeyringError {
+    /// Indicates that no key is stored under the given name.
+    NotFound,
+    /// Indicates that the stored key does not have the length of the
+    /// requested key type. Includes the length of the stored key.
+    InvalidLength(usize),
+    /// Indicates that the platform keyring reported an error.
+    Backend(::keyring::Error),
+}
+
+#[cfg(feature = "os-keyring")]
+impl KeyringError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            KeyringError::NotFound => ErrorKind::Key,
+            KeyringError::InvalidLength(_) => ErrorKind::Key,
+            KeyringError::Backend(_) => ErrorKind::Io,
+        }
+    }
+}
+
+#[cfg(feature = "os-keyring")]
+impl From<::keyring::Error> for KeyringError {
+    /// Convert keyring errors, mapping missing entries to `NotFound`.
+    fn from(e: ::keyring::Error) -> Self {
+        match e {
+            ::keyring::Error::NoEntry => KeyringError::NotFound,
+            e => KeyringError::Backend(e),
+        }
+    }
+}
+
+#[cfg(feature = "os-keyring")]
+impl fmt::Display for KeyringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyringError::NotFound => write!(f, "no key is stored under the given name"),
+            KeyringError::InvalidLength(len) => write!(f, "stored key of {} bytes has the wrong length", len),
+            KeyringError::Backend(_) => write!(f, "platform keyring reported an error"),
+        }
+    }
+}
+
+#[cfg(feature = "os-keyring")]
+impl Error for KeyringError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            KeyringError::Backend(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error type describing failures of Argon2 key derivation.
+#[cfg(feature = "argon2")]
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KdfError {
+    /// Indicates that encoded parameters do not have the expected
+    /// length. Includes the length of the given input.
+    InvalidLength(usize),
+    /// Indicates that the cost parameters are out of range. Includes
+    /// the error reported by Argon2.
+    InvalidParams(::argon2::Error),
+}
+
+#[cfg(feature = "argon2")]
+impl KdfError {
+    /// Returns the broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            KdfError::InvalidLength(_) => ErrorKind::Format,
+            KdfError::InvalidParams(_) => ErrorKind::Format,
+        }
+    }
+}
+
+#[cfg(feature = "argon2")]
+impl From<::argon2::Error> for KdfError {
+    /// Convert errors reported by Argon2.
+    fn from(e: ::argon2::Error) -> Self {
+        KdfError::InvalidParams(e)
+    }
+}
+
+#[cfg(feature = "argon2")]
+impl fmt::Display for KdfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KdfError::InvalidLength(len) => write!(f, "encoded parameters of {} bytes have an invalid length", len),
+            KdfError::InvalidParams(_) => write!(f, "Argon2 parameters are out of range"),
+        }
+    }
+}
+
+#[cfg(feature = "argon2")]
+impl Error for KdfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            KdfError::InvalidParams(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypt_error_kinds() {
+        assert_eq!(DecryptError::from(String::from_utf8(vec![0xFF]).unwrap_err()).kind(), ErrorKind::Format);
+        assert_eq!(DecryptError::CiphertextNotAligned { len: 9, block_size: 8 }.kind(), ErrorKind::Format);
+        assert_eq!(DecryptError::CiphertextTooShort { len: 3, min_len: 8 }.kind(), ErrorKind::Format);
+        assert_eq!(DecryptError::InvalidPadding { block: 0 }.kind(), ErrorKind::Integrity);
+        assert_eq!(DecryptError::InitVecMissing.kind(), ErrorKind::Format);
+        assert_eq!(DecryptError::PlaintextLengthMismatch { len: 4, expected: 8 }.kind(), ErrorKind::Format);
+        assert_eq!(DecryptError::OutputTooSmall { len: 8, required: 16 }.kind(), ErrorKind::Format);
+        assert_eq!(DecryptError::Rejected.kind(), ErrorKind::Integrity);
+    }
+
+    #[test]
+    fn test_decrypt_error_display_and_source() {
+        let err = DecryptError::CiphertextNotAligned { len: 9, block_size: 8 };
+        assert_eq!(err.to_string(), "ciphertext length of 9 bytes is not a multiple of the block size of 8 bytes");
+        assert!(err.source().is_none());
+
+        let err = DecryptError::from(String::from_utf8(vec![0x41, 0x42, 0xFF]).unwrap_err());
+        assert!(matches!(err, DecryptError::Utf8Error { offset: 2, .. }));
+        assert_eq!(err.to_string(), "decrypted data is not valid UTF-8 at byte 2");
+        assert!(err.source().unwrap().is::<Utf8Error>());
+
+        // Usable as a boxed error
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(DecryptError::InvalidPadding { block: 3 });
+        assert_eq!(boxed.to_string(), "padding in block 3 is invalid or corrupted");
+    }
+
+    #[test]
+    fn test_utf8_error_debug_omits_plaintext() {
+        let mut bytes = b"secret plaintext".to_vec();
+        bytes.push(0xFF);
+        let err = DecryptError::from(String::from_utf8(bytes).unwrap_err());
+        let debug = format!("{:?}", err);
+        assert!(!debug.contains("secret"));
+        assert!(!debug.contains("115, 101, 99"));
+    }
+
+    #[test]
+    fn test_parse_error_kinds() {
+        assert_eq!(ParseError::InvalidLength(3).kind(), ErrorKind::Format);
+        assert_eq!(ParseError::InvalidHex.kind(), ErrorKind::Format);
+        assert_eq!(ParseError::InvalidFormat.kind(), ErrorKind::Format);
+        assert_eq!(ParseError::InvalidBase64.kind(), ErrorKind::Format);
+    }
+
+    #[test]
+    fn test_aead_error_kinds() {
+        assert_eq!(AeadError::InvalidTagLength(2).kind(), ErrorKind::Format);
+        assert_eq!(AeadError::InvalidNonceLength(9).kind(), ErrorKind::Format);
+        assert_eq!(AeadError::MessageTooLong(70000).kind(), ErrorKind::Format);
+        assert_eq!(AeadError::CiphertextTooShort(3).kind(), ErrorKind::Format);
+        assert_eq!(AeadError::AuthenticationFailed.kind(), ErrorKind::Integrity);
+    }
+
+    #[test]
+    fn test_envelope_error_kinds() {
+        assert_eq!(EnvelopeError::Malformed.kind(), ErrorKind::Format);
+        assert_eq!(EnvelopeError::UnsupportedVersion(2).kind(), ErrorKind::Format);
+        assert_eq!(EnvelopeError::KeyMismatch.kind(), ErrorKind::Key);
+        assert_eq!(EnvelopeError::UnknownKeyId(7).kind(), ErrorKind::Key);
+        assert_eq!(EnvelopeError::AuthenticationFailed.kind(), ErrorKind::Integrity);
+        assert_eq!(EnvelopeError::Unauthenticated.kind(), ErrorKind::Integrity);
+        assert_eq!(EnvelopeError::from(DecryptError::InvalidPadding { block: 0 }).kind(), ErrorKind::Integrity);
+    }
+
+    #[test]
+    fn test_error_display_and_source() {
+        let err = EnvelopeError::from(DecryptError::InvalidPadding { block: 0 });
+        assert_eq!(err.to_string(), "envelope could not be decrypted");
+        assert!(err.source().unwrap().is::<DecryptError>());
+        assert!(EnvelopeError::Malformed.source().is_none());
+
+        let err = EncryptError::from(RngError::from(::rand::Error::new(::std::io::Error::other("no entropy"))));
+        assert_eq!(err.to_string(), "no initialization vector could be generated");
+        let source = err.source().unwrap();
+        assert!(source.is::<RngError>());
+        assert!(source.source().unwrap().is::<::rand::Error>());
+
+        assert_eq!(KeyParseError::InvalidLength(3).to_string(), "key of 3 bytes has an unsupported length");
+        assert_eq!(ParseError::InvalidHex.to_string(), "input contains invalid hexadecimal digits");
+        assert_eq!(AeadError::AuthenticationFailed.to_string(), "authentication failed");
+        assert_eq!(BufferError::OutputTooSmall { len: 8, required: 16 }.to_string(),
+                   "output buffer of 8 bytes is too small, 16 bytes are required");
+        assert_eq!(FaultError::Detected.to_string(), "fault detected, the output was cleared");
+
+        // All of them can be boxed
+        let errors: Vec<Box<dyn Error + Send + Sync>> = vec![
+            Box::new(KeyParseError::InvalidHex),
+            Box::new(ParseError::InvalidFormat),
+            Box::new(AeadError::InvalidTagLength(2)),
+            Box::new(EnvelopeError::KeyMismatch),
+            Box::new(BufferError::NotAligned { len: 9, block_size: 8 }),
+            Box::new(FaultError::Detected),
+        ];
+        assert!(errors.iter().all(|err| !err.to_string().is_empty()));
+    }
+
+    #[test]
+    fn test_buffer_error_kinds() {
+        assert_eq!(BufferError::NotAligned { len: 9, block_size: 8 }.kind(), ErrorKind::Format);
+        assert_eq!(BufferError::OutputTooSmall { len: 8, required: 16 }.kind(), ErrorKind::Format);
+    }
+
+    #[test]
+    fn test_fault_error_kinds() {
+        assert_eq!(FaultError::Detected.kind(), ErrorKind::Integrity);
+    }
+
+    #[test]
+    fn test_encrypt_error_kinds() {
+        let err = EncryptError::from(RngError::from(::rand::Error::new(::std::io::Error::other("no entropy"))));
+        assert_eq!(err.kind(), ErrorKind::Rng);
+        assert_eq!(EncryptError::ModeNotAllowed.kind(), ErrorKind::Key);
+    }
+
+    #[test]
+    fn test_rng_error_kinds() {
+        let err = RngError::from(::rand::Error::new(::std::io::Error::other("no entropy")));
+        assert_eq!(err.kind(), ErrorKind::Rng);
+    }
+
+    #[test]
+    fn test_key_parse_error_kinds() {
+        assert_eq!(KeyParseError::InvalidLength(3).kind(), ErrorKind::Key);
+        assert_eq!(KeyParseError::InvalidBase64.kind(), ErrorKind::Key);
+        assert_eq!(KeyParseError::InvalidHex.kind(), ErrorKind::Key);
+    }
+
+    #[cfg(feature = "argon2")]
+    #[test]
+    fn test_kdf_error_kinds() {
+        assert_eq!(KdfError::InvalidLength(3).kind(), ErrorKind::Format);
+        assert_eq!(KdfError::from(::argon2::Error::SaltTooShort).kind(), ErrorKind::Format);
+
+        let err = KdfError::from(::argon2::Error::SaltTooShort);
+        assert_eq!(err.to_string(), "Argon2 parameters are out of range");
+        assert!(err.source().unwrap().is::<::argon2::Error>());
     }
 }
@@ -0,0 +1,102 @@
+use block::Block;
+use errors::{AeadError, EncryptError};
+use kdf::Hkdf;
+use keys::{Key, Key128Bit};
+use mac::cmac;
+use modes::{self, OpMode};
+use tag::tags_equal;
+use {decrypt_bytes, try_encrypt_bytes_with_iv};
+
+/// Labels for deriving the encryption and MAC subkeys.
+const ENC_LABEL: &[u8] = b"present etm enc";
+const MAC_LABEL: &[u8] = b"present etm mac";
+
+/// Encrypt data and authenticate the ciphertext.
+///
+/// The data is encrypted in CBC mode with a random IV, then a CMAC is
+/// computed over the IV and the ciphertext. Encryption and MAC use
+/// independent subkeys derived from `key` with
+/// [`Hkdf`](struct.Hkdf.html), so a single key can be passed safely.
+/// The result is a single buffer containing the IV, the ciphertext and
+/// the 8 byte tag, in that order.
+///
+/// # Errors
+///
+/// Returns `EncryptError::Rng` if the random number generator of
+/// the operating system fails.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_then_mac, verify_then_decrypt, Key80Bit};
+/// let key = Key80Bit::new([0x42; 10]);
+/// let framed = encrypt_then_mac(b"config blob", &key).unwrap();
+/// assert_eq!(verify_then_decrypt(&framed, &key).unwrap(), b"config blob");
+/// ```
+pub fn encrypt_then_mac<K: Key>(plaintext: &[u8], key: &K) -> Result<Vec<u8>, EncryptError> {
+    let (enc_key, mac_key) = subkeys(key);
+    let iv = modes::random_iv()?;
+    let mut framed = iv.to_bytes().to_vec();
+    framed.extend(try_encrypt_bytes_with_iv(plaintext, &enc_key, &OpMode::CBC, &iv)?);
+
+    let tag = cmac(&mac_key, &framed);
+    framed.extend_from_slice(&tag);
+    Ok(framed)
+}
+
+/// Verify and decrypt a buffer produced by
+/// [`encrypt_then_mac`](fn.encrypt_then_mac.html).
+///
+/// The tag is checked before anything is decrypted.
+///
+/// # Errors
+///
+/// Returns `AeadError::CiphertextTooShort` if the buffer cannot hold an
+/// IV, a ciphertext block and a tag, and `AeadError::AuthenticationFailed`
+/// if the tag does not match.
+pub fn verify_then_decrypt<K: Key>(framed: &[u8], key: &K) -> Result<Vec<u8>, AeadError> {
+    if framed.len() < 24 {
+        return Err(AeadError::CiphertextTooShort(framed.len()));
+    }
+    let (enc_key, mac_key) = subkeys(key);
+    let (data, tag) = framed.split_at(framed.len() - 8);
+    if !tags_equal(&cmac(&mac_key, data), tag) {
+        return Err(AeadError::AuthenticationFailed);
+    }
+
+    let mut iv = [0u8; 8];
+    iv.copy_from_slice(&data[..8]);
+
+    // A valid tag can only be produced by encrypt_then_mac, so decryption
+    // failures would mean the data was forged with the MAC key
+    decrypt_bytes(&data[8..], &enc_key, &OpMode::CBC, Some(Block::from_bytes(&iv)))
+        .map_err(|_| AeadError::AuthenticationFailed)
+}
+
+/// Derives the encryption and the MAC subkey.
+fn subkeys<K: Key>(key: &K) -> (Key128Bit, Key128Bit) {
+    let hkdf = Hkdf::from_prk(key);
+    (hkdf.expand_key128(ENC_LABEL), hkdf.expand_key128(MAC_LABEL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key80Bit;
+
+    #[test]
+    fn test_roundtrip_and_tampering() {
+        let key = Key80Bit::new([0x42; 10]);
+        let framed = encrypt_then_mac(b"sixteen bytes!!!", &key).unwrap();
+        assert_eq!(framed.len(), 8 + 24 + 8);
+        assert_eq!(verify_then_decrypt(&framed, &key).unwrap(), b"sixteen bytes!!!");
+
+        for i in 0..framed.len() {
+            let mut tampered = framed.clone();
+            tampered[i] ^= 0x01;
+            assert_eq!(verify_then_decrypt(&tampered, &key), Err(AeadError::AuthenticationFailed));
+        }
+        assert_eq!(verify_then_decrypt(&framed, &Key80Bit::new([0x43; 10])), Err(AeadError::AuthenticationFailed));
+        assert_eq!(verify_then_decrypt(&framed[..23], &key), Err(AeadError::CiphertextTooShort(23)));
+    }
+}
@@ -0,0 +1,215 @@
+use std::ops::Range;
+
+/// Summary of a fault injection run.
+///
+/// Each injected fault flips exactly one bit of the targeted input
+/// and attempts decryption. The outcome is classified as rejected
+/// (decryption returned an error), corrupted (decryption succeeded
+/// but produced a different plaintext) or unnoticed (decryption
+/// produced the original plaintext).
+#[derive(Debug, Default)]
+pub struct FaultReport {
+    /// Number of faults that were injected.
+    pub injected: usize,
+    /// Number of faults that made decryption return an error.
+    pub rejected: usize,
+    /// Number of faults that silently produced a different plaintext.
+    pub corrupted: usize,
+    /// Number of faults that had no effect on the plaintext.
+    pub unnoticed: usize,
+    /// Positions of all flipped bits that were not rejected, counted
+    /// from the most significant bit of the first byte.
+    pub accepted_bits: Vec<usize>,
+}
+
+impl FaultReport {
+    /// Returns `true` if every injected fault was rejected by decryption.
+    pub fn all_rejected(&self) -> bool {
+        self.rejected == self.injected
+    }
+
+    /// Returns `true` if no injected fault went completely unnoticed.
+    pub fn all_detectable(&self) -> bool {
+        self.unnoticed == 0
+    }
+
+    fn record(&mut self, bit: usize, result: Option<bool>) {
+        self.injected += 1;
+        match result {
+            None => self.rejected += 1,
+            Some(true) => {
+                self.unnoticed += 1;
+                self.accepted_bits.push(bit);
+            },
+            Some(false) => {
+                self.corrupted += 1;
+                self.accepted_bits.push(bit);
+            },
+        }
+    }
+}
+
+/// Flip every bit of a ciphertext in turn and report how decryption reacts.
+///
+/// `open` is called with the ciphertext after flipping a single bit of
+/// it, and should decrypt it the same way the unmodified ciphertext was
+/// decrypted, e.g. with [`decrypt_bytes`](fn.decrypt_bytes.html),
+/// [`aead::open`](aead/fn.open.html) or
+/// [`Ccm::decrypt()`](struct.Ccm.html#method.decrypt). `plaintext` is
+/// the expected result of decrypting the unmodified ciphertext.
+///
+/// Note that ECB, CBC, PCBC, CFB and CTR do not authenticate the
+/// ciphertext, so most faults are expected to show up as corrupted
+/// rather than rejected. With authenticated encryption, every fault
+/// should be rejected.
+///
+/// # Examples
+///
+/// ```
+/// use present::{decrypt_bytes, encrypt_bytes, flip_ciphertext_bits, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0x13; 10]);
+/// let (ciphertext, iv) = encrypt_bytes(b"fault", &key, &OpMode::CBC).unwrap().into_parts();
+/// let report = flip_ciphertext_bits(&ciphertext, b"fault", |faulty| decrypt_bytes(faulty, &key, &OpMode::CBC, iv));
+/// assert_eq!(report.injected, 64);
+/// assert!(report.all_detectable());
+/// ```
+pub fn flip_ciphertext_bits<F, E>(ciphertext: &[u8], plaintext: &[u8], open: F) -> FaultReport
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, E> {
+
+    flip_range(ciphertext, 0..ciphertext.len(), plaintext, open)
+}
+
+/// Flip every bit of an initialization vector in turn and report how
+/// decryption reacts.
+///
+/// This works like [`flip_ciphertext_bits`](fn.flip_ciphertext_bits.html),
+/// but `open` is called with the faulty IV, so it can decrypt the
+/// untouched ciphertext with it.
+///
+/// # Examples
+///
+/// ```
+/// use std::convert::TryInto;
+/// use present::{decrypt_bytes, encrypt_bytes, flip_init_vec_bits, Block, Key80Bit, OpMode};
+///
+/// let key = Key80Bit::new([0x13; 10]);
+/// let (ciphertext, iv) = encrypt_bytes(b"fault", &key, &OpMode::CBC).unwrap().into_parts();
+/// let report = flip_init_vec_bits(&iv.unwrap().to_bytes(), b"fault", |faulty| {
+///     decrypt_bytes(&ciphertext, &key, &OpMode::CBC, Some(Block::from_bytes(faulty.try_into().unwrap())))
+/// });
+/// assert_eq!(report.injected, 64);
+/// assert!(report.all_detectable());
+/// ```
+pub fn flip_init_vec_bits<F, E>(init_vec: &[u8], plaintext: &[u8], open: F) -> FaultReport
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, E> {
+
+    flip_range(init_vec, 0..init_vec.len(), plaintext, open)
+}
+
+/// Flip every bit of an authentication tag in turn and report how
+/// decryption reacts.
+///
+/// `sealed` is the whole output of the authenticated encryption and
+/// `tag` the range of bytes holding the tag, e.g. the last 8 bytes for
+/// [`aead::seal`](aead/fn.seal.html) or the first 8 bytes for
+/// [`Siv`](struct.Siv.html). `open` is called with the sealed message
+/// after flipping a single bit of the tag, and every fault should be
+/// rejected. Bit positions are counted from the start of `sealed`.
+///
+/// # Panics
+///
+/// Panics if `tag` is not within `sealed`.
+///
+/// # Examples
+///
+/// ```
+/// use present::{flip_tag_bits, Key128Bit};
+/// use present::aead;
+///
+/// let key = Key128Bit::new([0x42; 16]);
+/// let sealed = aead::seal(&key, b"nonce", b"header", b"payload");
+/// let tag = (sealed.len() - aead::TAG_LEN)..sealed.len();
+/// let report = flip_tag_bits(&sealed, tag, b"payload", |faulty| aead::open(&key, b"nonce", b"header", faulty));
+/// assert!(report.all_rejected());
+/// ```
+pub fn flip_tag_bits<F, E>(sealed: &[u8], tag: Range<usize>, plaintext: &[u8], open: F) -> FaultReport
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, E> {
+
+    if tag.start > tag.end || tag.end > sealed.len() {
+        panic!("Tag range {:?} is not within the {} bytes of the sealed message", tag, sealed.len());
+    }
+    flip_range(sealed, tag, plaintext, open)
+}
+
+/// Flips every bit of the given bytes of `input` in turn and passes the
+/// faulty input to `open`.
+fn flip_range<F, E>(input: &[u8], bytes: Range<usize>, plaintext: &[u8], mut open: F) -> FaultReport
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, E> {
+
+    let mut report = FaultReport::default();
+    let mut faulty = input.to_vec();
+
+    for bit in (bytes.start * 8)..(bytes.end * 8) {
+        let mask = 0x80u8 >> (bit % 8);
+        faulty[bit / 8] ^= mask;
+        let result = open(&faulty).ok().map(|decrypted| decrypted == plaintext);
+        report.record(bit, result);
+        faulty[bit / 8] ^= mask;
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_classification() {
+        let mut report = FaultReport::default();
+        report.record(0, None);
+        report.record(1, Some(false));
+        report.record(2, Some(true));
+        assert_eq!(report.injected, 3);
+        assert_eq!(report.rejected, 1);
+        assert_eq!(report.corrupted, 1);
+        assert_eq!(report.unnoticed, 1);
+        assert_eq!(report.accepted_bits, vec![1, 2]);
+        assert!(!report.all_rejected());
+        assert!(!report.all_detectable());
+    }
+
+    #[test]
+    fn test_only_the_tag_is_flipped() {
+        let sealed = [0u8; 6];
+        let mut seen = Vec::new();
+        let report = flip_tag_bits(&sealed, 4..6, &[], |faulty| {
+            seen.push(faulty.to_vec());
+            Err::<Vec<u8>, ()>(())
+        });
+        assert_eq!(report.injected, 16);
+        assert!(report.all_rejected());
+        assert_eq!(seen[0], [0, 0, 0, 0, 0x80, 0]);
+        assert_eq!(seen[15], [0, 0, 0, 0, 0, 0x01]);
+    }
+
+    #[cfg(feature = "insecure-modes")]
+    #[allow(deprecated)]
+    #[test]
+    fn test_ecb_ignores_init_vec_faults() {
+        use std::convert::TryInto;
+
+        use block::Block;
+        use keys::Key80Bit;
+        use modes::OpMode;
+        use {decrypt_bytes, encrypt_bytes};
+
+        let key = Key80Bit::new([0x77; 10]);
+        let (ciphertext, _) = encrypt_bytes(b"iv unused", &key, &OpMode::ECB).unwrap().into_parts();
+        let report = flip_init_vec_bits(&[0u8; 8], b"iv unused", |faulty| {
+            decrypt_bytes(&ciphertext, &key, &OpMode::ECB, Some(Block::from_bytes(faulty.try_into().unwrap())))
+        });
+        assert_eq!(report.injected, 64);
+        assert_eq!(report.unnoticed, 64);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use block::Block;
+use keys::Key;
+use modes::{self, OpMode};
+use stream::{decrypt_stream, encrypt_stream};
+
+/// Magic bytes at the start of every encrypted file.
+const MAGIC: [u8; 4] = *b"PRST";
+
+/// Version of the file format.
+const VERSION: u8 = 1;
+
+/// Encrypts a file.
+///
+/// The file is streamed, so files of any size can be encrypted with a
+/// fixed amount of memory. The encrypted file starts with a 14-byte
+/// header consisting of the magic bytes `PRST`, a format version byte,
+/// a byte identifying the mode of operation and the IV, which is
+/// generated randomly (and all zeros for ECB). The ciphertext follows,
+/// with the same padding as in
+/// [`encrypt_bytes()`](fn.encrypt_bytes.html). An existing file at
+/// `dst` is overwritten. Returns the size of the plaintext in bytes.
+///
+/// The file is only encrypted, not authenticated, so modifications
+/// can go unnoticed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use present::{decrypt_file, encrypt_file, Key128Bit, OpMode};
+/// let key = Key128Bit::new([0x42; 16]);
+/// encrypt_file(Path::new("notes.txt"), Path::new("notes.txt.enc"), &key, &OpMode::CBC).unwrap();
+/// decrypt_file(Path::new("notes.txt.enc"), Path::new("notes.txt"), &key, &OpMode::CBC).unwrap();
+/// ```
+pub fn encrypt_file<K: Key>(src: &Path, dst: &Path, key: &K, mode: &OpMode) -> io::Result<u64> {
+    let iv = match *mode {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        OpMode::ECB => Block::new(0),
+        OpMode::CBC | OpMode::PCBC | OpMode::CFB | OpMode::CTR => modes::random_iv().map_err(|e| io::Error::other(format!("IV generation failed: {:?}", e)))?,
+    };
+
+    let mut reader = BufReader::new(File::open(src)?);
+    write_output(dst, |writer| {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION, mode_id(mode)])?;
+        writer.write_all(&iv.to_bytes())?;
+        encrypt_stream(&mut reader, writer, key, mode, &iv)
+    })
+}
+
+/// Decrypts a file that was encrypted with
+/// [`encrypt_file()`](fn.encrypt_file.html).
+///
+/// The IV is taken from the header of the file. If decryption fails,
+/// `dst` is removed again, so no partial plaintext is left behind. An
+/// existing file at `dst` is overwritten. Returns the size of the
+/// plaintext in bytes.
+///
+/// # Errors
+///
+/// Returns an `io::Error` of kind `InvalidData` if the header is
+/// invalid, the file was encrypted with a different mode of operation
+/// or decryption fails, e.g. because of invalid padding.
+pub fn decrypt_file<K: Key>(src: &Path, dst: &Path, key: &K, mode: &OpMode) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(src)?);
+
+    let mut header = [0u8; 14];
+    reader.read_exact(&mut header).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => invalid_data("File is too short for the header"),
+        _ => e,
+    })?;
+    if header[..4] != MAGIC {
+        return Err(invalid_data("File is not encrypted with this crate"));
+    }
+    if header[4] != VERSION {
+        return Err(invalid_data("Unsupported file format version"));
+    }
+    if header[5] != mode_id(mode) {
+        return Err(invalid_data("File was encrypted with a different mode of operation"));
+    }
+
+    let mut iv_bytes = [0u8; 8];
+    iv_bytes.copy_from_slice(&header[6..]);
+    let iv = Block::from_bytes(&iv_bytes);
+
+    write_output(dst, |writer| decrypt_stream(&mut reader, writer, key, mode, &iv))
+}
+
+/// Creates the output file and runs `write` on it, removing the file
+/// again if writing fails.
+fn write_output<F>(dst: &Path, write: F) -> io::Result<u64>
+    where F: FnOnce(&mut BufWriter<File>) -> io::Result<u64> {
+
+    let mut writer = BufWriter::new(File::create(dst)?);
+    let result = write(&mut writer).and_then(|len| {
+        writer.flush()?;
+        Ok(len)
+    });
+
+    if result.is_err() {
+        drop(writer);
+        let _ = fs::remove_file(dst);
+    }
+    result
+}
+
+fn mode_id(mode: &OpMode) -> u8 {
+    match *mode {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        OpMode::ECB => 0,
+        OpMode::CBC => 1,
+        OpMode::PCBC => 2,
+        OpMode::CFB => 3,
+        OpMode::CTR => 4,
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modes::all_modes;
+    use std::env;
+    use std::process;
+    use keys::Key80Bit;
+
+    #[test]
+    fn test_file_roundtrip() {
+        let dir = env::temp_dir();
+        let src = dir.join(format!("present-file-{}", process::id()));
+        let enc = dir.join(format!("present-file-{}.enc", process::id()));
+        let dec = dir.join(format!("present-file-{}.dec", process::id()));
+        let key = Key80Bit::new([0x42; 10]);
+        let plaintext: Vec<u8> = (0..10000u32).map(|i| i as u8).collect();
+        fs::write(&src, &plaintext).unwrap();
+
+        for mode in all_modes().iter() {
+            assert_eq!(encrypt_file(&src, &enc, &key, mode).unwrap(), 10000);
+            let encrypted = fs::read(&enc).unwrap();
+            assert_eq!(&encrypted[..4], b"PRST");
+
+            let mut iv = [0u8; 8];
+            iv.copy_from_slice(&encrypted[6..14]);
+            assert_eq!(&encrypted[14..], &::try_encrypt_bytes_with_iv(&plaintext, &key, mode, &Block::from_bytes(&iv)).unwrap()[..]);
+
+            assert_eq!(decrypt_file(&enc, &dec, &key, mode).unwrap(), 10000);
+            assert_eq!(fs::read(&dec).unwrap(), plaintext);
+        }
+
+        // A mismatching mode or a corrupted file is rejected
+        encrypt_file(&src, &enc, &key, &OpMode::CBC).unwrap();
+        let err = decrypt_file(&enc, &dec, &key, &OpMode::PCBC).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut encrypted = fs::read(&enc).unwrap();
+        encrypted.pop();
+        fs::write(&enc, &encrypted).unwrap();
+        let err = decrypt_file(&enc, &dec, &key, &OpMode::CBC).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!dec.exists());
+
+        for path in [src, enc].iter() {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
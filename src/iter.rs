@@ -0,0 +1,189 @@
+#[cfg(feature = "insecure-modes")]
+use context::Expanded;
+#[cfg(feature = "insecure-modes")]
+use keys::Key;
+#[cfg(feature = "insecure-modes")]
+use policy::{self, SecurityEvent};
+use add_padding;
+
+/// Iterator adapter that encrypts 8-byte blocks lazily.
+///
+/// Each block is encrypted on its own (i.e. ECB without padding) when
+/// it is requested, so data can be piped through without collecting it
+/// into a single buffer first. The key is expanded once on
+/// construction. To encrypt a stream of bytes with padding, use
+/// [`EncryptBlocks::from_bytes()`](#method.from_bytes), which yields
+/// the same ciphertext as [`encrypt_bytes()`](fn.encrypt_bytes.html)
+/// with ECB.
+///
+/// Since this is ECB, it is only available with the `insecure-modes`
+/// feature and deprecated like [`OpMode::ECB`](enum.OpMode.html).
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "insecure-modes")]
+/// # #[allow(deprecated)]
+/// # fn main() {
+/// use present::{EncryptBlocks, Key80Bit};
+/// let key = Key80Bit::new([0u8; 10]);
+/// let mut blocks = EncryptBlocks::new(vec![[0u8; 8]; 3], &key);
+/// assert_eq!(blocks.next(), Some([0x55, 0x79, 0xC1, 0x38, 0x7B, 0x22, 0x84, 0x45]));
+/// assert_eq!(blocks.count(), 2);
+/// # }
+/// # #[cfg(not(feature = "insecure-modes"))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "insecure-modes")]
+#[deprecated(note = "ECB leaks patterns in the plaintext, use `EncryptWriter` or `Ctr`")]
+pub struct EncryptBlocks<I> {
+    blocks: I,
+    cipher: Expanded,
+}
+
+#[cfg(feature = "insecure-modes")]
+#[allow(deprecated)]
+impl<I: Iterator<Item = [u8; 8]>> EncryptBlocks<I> {
+    /// Constructs a new adapter encrypting the given blocks.
+    pub fn new<T, K>(blocks: T, key: &K) -> Self
+        where T: IntoIterator<IntoIter = I, Item = [u8; 8]>, K: Key {
+
+        policy::notify(SecurityEvent::EcbSelected);
+        EncryptBlocks { blocks: blocks.into_iter(), cipher: Expanded::new(key) }
+    }
+}
+
+#[cfg(feature = "insecure-modes")]
+#[allow(deprecated)]
+impl<B: Iterator<Item = u8>> EncryptBlocks<PaddedBlocks<B>> {
+    /// Constructs a new adapter encrypting the given bytes, with
+    /// padding in the final block.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{encrypt_bytes, EncryptBlocks, Key80Bit, OpMode};
+    /// # #[cfg(feature = "insecure-modes")]
+    /// # #[allow(deprecated)]
+    /// # fn main() {
+    /// let key = Key80Bit::new([0x42; 10]);
+    /// let lazy: Vec<u8> = EncryptBlocks::from_bytes(b"piped through".iter().cloned(), &key)
+    ///     .flat_map(|block| block.to_vec())
+    ///     .collect();
+    /// assert_eq!(lazy, encrypt_bytes(b"piped through", &key, &OpMode::ECB).unwrap().into_bytes());
+    /// # }
+    /// # #[cfg(not(feature = "insecure-modes"))]
+    /// # fn main() {}
+    /// ```
+    pub fn from_bytes<T, K>(bytes: T, key: &K) -> Self
+        where T: IntoIterator<IntoIter = B, Item = u8>, K: Key {
+
+        EncryptBlocks::new(PaddedBlocks::new(bytes), key)
+    }
+}
+
+#[cfg(feature = "insecure-modes")]
+#[allow(deprecated)]
+impl<I: Iterator<Item = [u8; 8]>> Iterator for EncryptBlocks<I> {
+    type Item = [u8; 8];
+
+    fn next(&mut self) -> Option<[u8; 8]> {
+        let mut bytes = self.blocks.next()?;
+        self.cipher.encrypt_blocks(&mut bytes);
+        Some(bytes)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.blocks.size_hint()
+    }
+}
+
+/// Iterator adapter that groups bytes into padded 8-byte blocks.
+///
+/// The final block is padded like in
+/// [`encrypt_bytes()`](fn.encrypt_bytes.html), so a full block of
+/// padding follows input whose length is a multiple of 8 bytes.
+pub struct PaddedBlocks<B> {
+    bytes: B,
+    done: bool,
+}
+
+impl<B: Iterator<Item = u8>> PaddedBlocks<B> {
+    /// Constructs a new adapter grouping the given bytes.
+    pub fn new<T: IntoIterator<IntoIter = B, Item = u8>>(bytes: T) -> Self {
+        PaddedBlocks { bytes: bytes.into_iter(), done: false }
+    }
+}
+
+impl<B: Iterator<Item = u8>> Iterator for PaddedBlocks<B> {
+    type Item = [u8; 8];
+
+    fn next(&mut self) -> Option<[u8; 8]> {
+        if self.done {
+            return None;
+        }
+
+        let mut current_bytes = [0u8; 8];
+        for i in 0..8 {
+            match self.bytes.next() {
+                Some(byte) => current_bytes[i] = byte,
+                None => {
+                    // This is the final block
+                    add_padding(&mut current_bytes, 8 - i);
+                    self.done = true;
+                    break;
+                },
+            }
+        }
+        Some(current_bytes)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            return (0, Some(0));
+        }
+        let (lower, upper) = self.bytes.size_hint();
+        (lower / 8 + 1, upper.and_then(|upper| upper.checked_add(8)).map(|upper| upper / 8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "insecure-modes")]
+    use block::encrypt_block8;
+    #[cfg(feature = "insecure-modes")]
+    use keys::Key80Bit;
+    #[cfg(feature = "insecure-modes")]
+    use testing::blocks;
+
+    #[cfg(feature = "insecure-modes")]
+    #[allow(deprecated)]
+    #[test]
+    fn test_matches_block_encryption() {
+        let key = Key80Bit::new([0xFF; 10]);
+        let round_keys = key.generate_round_keys();
+        let original = blocks(5);
+
+        let encrypted: Vec<[u8; 8]> = EncryptBlocks::new(original.iter().cloned(), &key).collect();
+        assert_eq!(encrypted.len(), original.len());
+        for (block, plain) in encrypted.iter().zip(original.iter()) {
+            let mut expected = *plain;
+            encrypt_block8(&mut expected, &round_keys);
+            assert_eq!(*block, expected);
+        }
+    }
+
+    #[test]
+    fn test_padded_blocks() {
+        for len in 0..20usize {
+            let bytes: Vec<u8> = (0..len as u8).collect();
+            let blocks = PaddedBlocks::new(bytes.iter().cloned());
+            assert_eq!(blocks.size_hint(), (len / 8 + 1, Some(len / 8 + 1)));
+
+            let blocks: Vec<[u8; 8]> = blocks.collect();
+            assert_eq!(blocks.len(), len / 8 + 1);
+            assert_eq!(blocks.concat(), ::padded(&bytes, &::padding::Pkcs7));
+        }
+    }
+}
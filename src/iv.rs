@@ -0,0 +1,137 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use block::Block;
+use errors::{ParseError, RngError};
+
+/// An initialization vector for the modes of operation that need one.
+///
+/// An IV holds the same 64 bits as a [`Block`](struct.Block.html), but
+/// is a type of its own, so it cannot be mixed up with a data block in
+/// user code. Unlike keys, IVs are not secret and are sent along with
+/// the ciphertext, so they can be copied and compared freely.
+///
+/// # Examples
+///
+/// ```
+/// use present::{decrypt_bytes_with_mode, encrypt_bytes_with_mode, Iv, Key80Bit, Mode};
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let iv: Iv = "0123456789abcdef".parse().unwrap();
+/// let ciphertext = encrypt_bytes_with_mode(b"Hello, world!", &key, &Mode::CBC { iv }).unwrap();
+///
+/// let plaintext = decrypt_bytes_with_mode(ciphertext.as_bytes(), &key, &Mode::CBC { iv });
+/// assert_eq!(plaintext.unwrap(), b"Hello, world!");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Iv([u8; 8]);
+
+impl Iv {
+    /// Generates a random IV using a random number generator provided
+    /// by the operating system.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RngError::Unavailable` if the operating system fails to
+    /// provide randomness.
+    pub fn random() -> Result<Self, RngError> {
+        Iv::random_with(&mut OsRng)
+    }
+
+    /// Generates a random IV using the given random number generator,
+    /// see [`random_iv_with`](fn.random_iv_with.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns `RngError::Unavailable` if the RNG fails to provide
+    /// randomness.
+    pub fn random_with<R: RngCore + ?Sized>(rng: &mut R) -> Result<Self, RngError> {
+        let mut bytes = [0u8; 8];
+        rng.try_fill_bytes(&mut bytes)?;
+        Ok(Iv(bytes))
+    }
+
+    /// Constructs an IV from the given bytes, e.g. after receiving it
+    /// along with a ciphertext.
+    pub fn from_bytes(bytes: &[u8; 8]) -> Self {
+        Iv(*bytes)
+    }
+
+    /// Returns the bytes of the IV.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        self.0
+    }
+
+    /// Converts the IV into a block, for the functions that take the IV
+    /// as a `Block`.
+    pub fn to_block(&self) -> Block {
+        Block::from_bytes(&self.0)
+    }
+}
+
+impl From<Block> for Iv {
+    fn from(block: Block) -> Self {
+        Iv(block.to_bytes())
+    }
+}
+
+impl From<Iv> for Block {
+    fn from(iv: Iv) -> Self {
+        iv.to_block()
+    }
+}
+
+impl fmt::Display for Iv {
+    /// Formats the IV as 16 lowercase hexadecimal digits.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_block())
+    }
+}
+
+impl fmt::Debug for Iv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Iv({})", self)
+    }
+}
+
+impl FromStr for Iv {
+    type Err = ParseError;
+
+    /// Parses an IV from 16 hexadecimal digits.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::InvalidLength` if the string does not have
+    /// 16 characters and `ParseError::InvalidHex` if any of them is not
+    /// a hexadecimal digit.
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        hex.parse::<Block>().map(Iv::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    #[test]
+    fn test_iv_conversions() {
+        let iv = Iv::from_bytes(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF]);
+        assert_eq!(iv.to_block().get_state(), 0x0123456789ABCDEF);
+        assert_eq!(Iv::from(Block::new(0x0123456789ABCDEF)), iv);
+        assert_eq!(iv.to_string(), "0123456789abcdef");
+        assert_eq!(format!("{:?}", iv), "Iv(0123456789abcdef)");
+        assert_eq!("0123456789ABCDEF".parse::<Iv>(), Ok(iv));
+        assert_eq!("0123".parse::<Iv>(), Err(ParseError::InvalidLength(4)));
+        assert_eq!("0123456789abcdeg".parse::<Iv>(), Err(ParseError::InvalidHex));
+    }
+
+    #[test]
+    fn test_random_iv() {
+        let mut rng = StepRng::new(7, 3);
+        assert_eq!(Iv::random_with(&mut rng).unwrap().to_bytes(), 7u64.to_le_bytes());
+        assert!(Iv::random().unwrap() != Iv::random().unwrap());
+    }
+}
@@ -0,0 +1,298 @@
+use std::mem;
+
+use diversify::diversify_key128;
+use keys::{wipe, Key, Key80Bit, Key128Bit};
+use mac::Cmac;
+
+/// A pseudorandom function keyed with a passphrase, for use with
+/// [`pbkdf2`](fn.pbkdf2.html).
+///
+/// Implement this to plug in a different PRF, e.g. HMAC with a hash
+/// function from another crate.
+pub trait Prf {
+    /// Returns the length of the output in bytes.
+    fn output_len(&self) -> usize;
+
+    /// Computes the PRF of the concatenation of `input` and writes
+    /// `output_len()` bytes to `output`.
+    fn compute(&self, input: &[&[u8]], output: &mut [u8]);
+}
+
+/// The default PRF: CMAC with PRESENT, keyed with the passphrase.
+///
+/// Passphrases have arbitrary length, so they are first compressed into
+/// a 128-bit key with CMAC under the all-zero key, similar to
+/// AES-CMAC-PRF-128 from RFC 4615. The output is 8 bytes long.
+#[derive(Clone)]
+pub struct CmacPrf {
+    mac: Cmac,
+}
+
+impl CmacPrf {
+    /// Constructs the PRF for the given passphrase.
+    pub fn new(passphrase: &[u8]) -> Self {
+        let key = diversify_key128(&Key128Bit::new([0u8; 16]), passphrase);
+        CmacPrf::from_key(&key)
+    }
+
+    /// Constructs the PRF for a key that is already uniformly random,
+    /// without compressing it first.
+    pub fn from_key<K: Key>(key: &K) -> Self {
+        CmacPrf { mac: Cmac::new(key) }
+    }
+}
+
+impl Prf for CmacPrf {
+    fn output_len(&self) -> usize {
+        8
+    }
+
+    fn compute(&self, input: &[&[u8]], output: &mut [u8]) {
+        let mut mac = self.mac.clone();
+        for part in input.iter() {
+            mac.update(part);
+        }
+        output.copy_from_slice(&mac.finalize());
+    }
+}
+
+/// Derivation of independent subkeys from a master key, following
+/// HKDF (RFC 5869) with CMAC in place of HMAC.
+///
+/// The extract step condenses input key material that is not uniformly
+/// random, e.g. a Diffie-Hellman shared secret, into a pseudorandom key.
+/// The expand step derives any number of subkeys from it, one for every
+/// `info` label, so a key used for encryption is never also used for a
+/// MAC. Keys of this crate's key types are already uniformly random and
+/// can skip extraction with [`from_prk()`](#method.from_prk).
+///
+/// The 8-byte output blocks of CMAC limit a single expansion to
+/// 2040 bytes.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Hkdf, Key128Bit};
+/// let hkdf = Hkdf::from_prk(&Key128Bit::new([0x42; 16]));
+/// let enc_key = hkdf.expand_key128(b"my app encryption");
+/// let mac_key = hkdf.expand_key128(b"my app mac");
+/// assert!(enc_key != mac_key);
+/// ```
+#[derive(Clone)]
+pub struct Hkdf {
+    prf: CmacPrf,
+}
+
+impl Hkdf {
+    /// Extracts a pseudorandom key from the input key material and the
+    /// salt. The salt may be empty.
+    pub fn extract(salt: &[u8], ikm: &[u8]) -> Self {
+        let prf = CmacPrf::new(salt);
+        let mut prk = [0u8; 16];
+        for (half, counter) in prk.chunks_mut(8).zip([1u8, 2u8].iter()) {
+            prf.compute(&[&[*counter], ikm], half);
+        }
+
+        let hkdf = Hkdf::from_prk(&Key128Bit::new(prk));
+        wipe(&mut prk);
+        hkdf
+    }
+
+    /// Uses a uniformly random key as the pseudorandom key directly.
+    pub fn from_prk<K: Key>(prk: &K) -> Self {
+        Hkdf { prf: CmacPrf::from_key(prk) }
+    }
+
+    /// Fills `output` with key material for the given label.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `output` is longer than 2040 bytes.
+    pub fn expand(&self, info: &[u8], output: &mut [u8]) {
+        assert!(output.len() <= 255 * 8, "HKDF output is limited to 2040 bytes");
+
+        // T(i) = PRF(T(i - 1) || info || i), with T(0) empty
+        let mut previous = [0u8; 8];
+        for (index, chunk) in output.chunks_mut(8).enumerate() {
+            let feedback: &[u8] = if index == 0 { &[] } else { &previous };
+            let mut block = [0u8; 8];
+            self.prf.compute(&[feedback, info, &[index as u8 + 1]], &mut block);
+            chunk.copy_from_slice(&block[..chunk.len()]);
+            previous = block;
+        }
+        wipe(&mut previous);
+    }
+
+    /// Derives an 80-bit subkey for the given label.
+    pub fn expand_key80(&self, info: &[u8]) -> Key80Bit {
+        let mut value = [0u8; 10];
+        self.expand(info, &mut value);
+        Key80Bit::new(value)
+    }
+
+    /// Derives a 128-bit subkey for the given label.
+    pub fn expand_key128(&self, info: &[u8]) -> Key128Bit {
+        let mut value = [0u8; 16];
+        self.expand(info, &mut value);
+        Key128Bit::new(value)
+    }
+}
+
+/// Derives key material from a passphrase with PBKDF2 (RFC 8018).
+///
+/// Fills `output` with key material derived from the passphrase the
+/// PRF is keyed with, the salt and the iteration count. The salt should
+/// be random and stored along with the ciphertext, and the iteration
+/// count should be as high as the application can tolerate, to slow
+/// down guessing of the passphrase.
+///
+/// # Panics
+///
+/// Panics if `iterations` is zero.
+pub fn pbkdf2<P: Prf>(prf: &P, salt: &[u8], iterations: u32, output: &mut [u8]) {
+    assert!(iterations > 0, "PBKDF2 needs at least one iteration");
+
+    let len = prf.output_len();
+    let mut block = vec![0u8; len];
+    let mut next = vec![0u8; len];
+    let mut sum = vec![0u8; len];
+
+    for (index, chunk) in output.chunks_mut(len).enumerate() {
+        let counter = (index as u32 + 1).to_be_bytes();
+        prf.compute(&[salt, &counter], &mut block);
+        sum.copy_from_slice(&block);
+
+        for _ in 1..iterations {
+            prf.compute(&[&block], &mut next);
+            mem::swap(&mut block, &mut next);
+            for (s, b) in sum.iter_mut().zip(block.iter()) {
+                *s ^= b;
+            }
+        }
+        chunk.copy_from_slice(&sum[..chunk.len()]);
+    }
+
+    wipe(&mut block[..]);
+    wipe(&mut next[..]);
+    wipe(&mut sum[..]);
+}
+
+/// Derives an 80-bit key from a passphrase with PBKDF2 and
+/// [`CmacPrf`](struct.CmacPrf.html).
+///
+/// See [`pbkdf2`](fn.pbkdf2.html) for details.
+///
+/// # Panics
+///
+/// Panics if `iterations` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use present::{pbkdf2_key80, encrypt_str, decrypt_str, OpMode};
+/// let key = pbkdf2_key80(b"correct horse battery staple", b"random salt", 10000);
+///
+/// let (ciphertext, iv) = encrypt_str("Hello, world!", &key, &OpMode::CBC).unwrap().into_parts();
+/// assert_eq!(decrypt_str(&ciphertext, &key, &OpMode::CBC, iv).unwrap(), "Hello, world!");
+/// ```
+pub fn pbkdf2_key80(passphrase: &[u8], salt: &[u8], iterations: u32) -> Key80Bit {
+    let mut value = [0u8; 10];
+    pbkdf2(&CmacPrf::new(passphrase), salt, iterations, &mut value);
+    Key80Bit::new(value)
+}
+
+/// Derives a 128-bit key from a passphrase with PBKDF2 and
+/// [`CmacPrf`](struct.CmacPrf.html).
+///
+/// See [`pbkdf2`](fn.pbkdf2.html) for details.
+///
+/// # Panics
+///
+/// Panics if `iterations` is zero.
+pub fn pbkdf2_key128(passphrase: &[u8], salt: &[u8], iterations: u32) -> Key128Bit {
+    let mut value = [0u8; 16];
+    pbkdf2(&CmacPrf::new(passphrase), salt, iterations, &mut value);
+    Key128Bit::new(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A weak PRF that is easy to compute by hand.
+    struct SumPrf;
+
+    impl Prf for SumPrf {
+        fn output_len(&self) -> usize {
+            3
+        }
+
+        fn compute(&self, input: &[&[u8]], output: &mut [u8]) {
+            let sum = input.iter().flat_map(|part| part.iter()).fold(0u8, |acc, b| acc.wrapping_add(*b));
+            output.copy_from_slice(&[sum, sum.wrapping_mul(3), 0xFF]);
+        }
+    }
+
+    #[test]
+    fn test_pbkdf2_structure() {
+        let mut output = [0u8; 5];
+        pbkdf2(&SumPrf, &[0x10], 2, &mut output);
+
+        // Block 1: U1 = PRF(salt || 1), U2 = PRF(U1)
+        let u1 = [0x11, 0x33, 0xFF];
+        let u2 = [0x43, 0xC9, 0xFF];
+        // Block 2: U1 = PRF(salt || 2), U2 = PRF(U1)
+        let v1 = [0x12, 0x36, 0xFF];
+        let v2 = [0x47, 0xD5, 0xFF];
+        assert_eq!(output, [u1[0] ^ u2[0], u1[1] ^ u2[1], u1[2] ^ u2[2], v1[0] ^ v2[0], v1[1] ^ v2[1]]);
+    }
+
+    #[test]
+    fn test_single_iteration_is_prf() {
+        let prf = CmacPrf::new(b"passphrase");
+        let mut expected = [0u8; 8];
+        prf.compute(&[b"salt", &[0, 0, 0, 1]], &mut expected);
+
+        let mut output = [0u8; 8];
+        pbkdf2(&prf, b"salt", 1, &mut output);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_derived_keys_depend_on_all_inputs() {
+        let key = pbkdf2_key128(b"passphrase", b"salt", 100);
+        assert!(key == pbkdf2_key128(b"passphrase", b"salt", 100));
+        assert!(key != pbkdf2_key128(b"passphrase", b"salt", 101));
+        assert!(key != pbkdf2_key128(b"passphrase", b"pepper", 100));
+        assert!(key != pbkdf2_key128(b"passphrasf", b"salt", 100));
+        assert_eq!(pbkdf2_key80(b"passphrase", b"salt", 100).value, key.value[..10]);
+    }
+
+    #[test]
+    fn test_expand_chains_blocks() {
+        let master = Key128Bit::new([0x0F; 16]);
+        let prf = CmacPrf::from_key(&master);
+        let mut t1 = [0u8; 8];
+        prf.compute(&[b"label", &[1]], &mut t1);
+        let mut t2 = [0u8; 8];
+        prf.compute(&[&t1, b"label", &[2]], &mut t2);
+
+        let key = Hkdf::from_prk(&master).expand_key80(b"label");
+        assert_eq!(key.value[..8], t1);
+        assert_eq!(key.value[8..], t2[..2]);
+    }
+
+    #[test]
+    fn test_subkeys_are_independent() {
+        let hkdf = Hkdf::extract(b"salt", b"shared secret");
+        assert!(hkdf.expand_key128(b"enc") == Hkdf::extract(b"salt", b"shared secret").expand_key128(b"enc"));
+        assert!(hkdf.expand_key128(b"enc") != hkdf.expand_key128(b"mac"));
+        assert!(hkdf.expand_key128(b"enc") != Hkdf::extract(b"", b"shared secret").expand_key128(b"enc"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_expand_limit() {
+        Hkdf::extract(b"", b"").expand(b"", &mut [0u8; 2041]);
+    }
+}
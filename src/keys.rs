@@ -1,4 +1,24 @@
-use sbox::S_BOX;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::slice;
+use std::sync::Arc;
+
+use subtle::{Choice, ConstantTimeEq};
+#[cfg(feature = "base64")]
+use base64ct::{Base64, Encoding};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+use block::encrypt_block8;
+use context::{Expanded, Sealed};
+use errors::KeyParseError;
+use mac::Cmac;
+use sbox;
+
+/// Label that separates fingerprints from all other uses of a key.
+const FINGERPRINT_LABEL: &[u8] = b"present key fingerprint";
 
 /// The `Key` trait.
 ///
@@ -8,7 +28,137 @@ pub trait Key {
     // PRESENT consists of 31 rounds plus a special 32nd round
     /// Generate 32 round keys that will be used for the 32 rounds
     /// of the PRESENT algorithm.
-    fn generate_round_keys(&self) -> [RoundKey; 32];
+    fn generate_round_keys(&self) -> RoundKeys;
+
+    /// Expands the key for the block cipher operations of this crate.
+    ///
+    /// Types with a protected key schedule of their own override this,
+    /// so the schedule is kept instead of being rebuilt from the plain
+    /// round keys. The `Sealed` argument keeps other crates from
+    /// calling or overriding it.
+    #[doc(hidden)]
+    fn expand(&self, _: Sealed) -> Expanded {
+        Expanded::from_round_keys(self.generate_round_keys())
+    }
+
+    /// Computes the key check value (KCV) of this key.
+    ///
+    /// Following the convention of the payment industry, this is the
+    /// first 3 bytes of the encryption of an all-zero block. Operators
+    /// can compare it with the KCV that came with the key to make sure
+    /// the right key was loaded, without revealing the key itself. It is
+    /// still a known plaintext and ciphertext pair, so anyone who sees
+    /// it can check guessed keys against it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{Key, Key80Bit};
+    /// let key = Key80Bit::new([0u8; 10]);
+    /// assert_eq!(key.check_value(), [0x55, 0x79, 0xC1]);
+    /// ```
+    fn check_value(&self) -> [u8; 3] {
+        let mut round_keys = self.generate_round_keys();
+        let mut block = [0u8; 8];
+        encrypt_block8(&mut block, &round_keys);
+        wipe(&mut round_keys);
+        [block[0], block[1], block[2]]
+    }
+
+    /// Computes a short, stable identifier for this key.
+    ///
+    /// The fingerprint is the CMAC of a fixed label under the key, so
+    /// it is the same every time. Log it to record which key was used
+    /// without logging the key itself. Like the
+    /// [`check_value()`](#method.check_value), it is computed from public
+    /// input, so anyone who sees it can check guessed keys against it.
+    /// Only use it for keys that cannot be guessed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{Key, Key128Bit};
+    /// let key = Key128Bit::new([0x42; 16]);
+    /// println!("Encrypting with key {}", key.fingerprint());
+    /// assert_eq!(key.fingerprint(), Key128Bit::new([0x42; 16]).fingerprint());
+    /// ```
+    fn fingerprint(&self) -> KeyFingerprint {
+        let mut round_keys = self.generate_round_keys();
+        let fingerprint = fingerprint_of(Expanded::from_round_keys(round_keys));
+        wipe(&mut round_keys);
+        fingerprint
+    }
+
+    /// Returns the length of the key in bytes, or zero for keys that
+    /// do not hold their raw bytes.
+    fn key_size(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    /// Returns the raw bytes of the key, so generic code can serialize
+    /// keys or pass them across an FFI boundary.
+    ///
+    /// Key schedules, like [`RoundKeys`](struct.RoundKeys.html) and
+    /// [`SharedKey`](struct.SharedKey.html), return the 256 bytes of
+    /// [`RoundKeys::to_bytes`](struct.RoundKeys.html#method.to_bytes).
+    /// Types that never expose their key, like the keys held in
+    /// protected memory, return an empty slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{Key, Key80Bit, Key128Bit};
+    ///
+    /// fn roundtrip<K: Key>(key: &K) -> K {
+    ///     K::from_bytes(&key.as_bytes()).unwrap()
+    /// }
+    ///
+    /// assert_eq!(roundtrip(&Key80Bit::new([0x42; 10])).value, [0x42; 10]);
+    /// assert_eq!(Key128Bit::new([0x42; 16]).key_size(), 16);
+    /// ```
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&[])
+    }
+
+    /// Constructs a key from its raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyParseError::InvalidLength` if the length of `bytes`
+    /// is not supported by the key type. Types that cannot be
+    /// constructed from raw bytes always return it.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KeyParseError> where Self: Sized {
+        Err(KeyParseError::InvalidLength(bytes.len()))
+    }
+}
+
+/// Computes the fingerprint of the key that was expanded into `cipher`.
+pub fn fingerprint_of(cipher: Expanded) -> KeyFingerprint {
+    let mut mac = Cmac::with_cipher(cipher);
+    mac.update(FINGERPRINT_LABEL);
+    KeyFingerprint(mac.finalize())
+}
+
+/// The fingerprint of a key, see [`Key::fingerprint()`](trait.Key.html#method.fingerprint).
+///
+/// It is displayed as 16 lowercase hexadecimal digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyFingerprint([u8; 8]);
+
+impl KeyFingerprint {
+    /// Returns the fingerprint as bytes.
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for KeyFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
 }
 
 /// An 80-bit key.
@@ -16,6 +166,9 @@ pub trait Key {
 /// The [paper](https://link.springer.com/chapter/10.1007%2F978-3-540-74735-2_31)
 /// introduces two key lengths: 80-bit and 128-bit. This struct represents
 /// an 80-bit key and implements the appropriate key schedule.
+///
+/// With the `zeroize` feature, the key is overwritten when it is
+/// dropped.
 pub struct Key80Bit {
     /// The value of the key as a byte array.
     pub value: [u8; 10],
@@ -24,7 +177,62 @@ pub struct Key80Bit {
 impl Key80Bit {
     /// Constructs a new 80-bit key from the given bytes.
     pub fn new(value: [u8; 10]) -> Self {
-        Key80Bit { value: value }
+        Key80Bit { value }
+    }
+
+    /// Parses an 80-bit key from a string of 20 hexadecimal digits.
+    ///
+    /// Parsing runs in constant time with respect to the key value, so
+    /// it does not leak the key through timing side channels.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `KeyParseError` if the string does not
+    /// consist of exactly 20 hexadecimal digits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::Key80Bit;
+    /// let key = Key80Bit::from_hex("0123456789abcdefABCD").unwrap();
+    /// assert_eq!(key.value, [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0xAB, 0xCD]);
+    /// ```
+    pub fn from_hex(hex: &str) -> Result<Self, KeyParseError> {
+        let mut value = [0u8; 10];
+        decode_hex(hex, &mut value)?;
+        Ok(Key80Bit { value })
+    }
+
+    /// Parses an 80-bit key from padded Base64 in the standard
+    /// alphabet, i.e. 16 characters ending in `==`.
+    ///
+    /// Like [`from_hex()`](#method.from_hex), decoding runs in constant
+    /// time with respect to the key value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `KeyParseError` if the string has the wrong
+    /// length or is not valid Base64.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::Key80Bit;
+    /// let key = Key80Bit::from_base64("QkJCQkJCQkJCQg==").unwrap();
+    /// assert_eq!(key.value, [0x42; 10]);
+    /// assert_eq!(key.to_base64(), "QkJCQkJCQkJCQg==");
+    /// ```
+    #[cfg(feature = "base64")]
+    pub fn from_base64(encoded: &str) -> Result<Self, KeyParseError> {
+        let mut value = [0u8; 10];
+        decode_base64(encoded, &mut value)?;
+        Ok(Key80Bit { value })
+    }
+
+    /// Formats the key as padded Base64 in the standard alphabet.
+    #[cfg(feature = "base64")]
+    pub fn to_base64(&self) -> String {
+        Base64::encode_string(&self.value)
     }
 }
 
@@ -33,48 +241,68 @@ impl Key for Key80Bit {
     ///
     /// This function generates 32 round keys that are derived
     /// from the value of this key.
-    fn generate_round_keys(&self) -> [RoundKey; 32] {
-        // The round keys are generated as follows:
-        // 1. Take the 64 leftmost bits of the key register
-        // 2. Mutate the key register (cyclic bitshift, S-Box (partial),
-        //    XOR with round counter (partial))
-        // 3. Repeat until 32 round keys are extracted
-        let mut round_keys = [RoundKey { value: 0u64 }; 32];
-        let mut key_register = self.value;
-
-        for round in 1u8..32u8 { // round counter starts at 1!
-            // Get round key
-            let mut key_val = 0u64;
-            for byte in 0..8 {
-                key_val += (key_register[byte] as u64) << ((7 - byte) * 8);
-            }
-            round_keys[(round - 1) as usize].value = key_val;
+    fn generate_round_keys(&self) -> RoundKeys {
+        let mut bytes = schedule_80bit(&self.value);
+        let round_keys = to_round_keys(&bytes);
+        wipe(&mut bytes);
+        round_keys
+    }
 
-            // Cyclic bitshift (rotate by 61 bits to the left)
-            let tmp_register = key_register;
-            for byte in 0..10 {
-                key_register[byte] = (tmp_register[(byte + 7) % 10] << 5) +
-                                     (tmp_register[(byte + 8) % 10] >> 3);
-            }
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KeyParseError> {
+        Key80Bit::try_from(bytes)
+    }
 
-            // Apply S-Box to leftmost 4 bits
-            let sbox_result = S_BOX.apply_enc(key_register[0] >> 4);
-            key_register[0] = key_register[0] % 16;
-            key_register[0] += sbox_result << 4;
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.value)
+    }
+}
 
-            // XOR bits 19, ..., 15 with the round counter
-            key_register[7] ^= round >> 1;
-            key_register[8] ^= round << 7;
-        }
+impl<'a> TryFrom<&'a [u8]> for Key80Bit {
+    type Error = KeyParseError;
 
-        // Get final round key
-        let mut final_key = 0u64;
-        for byte in 0..8 {
-            final_key += (key_register[byte] as u64) << ((7 - byte) * 8);
+    /// Copies the key out of a byte slice, e.g. one read from a file
+    /// or returned by a key management service.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyParseError::InvalidLength` if the slice is not 10
+    /// bytes long.
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let mut value = [0u8; 10];
+        if bytes.len() != value.len() {
+            return Err(KeyParseError::InvalidLength(bytes.len()));
         }
-        round_keys[31].value = final_key;
+        value.copy_from_slice(bytes);
+        Ok(Key80Bit { value })
+    }
+}
 
-        round_keys
+impl ConstantTimeEq for Key80Bit {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.ct_eq(&other.value)
+    }
+}
+
+impl PartialEq for Key80Bit {
+    /// Compares the keys in constant time.
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for Key80Bit {}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for Key80Bit {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Key80Bit {
+    fn drop(&mut self) {
+        self.zeroize();
     }
 }
 
@@ -83,6 +311,9 @@ impl Key for Key80Bit {
 /// The [paper](https://link.springer.com/chapter/10.1007%2F978-3-540-74735-2_31)
 /// introduces two key lengths: 80-bit and 128-bit. This struct represents
 /// a 128-bit key and implements the appropriate key schedule.
+///
+/// With the `zeroize` feature, the key is overwritten when it is
+/// dropped.
 pub struct Key128Bit {
     /// The value of the key as a byte array.
     pub value: [u8; 16],
@@ -91,7 +322,43 @@ pub struct Key128Bit {
 impl Key128Bit {
     /// Constructs a new 128-bit key from the given bytes.
     pub fn new(value: [u8; 16]) -> Self {
-        Key128Bit { value: value}
+        Key128Bit { value }
+    }
+
+    /// Parses a 128-bit key from a string of 32 hexadecimal digits.
+    ///
+    /// Parsing runs in constant time with respect to the key value, like
+    /// [`Key80Bit::from_hex()`](struct.Key80Bit.html#method.from_hex).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `KeyParseError` if the string does not
+    /// consist of exactly 32 hexadecimal digits.
+    pub fn from_hex(hex: &str) -> Result<Self, KeyParseError> {
+        let mut value = [0u8; 16];
+        decode_hex(hex, &mut value)?;
+        Ok(Key128Bit { value })
+    }
+
+    /// Parses a 128-bit key from padded Base64 in the standard
+    /// alphabet, i.e. 24 characters ending in `==`. See
+    /// [`Key80Bit::from_base64()`](struct.Key80Bit.html#method.from_base64).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `KeyParseError` if the string has the wrong
+    /// length or is not valid Base64.
+    #[cfg(feature = "base64")]
+    pub fn from_base64(encoded: &str) -> Result<Self, KeyParseError> {
+        let mut value = [0u8; 16];
+        decode_base64(encoded, &mut value)?;
+        Ok(Key128Bit { value })
+    }
+
+    /// Formats the key as padded Base64 in the standard alphabet.
+    #[cfg(feature = "base64")]
+    pub fn to_base64(&self) -> String {
+        Base64::encode_string(&self.value)
     }
 }
 
@@ -100,48 +367,429 @@ impl Key for Key128Bit {
     ///
     /// This function generates 32 round keys that are derived
     /// from the value of this key.
-    fn generate_round_keys(&self) -> [RoundKey; 32] {
-        // The round keys are generated as follows:
-        // 1. Take the 64 leftmost bits of the key register
-        // 2. Mutate the key register (cyclic bitshift, S-Box (partial),
-        //    XOR with round counter (partial))
-        // 3. Repeat until 32 round keys are extracted
-        let mut round_keys = [RoundKey { value: 0u64 }; 32];
-        let mut key_register = self.value;
-
-        for round in 1u8..32u8 { // round counter starts at 1!
-            // Get round key
-            let mut key_val = 0u64;
-            for byte in 0..8 {
-                key_val += (key_register[byte] as u64) << ((7 - byte) * 8);
-            }
-            round_keys[(round - 1) as usize].value = key_val;
+    fn generate_round_keys(&self) -> RoundKeys {
+        let mut bytes = schedule_128bit(&self.value);
+        let round_keys = to_round_keys(&bytes);
+        wipe(&mut bytes);
+        round_keys
+    }
 
-            // Cyclic bitshift (rotate by 61 bits to the left)
-            let tmp_register = key_register;
-            for byte in 0..16 {
-                key_register[byte] = (tmp_register[(byte + 7) % 16] << 5) +
-                                     (tmp_register[(byte + 8) % 16] >> 3);
-            }
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KeyParseError> {
+        Key128Bit::try_from(bytes)
+    }
+
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.value)
+    }
+}
 
-            // Apply S-Box to leftmost 8 bits
-            let sbox_result_1 = S_BOX.apply_enc(key_register[0] >> 4);
-            let sbox_result_2 = S_BOX.apply_enc(key_register[0] % 16);
-            key_register[0] = (sbox_result_1 << 4) + sbox_result_2;
+impl<'a> TryFrom<&'a [u8]> for Key128Bit {
+    type Error = KeyParseError;
 
-            // XOR bits 66, ..., 62 with the round counter
-            key_register[7] ^= round >> 2;
-            key_register[8] ^= round << 6;
+    /// Copies the key out of a byte slice, e.g. one read from a file
+    /// or returned by a key management service.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyParseError::InvalidLength` if the slice is not 16
+    /// bytes long.
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let mut value = [0u8; 16];
+        if bytes.len() != value.len() {
+            return Err(KeyParseError::InvalidLength(bytes.len()));
         }
+        value.copy_from_slice(bytes);
+        Ok(Key128Bit { value })
+    }
+}
+
+impl ConstantTimeEq for Key128Bit {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.ct_eq(&other.value)
+    }
+}
 
-        // Get final round key
-        let mut final_key = 0u64;
-        for byte in 0..8 {
-            final_key += (key_register[byte] as u64) << ((7 - byte) * 8);
+impl PartialEq for Key128Bit {
+    /// Compares the keys in constant time.
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for Key128Bit {}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for Key128Bit {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Key128Bit {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// A key whose length is chosen at runtime.
+///
+/// Applications that read the key size from a configuration can hold
+/// either key length in one type and pass it to all generic functions
+/// of this crate, instead of duplicating every code path for
+/// [`Key80Bit`](struct.Key80Bit.html) and
+/// [`Key128Bit`](struct.Key128Bit.html).
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_str, KeyAny, OpMode};
+/// let key = KeyAny::from_hex("0123456789abcdefABCD").unwrap();
+/// assert_eq!(key.bits(), 80);
+/// assert_eq!(key.as_bytes()[0], 0x01);
+/// assert!(encrypt_str("Hello, world!", &key, &OpMode::CBC).is_ok());
+/// ```
+#[derive(PartialEq, Eq)]
+pub enum KeyAny {
+    /// An 80-bit key.
+    Key80(Key80Bit),
+    /// A 128-bit key.
+    Key128(Key128Bit),
+}
+
+impl KeyAny {
+    /// Parses a key from a string of 20 or 32 hexadecimal digits,
+    /// choosing the key length accordingly.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `KeyParseError` if the string has any other
+    /// length or contains characters that are not hexadecimal digits.
+    pub fn from_hex(hex: &str) -> Result<Self, KeyParseError> {
+        match hex.len() {
+            20 => Key80Bit::from_hex(hex).map(KeyAny::Key80),
+            _ => Key128Bit::from_hex(hex).map(KeyAny::Key128),
         }
-        round_keys[31].value = final_key;
+    }
 
-        round_keys
+    /// Returns the length of the key in bits.
+    pub fn bits(&self) -> usize {
+        match *self {
+            KeyAny::Key80(_) => 80,
+            KeyAny::Key128(_) => 128,
+        }
+    }
+
+    /// Returns the value of the key as a byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        match *self {
+            KeyAny::Key80(ref key) => &key.value,
+            KeyAny::Key128(ref key) => &key.value,
+        }
+    }
+}
+
+impl Key for KeyAny {
+    /// Uses the key schedule for the length of the wrapped key.
+    fn generate_round_keys(&self) -> RoundKeys {
+        match *self {
+            KeyAny::Key80(ref key) => key.generate_round_keys(),
+            KeyAny::Key128(ref key) => key.generate_round_keys(),
+        }
+    }
+
+    /// Chooses the key length from the length of `bytes`, like
+    /// `KeyAny::try_from()`.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KeyParseError> {
+        KeyAny::try_from(bytes)
+    }
+
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(KeyAny::as_bytes(self))
+    }
+}
+
+impl From<Key80Bit> for KeyAny {
+    fn from(key: Key80Bit) -> Self {
+        KeyAny::Key80(key)
+    }
+}
+
+impl From<Key128Bit> for KeyAny {
+    fn from(key: Key128Bit) -> Self {
+        KeyAny::Key128(key)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for KeyAny {
+    type Error = KeyParseError;
+
+    /// Copies the key out of a byte slice of 10 or 16 bytes, choosing
+    /// the key length accordingly.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyParseError::InvalidLength` for any other length.
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        match bytes.len() {
+            10 => Key80Bit::try_from(bytes).map(KeyAny::Key80),
+            _ => Key128Bit::try_from(bytes).map(KeyAny::Key128),
+        }
+    }
+}
+
+/// A key with a precomputed key schedule that can be shared between threads.
+///
+/// The round keys are expanded once on construction and are immutable
+/// afterwards. Cloning a `SharedKey` only increments a reference count,
+/// so servers can hand one clone to every worker thread and encrypt
+/// concurrently without locking or repeating the key setup.
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+/// use present::{encrypt_str, Key80Bit, OpMode, SharedKey};
+///
+/// let key = SharedKey::new(&Key80Bit::new([0x42; 10]));
+/// let handles: Vec<_> = (0..4).map(|_| {
+///     let key = key.clone();
+///     thread::spawn(move || encrypt_str("Hello, world!", &key, &OpMode::CBC))
+/// }).collect();
+///
+/// for handle in handles {
+///     assert_eq!(handle.join().unwrap().unwrap().as_bytes().len(), 16);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SharedKey {
+    round_keys: Arc<RoundKeys>,
+}
+
+impl SharedKey {
+    /// Constructs a new shared key by expanding the given key.
+    pub fn new<K: Key>(key: &K) -> Self {
+        SharedKey { round_keys: Arc::new(key.generate_round_keys()) }
+    }
+}
+
+impl Key for SharedKey {
+    /// Returns a copy of the precomputed round keys.
+    fn generate_round_keys(&self) -> RoundKeys {
+        *self.round_keys
+    }
+
+    fn key_size(&self) -> usize {
+        self.round_keys.key_size()
+    }
+
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        self.round_keys.as_bytes()
+    }
+
+    /// Reads the round keys like `RoundKeys::from_bytes()`.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KeyParseError> {
+        RoundKeys::from_bytes(bytes).map(|round_keys| SharedKey { round_keys: Arc::new(round_keys) })
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for SharedKey {
+    /// Wipes the round keys when the last clone is dropped.
+    fn drop(&mut self) {
+        if let Some(round_keys) = Arc::get_mut(&mut self.round_keys) {
+            round_keys.zeroize();
+        }
+    }
+}
+
+/// The complete key schedule: one round key for each of the 32 rounds.
+///
+/// Round keys can be indexed by round and iterated over, so they can be
+/// compared against other implementations of PRESENT. The `Debug`
+/// output shows every round key as 16 hexadecimal digits.
+///
+/// Round keys are `Copy`, so every copy is independent of the others
+/// and none of them is wiped on drop. With the `zeroize` feature, the
+/// types of this crate that hold round keys wipe their own copy when
+/// they are dropped, but copies taken from them, e.g. with
+/// [`Key::generate_round_keys()`](trait.Key.html#tymethod.generate_round_keys),
+/// have to be wiped with `zeroize()` by the caller.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Key, Key80Bit};
+/// let round_keys = Key80Bit::new([0u8; 10]).generate_round_keys();
+/// assert_eq!(round_keys[0].value, 0);
+/// assert_eq!(round_keys[1].value, 0xC000000000000000);
+/// assert_eq!(round_keys.iter().count(), 32);
+/// ```
+#[derive(Copy, Clone)]
+pub struct RoundKeys([RoundKey; 32]);
+
+impl RoundKeys {
+    /// Constructs the key schedule from the given round keys.
+    pub fn new(round_keys: [RoundKey; 32]) -> Self {
+        RoundKeys(round_keys)
+    }
+
+    /// Returns an iterator over the round keys, starting with the first
+    /// round.
+    pub fn iter(&self) -> slice::Iter<'_, RoundKey> {
+        self.0.iter()
+    }
+
+    /// Returns an iterator that allows modifying the round keys.
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, RoundKey> {
+        self.0.iter_mut()
+    }
+
+    /// Returns the round keys as an array.
+    pub fn as_array(&self) -> &[RoundKey; 32] {
+        &self.0
+    }
+
+    /// Exports the key schedule as 256 bytes, each round key in
+    /// big-endian byte order.
+    ///
+    /// Devices that cannot spare the time or code size for the key
+    /// schedule can store the exported bytes, e.g. in flash, and load
+    /// them with [`from_bytes()`](#method.from_bytes) instead of the key.
+    ///
+    /// # Security
+    ///
+    /// The exported schedule is as secret as the key: the key can be
+    /// recovered from the first round keys, and the schedule alone is
+    /// enough to encrypt and decrypt. Protect it like the key itself,
+    /// and wipe the returned buffer once it is stored.
+    pub fn to_bytes(&self) -> [u8; 256] {
+        let mut bytes = [0u8; 256];
+        for (chunk, round_key) in bytes.chunks_mut(8).zip(self.0.iter()) {
+            chunk.copy_from_slice(&round_key.value.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Imports a key schedule exported with
+    /// [`to_bytes()`](#method.to_bytes).
+    ///
+    /// The bytes are not checked to be the schedule of any key, so a
+    /// corrupted schedule silently encrypts with the wrong round keys.
+    /// Compare the [`check_value()`](trait.Key.html#method.check_value)
+    /// of the imported schedule with a stored one to detect this.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyParseError::InvalidLength` if `bytes` is not 256
+    /// bytes long.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use present::{Key, Key80Bit, RoundKeys};
+    /// let key = Key80Bit::new([0x42; 10]);
+    /// let stored = key.generate_round_keys().to_bytes();
+    ///
+    /// let round_keys = RoundKeys::from_bytes(&stored).unwrap();
+    /// assert_eq!(round_keys.check_value(), key.check_value());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KeyParseError> {
+        if bytes.len() != 256 {
+            return Err(KeyParseError::InvalidLength(bytes.len()));
+        }
+
+        let mut value = [0u8; 8];
+        let mut round_keys = RoundKeys::new([RoundKey { value: 0 }; 32]);
+        for (round_key, chunk) in round_keys.iter_mut().zip(bytes.chunks(8)) {
+            value.copy_from_slice(chunk);
+            round_key.value = u64::from_be_bytes(value);
+        }
+        wipe(&mut value);
+        Ok(round_keys)
+    }
+}
+
+impl Key for RoundKeys {
+    /// Returns a copy of the round keys, so a stored schedule can be
+    /// used without any key expansion.
+    fn generate_round_keys(&self) -> RoundKeys {
+        *self
+    }
+
+    fn key_size(&self) -> usize {
+        256
+    }
+
+    /// Returns the bytes of `RoundKeys::to_bytes()`.
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = self.to_bytes();
+        let owned = bytes.to_vec();
+        wipe(&mut bytes[..]);
+        Cow::Owned(owned)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KeyParseError> {
+        RoundKeys::from_bytes(bytes)
+    }
+}
+
+impl Index<usize> for RoundKeys {
+    type Output = RoundKey;
+
+    fn index(&self, round: usize) -> &RoundKey {
+        &self.0[round]
+    }
+}
+
+impl IndexMut<usize> for RoundKeys {
+    fn index_mut(&mut self, round: usize) -> &mut RoundKey {
+        &mut self.0[round]
+    }
+}
+
+impl<'a> IntoIterator for &'a RoundKeys {
+    type Item = &'a RoundKey;
+    type IntoIter = slice::Iter<'a, RoundKey>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl From<[RoundKey; 32]> for RoundKeys {
+    fn from(round_keys: [RoundKey; 32]) -> Self {
+        RoundKeys(round_keys)
+    }
+}
+
+impl ConstantTimeEq for RoundKeys {
+    /// Compares all 32 round keys, without stopping at the first one
+    /// that differs.
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.iter().zip(other.0.iter()).fold(Choice::from(1), |equal, (a, b)| equal & a.ct_eq(b))
+    }
+}
+
+impl PartialEq for RoundKeys {
+    /// Compares the schedules in constant time.
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for RoundKeys {}
+
+impl fmt::Debug for RoundKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.0.iter()).finish()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for RoundKeys {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
     }
 }
 
@@ -152,6 +800,198 @@ pub struct RoundKey {
     pub value: u64,
 }
 
+impl fmt::Debug for RoundKey {
+    /// Formats the round key as 16 hexadecimal digits.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.value)
+    }
+}
+
+impl ConstantTimeEq for RoundKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.ct_eq(&other.value)
+    }
+}
+
+impl PartialEq for RoundKey {
+    /// Compares the round keys in constant time.
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for RoundKey {}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for RoundKey {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+/// Overwrites key material that is no longer needed, if the `zeroize`
+/// feature is enabled.
+///
+/// Round keys are `Copy`, so they cannot wipe themselves on drop. Call
+/// this on every temporary copy of key material instead.
+#[cfg(feature = "zeroize")]
+pub fn wipe<Z: Zeroize + ?Sized>(value: &mut Z) {
+    value.zeroize();
+}
+
+#[cfg(not(feature = "zeroize"))]
+pub fn wipe<Z: ?Sized>(_value: &mut Z) {}
+
+/// The key schedule for 80-bit keys.
+///
+/// Returns the 64 leftmost bits of the key register for each of the
+/// 32 rounds as bytes. This only uses byte operations, so it can be
+/// shared with the implementations that avoid 64-bit arithmetic.
+pub fn schedule_80bit(value: &[u8; 10]) -> [[u8; 8]; 32] {
+    // The round keys are generated as follows:
+    // 1. Take the 64 leftmost bits of the key register
+    // 2. Mutate the key register (cyclic bitshift, S-Box (partial),
+    //    XOR with round counter (partial))
+    // 3. Repeat until 32 round keys are extracted
+    let mut round_keys = [[0u8; 8]; 32];
+    let mut key_register = *value;
+
+    for round in 1u8..32u8 { // round counter starts at 1!
+        // Get round key
+        round_keys[(round - 1) as usize].copy_from_slice(&key_register[..8]);
+
+        // Cyclic bitshift (rotate by 61 bits to the left)
+        let mut tmp_register = key_register;
+        for byte in 0..10 {
+            key_register[byte] = (tmp_register[(byte + 7) % 10] << 5) +
+                                 (tmp_register[(byte + 8) % 10] >> 3);
+        }
+        wipe(&mut tmp_register);
+
+        // Apply S-Box to leftmost 4 bits
+        let sbox_result = sbox::apply_enc(key_register[0] >> 4);
+        key_register[0] %= 16;
+        key_register[0] += sbox_result << 4;
+
+        // XOR bits 19, ..., 15 with the round counter
+        key_register[7] ^= round >> 1;
+        key_register[8] ^= round << 7;
+    }
+
+    // Get final round key
+    round_keys[31].copy_from_slice(&key_register[..8]);
+    wipe(&mut key_register);
+
+    round_keys
+}
+
+/// The key schedule for 128-bit keys.
+///
+/// Works like `schedule_80bit`, but with the 128-bit key register.
+pub fn schedule_128bit(value: &[u8; 16]) -> [[u8; 8]; 32] {
+    // The round keys are generated as follows:
+    // 1. Take the 64 leftmost bits of the key register
+    // 2. Mutate the key register (cyclic bitshift, S-Box (partial),
+    //    XOR with round counter (partial))
+    // 3. Repeat until 32 round keys are extracted
+    let mut round_keys = [[0u8; 8]; 32];
+    let mut key_register = *value;
+
+    for round in 1u8..32u8 { // round counter starts at 1!
+        // Get round key
+        round_keys[(round - 1) as usize].copy_from_slice(&key_register[..8]);
+
+        // Cyclic bitshift (rotate by 61 bits to the left)
+        let mut tmp_register = key_register;
+        for byte in 0..16 {
+            key_register[byte] = (tmp_register[(byte + 7) % 16] << 5) +
+                                 (tmp_register[(byte + 8) % 16] >> 3);
+        }
+        wipe(&mut tmp_register);
+
+        // Apply S-Box to leftmost 8 bits
+        let sbox_result_1 = sbox::apply_enc(key_register[0] >> 4);
+        let sbox_result_2 = sbox::apply_enc(key_register[0] % 16);
+        key_register[0] = (sbox_result_1 << 4) + sbox_result_2;
+
+        // XOR bits 66, ..., 62 with the round counter
+        key_register[7] ^= round >> 2;
+        key_register[8] ^= round << 6;
+    }
+
+    // Get final round key
+    round_keys[31].copy_from_slice(&key_register[..8]);
+    wipe(&mut key_register);
+
+    round_keys
+}
+
+/// Decodes hexadecimal digits into the given buffer in constant time.
+///
+/// All digits are processed without data-dependent branches or table
+/// lookups, and validity is only checked once all of them are decoded.
+fn decode_hex(hex: &str, value: &mut [u8]) -> Result<(), KeyParseError> {
+    let digits = hex.as_bytes();
+    if digits.len() != value.len() * 2 {
+        return Err(KeyParseError::InvalidLength(digits.len()));
+    }
+
+    let mut valid = 0xFFu8;
+    for (byte, pair) in value.iter_mut().zip(digits.chunks(2)) {
+        let (high, high_valid) = decode_nibble(pair[0]);
+        let (low, low_valid) = decode_nibble(pair[1]);
+        *byte = (high << 4) | low;
+        valid &= high_valid & low_valid;
+    }
+
+    if valid != 0xFF {
+        return Err(KeyParseError::InvalidHex);
+    }
+    Ok(())
+}
+
+/// Decodes padded Base64 into the given buffer in constant time.
+#[cfg(feature = "base64")]
+fn decode_base64(encoded: &str, value: &mut [u8]) -> Result<(), KeyParseError> {
+    if encoded.len() != value.len().div_ceil(3) * 4 {
+        return Err(KeyParseError::InvalidLength(encoded.len()));
+    }
+
+    let len = value.len();
+    match Base64::decode(encoded, value) {
+        Ok(decoded) if decoded.len() == len => Ok(()),
+        _ => Err(KeyParseError::InvalidBase64),
+    }
+}
+
+/// Decodes a single hexadecimal digit without branching on its value.
+///
+/// Returns the value of the digit and a mask that is `0xFF` if the
+/// digit is valid and `0x00` otherwise.
+fn decode_nibble(digit: u8) -> (u8, u8) {
+    let digit = digit as u32;
+
+    // '0'..'9': the mask is all ones if digit ^ 48 is below 10
+    let num = digit ^ 48;
+    let num_mask = (num.wrapping_sub(10) >> 8) as u8;
+
+    // 'a'..'f' and 'A'..'F': clear the lowercase bit, then the mask is
+    // all ones if the result minus 55 lies in 10..16
+    let alpha = (digit & !32).wrapping_sub(55);
+    let alpha_mask = ((alpha.wrapping_sub(10) ^ alpha.wrapping_sub(16)) >> 8) as u8;
+
+    ((num_mask & num as u8) | (alpha_mask & alpha as u8), num_mask | alpha_mask)
+}
+
+/// Converts round keys given as bytes to their 64-bit representation.
+pub fn to_round_keys(bytes: &[[u8; 8]; 32]) -> RoundKeys {
+    let mut round_keys = RoundKeys::new([RoundKey { value: 0 }; 32]);
+    for (round_key, value) in round_keys.iter_mut().zip(bytes.iter()) {
+        round_key.value = u64::from_be_bytes(*value);
+    }
+    round_keys
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,4 +1015,173 @@ mod tests {
         assert_eq!(round_keys[1].value, 0x7C5002554BFBE724_u64);
         assert_eq!(round_keys[2].value, 0xE42B029B9D8C9AF1_u64);
     }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize_clears_key_material() {
+        let mut key = Key128Bit::new([0xA5; 16]);
+        let mut round_keys = key.generate_round_keys();
+        key.zeroize();
+        assert_eq!(key.value, [0u8; 16]);
+
+        wipe(&mut round_keys);
+        assert!(round_keys.iter().all(|round_key| round_key.value == 0));
+    }
+
+    #[test]
+    fn test_key_equality() {
+        assert!(Key80Bit::new([0x42; 10]) == Key80Bit::new([0x42; 10]));
+        assert!(Key80Bit::new([0x42; 10]) != Key80Bit::from_hex("42424242424242424243").unwrap());
+        assert!(bool::from(Key128Bit::new([7; 16]).ct_eq(&Key128Bit::new([7; 16]))));
+        assert!(Key128Bit::new([7; 16]).generate_round_keys() != Key128Bit::new([8; 16]).generate_round_keys());
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_base64_roundtrip() {
+        let key = Key128Bit::new([0x00, 0x10, 0x83, 0x10, 0x51, 0x87, 0x20, 0x92, 0x8B, 0x30, 0xD3, 0x8F, 0x41, 0x14, 0x93, 0x51]);
+        assert_eq!(key.to_base64(), "ABCDEFGHIJKLMNOPQRSTUQ==");
+        assert!(Key128Bit::from_base64(&key.to_base64()).unwrap() == key);
+
+        assert_eq!(Key80Bit::from_base64("QkJCQkJCQkJC").err(), Some(KeyParseError::InvalidLength(12)));
+        assert_eq!(Key80Bit::from_base64("QkJCQkJCQkJCQkJC").err(), Some(KeyParseError::InvalidBase64));
+        assert_eq!(Key80Bit::from_base64("QkJCQkJCQkJCQ!==").err(), Some(KeyParseError::InvalidBase64));
+    }
+
+    #[test]
+    fn test_try_from_slice() {
+        let bytes = [0x42u8; 16];
+        assert!(Key80Bit::try_from(&bytes[..10]).unwrap() == Key80Bit::new([0x42; 10]));
+        assert!(Key128Bit::try_from(&bytes[..]).unwrap() == Key128Bit::new([0x42; 16]));
+        assert_eq!(Key80Bit::try_from(&bytes[..]).err(), Some(KeyParseError::InvalidLength(16)));
+        assert_eq!(Key128Bit::try_from(&bytes[..10]).err(), Some(KeyParseError::InvalidLength(10)));
+    }
+
+    #[test]
+    fn test_key_any() {
+        let bytes = [0x42u8; 16];
+        let key80 = KeyAny::try_from(&bytes[..10]).unwrap();
+        let key128 = KeyAny::try_from(&bytes[..]).unwrap();
+        assert_eq!(key80.bits(), 80);
+        assert_eq!(key128.as_bytes(), &bytes[..]);
+        assert!(key80.generate_round_keys() == Key80Bit::new([0x42; 10]).generate_round_keys());
+        assert!(key128.generate_round_keys() == Key128Bit::new([0x42; 16]).generate_round_keys());
+        assert!(key128 == KeyAny::from(Key128Bit::new([0x42; 16])));
+        assert!(KeyAny::from_hex(&"42".repeat(10)).unwrap() == key80);
+        assert_eq!(KeyAny::try_from(&bytes[..12]).err(), Some(KeyParseError::InvalidLength(12)));
+        assert_eq!(KeyAny::from_hex("0123").err(), Some(KeyParseError::InvalidLength(4)));
+    }
+
+    #[test]
+    fn test_key_bytes() {
+        let bytes = [0x42u8; 16];
+        let key = Key128Bit::from_bytes(&bytes).unwrap();
+        assert_eq!(key.as_bytes(), &bytes[..]);
+        assert_eq!(Key80Bit::from_bytes(&bytes[..10]).unwrap().key_size(), 10);
+        assert_eq!(KeyAny::from_bytes(&bytes[..10]).unwrap().key_size(), 10);
+        assert_eq!(Key80Bit::from_bytes(&bytes).err(), Some(KeyParseError::InvalidLength(16)));
+    }
+
+    #[test]
+    fn test_round_keys_accessors() {
+        let mut round_keys = Key80Bit::new([0u8; 10]).generate_round_keys();
+        for (round, round_key) in (&round_keys).into_iter().enumerate() {
+            assert!(*round_key == round_keys.as_array()[round]);
+        }
+        round_keys[0].value = 0x0123456789ABCDEF;
+        assert_eq!(format!("{:?}", round_keys[0]), "0123456789abcdef");
+        assert!(format!("{:?}", round_keys).starts_with("[0123456789abcdef, c000000000000000, "));
+    }
+
+    #[test]
+    fn test_round_keys_equality() {
+        let round_keys = Key80Bit::new([0x42; 10]).generate_round_keys();
+        let mut last_differs = round_keys;
+        last_differs[31].value ^= 1;
+        assert!(round_keys == Key80Bit::new([0x42; 10]).generate_round_keys());
+        assert!(round_keys != last_differs);
+        assert!(!bool::from(round_keys.ct_eq(&last_differs)));
+    }
+
+    #[test]
+    fn test_round_keys_export() {
+        let round_keys = Key128Bit::new([0x42; 16]).generate_round_keys();
+        let bytes = round_keys.to_bytes();
+        assert_eq!(bytes[..8], round_keys[0].value.to_be_bytes());
+        assert_eq!(bytes[248..], round_keys[31].value.to_be_bytes());
+        assert!(RoundKeys::from_bytes(&bytes).unwrap() == round_keys);
+        assert_eq!(RoundKeys::from_bytes(&bytes[..255]).err(), Some(KeyParseError::InvalidLength(255)));
+    }
+
+    #[test]
+    fn test_schedule_key_bytes() {
+        let round_keys = Key128Bit::new([0x42; 16]).generate_round_keys();
+        let shared = SharedKey::new(&round_keys);
+        assert_eq!(Key::key_size(&round_keys), 256);
+        assert_eq!(shared.key_size(), 256);
+        assert_eq!(Key::as_bytes(&round_keys), &round_keys.to_bytes()[..]);
+        assert_eq!(shared.as_bytes(), &round_keys.to_bytes()[..]);
+        assert!(<RoundKeys as Key>::from_bytes(&Key::as_bytes(&round_keys)).unwrap() == round_keys);
+        assert!(SharedKey::from_bytes(&shared.as_bytes()).unwrap().generate_round_keys() == round_keys);
+        assert_eq!(SharedKey::from_bytes(&[0; 16]).err(), Some(KeyParseError::InvalidLength(16)));
+    }
+
+    #[test]
+    fn test_check_value() {
+        assert_eq!(Key80Bit::new([0xFF; 10]).check_value(), [0xE7, 0x2C, 0x46]);
+        assert_eq!(Key128Bit::new([0u8; 16]).check_value(), SharedKey::new(&Key128Bit::new([0u8; 16])).check_value());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let key = Key80Bit::new([0x42; 10]);
+        let fingerprint = key.fingerprint();
+        assert_eq!(fingerprint.as_bytes(), &::mac::cmac(&key, FINGERPRINT_LABEL));
+        assert_eq!(fingerprint.to_string().len(), 16);
+        assert_ne!(fingerprint, Key80Bit::new([0x43; 10]).fingerprint());
+        assert_ne!(&fingerprint.as_bytes()[..3], &key.check_value());
+    }
+
+    #[test]
+    fn test_decode_nibble_all_inputs() {
+        for digit in 0..=255u8 {
+            let expected = (digit as char).to_digit(16);
+            let (value, valid) = decode_nibble(digit);
+            match expected {
+                Some(x) => {
+                    assert_eq!(valid, 0xFF);
+                    assert_eq!(value as u32, x);
+                },
+                None => assert_eq!(valid, 0x00),
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_from_hex() {
+        let key = Key128Bit::from_hex("0ac0a6e76326bc7e828012aa5fdf3925").unwrap();
+        assert_eq!(key.value, [0xA, 0xC0, 0xA6, 0xE7, 0x63, 0x26, 0xBC, 0x7E, 0x82, 0x80, 0x12, 0xAA, 0x5F, 0xDF, 0x39, 0x25]);
+
+        assert_eq!(Key80Bit::from_hex("0123").err(), Some(KeyParseError::InvalidLength(4)));
+        assert_eq!(Key80Bit::from_hex("0123456789abcdefABCG").err(), Some(KeyParseError::InvalidHex));
+        assert_eq!(Key80Bit::from_hex("0123456789abcdef ABC").err(), Some(KeyParseError::InvalidHex));
+    }
+
+    #[test]
+    fn test_keys_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Key80Bit>();
+        assert_send_sync::<Key128Bit>();
+        assert_send_sync::<SharedKey>();
+    }
+
+    #[test]
+    fn test_shared_key_uses_expanded_key() {
+        let key = Key128Bit::new([0xA, 0xC0, 0xA6, 0xE7, 0x63, 0x26, 0xBC, 0x7E, 0x82, 0x80, 0x12, 0xAA, 0x5F, 0xDF, 0x39, 0x25]);
+        let shared = SharedKey::new(&key);
+        let round_keys = shared.clone().generate_round_keys();
+        for (expected, actual) in key.generate_round_keys().iter().zip(round_keys.iter()) {
+            assert_eq!(expected.value, actual.value);
+        }
+    }
 }
@@ -0,0 +1,282 @@
+use std::fs::{self, OpenOptions};
+use std::ffi::OsString;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::process;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use kdf::pbkdf2_key128;
+use keys::{wipe, Key80Bit, Key128Bit};
+use siv::Siv;
+
+/// Magic bytes at the start of every keystore file.
+const MAGIC: [u8; 4] = *b"PRSK";
+
+/// Version of the keystore format.
+const VERSION: u8 = 1;
+
+/// Identifier of PBKDF2 with `CmacPrf`, the only KDF so far.
+const KDF_PBKDF2: u8 = 1;
+
+/// Length of the header: magic, version, key length, KDF identifier,
+/// iteration count and salt.
+const HEADER_LEN: usize = 27;
+
+/// Passphrase-protected key storage in files.
+///
+/// Every key is stored in a file of its own, encrypted under a key that
+/// is derived from a passphrase with [`pbkdf2`](fn.pbkdf2.html) and a
+/// random salt. The file starts with a 27-byte header consisting of the
+/// magic bytes `PRSK`, a format version byte, the length of the key, a
+/// byte identifying the KDF, the iteration count and the salt. The key
+/// follows, wrapped with [`Siv`](struct.Siv.html) with the header as
+/// associated data, so a wrong passphrase or any modification of the
+/// file is detected on loading.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use present::{Key128Bit, Keystore};
+///
+/// let keystore = Keystore::new();
+/// keystore.save_key128(Path::new("backup.key"), &Key128Bit::new([0x42; 16]), b"passphrase").unwrap();
+/// let key = keystore.load_key128(Path::new("backup.key"), b"passphrase").unwrap();
+/// assert_eq!(key.value, [0x42; 16]);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Keystore {
+    iterations: u32,
+}
+
+impl Keystore {
+    /// Iteration count of PBKDF2 for new keystore files.
+    pub const DEFAULT_ITERATIONS: u32 = 100_000;
+
+    /// Highest iteration count that is accepted, so that a crafted file
+    /// cannot make loading take hours.
+    pub const MAX_ITERATIONS: u32 = 10_000_000;
+
+    /// Constructs a keystore that protects new files with the default
+    /// iteration count.
+    pub fn new() -> Self {
+        Keystore { iterations: Keystore::DEFAULT_ITERATIONS }
+    }
+
+    /// Constructs a keystore that protects new files with the given
+    /// iteration count. Loading always uses the iteration count stored
+    /// in the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `iterations` is zero or greater than
+    /// [`MAX_ITERATIONS`](#associatedconstant.MAX_ITERATIONS).
+    pub fn with_iterations(iterations: u32) -> Self {
+        assert!(iterations > 0, "PBKDF2 needs at least one iteration");
+        assert!(iterations <= Keystore::MAX_ITERATIONS, "Iteration count is too high to be loaded again");
+        Keystore { iterations }
+    }
+
+    /// Saves an 80-bit key, replacing an existing file at `path`. The
+    /// key is written to a new file in the same directory first, which
+    /// is then renamed, so `path` either holds the old or the new file.
+    /// On Unix, the file is only readable by its owner, also if it
+    /// replaces a file that was readable by others.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the salt cannot be generated or the file cannot
+    /// be written.
+    pub fn save_key80(&self, path: &Path, key: &Key80Bit, passphrase: &[u8]) -> io::Result<()> {
+        write_private(path, &self.seal(&key.value, passphrase)?)
+    }
+
+    /// Saves a 128-bit key, see [`save_key80()`](#method.save_key80).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the salt cannot be generated or the file cannot
+    /// be written.
+    pub fn save_key128(&self, path: &Path, key: &Key128Bit, passphrase: &[u8]) -> io::Result<()> {
+        write_private(path, &self.seal(&key.value, passphrase)?)
+    }
+
+    /// Loads an 80-bit key saved with [`save_key80()`](#method.save_key80).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` of kind `InvalidData` if the file is not a
+    /// keystore file, does not hold an 80-bit key, or the passphrase is
+    /// wrong.
+    pub fn load_key80(&self, path: &Path, passphrase: &[u8]) -> io::Result<Key80Bit> {
+        let mut value = [0u8; 10];
+        open(&fs::read(path)?, passphrase, &mut value)?;
+        Ok(Key80Bit::new(value))
+    }
+
+    /// Loads a 128-bit key saved with
+    /// [`save_key128()`](#method.save_key128).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` of kind `InvalidData` if the file is not a
+    /// keystore file, does not hold a 128-bit key, or the passphrase is
+    /// wrong.
+    pub fn load_key128(&self, path: &Path, passphrase: &[u8]) -> io::Result<Key128Bit> {
+        let mut value = [0u8; 16];
+        open(&fs::read(path)?, passphrase, &mut value)?;
+        Ok(Key128Bit::new(value))
+    }
+
+    /// Encodes the header and wraps the key.
+    fn seal(&self, value: &[u8], passphrase: &[u8]) -> io::Result<Vec<u8>> {
+        let mut salt = [0u8; 16];
+        OsRng.try_fill_bytes(&mut salt).map_err(|e| io::Error::other(format!("Salt generation failed: {:?}", e)))?;
+
+        let mut sealed = MAGIC.to_vec();
+        sealed.extend_from_slice(&[VERSION, value.len() as u8, KDF_PBKDF2]);
+        sealed.extend_from_slice(&self.iterations.to_be_bytes());
+        sealed.extend_from_slice(&salt);
+
+        let siv = Siv::from_master_key(&pbkdf2_key128(passphrase, &salt, self.iterations));
+        let wrapped = siv.encrypt(b"", &sealed, value);
+        sealed.extend(wrapped);
+        Ok(sealed)
+    }
+}
+
+impl Default for Keystore {
+    fn default() -> Self {
+        Keystore::new()
+    }
+}
+
+/// Checks the header and unwraps the key into `value`.
+fn open(sealed: &[u8], passphrase: &[u8], value: &mut [u8]) -> io::Result<()> {
+    if sealed.len() < HEADER_LEN || sealed[..4] != MAGIC {
+        return Err(invalid_data("File is not a keystore file"));
+    }
+    if sealed[4] != VERSION || sealed[6] != KDF_PBKDF2 {
+        return Err(invalid_data("Unsupported keystore format version"));
+    }
+    if sealed[5] as usize != value.len() || sealed.len() != HEADER_LEN + 8 + value.len() {
+        return Err(invalid_data("Keystore file holds a key of a different length"));
+    }
+
+    let (header, wrapped) = sealed.split_at(HEADER_LEN);
+    let mut iterations = [0u8; 4];
+    iterations.copy_from_slice(&header[7..11]);
+    let iterations = u32::from_be_bytes(iterations);
+    if iterations == 0 || iterations > Keystore::MAX_ITERATIONS {
+        return Err(invalid_data("Keystore file has an invalid iteration count"));
+    }
+
+    let siv = Siv::from_master_key(&pbkdf2_key128(passphrase, &header[11..], iterations));
+    let mut unwrapped = siv.decrypt(b"", header, wrapped)
+        .map_err(|_| invalid_data("Wrong passphrase or corrupted keystore file"))?;
+    value.copy_from_slice(&unwrapped);
+    wipe(&mut unwrapped[..]);
+    Ok(())
+}
+
+/// Writes the file so that only its owner can read it.
+///
+/// The contents go to a new temporary file next to `path`, which is
+/// renamed over `path` once it is complete.
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    let mut temp_name = OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let result = options.open(&temp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_seal_and_open() {
+        let keystore = Keystore::with_iterations(10);
+        let sealed = keystore.seal(&[0x42; 16], b"passphrase").unwrap();
+        assert_eq!(sealed.len(), HEADER_LEN + 8 + 16);
+        assert_eq!(&sealed[..11], b"PRSK\x01\x10\x01\0\0\0\x0A");
+
+        let mut value = [0u8; 16];
+        open(&sealed, b"passphrase", &mut value).unwrap();
+        assert_eq!(value, [0x42; 16]);
+
+        let mut short = [0u8; 10];
+        assert_eq!(open(&sealed, b"passphrase", &mut short).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(open(&sealed, b"passphrasf", &mut value).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        // Flipping the second byte of the iteration count makes the
+        // derivation take too long for a test, the first one exceeds the
+        // maximum
+        for i in (0..8).chain(9..sealed.len()) {
+            let mut tampered = sealed.clone();
+            tampered[i] ^= 0x01;
+            assert!(open(&tampered, b"passphrase", &mut value).is_err());
+        }
+    }
+
+    #[test]
+    fn test_file_roundtrip() {
+        let path = env::temp_dir().join(format!("present-keystore-{}", process::id()));
+        let keystore = Keystore::with_iterations(10);
+        keystore.save_key80(&path, &Key80Bit::new([0x17; 10]), b"passphrase").unwrap();
+        let loaded = keystore.load_key80(&path, b"passphrase");
+        let wrong_size = keystore.load_key128(&path, b"passphrase");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap().value, [0x17; 10]);
+        assert_eq!(wrong_size.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn test_rejects_excessive_iterations() {
+        let mut sealed = Keystore::with_iterations(10).seal(&[0x42; 16], b"passphrase").unwrap();
+        sealed[7..11].copy_from_slice(&(Keystore::MAX_ITERATIONS + 1).to_be_bytes());
+        let mut value = [0u8; 16];
+        assert_eq!(open(&sealed, b"passphrase", &mut value).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replaced_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = env::temp_dir().join(format!("present-keystore-private-{}", process::id()));
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        Keystore::with_iterations(10).save_key128(&path, &Key128Bit::new([0x17; 16]), b"passphrase").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        let loaded = Keystore::new().load_key128(&path, b"passphrase");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(loaded.unwrap().value, [0x17; 16]);
+    }
+}
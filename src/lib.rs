@@ -1,28 +1,185 @@
-#[macro_use]
-extern crate lazy_static;
-
 extern crate rand;
+extern crate subtle;
+#[cfg(feature = "rand_core")]
+extern crate rand_core;
+#[cfg(feature = "os-keyring")]
+extern crate keyring;
+#[cfg(feature = "bytemuck")]
+extern crate bytemuck;
+#[cfg(feature = "rustcrypto")]
+extern crate aead as rustcrypto_aead;
+#[cfg(feature = "rustcrypto")]
+extern crate cipher;
+#[cfg(feature = "rustcrypto")]
+extern crate digest;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "zeroize")]
+extern crate zeroize;
+#[cfg(feature = "secrecy")]
+extern crate secrecy;
+#[cfg(all(unix, feature = "secure-memory"))]
+extern crate libc;
+#[cfg(feature = "base64")]
+extern crate base64ct;
+#[cfg(feature = "argon2")]
+extern crate argon2;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 mod block;
+mod ciphertext;
 mod keys;
 mod sbox;
 mod pbox;
 mod modes;
+mod iv;
 mod errors;
-
-pub use self::block::Block;
-pub use self::keys::{Key, Key80Bit, Key128Bit};
-pub use self::modes::OpMode;
-pub use self::errors::DecryptError;
+mod fault;
+mod counter;
+mod capability;
+mod builder;
+pub mod conformance;
+pub mod aead;
+pub mod padding;
+mod policy;
+mod profile;
+mod mac;
+mod diversify;
+mod kdf;
+mod keystore;
+mod rotation;
+mod xts;
+mod tag;
+mod ccm;
+mod ocb;
+mod siv;
+mod etm;
+mod envelope;
+mod ctr;
+mod context;
+mod masked;
+mod shuffled;
+mod bitslice;
+mod pair;
+mod iter;
+mod stream;
+mod transform;
+mod file;
+mod uniform;
+#[cfg(feature = "rand_core")]
+mod rng;
+#[cfg(feature = "small-state")]
+mod state16;
+#[cfg(all(debug_assertions, feature = "small-state"))]
+mod crosscheck;
+#[cfg(feature = "os-keyring")]
+mod os_keyring;
+#[cfg(feature = "bytemuck")]
+mod pod;
+#[cfg(feature = "rustcrypto")]
+mod rustcrypto;
+#[cfg(all(feature = "tables", not(feature = "ct-sbox")))]
+mod tables;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "async")]
+mod async_io;
+#[cfg(feature = "secrecy")]
+mod secret;
+#[cfg(all(unix, feature = "secure-memory"))]
+mod secure;
+#[cfg(feature = "argon2")]
+mod argon;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(test)]
+mod testing;
+
+use self::context::Expanded;
+use self::padding::{PaddingScheme, Pkcs7};
+
+pub use self::block::{Block, encrypt_block8, decrypt_block8, encrypt_blocks8, decrypt_blocks8};
+pub use self::ciphertext::Ciphertext;
+pub use self::keys::{Key, Key80Bit, Key128Bit, KeyAny, KeyFingerprint, RoundKey, RoundKeys, SharedKey};
+pub use self::modes::{Mode, ModeOfOperation, OpMode, random_iv_with};
+pub use self::iv::Iv;
+pub use self::errors::{AeadError, BufferError, DecryptError, EncryptError, EnvelopeError, ErrorKind, FaultError, KeyParseError, ParseError, RngError};
+pub use self::fault::{FaultReport, flip_ciphertext_bits, flip_init_vec_bits, flip_tag_bits};
+pub use self::counter::{CounterIv, CounterStore, FileCounterStore};
+pub use self::capability::{EncryptKey, DecryptKey};
+pub use self::builder::{Cbc, Cfb, Cipher, CipherBuilder, Pcbc};
+#[cfg(feature = "insecure-modes")]
+pub use self::builder::Ecb;
+pub use self::policy::{SecurityEvent, SecurityPolicy, set_security_policy, clear_security_policy};
+pub use self::mac::{Cmac, cmac};
+pub use self::diversify::{diversify_key80, diversify_key128};
+pub use self::kdf::{CmacPrf, Hkdf, Prf, pbkdf2, pbkdf2_key80, pbkdf2_key128};
+pub use self::keystore::Keystore;
+pub use self::rotation::KeyRing;
+pub use self::xts::Xts;
+pub use self::tag::{TagLen, tags_equal};
+pub use self::ccm::Ccm;
+pub use self::ocb::Ocb;
+pub use self::siv::Siv;
+pub use self::etm::{encrypt_then_mac, verify_then_decrypt};
+pub use self::envelope::Envelope;
+pub use self::ctr::Ctr;
+pub use self::context::{BlockCipher, Present80, Present128};
+#[cfg(feature = "insecure-modes")]
+#[allow(deprecated)]
+pub use self::iter::EncryptBlocks;
+pub use self::iter::PaddedBlocks;
+pub use self::stream::{DecryptReader, Decryptor, EncryptWriter, Encryptor, decrypt_stream, encrypt_stream};
+pub use self::transform::{Chain, Transform, transform_stream};
+pub use self::file::{decrypt_file, encrypt_file};
+pub use self::uniform::{decrypt_bytes_uniform, decrypt_str_uniform};
+pub use self::bitslice::{encrypt_blocks_bitsliced, decrypt_blocks_bitsliced};
+#[cfg(feature = "rand_core")]
+pub use self::rng::CtrRng;
+#[cfg(feature = "small-state")]
+pub use self::state16::{RoundKeys16, encrypt_block16, decrypt_block16};
+#[cfg(feature = "profiling")]
+pub use self::profile::{Profile, take_profile};
+#[cfg(feature = "os-keyring")]
+pub use self::os_keyring::OsKeyring;
+#[cfg(feature = "os-keyring")]
+pub use self::errors::KeyringError;
+#[cfg(feature = "bytemuck")]
+pub use self::pod::{encrypt_pod, decrypt_pod};
+#[cfg(feature = "rustcrypto")]
+pub use self::rustcrypto::PresentCipher;
+#[cfg(feature = "simd")]
+pub use self::simd::{encrypt_blocks_simd, decrypt_blocks_simd};
+#[cfg(feature = "parallel")]
+pub use self::parallel::{encrypt_bytes_par, decrypt_bytes_par};
+#[cfg(feature = "async")]
+pub use self::async_io::{AsyncDecryptReader, AsyncEncryptWriter};
+#[cfg(feature = "secrecy")]
+pub use self::secret::{SecretKey80, SecretKey128};
+#[cfg(all(unix, feature = "secure-memory"))]
+pub use self::secure::SecureKey;
+#[cfg(feature = "argon2")]
+pub use self::argon::Argon2Params;
+#[cfg(feature = "argon2")]
+pub use self::errors::KdfError;
+#[cfg(feature = "serde")]
+pub use self::serialize::serialize_fingerprint;
 
 /// Encrypt a string.
 ///
 /// Encrypt a string with a specific key and operation mode.
 /// Because the ciphertext is not a valid UTF-8 string, it is
-/// instead returned as a byte vector. In addition, if the
-/// specified operation mode needs an initialization vector,
-/// one will be randomly generated and returned with the
-/// ciphertext.
+/// instead returned as a [`Ciphertext`](struct.Ciphertext.html)
+/// holding the bytes. In addition, if the specified operation
+/// mode needs an initialization vector, one will be randomly
+/// generated and returned with the ciphertext.
 ///
 /// # Arguments
 ///
@@ -30,88 +187,104 @@ pub use self::errors::DecryptError;
 /// * `key` - The key to be used for encryption.
 /// * `mode` - Block cipher mode of operation that will be used.
 ///
+/// # Errors
+///
+/// Returns `EncryptError::Rng` if an IV is needed and the random
+/// number generator of the operating system fails.
+///
 /// # Examples
 ///
 /// ```
 /// use present::{encrypt_str, Key80Bit, OpMode};
 /// let key = Key80Bit::new([0xFF; 10]);
-/// let (ciphertext, iv) = encrypt_str("Hello, world!", &key, &OpMode::CBC);
-/// assert!(iv.is_some());
+/// let ciphertext = encrypt_str("Hello, world!", &key, &OpMode::CBC).unwrap();
+/// assert!(ciphertext.init_vec().is_some());
+/// assert_eq!(ciphertext.decrypt_str(&key).unwrap(), "Hello, world!");
 /// ```
-pub fn encrypt_str<K: Key>(text: &str, key: &K, mode: &OpMode) -> (Vec<u8>, Option<Block>) {
-    // Check how much padding needs to be appended to the string
-    let pad_len = match text.len() % 8 {
-        0 => 8,
-        x => 8 - x,
-    };
-    let mut ciphertext: Vec<Block> = Vec::with_capacity((text.len() + pad_len) / 8);
-
-    match *mode {
-        OpMode::ECB => {
-            let mut current_bytes = [0u8; 8];
-            for (i, byte) in text.bytes().enumerate() {
-                current_bytes[i % 8] = byte;
-
-                if i % 8 == 7 {
-                    let mut block = Block::from_bytes(&current_bytes);
-                    block.encrypt(key);
-                    ciphertext.push(block);
-                }
-            }
-
-            add_padding(&mut current_bytes, pad_len);
-
-            let mut block = Block::from_bytes(&current_bytes);
-            block.encrypt(key);
-            ciphertext.push(block);
-
-            (blocks_to_bytes(ciphertext), None)
-        },
-        OpMode::CBC => {
-            let iv = modes::random_iv();
-
-            let mut current_bytes = [0u8; 8];
-            for (i, byte) in text.bytes().enumerate() {
-                // Fill current block with bytes from the input string
-                current_bytes[i % 8] = byte;
-
-                // When a block is full, process it
-                if i % 8 == 7 {
-                    // Encrypt current block
-                    let mut block = Block::from_bytes(&current_bytes);
-
-                    // XOR with previous block (IV for the first block)
-                    match ciphertext.iter().rev().next() {
-                        Some(pb) => block ^= pb,
-                        None => block ^= &iv,
-                    };
+pub fn encrypt_str<K: Key>(text: &str, key: &K, mode: &OpMode) -> Result<Ciphertext, EncryptError> {
+    encrypt_bytes(text.as_bytes(), key, mode)
+}
 
-                    // Perform actual encryption
-                    block.encrypt(key);
+/// Encrypt a string with a given initialization vector.
+///
+/// Works like [`encrypt_str`](fn.encrypt_str.html), but uses the given
+/// IV instead of generating a random one. This is meant for IVs that
+/// come from another source, e.g. a [`CounterIv`](struct.CounterIv.html).
+/// The IV is ignored by operation modes that do not need one.
+///
+/// Never use the same IV twice with the same key.
+///
+/// # Errors
+///
+/// None of the current modes of operation can fail with a given IV,
+/// but future modes may return an `EncryptError`.
+///
+/// # Examples
+///
+/// ```
+/// use present::{try_encrypt_str_with_iv, decrypt_str, Block, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let ciphertext = try_encrypt_str_with_iv("Hello, world!", &key, &OpMode::CBC, &Block::new(42)).unwrap();
+///
+/// let decrypt_result = decrypt_str(&ciphertext, &key, &OpMode::CBC, Some(Block::new(42)));
+/// assert_eq!(decrypt_result.unwrap(), "Hello, world!");
+/// ```
+pub fn try_encrypt_str_with_iv<K: Key, M: ModeOfOperation>(text: &str, key: &K, mode: &M, init_vec: &Block) -> Result<Vec<u8>, EncryptError> {
+    try_encrypt_bytes_with_iv(text.as_bytes(), key, mode, init_vec)
+}
 
-                    // Add encrypted block to ciphertext vector
-                    ciphertext.push(block);
-                }
-            }
+/// Encrypt a string with a given initialization vector.
+///
+/// Panics if encryption fails. See
+/// [`try_encrypt_str_with_iv`](fn.try_encrypt_str_with_iv.html).
+#[deprecated(note = "use `try_encrypt_str_with_iv()`, which returns a `Result`")]
+pub fn encrypt_str_with_iv<K: Key>(text: &str, key: &K, mode: &OpMode, init_vec: &Block) -> Vec<u8> {
+    try_encrypt_str_with_iv(text, key, mode, init_vec).expect("Encryption failed")
+}
 
-            // Add padding
-            add_padding(&mut current_bytes, pad_len);
+/// Encrypt arbitrary binary data.
+///
+/// Works like [`encrypt_str`](fn.encrypt_str.html), but accepts any
+/// byte slice as plaintext, so binary payloads such as files or
+/// serialized messages can be encrypted without converting them to
+/// a string first.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_bytes, decrypt_bytes, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let payload = [0x00, 0xFF, 0x80, 0x7F, 0xC3, 0x28];
+/// let (ciphertext, iv) = encrypt_bytes(&payload, &key, &OpMode::CBC).unwrap().into_parts();
+///
+/// let decrypt_result = decrypt_bytes(&ciphertext, &key, &OpMode::CBC, iv);
+/// assert_eq!(decrypt_result.unwrap(), payload);
+/// ```
+pub fn encrypt_bytes<K: Key>(plaintext: &[u8], key: &K, mode: &OpMode) -> Result<Ciphertext, EncryptError> {
+    let iv = if mode.needs_init_vec() {
+        Some(modes::random_iv()?)
+    } else {
+        None
+    };
 
-            // Encrypt final block
-            let mut block = Block::from_bytes(&current_bytes);
-            match ciphertext.iter().rev().next() {
-                Some(pb) => block ^= pb,
-                None => block ^= &iv,
-            };
-            block.encrypt(key);
+    Ok(Ciphertext::new(encrypt_with_mode(plaintext, key, mode, iv.as_ref()), *mode, iv))
+}
 
-            // Add final block to ciphertext vector
-            ciphertext.push(block);
+/// Encrypt arbitrary binary data with a given initialization vector.
+///
+/// See [`try_encrypt_str_with_iv`](fn.try_encrypt_str_with_iv.html) and
+/// [`encrypt_bytes`](fn.encrypt_bytes.html) for details.
+pub fn try_encrypt_bytes_with_iv<K: Key, M: ModeOfOperation>(plaintext: &[u8], key: &K, mode: &M, init_vec: &Block) -> Result<Vec<u8>, EncryptError> {
+    Ok(encrypt_with_mode(plaintext, key, mode, Some(init_vec)))
+}
 
-            // Return ciphertext in bytes + IV
-            (blocks_to_bytes(ciphertext), Some(iv))
-        },
-    }
+/// Encrypt arbitrary binary data with a given initialization vector.
+///
+/// Panics if encryption fails. See
+/// [`try_encrypt_bytes_with_iv`](fn.try_encrypt_bytes_with_iv.html).
+#[deprecated(note = "use `try_encrypt_bytes_with_iv()`, which returns a `Result`")]
+pub fn encrypt_bytes_with_iv<K: Key>(plaintext: &[u8], key: &K, mode: &OpMode, init_vec: &Block) -> Vec<u8> {
+    try_encrypt_bytes_with_iv(plaintext, key, mode, init_vec).expect("Encryption failed")
 }
 
 /// Decrypt a string.
@@ -130,6 +303,11 @@ pub fn encrypt_str<K: Key>(text: &str, key: &K, mode: &OpMode) -> (Vec<u8>, Opti
 /// * `init_vec` - If the operation mode uses an initialization vector, pass `Some` with the IV
 ///   that was returned by `encrypt_str`. Otherwise, pass `None`.
 ///
+/// A missing IV is only detected at runtime. For the built-in modes,
+/// [`decrypt_str_with_mode`](fn.decrypt_str_with_mode.html) takes the IV
+/// as part of a [`Mode`](enum.Mode.html) instead, so it cannot be
+/// missing. This function remains for custom modes of operation.
+///
 /// # Errors
 ///
 /// Returns `Err` with a `DecryptError` if an error occurred during decryption. See the
@@ -141,70 +319,277 @@ pub fn encrypt_str<K: Key>(text: &str, key: &K, mode: &OpMode) -> (Vec<u8>, Opti
 /// use present::{encrypt_str, decrypt_str, Key80Bit, OpMode};
 /// let key = Key80Bit::new([0xFF; 10]);
 /// let op_mode = OpMode::CBC;
-/// let (ciphertext, iv) = encrypt_str("Hello, world!", &key, &op_mode);
+/// let (ciphertext, iv) = encrypt_str("Hello, world!", &key, &op_mode).unwrap().into_parts();
 ///
 /// let decrypt_result = decrypt_str(&ciphertext, &key, &op_mode, iv);
 /// assert_eq!(decrypt_result.unwrap(), "Hello, world!");
 /// ```
-pub fn decrypt_str<K: Key>(ciphertext: &[u8], key: &K, mode: &OpMode, init_vec: Option<Block>) -> Result<String, DecryptError> {
-    // Check that ciphertext is at least one block
-    if ciphertext.len() < 8 {
-        return Err(DecryptError::CiphertextTooShort(ciphertext.len()));
-    }
+pub fn decrypt_str<K: Key, M: ModeOfOperation>(ciphertext: &[u8], key: &K, mode: &M, init_vec: Option<Block>) -> Result<String, DecryptError> {
+    let plain_bytes = decrypt_bytes(ciphertext, key, mode, init_vec)?;
+    String::from_utf8(plain_bytes).map_err(DecryptError::from)
+}
+
+/// Decrypt a string, replacing invalid UTF-8.
+///
+/// Works like [`decrypt_str`](fn.decrypt_str.html), but decrypts to
+/// bytes first and replaces any invalid UTF-8 sequences with
+/// `U+FFFD REPLACEMENT CHARACTER` instead of failing, so partially
+/// corrupted plaintext can still be inspected.
+///
+/// # Errors
+///
+/// Returns `Err` with a `DecryptError` if an error occurred during decryption, e.g. because
+/// the padding is invalid. Invalid UTF-8 is never reported as an error.
+///
+/// # Examples
+///
+/// ```
+/// use present::{decrypt_str_lossy, encrypt_bytes, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let (ciphertext, iv) = encrypt_bytes(b"Hello, \xF0\x90world!", &key, &OpMode::CBC).unwrap().into_parts();
+///
+/// let decrypt_result = decrypt_str_lossy(&ciphertext, &key, &OpMode::CBC, iv);
+/// assert_eq!(decrypt_result.unwrap(), "Hello, \u{FFFD}world!");
+/// ```
+pub fn decrypt_str_lossy<K: Key, M: ModeOfOperation>(ciphertext: &[u8], key: &K, mode: &M, init_vec: Option<Block>) -> Result<String, DecryptError> {
+    let plain_bytes = decrypt_bytes(ciphertext, key, mode, init_vec)?;
+    Ok(String::from_utf8_lossy(&plain_bytes).into_owned())
+}
+
+/// Decrypt arbitrary binary data.
+///
+/// Works like [`decrypt_str`](fn.decrypt_str.html), but returns the
+/// decrypted bytes without requiring them to be valid UTF-8. See
+/// [`decrypt_bytes_with_mode`](fn.decrypt_bytes_with_mode.html) for the
+/// variant taking a [`Mode`](enum.Mode.html).
+///
+/// # Errors
+///
+/// Returns `Err` with a `DecryptError` if an error occurred during decryption. See the
+/// [documentation of `DecryptError`](enum.DecryptError.html) for details.
+pub fn decrypt_bytes<K: Key, M: ModeOfOperation>(ciphertext: &[u8], key: &K, mode: &M, init_vec: Option<Block>) -> Result<Vec<u8>, DecryptError> {
+    decrypt_bytes_with_padding(ciphertext, key, mode, init_vec, &Pkcs7)
+}
 
-    // Check that ciphertext length aligns with block size
-    if ciphertext.len() % 8 != 0 {
-        return Err(DecryptError::CiphertextNotAligned(ciphertext.len()));
+/// Encrypt a string in a mode of operation with its parameters.
+///
+/// See [`encrypt_bytes_with_mode`](fn.encrypt_bytes_with_mode.html).
+///
+/// # Errors
+///
+/// None of the current modes of operation can fail with a given IV,
+/// but future modes may return an `EncryptError`.
+pub fn encrypt_str_with_mode<K: Key>(text: &str, key: &K, mode: &Mode) -> Result<Ciphertext, EncryptError> {
+    encrypt_bytes_with_mode(text.as_bytes(), key, mode)
+}
+
+/// Encrypt arbitrary binary data in a mode of operation with its
+/// parameters.
+///
+/// Works like [`try_encrypt_bytes_with_iv`](fn.try_encrypt_bytes_with_iv.html),
+/// but the IV is part of the [`Mode`](enum.Mode.html), so it cannot be
+/// missing or given to ECB.
+///
+/// # Errors
+///
+/// None of the current modes of operation can fail with a given IV,
+/// but future modes may return an `EncryptError`.
+pub fn encrypt_bytes_with_mode<K: Key>(plaintext: &[u8], key: &K, mode: &Mode) -> Result<Ciphertext, EncryptError> {
+    let op_mode = mode.op_mode();
+    let init_vec = mode.init_vec().map(Iv::to_block);
+    Ok(Ciphertext::new(encrypt_with_mode(plaintext, key, &op_mode, init_vec.as_ref()), op_mode, init_vec))
+}
+
+/// Decrypt a string in a mode of operation with its parameters.
+///
+/// Works like [`decrypt_str`](fn.decrypt_str.html), but takes the IV
+/// as part of the [`Mode`](enum.Mode.html), so it cannot be missing.
+///
+/// # Errors
+///
+/// Returns `Err` with a `DecryptError` if an error occurred during
+/// decryption or the plaintext is not valid UTF-8.
+pub fn decrypt_str_with_mode<K: Key>(ciphertext: &[u8], key: &K, mode: &Mode) -> Result<String, DecryptError> {
+    let plain_bytes = decrypt_bytes_with_mode(ciphertext, key, mode)?;
+    String::from_utf8(plain_bytes).map_err(DecryptError::from)
+}
+
+/// Decrypt arbitrary binary data in a mode of operation with its
+/// parameters. See [`decrypt_str_with_mode`](fn.decrypt_str_with_mode.html).
+///
+/// # Errors
+///
+/// Returns `Err` with a `DecryptError` if an error occurred during decryption.
+pub fn decrypt_bytes_with_mode<K: Key>(ciphertext: &[u8], key: &K, mode: &Mode) -> Result<Vec<u8>, DecryptError> {
+    decrypt_bytes(ciphertext, key, &mode.op_mode(), mode.init_vec().map(Iv::to_block))
+}
+
+/// Encrypt arbitrary binary data with a given padding scheme.
+///
+/// Works like [`encrypt_bytes`](fn.encrypt_bytes.html), but pads the
+/// plaintext with the given [`PaddingScheme`](padding/trait.PaddingScheme.html)
+/// instead of PKCS#7. The padding is ignored by CFB, which does not
+/// pad the plaintext.
+///
+/// # Errors
+///
+/// Returns `EncryptError::Rng` if an IV is needed and the random
+/// number generator of the operating system fails.
+///
+/// # Examples
+///
+/// ```
+/// use present::{decrypt_bytes_with_padding, encrypt_bytes_with_padding, Key80Bit, OpMode};
+/// use present::padding::Iso7816;
+///
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let (ciphertext, iv) = encrypt_bytes_with_padding(b"Hello, world!", &key, &OpMode::CBC, &Iso7816).unwrap().into_parts();
+///
+/// let decrypt_result = decrypt_bytes_with_padding(&ciphertext, &key, &OpMode::CBC, iv, &Iso7816);
+/// assert_eq!(decrypt_result.unwrap(), b"Hello, world!");
+/// ```
+pub fn encrypt_bytes_with_padding<K: Key, P: PaddingScheme>(plaintext: &[u8], key: &K, mode: &OpMode, padding: &P) -> Result<Ciphertext, EncryptError> {
+    let iv = if mode.needs_init_vec() {
+        Some(modes::random_iv()?)
+    } else {
+        None
+    };
+
+    Ok(Ciphertext::new(encrypt_with_padding(plaintext, key, mode, iv.as_ref(), padding), *mode, iv))
+}
+
+/// Decrypt arbitrary binary data that was padded with a given padding
+/// scheme.
+///
+/// Works like [`decrypt_bytes`](fn.decrypt_bytes.html), but checks and
+/// removes the padding with the given
+/// [`PaddingScheme`](padding/trait.PaddingScheme.html) instead of PKCS#7.
+///
+/// # Errors
+///
+/// Returns `Err` with a `DecryptError` if an error occurred during decryption. See the
+/// [documentation of `DecryptError`](enum.DecryptError.html) for details.
+pub fn decrypt_bytes_with_padding<K: Key, M: ModeOfOperation, P: PaddingScheme>(ciphertext: &[u8], key: &K, mode: &M, init_vec: Option<Block>, padding: &P) -> Result<Vec<u8>, DecryptError> {
+    let buffer = decrypt_raw(ciphertext, key, mode, init_vec)?;
+    if mode.is_padded() {
+        remove_padding(buffer, padding)
+    } else {
+        Ok(buffer)
     }
+}
 
-    let mut plain_bytes: Vec<u8> = Vec::with_capacity(ciphertext.len());
+/// Decrypt the ciphertext, leaving the padding in place.
+fn decrypt_raw<K: Key, M: ModeOfOperation>(ciphertext: &[u8], key: &K, mode: &M, init_vec: Option<Block>) -> Result<Vec<u8>, DecryptError> {
+    check_ciphertext_len(ciphertext, mode)?;
+    report_mode(mode);
 
-    match *mode {
-        OpMode::ECB => {
-            let mut current_bytes = [0u8; 8];
-            for (i, byte) in ciphertext.iter().enumerate() {
-                current_bytes[i % 8] = *byte;
+    let mut feedback = match init_vec {
+        _ if !mode.needs_init_vec() => Block::new(0),
+        Some(x) => x,
+        None => return Err(DecryptError::InitVecMissing),
+    };
 
-                if i % 8 == 7 {
-                    let mut block = Block::from_bytes(&current_bytes);
-                    block.decrypt(key);
-                    plain_bytes.extend(block.to_bytes().iter());
-                }
-            }
+    // Decrypt a copy of the ciphertext in place, so the plaintext
+    // takes up no more memory than the ciphertext
+    let cipher = Expanded::new(key);
+    let mut buffer = ciphertext.to_vec();
+    mode.decrypt_in_place(&mut buffer, &cipher, &mut feedback);
+    Ok(buffer)
+}
 
-            let len = plain_bytes.len();
-            let to_remove = check_padding(&plain_bytes[(len - 8)..])?;
-            plain_bytes.truncate(len - to_remove);
-
-            String::from_utf8(plain_bytes).map_err(|e| DecryptError::from(e))
-        },
-        OpMode::CBC => {
-            let mut last_block = match init_vec {
-                Some(x) => x,
-                None => return Err(DecryptError::InitVecMissing),
-            };
-
-            let mut current_bytes = [0u8; 8];
-            for(i, byte) in ciphertext.iter().enumerate() {
-                current_bytes[i % 8] = *byte;
-
-                if i % 8 == 7 {
-                    let mut block = Block::from_bytes(&current_bytes);
-                    block.decrypt(key);
-                    block ^= &last_block;
-                    plain_bytes.extend(block.to_bytes().iter());
-
-                    last_block = Block::from_bytes(&current_bytes);
-                }
-            }
+fn encrypt_with_mode<K: Key, M: ModeOfOperation>(plaintext: &[u8], key: &K, mode: &M, init_vec: Option<&Block>) -> Vec<u8> {
+    encrypt_with_padding(plaintext, key, mode, init_vec, &Pkcs7)
+}
+
+fn encrypt_with_padding<K: Key, M: ModeOfOperation, P: PaddingScheme>(plaintext: &[u8], key: &K, mode: &M, init_vec: Option<&Block>, padding: &P) -> Vec<u8> {
+    // Copy the plaintext into the output buffer, which is then
+    // encrypted in place
+    let mut buffer = if mode.is_padded() {
+        padded(plaintext, padding)
+    } else {
+        plaintext.to_vec()
+    };
+
+    let mut feedback = if mode.needs_init_vec() {
+        let iv = init_vec.expect("Logic error! This mode requires an initialization vector!");
+        Block::new(iv.get_state())
+    } else {
+        Block::new(0)
+    };
+
+    let cipher = Expanded::new(key);
+    report_encryption(mode, &cipher, &feedback, buffer.len().div_ceil(8));
+    mode.encrypt_in_place(&mut buffer, &cipher, &mut feedback);
+    buffer
+}
 
-            let len = plain_bytes.len();
-            let to_remove = check_padding(&plain_bytes[(len - 8)..])?;
-            plain_bytes.truncate(len - to_remove);
+/// Copy the plaintext and pad it to a multiple of the block size.
+fn padded<P: PaddingScheme>(plaintext: &[u8], padding: &P) -> Vec<u8> {
+    let aligned = plaintext.len() - plaintext.len() % 8;
+    let mut buffer = Vec::with_capacity(aligned + 8);
+    buffer.extend_from_slice(&plaintext[..aligned]);
+    buffer.extend_from_slice(&final_block_with(plaintext, padding));
+    buffer
+}
+
+/// Build the last block of the padded plaintext, which holds the
+/// remaining bytes of the plaintext and the padding.
+fn final_block(plaintext: &[u8]) -> [u8; 8] {
+    final_block_with(plaintext, &Pkcs7)
+}
 
-            String::from_utf8(plain_bytes).map_err(|e| DecryptError::from(e))
-        },
+fn final_block_with<P: PaddingScheme>(plaintext: &[u8], padding: &P) -> [u8; 8] {
+    let remainder = plaintext.len() % 8;
+    let mut current_bytes = [0u8; 8];
+    current_bytes[..remainder].copy_from_slice(&plaintext[(plaintext.len() - remainder)..]);
+    padding.pad(&mut current_bytes, remainder);
+    current_bytes
+}
+
+/// Check that the ciphertext length is valid for the mode.
+fn check_ciphertext_len<M: ModeOfOperation>(ciphertext: &[u8], mode: &M) -> Result<(), DecryptError> {
+    if mode.is_padded() {
+        // Check that ciphertext is at least one block
+        if ciphertext.len() < 8 {
+            return Err(DecryptError::CiphertextTooShort { len: ciphertext.len(), min_len: 8 });
+        }
+
+        // Check that ciphertext length aligns with block size
+        if !ciphertext.len().is_multiple_of(8) {
+            return Err(DecryptError::CiphertextNotAligned { len: ciphertext.len(), block_size: 8 });
+        }
     }
+    Ok(())
+}
+
+/// Check and remove the padding of decrypted data.
+fn remove_padding<P: PaddingScheme>(mut plain_bytes: Vec<u8>, padding: &P) -> Result<Vec<u8>, DecryptError> {
+    let len = plain_bytes.len();
+    let mut final_block = [0u8; 8];
+    final_block.copy_from_slice(&plain_bytes[(len - 8)..]);
+
+    // Don't trust custom schemes to stay within the final block
+    let to_remove = match padding.unpad(&final_block) {
+        Some(pad) if (1..=8).contains(&pad) => pad,
+        _ => return Err(DecryptError::InvalidPadding { block: len / 8 - 1 }),
+    };
+    plain_bytes.truncate(len - to_remove);
+    Ok(plain_bytes)
+}
+
+/// Notify the security policy about the mode used.
+fn report_mode<M: ModeOfOperation>(mode: &M) {
+    if mode.is_insecure() {
+        policy::notify(SecurityEvent::EcbSelected);
+    }
+}
+
+/// Notify the security policy about the mode, the IV and the amount of
+/// data used for encryption under the expanded key.
+fn report_encryption<M: ModeOfOperation>(mode: &M, cipher: &Expanded, init_vec: &Block, blocks: usize) {
+    report_mode(mode);
+    let init_vec = if mode.needs_init_vec() { Some(init_vec.get_state()) } else { None };
+    policy::record_encryption(policy::fingerprint(cipher), init_vec, blocks as u64);
 }
 
 fn add_padding(current_bytes: &mut [u8; 8], pad_len: usize) {
@@ -219,39 +604,31 @@ fn add_padding(current_bytes: &mut [u8; 8], pad_len: usize) {
     }
 }
 
-fn check_padding(final_block: &[u8]) -> Result<usize, DecryptError> {
+/// Check the padding of the final block, which has the given index in
+/// the decrypted data, and return the number of padding bytes.
+fn check_padding(final_block: &[u8], block: usize) -> Result<usize, DecryptError> {
     if final_block.len() != 8 {
         panic!("Logic error! Received {} element slice for padding check, expected 8 elements!", final_block.len());
     }
 
     let pad = final_block[7];
-    if pad > 8 {
-        return Err(DecryptError::InvalidPadding);
+    if pad == 0 || pad > 8 {
+        return Err(DecryptError::InvalidPadding { block });
     }
 
     for byte in final_block.iter().rev().take(pad as usize) {
         if *byte != pad {
-            return Err(DecryptError::InvalidPadding);
+            return Err(DecryptError::InvalidPadding { block });
         }
     }
 
     Ok(pad as usize)
 }
 
-fn blocks_to_bytes(blocks: Vec<Block>) -> Vec<u8> {
-    let mut ret = Vec::with_capacity(blocks.len() * 8);
-    for block in &blocks {
-        for byte in &(block.to_bytes()) {
-            ret.push(*byte);
-        }
-    }
-    ret
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::{add_padding, blocks_to_bytes, check_padding};
+    use super::{add_padding, check_padding};
 
     #[test]
     fn test_add_padding_to_block() {
@@ -276,46 +653,98 @@ mod tests {
     #[test]
     fn test_check_padding_returns_correct_values() {
         let bytes = [0x4E, 0xDD, 0xA0, 0x34, 0x04, 0x04, 0x04, 0x04];
-        assert_eq!(check_padding(&bytes).unwrap(), 4);
+        assert_eq!(check_padding(&bytes, 0).unwrap(), 4);
 
         let bytes = [0x4E, 0xDD, 0xA0, 0x34, 0x04, 0x03, 0x03, 0x03];
-        assert_eq!(check_padding(&bytes).unwrap(), 3);
+        assert_eq!(check_padding(&bytes, 0).unwrap(), 3);
 
         let bytes = [0x4E, 0xDD, 0xA0, 0x34, 0xBC, 0xE5, 0xA2, 0x01];
-        assert_eq!(check_padding(&bytes).unwrap(), 1);
+        assert_eq!(check_padding(&bytes, 0).unwrap(), 1);
 
         let bytes = [0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08];
-        assert_eq!(check_padding(&bytes).unwrap(), 8);
+        assert_eq!(check_padding(&bytes, 0).unwrap(), 8);
     }
 
     #[test]
     #[should_panic]
     fn test_check_padding_rejects_invalid_slices() {
         let bytes = [0x34, 0x14];
-        check_padding(&bytes).unwrap();
+        check_padding(&bytes, 0).unwrap();
     }
 
     #[test]
     #[should_panic]
     fn test_check_padding_rejects_invalid_padding() {
         let bytes = [0x09, 0x09, 0x09, 0x09, 0x09, 0x09, 0x09, 0x09];
-        check_padding(&bytes).unwrap();
+        check_padding(&bytes, 0).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_check_padding_rejects_zero_padding() {
+        let bytes = [0x35, 0xD2, 0x39, 0xE5, 0xAA, 0x04, 0x03, 0x00];
+        check_padding(&bytes, 0).unwrap();
+    }
+
+    #[test]
+    fn test_remove_padding_rejects_out_of_range_lengths() {
+        struct Claims(usize);
+
+        impl PaddingScheme for Claims {
+            fn pad(&self, _block: &mut [u8; 8], _len: usize) {}
+
+            fn unpad(&self, _block: &[u8; 8]) -> Option<usize> {
+                Some(self.0)
+            }
+        }
+
+        assert_eq!(remove_padding(vec![0u8; 16], &Claims(8)).unwrap(), vec![0u8; 8]);
+        assert!(matches!(remove_padding(vec![0u8; 16], &Claims(0)), Err(DecryptError::InvalidPadding { block: 1 })));
+        assert!(matches!(remove_padding(vec![0u8; 16], &Claims(9)), Err(DecryptError::InvalidPadding { block: 1 })));
+        assert!(matches!(remove_padding(vec![0u8; 16], &Claims(17)), Err(DecryptError::InvalidPadding { block: 1 })));
     }
 
     #[test]
     #[should_panic]
     fn test_check_padding_rejects_wrong_padding() {
         let bytes = [0x35, 0xD2, 0x39, 0xE5, 0xAA, 0x04, 0x03, 0x03];
-        check_padding(&bytes).unwrap();
+        check_padding(&bytes, 0).unwrap();
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let key = Key128Bit::new([0x3C; 16]);
+        let payload: Vec<u8> = (0..=255u8).rev().collect();
+
+        for mode in &modes::all_modes() {
+            let (ciphertext, iv) = encrypt_bytes(&payload, &key, mode).unwrap().into_parts();
+            assert_eq!(ciphertext.len(), if mode.is_padded() { 264 } else { 256 });
+            assert_eq!(decrypt_bytes(&ciphertext, &key, mode, iv).unwrap(), payload);
+
+            let (ciphertext, iv) = encrypt_bytes(&[], &key, mode).unwrap().into_parts();
+            assert_eq!(decrypt_bytes(&ciphertext, &key, mode, iv).unwrap(), Vec::<u8>::new());
+        }
+    }
+
+    #[test]
+    fn test_errors_report_context() {
+        let key = Key80Bit::new([0x3C; 10]);
+        let mut ciphertext = try_encrypt_bytes_with_iv(&[0x11; 20], &key, &OpMode::CBC, &Block::new(0)).unwrap();
+        ciphertext[23] ^= 0xFF;
+        assert!(matches!(decrypt_bytes(&ciphertext, &key, &OpMode::CBC, Some(Block::new(0))), Err(DecryptError::InvalidPadding { block: 2 })));
+        assert!(matches!(decrypt_bytes(&ciphertext[..5], &key, &OpMode::CBC, Some(Block::new(0))),
+                         Err(DecryptError::CiphertextTooShort { len: 5, min_len: 8 })));
     }
 
     #[test]
-    fn test_blocks_to_bytes() {
-        let blocks = vec![Block::new(0x0123456789ABCDEF_u64), Block::new(0xFEDCBA9876543210_u64)];
-        let bytes = blocks_to_bytes(blocks);
-        assert_eq!(bytes[0], 0x01);
-        assert_eq!(bytes[5], 0xAB);
-        assert_eq!(bytes[8], 0xFE);
-        assert_eq!(bytes[15], 0x10);
+    #[allow(deprecated)]
+    fn test_deprecated_shims_match() {
+        let key = Key80Bit::new([0x3C; 10]);
+        let iv = Block::new(0x0123456789ABCDEF);
+
+        assert_eq!(encrypt_str_with_iv("shim", &key, &OpMode::PCBC, &iv),
+                   try_encrypt_str_with_iv("shim", &key, &OpMode::PCBC, &iv).unwrap());
+        assert_eq!(encrypt_bytes_with_iv(b"shim", &key, &OpMode::CFB, &iv),
+                   try_encrypt_bytes_with_iv(b"shim", &key, &OpMode::CFB, &iv).unwrap());
     }
 }
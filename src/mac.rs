@@ -0,0 +1,212 @@
+use block::Block;
+use context::Expanded;
+use keys::{Key, RoundKeys};
+use tag::tags_equal;
+#[cfg(feature = "zeroize")]
+use keys::wipe;
+
+/// Reduction constant for doubling in GF(2^64), from the polynomial
+/// x^64 + x^4 + x^3 + x + 1.
+const RB: u64 = 0x1B;
+
+/// CMAC (OMAC1) message authentication code using PRESENT.
+///
+/// CMAC turns a block cipher into a MAC that is secure for messages
+/// of any length. With PRESENT's 64-bit blocks, the resulting tag is
+/// 64 bits long. Data can be fed in arbitrary chunks with
+/// [`update()`](#method.update).
+///
+/// # Examples
+///
+/// ```
+/// use present::{cmac, Cmac, Key128Bit};
+/// let key = Key128Bit::new([0x2B; 16]);
+///
+/// let mut mac = Cmac::new(&key);
+/// mac.update(b"Hello, ");
+/// mac.update(b"world!");
+/// assert_eq!(mac.finalize(), cmac(&key, b"Hello, world!"));
+/// ```
+#[derive(Clone)]
+pub struct Cmac {
+    cipher: Expanded,
+    k1: u64,
+    k2: u64,
+    state: u64,
+    buffer: [u8; 8],
+    buffered: usize,
+}
+
+impl Cmac {
+    /// Constructs a new CMAC computation with the given key.
+    pub fn new<K: Key>(key: &K) -> Self {
+        Cmac::with_cipher(Expanded::new(key))
+    }
+
+    /// Constructs a new CMAC computation from precomputed round keys.
+    pub fn from_round_keys(round_keys: RoundKeys) -> Self {
+        Cmac::with_cipher(Expanded::from_round_keys(round_keys))
+    }
+
+    /// Constructs a new CMAC computation with an expanded key.
+    pub(crate) fn with_cipher(cipher: Expanded) -> Self {
+        let mut l = Block::new(0);
+        cipher.encrypt_block(&mut l);
+        let k1 = double(l.get_state());
+        let k2 = double(k1);
+
+        Cmac { cipher, k1, k2, state: 0, buffer: [0u8; 8], buffered: 0 }
+    }
+
+    /// Feeds more data into the MAC computation.
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data.iter() {
+            // The buffered block is only processed once more data
+            // arrives, since the final block needs special treatment
+            if self.buffered == 8 {
+                self.process_buffer();
+            }
+            self.buffer[self.buffered] = *byte;
+            self.buffered += 1;
+        }
+    }
+
+    /// Finishes the computation and returns the tag.
+    pub fn finalize(mut self) -> [u8; 8] {
+        let last = if self.buffered == 8 {
+            u64::from_be_bytes(self.buffer) ^ self.k1
+        } else {
+            // Pad with a single one bit followed by zeros
+            self.buffer[self.buffered] = 0x80;
+            for byte in self.buffer.iter_mut().skip(self.buffered + 1) {
+                *byte = 0;
+            }
+            u64::from_be_bytes(self.buffer) ^ self.k2
+        };
+
+        let mut tag = Block::new(self.state ^ last);
+        self.cipher.encrypt_block(&mut tag);
+        tag.to_bytes()
+    }
+
+    /// Finishes the computation and checks the given tag in constant
+    /// time. See [`tags_equal`](fn.tags_equal.html).
+    pub fn verify(self, tag: &[u8]) -> bool {
+        tags_equal(&self.finalize(), tag)
+    }
+
+    /// Resets the computation, keeping the key.
+    pub fn reset(&mut self) {
+        self.state = 0;
+        self.buffered = 0;
+    }
+
+    fn process_buffer(&mut self) {
+        let mut block = Block::new(self.state ^ u64::from_be_bytes(self.buffer));
+        self.cipher.encrypt_block(&mut block);
+        self.state = block.get_state();
+        self.buffered = 0;
+    }
+}
+
+/// Computes the CMAC of the given data in a single call.
+///
+/// See [`Cmac`](struct.Cmac.html) for details.
+pub fn cmac<K: Key>(key: &K, data: &[u8]) -> [u8; 8] {
+    let mut mac = Cmac::new(key);
+    mac.update(data);
+    mac.finalize()
+}
+
+/// Multiplies a value by x in GF(2^64).
+pub fn double(value: u64) -> u64 {
+    let carry = value >> 63;
+    (value << 1) ^ (carry.wrapping_neg() & RB)
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Cmac {
+    fn drop(&mut self) {
+        // The cipher wipes itself
+        wipe(&mut self.k1);
+        wipe(&mut self.k2);
+        wipe(&mut self.state);
+        wipe(&mut self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::Block;
+    use keys::Key80Bit;
+
+    /// Straightforward CMAC implementation following the specification.
+    fn reference_cmac(key: &Key80Bit, data: &[u8]) -> [u8; 8] {
+        let mut l = Block::new(0);
+        l.encrypt(key);
+        let k1 = double(l.get_state());
+        let k2 = double(k1);
+
+        let mut blocks: Vec<Vec<u8>> = data.chunks(8).map(|c| c.to_vec()).collect();
+        let complete = match blocks.last() {
+            Some(b) => b.len() == 8,
+            None => false,
+        };
+        if !complete {
+            if blocks.is_empty() {
+                blocks.push(Vec::new());
+            }
+            let last = blocks.last_mut().unwrap();
+            last.push(0x80);
+            last.resize(8, 0);
+        }
+
+        let mut state = 0u64;
+        let count = blocks.len();
+        for (i, b) in blocks.iter().enumerate() {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(b);
+            let mut value = state ^ u64::from_be_bytes(bytes);
+            if i == count - 1 {
+                value ^= if complete { k1 } else { k2 };
+            }
+            let mut block = Block::new(value);
+            block.encrypt(key);
+            state = block.get_state();
+        }
+        state.to_be_bytes()
+    }
+
+    #[test]
+    fn test_double() {
+        assert_eq!(double(1), 2);
+        assert_eq!(double(0x8000000000000000), 0x1B);
+        assert_eq!(double(0xC000000000000001), 0x8000000000000019);
+    }
+
+    #[test]
+    fn test_cmac_matches_reference() {
+        let key = Key80Bit::new([0xA, 0xC0, 0xA6, 0xE7, 0x63, 0x26, 0xBC, 0x7E, 0x82, 0x80]);
+        let data: Vec<u8> = (0..40u8).collect();
+        for len in 0..data.len() {
+            assert_eq!(cmac(&key, &data[..len]), reference_cmac(&key, &data[..len]));
+        }
+    }
+
+    #[test]
+    fn test_cmac_chunking_and_reset() {
+        let key = Key80Bit::new([0x55; 10]);
+        let data = b"a message that spans several PRESENT blocks";
+        let mut mac = Cmac::new(&key);
+        for chunk in data.chunks(3) {
+            mac.update(chunk);
+        }
+        let tag = mac.clone().finalize();
+        assert_eq!(tag, cmac(&key, data));
+
+        mac.reset();
+        mac.update(b"other");
+        assert_eq!(mac.finalize(), cmac(&key, b"other"));
+    }
+}
@@ -0,0 +1,322 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::RngCore;
+
+use errors::RngError;
+use keys::{wipe, RoundKey, RoundKeys};
+use pbox;
+use profile;
+use sbox;
+
+/// The lowest bit of every nibble, for spreading a nibble mask over
+/// the whole state.
+const NIBBLE_LOW_BITS: u64 = 0x1111111111111111;
+
+/// Increment of the mask generator, the golden ratio as in SplitMix64.
+const GOLDEN_GAMMA: u64 = 0x9E3779B97F4A7C15;
+
+/// A key schedule protected by first-order boolean masking.
+///
+/// Every round key is stored as two shares whose XOR is the actual
+/// round key, and the key schedule that produces them never handles
+/// an unmasked key register. During encryption, the state is masked
+/// with fresh random nibble masks for every block, and the S-Box is
+/// evaluated with a table that is recomputed for these masks, so no
+/// intermediate value depends on the key or the data alone.
+#[derive(Clone)]
+pub struct MaskedSchedule {
+    keys: [u64; 32],
+    masks: [u64; 32],
+    /// Generator for the per-block masks.
+    generator: SplitMix,
+}
+
+impl MaskedSchedule {
+    /// Runs the masked key schedule for an 80-bit key.
+    pub fn new_80bit<R: RngCore + ?Sized>(value: &[u8; 10], rng: &mut R) -> Result<Self, RngError> {
+        let mut bytes = [0u8; 16];
+        bytes[6..].copy_from_slice(value);
+        Self::new(u128::from_be_bytes(bytes), rng, &Register80)
+    }
+
+    /// Runs the masked key schedule for a 128-bit key.
+    pub fn new_128bit<R: RngCore + ?Sized>(value: &[u8; 16], rng: &mut R) -> Result<Self, RngError> {
+        Self::new(u128::from_be_bytes(*value), rng, &Register128)
+    }
+
+    fn new<R: RngCore + ?Sized, G: KeyRegister>(key: u128, rng: &mut R, register: &G) -> Result<Self, RngError> {
+        let mut mask_bytes = [0u8; 16];
+        rng.try_fill_bytes(&mut mask_bytes)?;
+
+        // The key register is only ever held as two shares
+        let mut mask = register.truncate(u128::from_be_bytes(mask_bytes));
+        let mut data = key ^ mask;
+
+        let mut schedule = MaskedSchedule { keys: [0; 32], masks: [0; 32], generator: SplitMix::from_rng(rng)? };
+        for round in 1u8..32u8 {
+            schedule.keys[(round - 1) as usize] = register.round_key(data);
+            schedule.masks[(round - 1) as usize] = register.round_key(mask);
+
+            data = register.rotate(data);
+            mask = register.rotate(mask);
+
+            // Substitute the top nibbles with tables for fresh output masks
+            for &shift in register.sbox_shifts() {
+                let input_mask = ((mask >> shift) & 0xF) as u8;
+                let output_mask = (schedule.generator.next() & 0xF) as u8;
+                let table = masked_table(sbox::apply_enc, input_mask, output_mask);
+                let nibble = table[((data >> shift) & 0xF) as usize];
+
+                data = (data & !(0xF << shift)) | ((nibble as u128) << shift);
+                mask = (mask & !(0xF << shift)) | ((output_mask as u128) << shift);
+            }
+
+            data ^= register.round_counter(round);
+        }
+        schedule.keys[31] = register.round_key(data);
+        schedule.masks[31] = register.round_key(mask);
+        wipe(&mut data);
+        wipe(&mut mask);
+        wipe(&mut mask_bytes);
+
+        Ok(schedule)
+    }
+
+    /// Encrypts a state.
+    pub fn encrypt(&self, state: u64) -> u64 {
+        profile::count_block();
+
+        let (input_mask, output_mask) = self.next_masks();
+        let table = masked_table(sbox::apply_enc, input_mask, output_mask);
+        let input_mask = spread(input_mask);
+        let output_mask = spread(output_mask);
+        // Changes the mask after the permutation back to the input mask
+        let remask = pbox::apply_enc(output_mask) ^ input_mask;
+
+        let mut state = state ^ input_mask;
+        for round in 0..31 {
+            state ^= self.keys[round];
+            state ^= self.masks[round];
+            state = substitute(state, &table);
+            state = pbox::apply_enc(state);
+            state ^= remask;
+        }
+        state ^= self.keys[31];
+        state ^= self.masks[31];
+        state ^ input_mask
+    }
+
+    /// Decrypts a state.
+    pub fn decrypt(&self, state: u64) -> u64 {
+        profile::count_block();
+
+        let (input_mask, output_mask) = self.next_masks();
+        let table = masked_table(sbox::apply_dec, input_mask, output_mask);
+        let input_mask = spread(input_mask);
+        let output_mask = spread(output_mask);
+        let permutation_remask = pbox::apply_dec(input_mask) ^ input_mask;
+        let substitution_remask = output_mask ^ input_mask;
+
+        let mut state = state ^ input_mask;
+        for round in (1..32).rev() {
+            state ^= self.keys[round];
+            state ^= self.masks[round];
+            state = pbox::apply_dec(state);
+            state ^= permutation_remask;
+            state = substitute(state, &table);
+            state ^= substitution_remask;
+        }
+        state ^= self.keys[0];
+        state ^= self.masks[0];
+        state ^ input_mask
+    }
+
+    /// Combines the shares into the plain round keys.
+    pub fn round_keys(&self) -> RoundKeys {
+        let mut round_keys = RoundKeys::new([RoundKey { value: 0 }; 32]);
+        for (round_key, (key, mask)) in round_keys.iter_mut().zip(self.keys.iter().zip(self.masks.iter())) {
+            round_key.value = key ^ mask;
+        }
+        round_keys
+    }
+
+    /// Returns an input and an output mask for the S-Box.
+    fn next_masks(&self) -> (u8, u8) {
+        let value = self.generator.next();
+        ((value & 0xF) as u8, ((value >> 4) & 0xF) as u8)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for MaskedSchedule {
+    fn drop(&mut self) {
+        wipe(&mut self.keys);
+        wipe(&mut self.masks);
+    }
+}
+
+/// A SplitMix64 generator for the randomness of side-channel
+/// countermeasures, seeded once from the caller's RNG.
+///
+/// It only needs to be unpredictable to an attacker who measures the
+/// device, not cryptographically strong, and it is far cheaper than
+/// asking the RNG for every block. The state is atomic, so ciphers
+/// using it stay `Sync`.
+pub struct SplitMix {
+    state: AtomicU64,
+}
+
+impl SplitMix {
+    /// Constructs a new generator with the given seed.
+    pub fn new(seed: u64) -> Self {
+        SplitMix { state: AtomicU64::new(seed) }
+    }
+
+    /// Seeds a new generator from the given RNG.
+    pub fn from_rng<R: RngCore + ?Sized>(rng: &mut R) -> Result<Self, RngError> {
+        let mut seed = [0u8; 8];
+        rng.try_fill_bytes(&mut seed)?;
+        Ok(SplitMix::new(u64::from_be_bytes(seed)))
+    }
+
+    /// Returns the next value, advancing the shared state.
+    pub fn next(&self) -> u64 {
+        let mut z = self.state.fetch_add(GOLDEN_GAMMA, Ordering::Relaxed).wrapping_add(GOLDEN_GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Clone for SplitMix {
+    /// Seeds the clone from this generator, so the two do not produce
+    /// the same values.
+    fn clone(&self) -> Self {
+        SplitMix::new(self.next())
+    }
+}
+
+/// The parts of the key schedule that differ between the key sizes.
+/// The key register is held in the low bits of a `u128`.
+trait KeyRegister {
+    fn truncate(&self, register: u128) -> u128;
+    fn round_key(&self, register: u128) -> u64;
+    fn rotate(&self, register: u128) -> u128;
+    fn sbox_shifts(&self) -> &'static [u32];
+    fn round_counter(&self, round: u8) -> u128;
+}
+
+struct Register80;
+
+impl KeyRegister for Register80 {
+    fn truncate(&self, register: u128) -> u128 {
+        register & ((1 << 80) - 1)
+    }
+
+    fn round_key(&self, register: u128) -> u64 {
+        (register >> 16) as u64
+    }
+
+    fn rotate(&self, register: u128) -> u128 {
+        self.truncate((register << 61) | (register >> 19))
+    }
+
+    fn sbox_shifts(&self) -> &'static [u32] {
+        &[76]
+    }
+
+    fn round_counter(&self, round: u8) -> u128 {
+        (round as u128) << 15
+    }
+}
+
+struct Register128;
+
+impl KeyRegister for Register128 {
+    fn truncate(&self, register: u128) -> u128 {
+        register
+    }
+
+    fn round_key(&self, register: u128) -> u64 {
+        (register >> 64) as u64
+    }
+
+    fn rotate(&self, register: u128) -> u128 {
+        register.rotate_left(61)
+    }
+
+    fn sbox_shifts(&self) -> &'static [u32] {
+        &[124, 120]
+    }
+
+    fn round_counter(&self, round: u8) -> u128 {
+        (round as u128) << 62
+    }
+}
+
+/// Recomputes an S-Box table for the given masks, so that looking up
+/// a value masked with `input_mask` gives the output masked with
+/// `output_mask`.
+fn masked_table<F: Fn(u8) -> u8>(sbox: F, input_mask: u8, output_mask: u8) -> [u8; 16] {
+    let mut table = [0u8; 16];
+    for (value, entry) in table.iter_mut().enumerate() {
+        *entry = sbox(value as u8 ^ input_mask) ^ output_mask;
+    }
+    table
+}
+
+/// Applies a masked S-Box table to all nibbles of the state.
+fn substitute(state: u64, table: &[u8; 16]) -> u64 {
+    let mut output = 0u64;
+    for nibble in 0..16 {
+        output |= (table[((state >> (4 * nibble)) & 0xF) as usize] as u64) << (4 * nibble);
+    }
+    output
+}
+
+/// Repeats a nibble mask in every nibble of the state.
+fn spread(mask: u8) -> u64 {
+    (mask as u64) * NIBBLE_LOW_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+    use keys::{schedule_80bit, schedule_128bit};
+
+    #[test]
+    fn test_masked_schedule_matches_plain_schedule() {
+        let key80 = [0x3Cu8; 10];
+        let schedule = MaskedSchedule::new_80bit(&key80, &mut StepRng::new(0x0123456789ABCDEF, 0x9E37)).unwrap();
+        for (round_key, expected) in schedule.round_keys().iter().zip(schedule_80bit(&key80).iter()) {
+            assert_eq!(round_key.value, u64::from_be_bytes(*expected));
+        }
+
+        let key128 = [0xA5u8; 16];
+        let schedule = MaskedSchedule::new_128bit(&key128, &mut StepRng::new(42, 7)).unwrap();
+        for (round_key, expected) in schedule.round_keys().iter().zip(schedule_128bit(&key128).iter()) {
+            assert_eq!(round_key.value, u64::from_be_bytes(*expected));
+        }
+    }
+
+    #[test]
+    fn test_shares_are_masked() {
+        let schedule = MaskedSchedule::new_80bit(&[0u8; 10], &mut StepRng::new(0x0123456789ABCDEF, 0x9E37)).unwrap();
+        let round_keys = schedule.round_keys();
+        assert!(schedule.keys.iter().zip(round_keys.iter()).any(|(key, round_key)| *key != round_key.value));
+    }
+
+    #[test]
+    fn test_masked_rounds_match_test_vectors() {
+        let schedule = MaskedSchedule::new_80bit(&[0u8; 10], &mut StepRng::new(1, 1)).unwrap();
+        for _ in 0..16 {
+            // The masks change for every block, the result does not
+            assert_eq!(schedule.encrypt(0), 0x5579C1387B228445);
+            assert_eq!(schedule.decrypt(0x5579C1387B228445), 0);
+        }
+
+        let schedule = MaskedSchedule::new_80bit(&[0xFF; 10], &mut StepRng::new(1, 1)).unwrap();
+        assert_eq!(schedule.encrypt(0xFFFFFFFFFFFFFFFF), 0x3333DCD3213210D2);
+    }
+}
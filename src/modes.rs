@@ -1,37 +1,516 @@
-use rand::{Rng, OsRng};
+use rand::RngCore;
+use rand::rngs::OsRng;
 use block::Block;
+use context::BlockCipher;
+use errors::RngError;
+use iv::Iv;
+use profile::{self, Stage};
 
 /// Enum representing block cipher modes of operation.
+#[derive(Clone, Copy)]
 pub enum OpMode {
     /// Electronic Code Book (unsafe). Does not require an initialization vector.
+    /// Only available with the `insecure-modes` feature.
+    #[cfg(feature = "insecure-modes")]
+    #[deprecated(note = "ECB leaks patterns in the plaintext, use CBC, CFB or `Ctr`")]
     ECB,
     /// Cipher Block Chaining. Requires an initialization vector.
     CBC,
+    /// Propagating Cipher Block Chaining. Requires an initialization
+    /// vector. Each plaintext block is XORed with both the previous
+    /// plaintext and ciphertext block, so an error propagates through
+    /// the rest of the message. Only provided for compatibility with
+    /// legacy protocols.
+    PCBC,
+    /// Cipher Feedback with 64-bit segments. Requires an initialization
+    /// vector. No padding is applied, so the ciphertext has the same
+    /// length as the plaintext.
+    CFB,
+    /// Counter mode. Requires an initialization vector, which is the
+    /// first counter block and is incremented for every following
+    /// block, giving the same keystream as [`Ctr`](struct.Ctr.html).
+    /// No padding is applied. The IV does not have to be unpredictable,
+    /// but no counter block may ever be used twice under the same key.
+    CTR,
+}
+
+/// A block cipher mode of operation together with its parameters.
+///
+/// Unlike passing an [`OpMode`](enum.OpMode.html) and an optional IV
+/// separately, the IV is part of the variants that need one, so ECB with
+/// an IV or CBC without one cannot be expressed. This is the preferred
+/// way to use the built-in modes, with
+/// [`encrypt_bytes_with_mode`](fn.encrypt_bytes_with_mode.html) and
+/// [`decrypt_bytes_with_mode`](fn.decrypt_bytes_with_mode.html). The
+/// functions taking an `OpMode` and an `Option<Block>` remain for
+/// modes implementing [`ModeOfOperation`](trait.ModeOfOperation.html).
+///
+/// # Examples
+///
+/// ```
+/// use present::{decrypt_str_with_mode, encrypt_bytes_with_mode, Key80Bit, Mode, OpMode};
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let mode = Mode::random(OpMode::CBC).unwrap();
+/// let ciphertext = encrypt_bytes_with_mode(b"Hello, world!", &key, &mode).unwrap();
+///
+/// let plaintext = decrypt_str_with_mode(ciphertext.as_bytes(), &key, &mode);
+/// assert_eq!(plaintext.unwrap(), "Hello, world!");
+/// ```
+pub enum Mode {
+    /// Electronic Code Book (unsafe), see [`OpMode::ECB`](enum.OpMode.html#variant.ECB).
+    #[cfg(feature = "insecure-modes")]
+    #[deprecated(note = "ECB leaks patterns in the plaintext, use CBC, CFB or `Ctr`")]
+    ECB,
+    /// Cipher Block Chaining, see [`OpMode::CBC`](enum.OpMode.html#variant.CBC).
+    CBC { iv: Iv },
+    /// Propagating Cipher Block Chaining, see [`OpMode::PCBC`](enum.OpMode.html#variant.PCBC).
+    PCBC { iv: Iv },
+    /// Cipher Feedback, see [`OpMode::CFB`](enum.OpMode.html#variant.CFB).
+    CFB { iv: Iv },
+    /// Counter mode, see [`OpMode::CTR`](enum.OpMode.html#variant.CTR).
+    /// The nonce is the first counter block.
+    CTR { nonce: Iv },
+}
+
+impl Mode {
+    /// Constructs the given mode of operation with a random IV if it
+    /// requires one.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RngError::Unavailable` if no IV could be generated.
+    pub fn random(mode: OpMode) -> Result<Self, RngError> {
+        Ok(match mode {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            OpMode::ECB => Mode::ECB,
+            OpMode::CBC => Mode::CBC { iv: Iv::random()? },
+            OpMode::PCBC => Mode::PCBC { iv: Iv::random()? },
+            OpMode::CFB => Mode::CFB { iv: Iv::random()? },
+            OpMode::CTR => Mode::CTR { nonce: Iv::random()? },
+        })
+    }
+
+    /// Constructs the given mode of operation with an IV, or returns
+    /// `None` if the IV does not match the mode, i.e. if an IV is given
+    /// for ECB or missing for the other modes.
+    pub fn with_init_vec(mode: OpMode, init_vec: Option<Iv>) -> Option<Self> {
+        match (mode, init_vec) {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            (OpMode::ECB, None) => Some(Mode::ECB),
+            (OpMode::CBC, Some(iv)) => Some(Mode::CBC { iv }),
+            (OpMode::PCBC, Some(iv)) => Some(Mode::PCBC { iv }),
+            (OpMode::CFB, Some(iv)) => Some(Mode::CFB { iv }),
+            (OpMode::CTR, Some(nonce)) => Some(Mode::CTR { nonce }),
+            _ => None,
+        }
+    }
+
+    /// Returns the mode of operation without its parameters.
+    pub fn op_mode(&self) -> OpMode {
+        match *self {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            Mode::ECB => OpMode::ECB,
+            Mode::CBC { .. } => OpMode::CBC,
+            Mode::PCBC { .. } => OpMode::PCBC,
+            Mode::CFB { .. } => OpMode::CFB,
+            Mode::CTR { .. } => OpMode::CTR,
+        }
+    }
+
+    /// Returns the IV, or the nonce for CTR, or `None` for ECB.
+    pub fn init_vec(&self) -> Option<&Iv> {
+        match *self {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            Mode::ECB => None,
+            Mode::CBC { ref iv } | Mode::PCBC { ref iv } | Mode::CFB { ref iv } => Some(iv),
+            Mode::CTR { ref nonce } => Some(nonce),
+        }
+    }
+}
+
+/// All modes of operation available with the enabled features.
+pub fn all_modes() -> Vec<OpMode> {
+    vec![
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        OpMode::ECB,
+        OpMode::CBC,
+        OpMode::PCBC,
+        OpMode::CFB,
+        OpMode::CTR,
+    ]
 }
 
 /// Generate a random initialization vector using a random
 /// number generator provided by the operating system.
 /// For details on how randomness is achieved, see
-/// [the `OsRng` docs](https://doc.rust-lang.org/rand/rand/os/struct.OsRng.html)
+/// [the `OsRng` docs](https://docs.rs/rand/0.8/rand/rngs/struct.OsRng.html)
 /// from the `rand` crate.
-pub fn random_iv() -> Block {
-    let mut rng = match OsRng::new() {
-        Ok(g) => g,
-        Err(e) => panic!("Unable to obtain RNG from OS: {}", e),
-    };
+///
+/// `OsRng` does not hold a handle of its own, and the `getrandom` crate
+/// behind it keeps the file descriptor open on platforms that need one,
+/// so there is no RNG to cache between calls.
+pub fn random_iv() -> Result<Block, RngError> {
+    random_iv_with(&mut OsRng)
+}
+
+/// Generate a random initialization vector using the given random
+/// number generator.
+///
+/// This allows injecting other sources of randomness, e.g. a hardware
+/// TRNG on embedded targets, or a deterministic RNG in tests. Outside
+/// of tests, the RNG must be cryptographically secure, since the IVs
+/// of CBC, PCBC and CFB must be unpredictable.
+///
+/// # Errors
+///
+/// Returns `RngError::Unavailable` if the RNG fails to provide
+/// randomness.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate present;
+/// # extern crate rand;
+/// use present::{random_iv_with, try_encrypt_bytes_with_iv, Key80Bit, OpMode};
+/// use rand::rngs::mock::StepRng;
+///
+/// # fn main() {
+/// // The same seed gives the same IV, and thus the same ciphertext
+/// let iv = random_iv_with(&mut StepRng::new(42, 1)).unwrap();
+/// assert_eq!(iv.get_state(), random_iv_with(&mut StepRng::new(42, 1)).unwrap().get_state());
+/// let ciphertext = try_encrypt_bytes_with_iv(b"reproducible", &Key80Bit::new([0; 10]), &OpMode::CBC, &iv).unwrap();
+/// # }
+/// ```
+pub fn random_iv_with<R: RngCore + ?Sized>(rng: &mut R) -> Result<Block, RngError> {
+    let mut bytes = [0u8; 8];
+    rng.try_fill_bytes(&mut bytes)?;
+    Ok(Block::from_bytes(&bytes))
+}
 
-    Block::new(rng.gen())
+/// A block cipher mode of operation.
+///
+/// The built-in modes are the variants of [`OpMode`](enum.OpMode.html).
+/// Implement this trait for experimental or proprietary modes, which can
+/// then be passed to [`try_encrypt_bytes_with_iv`](fn.try_encrypt_bytes_with_iv.html)
+/// and [`decrypt_bytes`](fn.decrypt_bytes.html) like the built-in ones.
+///
+/// # Examples
+///
+/// Output Feedback, which turns the cipher into a keystream generator:
+///
+/// ```
+/// use present::{decrypt_bytes, try_encrypt_bytes_with_iv, Block, BlockCipher, Key80Bit, ModeOfOperation};
+///
+/// struct Ofb;
+///
+/// impl ModeOfOperation for Ofb {
+///     fn is_padded(&self) -> bool {
+///         false
+///     }
+///
+///     fn encrypt_in_place<C: BlockCipher + ?Sized>(&self, buffer: &mut [u8], cipher: &C, feedback: &mut Block) {
+///         for chunk in buffer.chunks_mut(8) {
+///             cipher.encrypt_block(feedback);
+///             for (byte, k) in chunk.iter_mut().zip(feedback.to_bytes().iter()) {
+///                 *byte ^= k;
+///             }
+///         }
+///     }
+///
+///     fn decrypt_in_place<C: BlockCipher + ?Sized>(&self, buffer: &mut [u8], cipher: &C, feedback: &mut Block) {
+///         self.encrypt_in_place(buffer, cipher, feedback);
+///     }
+/// }
+///
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let ciphertext = try_encrypt_bytes_with_iv(b"Hello, world!", &key, &Ofb, &Block::new(42)).unwrap();
+/// assert_eq!(ciphertext.len(), 13);
+/// assert_eq!(decrypt_bytes(&ciphertext, &key, &Ofb, Some(Block::new(42))).unwrap(), b"Hello, world!");
+/// ```
+pub trait ModeOfOperation {
+    /// Whether the mode uses an IV. Modes without one get a zero block
+    /// as the initial feedback. Defaults to `true`.
+    fn needs_init_vec(&self) -> bool {
+        true
+    }
+
+    /// Whether the plaintext is padded to a multiple of the block size.
+    /// Padded modes are only given whole blocks, the others data of any
+    /// length. Defaults to `true`.
+    fn is_padded(&self) -> bool {
+        true
+    }
+
+    /// Whether the mode leaks patterns in the plaintext like ECB. Insecure
+    /// modes are reported to the security policy as
+    /// `SecurityEvent::EcbSelected`. Defaults to `false`.
+    fn is_insecure(&self) -> bool {
+        false
+    }
+
+    /// Encrypts data in place without padding. The feedback starts out
+    /// as the IV and is left in the state for the data following the
+    /// buffer, so a message can be processed in several calls.
+    fn encrypt_in_place<C: BlockCipher + ?Sized>(&self, buffer: &mut [u8], cipher: &C, feedback: &mut Block);
+
+    /// Decrypts data in place without removing padding. This is the
+    /// inverse of [`encrypt_in_place()`](#tymethod.encrypt_in_place).
+    fn decrypt_in_place<C: BlockCipher + ?Sized>(&self, buffer: &mut [u8], cipher: &C, feedback: &mut Block);
+}
+
+impl ModeOfOperation for OpMode {
+    fn needs_init_vec(&self) -> bool {
+        match *self {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            OpMode::ECB => false,
+            OpMode::CBC | OpMode::PCBC | OpMode::CFB | OpMode::CTR => true,
+        }
+    }
+
+    fn is_padded(&self) -> bool {
+        match *self {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            OpMode::ECB => true,
+            OpMode::CBC | OpMode::PCBC => true,
+            OpMode::CFB | OpMode::CTR => false,
+        }
+    }
+
+    /// Returns `true` for ECB, which is only available with the
+    /// `insecure-modes` feature.
+    fn is_insecure(&self) -> bool {
+        !self.needs_init_vec()
+    }
+
+    /// ECB, CBC and PCBC require the length to be a multiple of the
+    /// block size, CFB and CTR accept a partial final segment. The
+    /// feedback is ignored by ECB and is the counter block for CTR.
+    fn encrypt_in_place<C: BlockCipher + ?Sized>(&self, buffer: &mut [u8], cipher: &C, feedback: &mut Block) {
+        match *self {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            OpMode::ECB => cipher.encrypt_blocks(buffer),
+            OpMode::CBC => {
+                let last_block = feedback;
+                for chunk in buffer.chunks_exact_mut(8) {
+                    let mut block = Block::from_bytes(&to_array(chunk));
+                    profile::measure(Stage::Chaining, || block ^= &*last_block);
+                    cipher.encrypt_block(&mut block);
+                    chunk.copy_from_slice(&block.to_bytes());
+                    *last_block = block;
+                }
+            },
+            OpMode::PCBC => {
+                for chunk in buffer.chunks_exact_mut(8) {
+                    let plain = Block::from_bytes(&to_array(chunk));
+                    let mut block = Block::new(plain.get_state());
+                    profile::measure(Stage::Chaining, || block ^= &*feedback);
+                    cipher.encrypt_block(&mut block);
+                    chunk.copy_from_slice(&block.to_bytes());
+
+                    // Next block is chained with plaintext XOR ciphertext
+                    *feedback = plain;
+                    *feedback ^= &block;
+                }
+            },
+            OpMode::CFB => cfb_in_place(buffer, cipher, feedback, false),
+            OpMode::CTR => ctr_in_place(buffer, cipher, feedback),
+        }
+    }
+
+    fn decrypt_in_place<C: BlockCipher + ?Sized>(&self, buffer: &mut [u8], cipher: &C, feedback: &mut Block) {
+        match *self {
+            #[cfg(feature = "insecure-modes")]
+            #[allow(deprecated)]
+            OpMode::ECB => cipher.decrypt_blocks(buffer),
+            OpMode::CBC => {
+                let last_block = feedback;
+                for chunk in buffer.chunks_exact_mut(8) {
+                    let encrypted = Block::from_bytes(&to_array(chunk));
+                    let mut block = Block::new(encrypted.get_state());
+                    cipher.decrypt_block(&mut block);
+                    profile::measure(Stage::Chaining, || block ^= &*last_block);
+                    chunk.copy_from_slice(&block.to_bytes());
+                    *last_block = encrypted;
+                }
+            },
+            OpMode::PCBC => {
+                for chunk in buffer.chunks_exact_mut(8) {
+                    let encrypted = Block::from_bytes(&to_array(chunk));
+                    let mut block = Block::new(encrypted.get_state());
+                    cipher.decrypt_block(&mut block);
+                    profile::measure(Stage::Chaining, || block ^= &*feedback);
+                    chunk.copy_from_slice(&block.to_bytes());
+
+                    *feedback = encrypted;
+                    *feedback ^= &block;
+                }
+            },
+            OpMode::CFB => cfb_in_place(buffer, cipher, feedback, true),
+            OpMode::CTR => ctr_in_place(buffer, cipher, feedback),
+        }
+    }
+}
+
+/// Apply Cipher Feedback mode to the given data.
+///
+/// Each segment is XORed with the encryption of the previous ciphertext
+/// block (the IV for the first segment). Since the cipher is only used
+/// in the encryption direction, the same function handles decryption;
+/// `decrypt` only selects whether the input or the output is the
+/// ciphertext fed back into the cipher.
+fn cfb_in_place<C: BlockCipher + ?Sized>(buffer: &mut [u8], cipher: &C, feedback: &mut Block, decrypt: bool) {
+    for chunk in buffer.chunks_mut(8) {
+        let mut keystream = Block::new(feedback.get_state());
+        cipher.encrypt_block(&mut keystream);
+        let keystream = keystream.to_bytes();
+
+        // A partial segment can only occur at the end, so the feedback
+        // is only updated for full blocks
+        let mut next = [0u8; 8];
+        if decrypt && chunk.len() == 8 {
+            next.copy_from_slice(chunk);
+        }
+
+        profile::measure(Stage::Chaining, || {
+            for (byte, k) in chunk.iter_mut().zip(keystream.iter()) {
+                *byte ^= k;
+            }
+        });
+
+        if chunk.len() == 8 {
+            if !decrypt {
+                next.copy_from_slice(chunk);
+            }
+            *feedback = Block::from_bytes(&next);
+        }
+    }
+}
+
+/// Apply Counter mode to the given data.
+///
+/// Each segment is XORed with the encryption of the counter, which is
+/// incremented after every full block. Encryption and decryption are
+/// the same.
+fn ctr_in_place<C: BlockCipher + ?Sized>(buffer: &mut [u8], cipher: &C, counter: &mut Block) {
+    for chunk in buffer.chunks_mut(8) {
+        let mut keystream = *counter;
+        cipher.encrypt_block(&mut keystream);
+        let keystream = keystream.to_bytes();
+
+        profile::measure(Stage::Chaining, || {
+            for (byte, k) in chunk.iter_mut().zip(keystream.iter()) {
+                *byte ^= k;
+            }
+        });
+
+        // A partial segment can only occur at the end
+        if chunk.len() == 8 {
+            *counter = Block::new(counter.get_state().wrapping_add(1));
+        }
+    }
+}
+
+fn to_array(chunk: &[u8]) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(chunk);
+    bytes
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use context::Expanded;
+    use keys::Key80Bit;
+    use rand::rngs::mock::StepRng;
 
     #[test]
     #[should_panic]
     fn test_that_ivs_are_actually_random() {
-        let a = random_iv();
-        let b = random_iv();
+        let a = random_iv().unwrap();
+        let b = random_iv().unwrap();
         assert_eq!(a.get_state(), b.get_state());
     }
+
+    #[test]
+    fn test_iv_from_given_rng() {
+        // The generator output is taken as the bytes of the IV
+        let mut rng = StepRng::new(7, 3);
+        assert_eq!(random_iv_with(&mut rng).unwrap().to_bytes(), 7u64.to_le_bytes());
+        assert_eq!(random_iv_with(&mut rng).unwrap().to_bytes(), 10u64.to_le_bytes());
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    fn test_mode_parameters() {
+        #[cfg(feature = "insecure-modes")]
+        assert!(Mode::random(OpMode::ECB).unwrap().init_vec().is_none());
+        let mode = Mode::random(OpMode::PCBC).unwrap();
+        assert!(mode.init_vec().is_some());
+        assert!(matches!(mode.op_mode(), OpMode::PCBC));
+
+        let iv = Iv::from_bytes(&[1; 8]);
+        #[cfg(feature = "insecure-modes")]
+        assert!(Mode::with_init_vec(OpMode::ECB, Some(iv)).is_none());
+        assert!(Mode::with_init_vec(OpMode::CBC, None).is_none());
+        let mode = Mode::with_init_vec(OpMode::CFB, Some(iv)).unwrap();
+        assert_eq!(mode.init_vec(), Some(&iv));
+        let mode = Mode::with_init_vec(OpMode::CTR, Some(iv)).unwrap();
+        assert!(matches!(mode, Mode::CTR { nonce } if nonce == iv));
+        assert!(Mode::with_init_vec(OpMode::CTR, None).is_none());
+
+        #[cfg(feature = "insecure-modes")]
+        assert!(OpMode::ECB.is_insecure());
+        assert!(!OpMode::CBC.is_insecure());
+    }
+
+    #[test]
+    fn test_ctr_matches_keystream() {
+        let key = Key80Bit::new([0x5A; 10]);
+        let plaintext: Vec<u8> = (0..21u8).collect();
+        let mut expected = plaintext.clone();
+        ::Ctr::new(&key, &Block::new(u64::MAX - 1)).apply_keystream(&mut expected);
+
+        // The counter wraps around like the one of Ctr
+        let mode = Mode::CTR { nonce: Iv::from_bytes(&(u64::MAX - 1).to_be_bytes()) };
+        let ciphertext = ::encrypt_bytes_with_mode(&plaintext, &key, &mode).unwrap();
+        assert_eq!(ciphertext.as_bytes(), &expected[..]);
+        assert_eq!(::decrypt_bytes_with_mode(ciphertext.as_bytes(), &key, &mode).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_cfb_matches_definition() {
+        let key = Key80Bit::new([0x5A; 10]);
+        let iv = Block::new(0x0123456789ABCDEF);
+        let plaintext: Vec<u8> = (0..20u8).collect();
+        let cipher = Expanded::new(&key);
+        let mut ciphertext = plaintext.clone();
+        OpMode::CFB.encrypt_in_place(&mut ciphertext, &cipher, &mut Block::new(iv.get_state()));
+        assert_eq!(ciphertext.len(), 20);
+
+        let mut feedback = iv.get_state();
+        for (i, chunk) in plaintext.chunks(8).enumerate() {
+            let mut keystream = Block::new(feedback);
+            keystream.encrypt(&key);
+            let keystream = keystream.to_bytes();
+            for (j, byte) in chunk.iter().enumerate() {
+                assert_eq!(ciphertext[i * 8 + j], byte ^ keystream[j]);
+            }
+            if chunk.len() == 8 {
+                let mut next = [0u8; 8];
+                next.copy_from_slice(&ciphertext[i * 8..i * 8 + 8]);
+                feedback = u64::from_be_bytes(next);
+            }
+        }
+
+        OpMode::CFB.decrypt_in_place(&mut ciphertext, &cipher, &mut Block::new(iv.get_state()));
+        assert_eq!(ciphertext, plaintext);
+    }
 }
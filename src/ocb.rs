@@ -0,0 +1,286 @@
+use block::{encrypt_block8, decrypt_block8};
+use errors::AeadError;
+use keys::{Key, RoundKeys};
+use policy;
+use mac::double;
+use tag::{tags_equal, TagLen};
+#[cfg(feature = "zeroize")]
+use keys::wipe;
+
+/// OCB authenticated encryption.
+///
+/// OCB encrypts and authenticates in a single pass over the plaintext,
+/// needing only one block cipher call per block, which makes it the
+/// fastest authenticated mode of this crate. The associated data is
+/// authenticated but not encrypted.
+///
+/// The construction follows OCB3 (RFC 7253) with PRESENT's 64-bit
+/// block. Since RFC 7253 is only defined for 128-bit blocks, the
+/// initial offset is computed by encrypting the formatted nonce instead
+/// of using the nonce stretching of the RFC, so the output is not
+/// interoperable with other implementations. Nonces are 1 to 6 bytes
+/// long.
+///
+/// Never encrypt two messages with the same nonce and key. Because of
+/// the small block, a key should not be used for much more than 2^32
+/// blocks of data in total.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Key128Bit, Ocb, TagLen};
+/// let ocb = Ocb::new(&Key128Bit::new([0x42; 16]), TagLen::FULL);
+/// let nonce = [0x00, 0x00, 0x00, 0x2A];
+///
+/// let sealed = ocb.encrypt(&nonce, b"header", b"telemetry frame").unwrap();
+/// assert_eq!(sealed.len(), 15 + 8);
+/// assert_eq!(ocb.decrypt(&nonce, b"header", &sealed).unwrap(), b"telemetry frame");
+/// ```
+pub struct Ocb {
+    round_keys: RoundKeys,
+    tag_len: TagLen,
+    l_star: u64,
+    l_dollar: u64,
+    l: [u64; 64],
+}
+
+impl Ocb {
+    /// Constructs a new OCB instance producing tags of the given length.
+    pub fn new<K: Key>(key: &K, tag_len: TagLen) -> Self {
+        policy::check_tag_len(tag_len);
+        let round_keys = key.generate_round_keys();
+
+        let l_star = encrypt(&round_keys, 0);
+        let l_dollar = double(l_star);
+        let mut l = [0u64; 64];
+        l[0] = double(l_dollar);
+        for i in 1..l.len() {
+            l[i] = double(l[i - 1]);
+        }
+
+        Ocb { round_keys, tag_len, l_star, l_dollar, l }
+    }
+
+    /// Encrypts and authenticates a message.
+    ///
+    /// Returns the ciphertext with the tag appended.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AeadError::InvalidNonceLength` if the nonce is not
+    /// between 1 and 6 bytes long.
+    pub fn encrypt(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AeadError> {
+        let mut offset = self.initial_offset(nonce)?;
+        let mut checksum = 0u64;
+        let mut output = Vec::with_capacity(plaintext.len() + self.tag_len.bytes());
+
+        let mut chunks = plaintext.chunks_exact(8);
+        for (i, chunk) in (&mut chunks).enumerate() {
+            offset ^= self.l_at(i + 1);
+            let p = to_u64(chunk);
+            checksum ^= p;
+            output.extend_from_slice(&(offset ^ encrypt(&self.round_keys, p ^ offset)).to_be_bytes());
+        }
+
+        let last = chunks.remainder();
+        if !last.is_empty() {
+            offset ^= self.l_star;
+            let pad = encrypt(&self.round_keys, offset).to_be_bytes();
+            output.extend(last.iter().zip(pad.iter()).map(|(x, k)| x ^ k));
+            checksum ^= pad_block(last);
+        }
+
+        let tag = self.tag(checksum, offset, aad);
+        output.extend_from_slice(&tag[..self.tag_len.bytes()]);
+        Ok(output)
+    }
+
+    /// Verifies and decrypts a message produced by
+    /// [`encrypt()`](#method.encrypt).
+    ///
+    /// # Errors
+    ///
+    /// Returns `AeadError::AuthenticationFailed` if the tag does not
+    /// match, plus the errors of `encrypt()` for invalid parameters.
+    pub fn decrypt(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AeadError> {
+        let tag_len = self.tag_len.bytes();
+        if ciphertext.len() < tag_len {
+            return Err(AeadError::CiphertextTooShort(ciphertext.len()));
+        }
+        let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - tag_len);
+
+        let mut offset = self.initial_offset(nonce)?;
+        let mut checksum = 0u64;
+        let mut output = Vec::with_capacity(ciphertext.len());
+
+        let mut chunks = ciphertext.chunks_exact(8);
+        for (i, chunk) in (&mut chunks).enumerate() {
+            offset ^= self.l_at(i + 1);
+            let p = offset ^ decrypt(&self.round_keys, to_u64(chunk) ^ offset);
+            checksum ^= p;
+            output.extend_from_slice(&p.to_be_bytes());
+        }
+
+        let last = chunks.remainder();
+        if !last.is_empty() {
+            offset ^= self.l_star;
+            let pad = encrypt(&self.round_keys, offset).to_be_bytes();
+            let start = output.len();
+            output.extend(last.iter().zip(pad.iter()).map(|(x, k)| x ^ k));
+            checksum ^= pad_block(&output[start..]);
+        }
+
+        let expected = self.tag(checksum, offset, aad);
+        if !tags_equal(&expected[..tag_len], tag) {
+            return Err(AeadError::AuthenticationFailed);
+        }
+
+        Ok(output)
+    }
+
+    /// Returns the configured tag length.
+    pub fn tag_len(&self) -> TagLen {
+        self.tag_len
+    }
+
+    /// Returns the offset for block `i` (counted from 1), i.e. L_ntz(i).
+    fn l_at(&self, i: usize) -> u64 {
+        self.l[i.trailing_zeros() as usize]
+    }
+
+    fn initial_offset(&self, nonce: &[u8]) -> Result<u64, AeadError> {
+        if nonce.is_empty() || nonce.len() > 6 {
+            return Err(AeadError::InvalidNonceLength(nonce.len()));
+        }
+
+        // Tag length, zero padding, a one byte marker and the nonce, so
+        // that tag lengths and nonce lengths are domain separated
+        let mut block = [0u8; 8];
+        block[0] = self.tag_len.bytes() as u8;
+        block[7 - nonce.len()] = 0x01;
+        block[(8 - nonce.len())..].copy_from_slice(nonce);
+        Ok(encrypt(&self.round_keys, u64::from_be_bytes(block)))
+    }
+
+    fn tag(&self, checksum: u64, offset: u64, aad: &[u8]) -> [u8; 8] {
+        let tag = encrypt(&self.round_keys, checksum ^ offset ^ self.l_dollar) ^ self.hash(aad);
+        tag.to_be_bytes()
+    }
+
+    fn hash(&self, aad: &[u8]) -> u64 {
+        let mut offset = 0u64;
+        let mut sum = 0u64;
+
+        let mut chunks = aad.chunks_exact(8);
+        for (i, chunk) in (&mut chunks).enumerate() {
+            offset ^= self.l_at(i + 1);
+            sum ^= encrypt(&self.round_keys, to_u64(chunk) ^ offset);
+        }
+
+        let last = chunks.remainder();
+        if !last.is_empty() {
+            offset ^= self.l_star;
+            sum ^= encrypt(&self.round_keys, pad_block(last) ^ offset);
+        }
+
+        sum
+    }
+}
+
+fn encrypt(round_keys: &RoundKeys, value: u64) -> u64 {
+    let mut bytes = value.to_be_bytes();
+    encrypt_block8(&mut bytes, round_keys);
+    u64::from_be_bytes(bytes)
+}
+
+fn decrypt(round_keys: &RoundKeys, value: u64) -> u64 {
+    let mut bytes = value.to_be_bytes();
+    decrypt_block8(&mut bytes, round_keys);
+    u64::from_be_bytes(bytes)
+}
+
+fn to_u64(chunk: &[u8]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(chunk);
+    u64::from_be_bytes(bytes)
+}
+
+/// Pads a partial block with a single one bit followed by zeros.
+fn pad_block(partial: &[u8]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes[..partial.len()].copy_from_slice(partial);
+    bytes[partial.len()] = 0x80;
+    u64::from_be_bytes(bytes)
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Ocb {
+    fn drop(&mut self) {
+        wipe(&mut self.round_keys);
+        wipe(&mut self.l_star);
+        wipe(&mut self.l_dollar);
+        wipe(&mut self.l);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::Block;
+    use keys::Key80Bit;
+
+    #[test]
+    fn test_single_block_matches_definition() {
+        let key = Key80Bit::new([0x5C; 10]);
+        let ocb = Ocb::new(&key, TagLen::FULL);
+        let sealed = ocb.encrypt(&[7], b"", b"one blk!").unwrap();
+
+        let e = |value: u64| {
+            let mut block = Block::new(value);
+            block.encrypt(&key);
+            block.get_state()
+        };
+        let l_dollar = double(e(0));
+        let l_0 = double(l_dollar);
+        let offset = e(0x0800000000000107) ^ l_0;
+        let p = u64::from_be_bytes(*b"one blk!");
+
+        assert_eq!(&sealed[..8], &(offset ^ e(p ^ offset)).to_be_bytes());
+        assert_eq!(&sealed[8..], &e(p ^ offset ^ l_dollar).to_be_bytes());
+    }
+
+    #[test]
+    fn test_roundtrip_and_tampering() {
+        let key = Key80Bit::new([0x5C; 10]);
+        let nonce = [1, 2, 3, 4, 5, 6];
+        for &tag_len in [4, 5, 8].iter() {
+            let ocb = Ocb::new(&key, TagLen::new(tag_len).unwrap());
+            for len in 0..40 {
+                let plaintext: Vec<u8> = (0..len as u8).collect();
+                let aad: Vec<u8> = (0..(len as u8 / 2)).collect();
+                let mut sealed = ocb.encrypt(&nonce, &aad, &plaintext).unwrap();
+                assert_eq!(sealed.len(), len + tag_len);
+                assert_eq!(ocb.decrypt(&nonce, &aad, &sealed).unwrap(), plaintext);
+
+                assert_eq!(ocb.decrypt(&nonce, b"other", &sealed), Err(AeadError::AuthenticationFailed));
+                assert_eq!(ocb.decrypt(&nonce[1..], &aad, &sealed), Err(AeadError::AuthenticationFailed));
+                let last = sealed.len() - 1;
+                sealed[last] ^= 0x01;
+                assert_eq!(ocb.decrypt(&nonce, &aad, &sealed), Err(AeadError::AuthenticationFailed));
+                sealed[last] ^= 0x01;
+                if len > 0 {
+                    sealed[len - 1] ^= 0x01;
+                    assert_eq!(ocb.decrypt(&nonce, &aad, &sealed), Err(AeadError::AuthenticationFailed));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_nonce_length() {
+        let ocb = Ocb::new(&Key80Bit::new([0; 10]), TagLen::FULL);
+        assert_eq!(ocb.encrypt(&[], b"", b"").err(), Some(AeadError::InvalidNonceLength(0)));
+        assert_eq!(ocb.encrypt(&[0; 7], b"", b"").err(), Some(AeadError::InvalidNonceLength(7)));
+        assert_eq!(ocb.decrypt(&[0; 6], b"", &[0; 7]).err(), Some(AeadError::CiphertextTooShort(7)));
+    }
+}
@@ -0,0 +1,117 @@
+use keyring::Entry;
+
+use errors::KeyringError;
+use keys::{wipe, Key80Bit, Key128Bit};
+
+/// Key storage in the platform keyring.
+///
+/// Keys are stored as binary secrets in the Keychain on macOS, the
+/// Credential Manager on Windows and the Secret Service on Linux, so
+/// applications do not need to keep raw key files on disk. Every key
+/// is identified by the service name of the keyring and its own name.
+///
+/// # Examples
+///
+/// ```no_run
+/// use present::{Key80Bit, OsKeyring};
+///
+/// let keyring = OsKeyring::new("my-app");
+/// keyring.store_key80("backup", &Key80Bit::new([0x42; 10])).unwrap();
+/// let key = keyring.load_key80("backup").unwrap();
+/// assert_eq!(key.value, [0x42; 10]);
+/// ```
+pub struct OsKeyring {
+    service: String,
+}
+
+impl OsKeyring {
+    /// Constructs a keyring handle for the given service name.
+    pub fn new(service: &str) -> Self {
+        OsKeyring { service: service.to_string() }
+    }
+
+    /// Stores an 80-bit key under the given name, replacing any key
+    /// that was previously stored under that name.
+    pub fn store_key80(&self, name: &str, key: &Key80Bit) -> Result<(), KeyringError> {
+        self.entry(name)?.set_secret(&key.value).map_err(KeyringError::from)
+    }
+
+    /// Loads the 80-bit key stored under the given name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyringError::NotFound` if no key is stored under that
+    /// name and `KeyringError::InvalidLength` if the stored key is not
+    /// an 80-bit key.
+    pub fn load_key80(&self, name: &str) -> Result<Key80Bit, KeyringError> {
+        let mut value = [0u8; 10];
+        copy_secret(self.entry(name)?.get_secret()?, &mut value)?;
+        Ok(Key80Bit::new(value))
+    }
+
+    /// Stores a 128-bit key under the given name, replacing any key
+    /// that was previously stored under that name.
+    pub fn store_key128(&self, name: &str, key: &Key128Bit) -> Result<(), KeyringError> {
+        self.entry(name)?.set_secret(&key.value).map_err(KeyringError::from)
+    }
+
+    /// Loads the 128-bit key stored under the given name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyringError::NotFound` if no key is stored under that
+    /// name and `KeyringError::InvalidLength` if the stored key is not
+    /// a 128-bit key.
+    pub fn load_key128(&self, name: &str) -> Result<Key128Bit, KeyringError> {
+        let mut value = [0u8; 16];
+        copy_secret(self.entry(name)?.get_secret()?, &mut value)?;
+        Ok(Key128Bit::new(value))
+    }
+
+    /// Removes the key stored under the given name.
+    pub fn delete_key(&self, name: &str) -> Result<(), KeyringError> {
+        self.entry(name)?.delete_credential().map_err(KeyringError::from)
+    }
+
+    fn entry(&self, name: &str) -> Result<Entry, KeyringError> {
+        Entry::new(&self.service, name).map_err(KeyringError::from)
+    }
+}
+
+/// Copies a secret loaded from the keyring into a key buffer, checking
+/// that the lengths match. The loaded secret is wiped either way.
+fn copy_secret(mut secret: Vec<u8>, value: &mut [u8]) -> Result<(), KeyringError> {
+    let result = if secret.len() == value.len() {
+        value.copy_from_slice(&secret);
+        Ok(())
+    } else {
+        Err(KeyringError::InvalidLength(secret.len()))
+    };
+    wipe(&mut secret[..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_secret_checks_length() {
+        let mut value = [0u8; 10];
+        copy_secret(vec![0xAB; 10], &mut value).unwrap();
+        assert_eq!(value, [0xAB; 10]);
+
+        match copy_secret(vec![0xAB; 16], &mut value) {
+            Err(KeyringError::InvalidLength(16)) => (),
+            _ => panic!("Expected a length error"),
+        }
+    }
+
+    #[test]
+    fn test_missing_entry_maps_to_not_found() {
+        match KeyringError::from(::keyring::Error::NoEntry) {
+            KeyringError::NotFound => (),
+            _ => panic!("Expected NotFound"),
+        }
+    }
+}
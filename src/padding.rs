@@ -0,0 +1,165 @@
+//! Padding schemes for the modes of operation that work on whole blocks.
+//!
+//! ECB, CBC and PCBC pad the plaintext to a multiple of the block size
+//! before encryption. By default, PKCS#7 padding is used, but other
+//! schemes can be selected with
+//! [`encrypt_bytes_with_padding`](../fn.encrypt_bytes_with_padding.html)
+//! and [`decrypt_bytes_with_padding`](../fn.decrypt_bytes_with_padding.html)
+//! to exchange data with other implementations.
+//!
+//! All schemes add between 1 and 8 bytes, so a full block of padding
+//! is added to plaintext that is already aligned.
+//!
+//! # Examples
+//!
+//! ```
+//! use present::{decrypt_bytes_with_padding, encrypt_bytes_with_padding, Key80Bit, OpMode};
+//! use present::padding::AnsiX923;
+//!
+//! let key = Key80Bit::new([0xFF; 10]);
+//! let (ciphertext, iv) = encrypt_bytes_with_padding(b"Hello, world!", &key, &OpMode::CBC, &AnsiX923).unwrap().into_parts();
+//! assert_eq!(ciphertext.len(), 16);
+//!
+//! let plaintext = decrypt_bytes_with_padding(&ciphertext, &key, &OpMode::CBC, iv, &AnsiX923);
+//! assert_eq!(plaintext.unwrap(), b"Hello, world!");
+//! ```
+
+use {add_padding, check_padding};
+
+/// A way of filling up the final block of the plaintext.
+///
+/// Implementations must add at least one byte of padding, so that it
+/// can always be told apart from the plaintext.
+pub trait PaddingScheme {
+    /// Pads the final block, whose first `len` bytes hold the end of the
+    /// plaintext. `len` is less than 8.
+    fn pad(&self, block: &mut [u8; 8], len: usize);
+
+    /// Returns the number of padding bytes at the end of the final
+    /// block, or `None` if the padding is invalid. Lengths outside of
+    /// 1 to 8 are treated as invalid padding as well.
+    fn unpad(&self, block: &[u8; 8]) -> Option<usize>;
+}
+
+/// PKCS#7 padding, which fills the block with bytes that all hold the
+/// number of padding bytes. This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pkcs7;
+
+impl PaddingScheme for Pkcs7 {
+    fn pad(&self, block: &mut [u8; 8], len: usize) {
+        add_padding(block, 8 - len);
+    }
+
+    fn unpad(&self, block: &[u8; 8]) -> Option<usize> {
+        check_padding(block, 0).ok()
+    }
+}
+
+/// Zero padding, which fills the block with zero bytes.
+///
+/// Zero bytes at the end of the plaintext cannot be told apart from
+/// the padding and are removed along with it, so only use this scheme
+/// for plaintext that does not end in zero bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZeroPadding;
+
+impl PaddingScheme for ZeroPadding {
+    fn pad(&self, block: &mut [u8; 8], len: usize) {
+        for byte in block[len..].iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    fn unpad(&self, block: &[u8; 8]) -> Option<usize> {
+        match block.iter().rev().take_while(|&&byte| byte == 0).count() {
+            0 => None,
+            pad => Some(pad),
+        }
+    }
+}
+
+/// Padding as specified in ISO/IEC 7816-4, which adds a single `0x80`
+/// byte followed by zero bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Iso7816;
+
+impl PaddingScheme for Iso7816 {
+    fn pad(&self, block: &mut [u8; 8], len: usize) {
+        block[len] = 0x80;
+        for byte in block[(len + 1)..].iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    fn unpad(&self, block: &[u8; 8]) -> Option<usize> {
+        let zeros = block.iter().rev().take_while(|&&byte| byte == 0).count();
+        if zeros < 8 && block[7 - zeros] == 0x80 {
+            Some(zeros + 1)
+        } else {
+            None
+        }
+    }
+}
+
+/// Padding as specified in ANSI X9.23, which adds zero bytes and ends
+/// the block with the number of padding bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AnsiX923;
+
+impl PaddingScheme for AnsiX923 {
+    fn pad(&self, block: &mut [u8; 8], len: usize) {
+        for byte in block[len..7].iter_mut() {
+            *byte = 0;
+        }
+        block[7] = (8 - len) as u8;
+    }
+
+    fn unpad(&self, block: &[u8; 8]) -> Option<usize> {
+        let pad = block[7] as usize;
+        if pad == 0 || pad > 8 || block[(8 - pad)..7].iter().any(|&byte| byte != 0) {
+            return None;
+        }
+        Some(pad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn padded<P: PaddingScheme>(padding: &P, data: &[u8]) -> [u8; 8] {
+        let mut block = [0xFF; 8];
+        block[..data.len()].copy_from_slice(data);
+        padding.pad(&mut block, data.len());
+        assert_eq!(padding.unpad(&block), Some(8 - data.len()));
+        block
+    }
+
+    #[test]
+    fn test_pad_and_unpad() {
+        assert_eq!(padded(&Pkcs7, &[1, 2, 3]), [1, 2, 3, 5, 5, 5, 5, 5]);
+        assert_eq!(padded(&ZeroPadding, &[1, 2, 3]), [1, 2, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(padded(&Iso7816, &[1, 2, 3]), [1, 2, 3, 0x80, 0, 0, 0, 0]);
+        assert_eq!(padded(&AnsiX923, &[1, 2, 3]), [1, 2, 3, 0, 0, 0, 0, 5]);
+
+        assert_eq!(padded(&Pkcs7, &[]), [8; 8]);
+        assert_eq!(padded(&ZeroPadding, &[]), [0; 8]);
+        assert_eq!(padded(&Iso7816, &[]), [0x80, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(padded(&AnsiX923, &[]), [0, 0, 0, 0, 0, 0, 0, 8]);
+
+        assert_eq!(padded(&Iso7816, &[1, 2, 3, 4, 5, 6, 7]), [1, 2, 3, 4, 5, 6, 7, 0x80]);
+        assert_eq!(padded(&AnsiX923, &[1, 2, 3, 4, 5, 6, 7]), [1, 2, 3, 4, 5, 6, 7, 1]);
+    }
+
+    #[test]
+    fn test_unpad_rejects_invalid_padding() {
+        assert_eq!(ZeroPadding.unpad(&[0, 0, 0, 0, 0, 0, 0, 1]), None);
+        assert_eq!(Iso7816.unpad(&[0; 8]), None);
+        assert_eq!(Pkcs7.unpad(&[1, 2, 3, 4, 5, 6, 7, 0]), None);
+        assert_eq!(Iso7816.unpad(&[1, 2, 3, 0x81, 0, 0, 0, 0]), None);
+        assert_eq!(AnsiX923.unpad(&[1, 2, 3, 0, 0, 0, 0, 9]), None);
+        assert_eq!(AnsiX923.unpad(&[1, 2, 3, 0, 0, 0, 0, 0]), None);
+        assert_eq!(AnsiX923.unpad(&[1, 2, 3, 0, 1, 0, 0, 5]), None);
+    }
+}
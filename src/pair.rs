@@ -0,0 +1,125 @@
+use block::{encrypt_block8, decrypt_block8};
+use keys::RoundKeys;
+use pbox;
+use profile::{self, Stage};
+use sbox;
+#[cfg(all(feature = "tables", not(feature = "ct-sbox")))]
+use tables;
+
+/// Encrypts two independent blocks in lockstep.
+///
+/// Both states are held in a single `u128`, so the round key addition
+/// covers both blocks at once, and the lookups of the two blocks are
+/// independent of each other, which lets the processor overlap them.
+/// The first block is in the high half, so a pair converts to and from
+/// 16 bytes with `u128::from_be_bytes()` and `to_be_bytes()`.
+pub fn encrypt_pair(mut pair: u128, round_keys: &RoundKeys) -> u128 {
+    profile::count_block();
+    profile::count_block();
+
+    for round_key in round_keys.iter().take(31) {
+        pair ^= duplicate(round_key.value);
+
+        // Same as in `Block::encrypt_rounds()`, the fused tables do both
+        // layers at once
+        #[cfg(all(feature = "tables", not(feature = "ct-sbox")))]
+        {
+            pair = profile::measure(Stage::Substitution, || substitute(pair, tables::substitute_permute));
+        }
+        #[cfg(any(not(feature = "tables"), feature = "ct-sbox"))]
+        {
+            pair = profile::measure(Stage::Substitution, || substitute(pair, sbox::apply_enc_state));
+            pair = profile::measure(Stage::Permutation, || permute(pair, pbox::apply_enc));
+        }
+    }
+
+    pair ^ duplicate(round_keys[31].value)
+}
+
+/// Decrypts two independent blocks in lockstep.
+///
+/// This is the inverse of `encrypt_pair()`.
+pub fn decrypt_pair(mut pair: u128, round_keys: &RoundKeys) -> u128 {
+    profile::count_block();
+    profile::count_block();
+
+    for round in (1..32).rev() {
+        pair ^= duplicate(round_keys[round].value);
+        pair = profile::measure(Stage::Permutation, || permute(pair, pbox::apply_dec));
+        pair = profile::measure(Stage::Substitution, || substitute(pair, sbox::apply_dec_state));
+    }
+
+    pair ^ duplicate(round_keys[0].value)
+}
+
+/// Encrypts independent blocks, two at a time. The length of the
+/// data must be a multiple of the block size.
+pub fn encrypt_blocks(blocks: &mut [u8], round_keys: &RoundKeys) {
+    process_blocks(blocks, round_keys, encrypt_pair, encrypt_block8);
+}
+
+/// Decrypts independent blocks, two at a time. The length of the
+/// data must be a multiple of the block size.
+pub fn decrypt_blocks(blocks: &mut [u8], round_keys: &RoundKeys) {
+    process_blocks(blocks, round_keys, decrypt_pair, decrypt_block8);
+}
+
+fn process_blocks<P, S>(blocks: &mut [u8], round_keys: &RoundKeys, process_pair: P, process_single: S)
+    where P: Fn(u128, &RoundKeys) -> u128, S: Fn(&mut [u8; 8], &RoundKeys) {
+
+    let mut pairs = blocks.chunks_exact_mut(16);
+    for chunk in &mut pairs {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(chunk);
+        chunk.copy_from_slice(&process_pair(u128::from_be_bytes(bytes), round_keys).to_be_bytes());
+    }
+
+    for chunk in pairs.into_remainder().chunks_exact_mut(8) {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(chunk);
+        process_single(&mut bytes, round_keys);
+        chunk.copy_from_slice(&bytes);
+    }
+}
+
+fn duplicate(value: u64) -> u128 {
+    ((value as u128) << 64) | value as u128
+}
+
+fn substitute<F: Fn(u64) -> u64>(pair: u128, sbox: F) -> u128 {
+    ((sbox((pair >> 64) as u64) as u128) << 64) | sbox(pair as u64) as u128
+}
+
+fn permute<F: Fn(u64) -> u64>(pair: u128, pbox: F) -> u128 {
+    ((pbox((pair >> 64) as u64) as u128) << 64) | pbox(pair as u64) as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::{Key, Key80Bit};
+
+    fn join(first: [u8; 8], second: [u8; 8]) -> u128 {
+        ((u64::from_be_bytes(first) as u128) << 64) | u64::from_be_bytes(second) as u128
+    }
+
+    #[test]
+    fn test_matches_block_encryption() {
+        let round_keys = Key80Bit::new([0xFF; 10]).generate_round_keys();
+        let first = [0xFF; 8];
+        let second = [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+
+        let pair = encrypt_pair(join(first, second), &round_keys);
+        let mut a = ((pair >> 64) as u64).to_be_bytes();
+        let mut b = (pair as u64).to_be_bytes();
+        assert_eq!(a, [0x33, 0x33, 0xDC, 0xD3, 0x21, 0x32, 0x10, 0xD2]);
+        let mut expected = second;
+        encrypt_block8(&mut expected, &round_keys);
+        assert_eq!(b, expected);
+
+        assert_eq!(decrypt_pair(pair, &round_keys), join(first, second));
+        decrypt_block8(&mut a, &round_keys);
+        decrypt_block8(&mut b, &round_keys);
+        assert_eq!((a, b), (first, second));
+    }
+}
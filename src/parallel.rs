@@ -0,0 +1,133 @@
+#[cfg(feature = "insecure-modes")]
+use rayon::prelude::*;
+
+use block::Block;
+use ciphertext::Ciphertext;
+#[cfg(feature = "insecure-modes")]
+use context::Expanded;
+use errors::{DecryptError, EncryptError};
+use keys::Key;
+use modes::OpMode;
+#[cfg(feature = "insecure-modes")]
+use padding::Pkcs7;
+use {decrypt_bytes, encrypt_bytes};
+#[cfg(feature = "insecure-modes")]
+use {padded, remove_padding, report_encryption, report_mode};
+
+/// Number of blocks each thread processes at a time.
+#[cfg(feature = "insecure-modes")]
+const BLOCKS_PER_TASK: usize = 4096;
+
+/// Encrypt arbitrary binary data on multiple threads.
+///
+/// Works like [`encrypt_bytes`](fn.encrypt_bytes.html) and produces the
+/// same output, but splits ECB encryption across the threads of the
+/// rayon thread pool. Only pays off for inputs of several hundred
+/// kilobytes or more. The chaining of the other modes makes every
+/// block depend on the previous one, so they are encrypted on the
+/// calling thread as usual. For counter mode, see
+/// [`Ctr::apply_keystream_par()`](struct.Ctr.html#method.apply_keystream_par).
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_bytes_par, decrypt_bytes_par, Key80Bit, OpMode};
+/// # #[cfg(feature = "insecure-modes")]
+/// # #[allow(deprecated)]
+/// # fn main() {
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let payload = vec![0x5A; 1 << 20];
+/// let (ciphertext, iv) = encrypt_bytes_par(&payload, &key, &OpMode::ECB).unwrap().into_parts();
+///
+/// assert_eq!(decrypt_bytes_par(&ciphertext, &key, &OpMode::ECB, iv).unwrap(), payload);
+/// # }
+/// # #[cfg(not(feature = "insecure-modes"))]
+/// # fn main() {}
+/// ```
+pub fn encrypt_bytes_par<K: Key>(plaintext: &[u8], key: &K, mode: &OpMode) -> Result<Ciphertext, EncryptError> {
+    match *mode {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        OpMode::ECB => {
+            let mut buffer = padded(plaintext, &Pkcs7);
+            let cipher = Expanded::new(key);
+            report_encryption(mode, &cipher, &Block::new(0), buffer.len() / 8);
+            buffer.par_chunks_mut(BLOCKS_PER_TASK * 8).for_each(|chunk| cipher.encrypt_blocks(chunk));
+            Ok(Ciphertext::new(buffer, *mode, None))
+        },
+        _ => encrypt_bytes(plaintext, key, mode),
+    }
+}
+
+/// Decrypt arbitrary binary data on multiple threads.
+///
+/// This is the counterpart of
+/// [`encrypt_bytes_par`](fn.encrypt_bytes_par.html) and works like
+/// [`decrypt_bytes`](fn.decrypt_bytes.html).
+///
+/// # Errors
+///
+/// Returns `Err` with a `DecryptError` if an error occurred during decryption. See the
+/// [documentation of `DecryptError`](enum.DecryptError.html) for details.
+pub fn decrypt_bytes_par<K: Key>(ciphertext: &[u8], key: &K, mode: &OpMode, init_vec: Option<Block>) -> Result<Vec<u8>, DecryptError> {
+    match *mode {
+        #[cfg(feature = "insecure-modes")]
+        #[allow(deprecated)]
+        OpMode::ECB if ciphertext.len() >= 8 && ciphertext.len().is_multiple_of(8) => {
+            report_mode(mode);
+
+            let cipher = Expanded::new(key);
+            let mut buffer = ciphertext.to_vec();
+            buffer.par_chunks_mut(BLOCKS_PER_TASK * 8).for_each(|chunk| cipher.decrypt_blocks(chunk));
+            remove_padding(buffer, &Pkcs7)
+        },
+        // Invalid lengths are reported by the sequential implementation
+        _ => decrypt_bytes(ciphertext, key, mode, init_vec),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ctr::Ctr;
+    use keys::Key80Bit;
+
+    #[cfg(feature = "insecure-modes")]
+    #[allow(deprecated)]
+    #[test]
+    fn test_matches_sequential_ecb() {
+        let key = Key80Bit::new([0x24; 10]);
+        for &len in [0usize, 7, 8, 100_003].iter() {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let (expected, _) = encrypt_bytes(&plaintext, &key, &OpMode::ECB).unwrap().into_parts();
+            let (ciphertext, iv) = encrypt_bytes_par(&plaintext, &key, &OpMode::ECB).unwrap().into_parts();
+            assert_eq!(ciphertext, expected);
+            assert!(iv.is_none());
+            assert_eq!(decrypt_bytes_par(&ciphertext, &key, &OpMode::ECB, None).unwrap(), plaintext);
+        }
+
+        assert!(matches!(decrypt_bytes_par(&[0; 9], &key, &OpMode::ECB, None), Err(DecryptError::CiphertextNotAligned { len: 9, .. })));
+    }
+
+    #[test]
+    fn test_chained_modes_fall_back() {
+        let key = Key80Bit::new([0x24; 10]);
+        let (ciphertext, iv) = encrypt_bytes_par(b"chained", &key, &OpMode::CBC).unwrap().into_parts();
+        assert_eq!(decrypt_bytes_par(&ciphertext, &key, &OpMode::CBC, iv).unwrap(), b"chained");
+    }
+
+    #[test]
+    fn test_ctr_matches_sequential() {
+        let key = Key80Bit::new([0x24; 10]);
+        let mut expected = vec![0x33u8; 300_001];
+        Ctr::new(&key, &Block::new(u64::MAX - 5)).apply_keystream(&mut expected);
+
+        let mut data = vec![0x33u8; 300_001];
+        let mut ctr = Ctr::new(&key, &Block::new(u64::MAX - 5));
+        ctr.apply_keystream(&mut data[..3]);
+        ctr.apply_keystream_par(&mut data[3..300_000]);
+        assert_eq!(ctr.current_pos(), 300_000);
+        ctr.apply_keystream(&mut data[300_000..]);
+        assert_eq!(data, expected);
+    }
+}
@@ -1,49 +1,133 @@
-use std::cmp::Ordering;
+/// Target bit position of each input bit during encryption.
+const P_ENC: [u8; 64] = positions(false);
 
-lazy_static! {
-    pub static ref P_BOX: PBox = PBox::new();
-}
+/// Target bit position of each input bit during decryption.
+#[cfg(any(test, not(feature = "ct-sbox")))]
+const P_DEC: [u8; 64] = positions(true);
 
-pub struct PBox {
-    // This currently does not require any fields, but to keep it consistent
-    // with the SBox implementation, I left it like this
-}
+/// The permuted output of every value of each state byte during
+/// encryption, so the permutation takes eight lookups instead of
+/// moving 64 single bits.
+#[cfg(not(feature = "ct-sbox"))]
+static P_ENC_BYTES: [[u64; 256]; 8] = byte_tables(&P_ENC);
+
+/// The byte tables for decryption.
+#[cfg(not(feature = "ct-sbox"))]
+static P_DEC_BYTES: [[u64; 256]; 8] = byte_tables(&P_DEC);
 
-impl PBox {
-    fn new() -> Self {
-		PBox { }
+const fn positions(inverse: bool) -> [u8; 64] {
+    let mut table = [0u8; 64];
+    let mut bit = 0;
+    while bit < 64 {
+        table[bit] = if inverse {
+            (bit / 16) + (bit % 16) * 4
+        } else {
+            (bit % 4) * 16 + (bit / 4)
+        } as u8;
+        bit += 1;
     }
+    table
+}
 
-    fn apply<F>(&self, calc_bit: F, input: u64) -> u64
-        where F: Fn(u32) -> u32 {
+const fn apply_bitwise(table: &[u8; 64], input: u64) -> u64 {
+    // Iterate over all input bits, move each to its new position
+    let mut output = 0u64;
+    let mut bit = 0;
+    while bit < 64 {
+        output |= ((input >> bit) & 1) << table[bit];
+        bit += 1;
+    }
+    output
+}
 
-        // Iterate over all input bits, shift to new position, add to result
-        let mut output = 0u64;
-        for bit in 0..64 {
-            let new_bit = calc_bit(bit);
-            let bit_value = input & (2u64.pow(bit));
-            let new_bit_value = match bit.cmp(&new_bit) {
-                Ordering::Less => bit_value << (new_bit - bit),
-                Ordering::Equal => bit_value,
-                Ordering::Greater => bit_value >> (bit - new_bit),
-            };
-            output += new_bit_value;
+#[cfg(not(feature = "ct-sbox"))]
+const fn byte_tables(table: &[u8; 64]) -> [[u64; 256]; 8] {
+    let mut tables = [[0u64; 256]; 8];
+    let mut byte = 0;
+    while byte < 8 {
+        let mut value = 0;
+        while value < 256 {
+            tables[byte][value] = apply_bitwise(table, (value as u64) << (8 * byte));
+            value += 1;
         }
-        output
+        byte += 1;
     }
+    tables
+}
 
-    pub fn apply_enc(&self, input: u64) -> u64 {
-        self.apply(|bit: u32| (bit % 4) * 16 + (bit / 4), input)
+#[cfg(not(feature = "ct-sbox"))]
+fn apply_bytes(tables: &[[u64; 256]; 8], input: u64) -> u64 {
+    let mut output = 0u64;
+    for (byte, table) in tables.iter().enumerate() {
+        output |= table[((input >> (8 * byte)) & 0xFF) as usize];
     }
+    output
+}
 
-    pub fn apply_dec(&self, input: u64) -> u64 {
-        self.apply(|bit: u32| (bit / 16) + (bit % 16) * 4, input)
-    }
+/// Applies PRESENT's bit permutation.
+#[cfg(not(feature = "ct-sbox"))]
+pub fn apply_enc(input: u64) -> u64 {
+    apply_bytes(&P_ENC_BYTES, input)
+}
+
+/// Applies the inverse of PRESENT's bit permutation.
+#[cfg(not(feature = "ct-sbox"))]
+pub fn apply_dec(input: u64) -> u64 {
+    apply_bytes(&P_DEC_BYTES, input)
+}
+
+/// Shifts and masks of the delta swaps that make up the permutation.
+///
+/// Bit `4 * i + j` moves to `16 * j + i`, which rotates the six bits of
+/// the bit index by two. Each delta swap exchanges two bits of the
+/// index, and four of them make up the rotation.
+#[cfg(feature = "ct-sbox")]
+const DELTA_SWAPS: [(u32, u64); 4] = [
+    (3, 0x0A0A0A0A0A0A0A0A),
+    (6, 0x00CC00CC00CC00CC),
+    (12, 0x0000F0F00000F0F0),
+    (24, 0x00000000FF00FF00),
+];
+
+#[cfg(feature = "ct-sbox")]
+fn delta_swap(input: u64, shift: u32, mask: u64) -> u64 {
+    let t = ((input >> shift) ^ input) & mask;
+    input ^ t ^ (t << shift)
+}
+
+/// Applies PRESENT's bit permutation with shifts and masks only, so
+/// the running time does not depend on the state.
+#[cfg(feature = "ct-sbox")]
+pub fn apply_enc(input: u64) -> u64 {
+    DELTA_SWAPS.iter().fold(input, |state, &(shift, mask)| delta_swap(state, shift, mask))
+}
+
+/// Applies the inverse of PRESENT's bit permutation with shifts and
+/// masks only.
+#[cfg(feature = "ct-sbox")]
+pub fn apply_dec(input: u64) -> u64 {
+    DELTA_SWAPS.iter().rev().fold(input, |state, &(shift, mask)| delta_swap(state, shift, mask))
+}
+
+/// Applies PRESENT's bit permutation bit by bit. Unlike
+/// [`apply_enc()`](fn.apply_enc.html), this can be evaluated at compile
+/// time, e.g. to build the tables of the `tables` feature.
+#[cfg_attr(any(not(feature = "tables"), feature = "ct-sbox"), allow(dead_code))]
+pub const fn apply_enc_bitwise(input: u64) -> u64 {
+    apply_bitwise(&P_ENC, input)
+}
+
+/// Applies the inverse of PRESENT's bit permutation bit by bit, at
+/// compile time if needed.
+#[cfg(test)]
+pub const fn apply_dec_bitwise(input: u64) -> u64 {
+    apply_bitwise(&P_DEC, input)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use testing::states;
 
     #[test]
     fn test_that_pbox_gives_correct_outputs() {
@@ -52,7 +136,33 @@ mod tests {
         // 0000000000000001101001101110011101100011100111100110000101100110
         // 0000101000110000000001111001101100001111110110110001000101100100
         //    |60  |55  |50  |45  |40  |35  |30  |25  |20  |15  |10  |5   |0
-        assert_eq!(P_BOX.apply_enc(0x1A6E7639E6166_u64), 0xA30079B0FDB1164_u64);
-        assert_eq!(P_BOX.apply_dec(0xA30079B0FDB1164_u64), 0x1A6E7639E6166_u64);
+        assert_eq!(apply_enc(0x1A6E7639E6166_u64), 0xA30079B0FDB1164_u64);
+        assert_eq!(apply_dec(0xA30079B0FDB1164_u64), 0x1A6E7639E6166_u64);
+    }
+
+    #[test]
+    fn test_that_pbox_works_in_const_context() {
+        const OUTPUT: u64 = apply_enc_bitwise(0x1A6E7639E6166);
+        const INPUT: u64 = apply_dec_bitwise(OUTPUT);
+        assert_eq!(OUTPUT, 0xA30079B0FDB1164);
+        assert_eq!(INPUT, 0x1A6E7639E6166);
+    }
+
+    #[test]
+    #[cfg(feature = "ct-sbox")]
+    fn test_that_delta_swaps_move_every_bit() {
+        for bit in 0..64 {
+            assert_eq!(apply_enc(1 << bit), 1 << P_ENC[bit]);
+            assert_eq!(apply_dec(1 << bit), 1 << P_DEC[bit]);
+        }
+    }
+
+    #[test]
+    fn test_that_byte_tables_match_bitwise_permutation() {
+        for &value in states(64).iter() {
+            assert_eq!(apply_enc(value), apply_bitwise(&P_ENC, value));
+            assert_eq!(apply_dec(value), apply_bitwise(&P_DEC, value));
+            assert_eq!(apply_dec(apply_enc(value)), value);
+        }
     }
 }
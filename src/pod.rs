@@ -0,0 +1,94 @@
+use bytemuck::{self, Pod};
+
+use block::Block;
+use ciphertext::Ciphertext;
+use errors::{DecryptError, EncryptError};
+use keys::Key;
+use modes::OpMode;
+use {decrypt_bytes, encrypt_bytes};
+
+/// Encrypt a plain-old-data value.
+///
+/// Works like [`encrypt_str`](fn.encrypt_str.html), but encrypts the
+/// in-memory representation of any type implementing `bytemuck::Pod`.
+/// This allows fixed-layout records, such as telemetry or sensor frames,
+/// to be encrypted without serializing them first.
+///
+/// Note that the in-memory representation depends on the byte order of
+/// the platform, so the value should only be decrypted on a platform
+/// with the same endianness.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_pod, decrypt_pod, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0xFF; 10]);
+/// let reading: [u16; 5] = [21, 1013, 47, 0, 3300];
+/// let (ciphertext, iv) = encrypt_pod(&reading, &key, &OpMode::CBC).unwrap().into_parts();
+///
+/// let decrypted: [u16; 5] = decrypt_pod(&ciphertext, &key, &OpMode::CBC, iv).unwrap();
+/// assert_eq!(decrypted, reading);
+/// ```
+pub fn encrypt_pod<T: Pod, K: Key>(value: &T, key: &K, mode: &OpMode) -> Result<Ciphertext, EncryptError> {
+    encrypt_bytes(bytemuck::bytes_of(value), key, mode)
+}
+
+/// Decrypt a plain-old-data value.
+///
+/// Works like [`decrypt_str`](fn.decrypt_str.html), but reinterprets the
+/// decrypted bytes as a value of type `T`.
+///
+/// # Errors
+///
+/// In addition to the errors returned by `decrypt_str`, this returns
+/// `DecryptError::PlaintextLengthMismatch` if the decrypted data does
+/// not have the size of `T`.
+pub fn decrypt_pod<T: Pod, K: Key>(ciphertext: &[u8], key: &K, mode: &OpMode, init_vec: Option<Block>) -> Result<T, DecryptError> {
+    let plain_bytes = decrypt_bytes(ciphertext, key, mode, init_vec)?;
+    if plain_bytes.len() != ::std::mem::size_of::<T>() {
+        return Err(DecryptError::PlaintextLengthMismatch { len: plain_bytes.len(), expected: ::std::mem::size_of::<T>() });
+    }
+
+    Ok(bytemuck::pod_read_unaligned(&plain_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key128Bit;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Frame {
+        timestamp: u32,
+        sensor: u16,
+        flags: u16,
+        value: f32,
+    }
+
+    unsafe impl bytemuck::Zeroable for Frame {}
+    unsafe impl Pod for Frame {}
+
+    #[test]
+    fn test_struct_roundtrip() {
+        let key = Key128Bit::new([0x42; 16]);
+        let frame = Frame { timestamp: 1700000000, sensor: 7, flags: 0x8001, value: 23.5 };
+
+        for mode in &[OpMode::CBC, OpMode::PCBC] {
+            let (ciphertext, iv) = encrypt_pod(&frame, &key, mode).unwrap().into_parts();
+            assert_eq!(ciphertext.len(), 16);
+            let decrypted: Frame = decrypt_pod(&ciphertext, &key, mode, iv).unwrap();
+            assert_eq!(decrypted, frame);
+        }
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let key = Key128Bit::new([0x42; 16]);
+        let (ciphertext, iv) = encrypt_pod(&[1u8; 12], &key, &OpMode::CBC).unwrap().into_parts();
+        match decrypt_pod::<u64, _>(&ciphertext, &key, &OpMode::CBC, iv) {
+            Err(DecryptError::PlaintextLengthMismatch { len: 12, expected: 8 }) => {},
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
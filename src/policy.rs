@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+
+use context::Expanded;
+use keys::{fingerprint_of, KeyFingerprint};
+use tag::TagLen;
+
+/// A security-relevant event reported to the installed
+/// [`SecurityPolicy`](trait.SecurityPolicy.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SecurityEvent {
+    /// ECB was selected as the mode of operation for encryption or
+    /// decryption. ECB leaks patterns in the plaintext.
+    EcbSelected,
+    /// An IV was used for encryption that was already used before with
+    /// the same key, among the last
+    /// [`iv_history()`](trait.SecurityPolicy.html#method.iv_history)
+    /// IVs of the key. Includes the fingerprint of the key.
+    IvReuse { key: KeyFingerprint },
+    /// The blocks encrypted under one key since the policy was
+    /// installed reached the policy's
+    /// [`block_limit()`](trait.SecurityPolicy.html#method.block_limit).
+    /// Includes the fingerprint of the key and the total number of
+    /// blocks, and is reported for every encryption from then on. With
+    /// PRESENT's 64-bit blocks, collisions become likely after about
+    /// 2^32 blocks per key.
+    BlockLimitApproached { key: KeyFingerprint, blocks: u64 },
+    /// An AEAD mode was set up or used with a tag shorter than
+    /// [`TagLen::FULL`](struct.TagLen.html#associatedconstant.FULL).
+    /// Includes the tag length in bytes.
+    ShortTag { bytes: usize },
+}
+
+/// A hook that is notified of risky operations.
+///
+/// Install a policy with [`set_security_policy`](fn.set_security_policy.html)
+/// to log, alert or hard-fail according to your own rules. A policy
+/// that wants to hard-fail can panic in `on_event`, which aborts the
+/// operation before any data is processed.
+///
+/// While a policy is installed, the blocks and IVs used for encryption
+/// are tracked per key, identified by its
+/// [`fingerprint()`](trait.Key.html#method.fingerprint). Decryption is
+/// not tracked. Installing or clearing a policy resets the tracking.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use present::{set_security_policy, clear_security_policy,
+///               Key80Bit, SecurityEvent, SecurityPolicy};
+///
+/// struct CountEcb(AtomicUsize);
+///
+/// impl SecurityPolicy for CountEcb {
+///     fn on_event(&self, event: &SecurityEvent) {
+///         if *event == SecurityEvent::EcbSelected {
+///             self.0.fetch_add(1, Ordering::SeqCst);
+///         }
+///     }
+/// }
+///
+/// # #[cfg(feature = "insecure-modes")]
+/// # #[allow(deprecated)]
+/// # fn main() {
+/// use present::EncryptBlocks;
+///
+/// let policy = Arc::new(CountEcb(AtomicUsize::new(0)));
+/// set_security_policy(policy.clone());
+/// EncryptBlocks::new(vec![[0u8; 8]], &Key80Bit::new([0xFF; 10])).count();
+/// clear_security_policy();
+/// assert_eq!(policy.0.load(Ordering::SeqCst), 1);
+/// # }
+/// # #[cfg(not(feature = "insecure-modes"))]
+/// # fn main() {}
+/// ```
+pub trait SecurityPolicy: Send + Sync {
+    /// Called whenever a security-relevant event occurs.
+    fn on_event(&self, event: &SecurityEvent);
+
+    /// The number of blocks per key from which on
+    /// `SecurityEvent::BlockLimitApproached` is reported.
+    /// Defaults to 2^30 blocks (8 GiB).
+    fn block_limit(&self) -> u64 {
+        1 << 30
+    }
+
+    /// The number of IVs per key that are remembered to detect
+    /// `SecurityEvent::IvReuse`. Defaults to 4096.
+    fn iv_history(&self) -> usize {
+        4096
+    }
+}
+
+static POLICY: RwLock<Option<Arc<dyn SecurityPolicy>>> = RwLock::new(None);
+
+/// Usage of each key since the policy was installed.
+static USAGE: Mutex<Option<HashMap<KeyFingerprint, Usage>>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Usage {
+    blocks: u64,
+    ivs: HashSet<u64>,
+    /// The remembered IVs, oldest first.
+    iv_order: VecDeque<u64>,
+}
+
+/// Installs a security policy for the whole process, replacing any
+/// previously installed policy.
+pub fn set_security_policy(policy: Arc<dyn SecurityPolicy>) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(policy);
+    reset_usage();
+}
+
+/// Removes the installed security policy, if any.
+pub fn clear_security_policy() {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = None;
+    reset_usage();
+}
+
+/// Reports an event to the installed policy.
+pub fn notify(event: SecurityEvent) {
+    if let Some(policy) = current_policy() {
+        policy.on_event(&event);
+    }
+}
+
+/// Returns the fingerprint of the expanded key if a policy is
+/// installed, so the key is only fingerprinted when its usage is
+/// tracked.
+pub fn fingerprint(cipher: &Expanded) -> Option<KeyFingerprint> {
+    current_policy().map(|_| fingerprint_of(cipher.clone()))
+}
+
+/// Records an encryption of the given number of blocks under the key,
+/// with the IV if the mode uses one. Reports `IvReuse` if the IV was
+/// used before and `BlockLimitApproached` if the total number of
+/// blocks reaches the limit.
+pub fn record_encryption(key: Option<KeyFingerprint>, init_vec: Option<u64>, blocks: u64) {
+    let (key, policy) = match (key, current_policy()) {
+        (Some(key), Some(policy)) => (key, policy),
+        _ => return,
+    };
+
+    let (reused, total) = {
+        let mut usage = USAGE.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = usage.get_or_insert_with(HashMap::new).entry(key).or_default();
+        let reused = init_vec.is_some_and(|iv| !remember_iv(usage, iv, policy.iv_history()));
+        usage.blocks = usage.blocks.saturating_add(blocks);
+        (reused, usage.blocks)
+    };
+
+    // Notify without holding the lock, the policy may panic
+    if reused {
+        policy.on_event(&SecurityEvent::IvReuse { key });
+    }
+    if total >= policy.block_limit() {
+        policy.on_event(&SecurityEvent::BlockLimitApproached { key, blocks: total });
+    }
+}
+
+/// Reports `ShortTag` if the tag length is below the full block.
+pub fn check_tag_len(tag_len: TagLen) {
+    if tag_len.bytes() < TagLen::FULL.bytes() {
+        notify(SecurityEvent::ShortTag { bytes: tag_len.bytes() });
+    }
+}
+
+/// Adds the IV to the history of the key, dropping the oldest one if
+/// the history is full. Returns `false` if it was already there.
+fn remember_iv(usage: &mut Usage, iv: u64, history: usize) -> bool {
+    if !usage.ivs.insert(iv) {
+        return false;
+    }
+    usage.iv_order.push_back(iv);
+    while usage.iv_order.len() > history {
+        if let Some(oldest) = usage.iv_order.pop_front() {
+            usage.ivs.remove(&oldest);
+        }
+    }
+    true
+}
+
+fn reset_usage() {
+    *USAGE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn current_policy() -> Option<Arc<dyn SecurityPolicy>> {
+    // Clone the policy so the lock is not held while it runs
+    POLICY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::Block;
+    use keys::Key80Bit;
+    use modes::OpMode;
+
+    struct Recorder {
+        limit: u64,
+        events: Mutex<Vec<SecurityEvent>>,
+    }
+
+    impl SecurityPolicy for Recorder {
+        fn on_event(&self, event: &SecurityEvent) {
+            self.events.lock().unwrap().push(*event);
+        }
+
+        fn block_limit(&self) -> u64 {
+            self.limit
+        }
+
+        fn iv_history(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn test_policy_receives_events() {
+        let recorder = Arc::new(Recorder { limit: 4, events: Mutex::new(Vec::new()) });
+        set_security_policy(recorder.clone());
+
+        let key = fingerprint(&Expanded::new(&Key80Bit::new([0x93; 10])));
+        let other = fingerprint(&Expanded::new(&Key80Bit::new([0x94; 10])));
+        assert!(key.is_some());
+        record_encryption(key, Some(1), 2);
+        record_encryption(other, Some(1), 3);
+        record_encryption(key, Some(2), 1);
+        record_encryption(key, Some(1), 1);
+        record_encryption(key, Some(3), 0);
+        record_encryption(key, Some(1), 0);
+        let cbc_key = Key80Bit::new([0x95; 10]);
+        for _ in 0..2 {
+            ::try_encrypt_bytes_with_iv(b"same IV", &cbc_key, &OpMode::CBC, &Block::new(7)).unwrap();
+        }
+        let cbc_key = fingerprint(&Expanded::new(&cbc_key)).unwrap();
+        check_tag_len(TagLen::FULL);
+        check_tag_len(TagLen::MIN);
+        notify(SecurityEvent::EcbSelected);
+        clear_security_policy();
+        notify(SecurityEvent::EcbSelected);
+        record_encryption(key, Some(1), 10);
+        assert_eq!(fingerprint(&Expanded::new(&Key80Bit::new([0x93; 10]))), None);
+
+        // Other tests may run while the policy is installed, so only the
+        // events for the keys of this test are compared
+        let (key, other) = (key.unwrap(), other.unwrap());
+        let events = recorder.events.lock().unwrap();
+        let for_key = |fingerprint| events.iter().filter(|event| match **event {
+            SecurityEvent::IvReuse { key } | SecurityEvent::BlockLimitApproached { key, .. } => key == fingerprint,
+            _ => false,
+        }).cloned().collect::<Vec<_>>();
+
+        // The IV 1 is reused once, and forgotten again after two more
+        // IVs. The other key neither reuses IVs nor reaches the limit.
+        assert_eq!(for_key(key), vec![
+            SecurityEvent::IvReuse { key },
+            SecurityEvent::BlockLimitApproached { key, blocks: 4 },
+            SecurityEvent::BlockLimitApproached { key, blocks: 4 },
+            SecurityEvent::BlockLimitApproached { key, blocks: 4 },
+        ]);
+        assert!(for_key(other).is_empty());
+        assert_eq!(for_key(cbc_key)[0], SecurityEvent::IvReuse { key: cbc_key });
+        assert!(events.contains(&SecurityEvent::ShortTag { bytes: 4 }));
+        assert!(events.contains(&SecurityEvent::EcbSelected));
+    }
+}
@@ -0,0 +1,112 @@
+#[cfg(feature = "profiling")]
+use std::cell::RefCell;
+#[cfg(feature = "profiling")]
+use std::time::{Duration, Instant};
+
+/// The stages of an operation that are timed separately.
+#[derive(Clone, Copy)]
+pub enum Stage {
+    KeySchedule,
+    Substitution,
+    Permutation,
+    Chaining,
+}
+
+/// Timing breakdown of the operations performed on the current thread.
+///
+/// Note that the measurement itself adds overhead, which is most
+/// noticeable for the very short substitution and permutation stages.
+/// Compare profiles relative to each other rather than to timings of
+/// builds without the `profiling` feature.
+#[cfg(feature = "profiling")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Profile {
+    /// Time spent generating round keys.
+    pub key_schedule: Duration,
+    /// Time spent in the substitution layer (S-Box).
+    pub substitution: Duration,
+    /// Time spent in the permutation layer (P-Box).
+    pub permutation: Duration,
+    /// Time spent combining blocks in the mode of operation.
+    pub chaining: Duration,
+    /// Number of blocks that were encrypted or decrypted.
+    pub blocks: u64,
+}
+
+#[cfg(feature = "profiling")]
+thread_local! {
+    static PROFILE: RefCell<Profile> = RefCell::new(Profile::default());
+}
+
+/// Returns the profile collected on the current thread and resets it.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_str, take_profile, Key80Bit, OpMode};
+///
+/// take_profile();
+/// encrypt_str("Hello, world!", &Key80Bit::new([0xFF; 10]), &OpMode::CBC).unwrap();
+/// let profile = take_profile();
+/// assert_eq!(profile.blocks, 2);
+/// println!("S-Box: {:?}, P-Box: {:?}", profile.substitution, profile.permutation);
+/// ```
+#[cfg(feature = "profiling")]
+pub fn take_profile() -> Profile {
+    PROFILE.with(|p| ::std::mem::take(&mut *p.borrow_mut()))
+}
+
+/// Runs the given closure and adds its runtime to the given stage.
+#[cfg(feature = "profiling")]
+pub fn measure<T, F: FnOnce() -> T>(stage: Stage, f: F) -> T {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+
+    PROFILE.with(|p| {
+        let mut profile = p.borrow_mut();
+        match stage {
+            Stage::KeySchedule => profile.key_schedule += elapsed,
+            Stage::Substitution => profile.substitution += elapsed,
+            Stage::Permutation => profile.permutation += elapsed,
+            Stage::Chaining => profile.chaining += elapsed,
+        }
+    });
+    result
+}
+
+/// Runs the given closure. Without the `profiling` feature, nothing is measured.
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub fn measure<T, F: FnOnce() -> T>(_stage: Stage, f: F) -> T {
+    f()
+}
+
+/// Counts a processed block.
+#[cfg(feature = "profiling")]
+pub fn count_block() {
+    PROFILE.with(|p| p.borrow_mut().blocks += 1);
+}
+
+/// Counts a processed block. Without the `profiling` feature, nothing is counted.
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub fn count_block() {}
+
+#[cfg(all(test, feature = "profiling"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stages_are_recorded_and_reset() {
+        take_profile();
+        measure(Stage::Chaining, || ::std::thread::sleep(Duration::from_millis(2)));
+        count_block();
+
+        let profile = take_profile();
+        assert!(profile.chaining >= Duration::from_millis(2));
+        assert_eq!(profile.key_schedule, Duration::from_secs(0));
+        assert_eq!(profile.blocks, 1);
+        assert_eq!(take_profile().blocks, 0);
+    }
+}
@@ -0,0 +1,169 @@
+use std::num::NonZeroU32;
+
+use rand_core::{impls, CryptoRng, Error, RngCore, SeedableRng};
+
+use block::encrypt_block8;
+use keys::{Key, Key128Bit, RoundKeys};
+#[cfg(feature = "zeroize")]
+use keys::wipe;
+
+/// Number of blocks after which the output would become
+/// distinguishable from random.
+const MAX_BLOCKS: u64 = 1 << 32;
+
+/// A random number generator backed by PRESENT in counter mode.
+///
+/// The generator encrypts an incrementing 64-bit counter with a fixed
+/// key and hands out the resulting keystream. This allows devices that
+/// already ship PRESENT to seed a general-purpose RNG from the same
+/// primitive and pass it to any crate expecting a `rand_core::RngCore`.
+///
+/// Because of PRESENT's 64-bit block size, the output becomes
+/// distinguishable from random after about 2^32 blocks (32 GiB). The
+/// generator therefore stops there: `fill_bytes` and the other methods
+/// panic, and `try_fill_bytes` returns an error. Long-running
+/// generators have to be replaced with a freshly seeded one before.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate present;
+/// # extern crate rand_core;
+/// use present::CtrRng;
+/// use rand_core::{RngCore, SeedableRng};
+///
+/// # fn main() {
+/// let mut rng = CtrRng::from_seed([0x42; 16]);
+/// let mut bytes = [0u8; 20];
+/// rng.fill_bytes(&mut bytes);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CtrRng {
+    round_keys: RoundKeys,
+    counter: u64,
+    buffer: [u8; 8],
+    index: usize,
+}
+
+impl CtrRng {
+    /// Constructs a new generator from the given key, starting
+    /// with a counter value of zero.
+    pub fn new<K: Key>(key: &K) -> Self {
+        CtrRng {
+            round_keys: key.generate_round_keys(),
+            counter: 0,
+            buffer: [0u8; 8],
+            index: 8,
+        }
+    }
+
+    /// Encrypts the next counter value into the keystream buffer.
+    fn refill(&mut self) {
+        assert!(self.counter < MAX_BLOCKS, "CtrRng is exhausted and has to be reseeded");
+        self.buffer = self.counter.to_be_bytes();
+        encrypt_block8(&mut self.buffer, &self.round_keys);
+        self.counter += 1;
+        self.index = 0;
+    }
+}
+
+impl RngCore for CtrRng {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            if self.index == 8 {
+                self.refill();
+            }
+            *byte = self.buffer[self.index];
+            self.index += 1;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        let remaining = (MAX_BLOCKS - self.counter) * 8 + (8 - self.index) as u64;
+        if dest.len() as u64 > remaining {
+            return Err(Error::from(NonZeroU32::new(Error::CUSTOM_START).unwrap()));
+        }
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for CtrRng {
+    /// The seed is used as a 128-bit PRESENT key.
+    type Seed = [u8; 16];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        CtrRng::new(&Key128Bit::new(seed))
+    }
+}
+
+impl CryptoRng for CtrRng {}
+
+#[cfg(feature = "zeroize")]
+impl Drop for CtrRng {
+    fn drop(&mut self) {
+        wipe(&mut self.round_keys);
+        wipe(&mut self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::Block;
+
+    #[test]
+    fn test_rng_output_is_counter_keystream() {
+        let key = Key128Bit::new([0x5A; 16]);
+        let mut rng = CtrRng::new(&key);
+
+        let mut first = Block::new(0);
+        first.encrypt(&key);
+        let mut second = Block::new(1);
+        second.encrypt(&key);
+
+        let mut bytes = [0u8; 16];
+        rng.fill_bytes(&mut bytes[..3]);
+        rng.fill_bytes(&mut bytes[3..]);
+        assert_eq!(bytes[..8], first.to_bytes());
+        assert_eq!(bytes[8..], second.to_bytes());
+    }
+
+    #[test]
+    fn test_rng_from_seed_is_deterministic() {
+        let mut a = CtrRng::from_seed([0x01; 16]);
+        let mut b = CtrRng::from_seed([0x01; 16]);
+        let mut c = CtrRng::from_seed([0x02; 16]);
+        let value = a.next_u64();
+        assert_eq!(value, b.next_u64());
+        assert_ne!(value, c.next_u64());
+    }
+
+    #[test]
+    fn test_rng_stops_at_block_limit() {
+        let mut rng = CtrRng::from_seed([0x01; 16]);
+        rng.counter = MAX_BLOCKS - 1;
+        let mut bytes = [0u8; 9];
+        assert!(rng.try_fill_bytes(&mut bytes).is_err());
+        assert!(rng.try_fill_bytes(&mut bytes[..5]).is_ok());
+        assert!(rng.try_fill_bytes(&mut bytes[..3]).is_ok());
+        assert!(rng.try_fill_bytes(&mut bytes[..1]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "CtrRng is exhausted")]
+    fn test_rng_panics_when_exhausted() {
+        let mut rng = CtrRng::from_seed([0x01; 16]);
+        rng.counter = MAX_BLOCKS;
+        rng.next_u32();
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use keys::Key;
+
+/// A set of keys identified by numeric IDs, for rotating keys.
+///
+/// New data is encrypted with the current key, and its ID is stored
+/// along with the ciphertext. After a rotation, the previous keys stay
+/// in the ring, so data encrypted before can still be decrypted by
+/// looking up the key with the stored ID. Remove a key once no data
+/// encrypted with it is left.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Key80Bit, KeyRing};
+///
+/// let mut keys = KeyRing::new();
+/// keys.rotate(1, Key80Bit::new([0x01; 10]));
+/// keys.rotate(2, Key80Bit::new([0x02; 10]));
+///
+/// let (id, _key) = keys.current().unwrap();
+/// assert_eq!(id, 2);
+/// assert_eq!(keys.get(1).unwrap().value, [0x01; 10]);
+/// ```
+pub struct KeyRing<K: Key> {
+    keys: BTreeMap<u32, K>,
+    current: Option<u32>,
+}
+
+impl<K: Key> KeyRing<K> {
+    /// Constructs an empty key ring without a current key.
+    pub fn new() -> Self {
+        KeyRing { keys: BTreeMap::new(), current: None }
+    }
+
+    /// Adds a key for decryption only, without changing the current
+    /// key. Returns the key previously stored under the ID, if any.
+    pub fn insert(&mut self, id: u32, key: K) -> Option<K> {
+        self.keys.insert(id, key)
+    }
+
+    /// Adds a key and makes it the current key for encryption. The
+    /// previous current key is kept for decryption. Returns the key
+    /// previously stored under the ID, if any.
+    pub fn rotate(&mut self, id: u32, key: K) -> Option<K> {
+        self.current = Some(id);
+        self.keys.insert(id, key)
+    }
+
+    /// Removes the key with the given ID. If it is the current key,
+    /// the ring has no current key afterwards.
+    pub fn remove(&mut self, id: u32) -> Option<K> {
+        if self.current == Some(id) {
+            self.current = None;
+        }
+        self.keys.remove(&id)
+    }
+
+    /// Returns the ID and the key to encrypt new data with, or `None`
+    /// if no key was added with [`rotate()`](#method.rotate) yet.
+    pub fn current(&self) -> Option<(u32, &K)> {
+        self.current.and_then(|id| self.keys.get(&id).map(|key| (id, key)))
+    }
+
+    /// Returns the key with the given ID.
+    pub fn get(&self, id: u32) -> Option<&K> {
+        self.keys.get(&id)
+    }
+
+    /// Returns the number of keys in the ring.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if the ring holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl<K: Key> Default for KeyRing<K> {
+    fn default() -> Self {
+        KeyRing::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key80Bit;
+
+    #[test]
+    fn test_rotation() {
+        let mut keys = KeyRing::new();
+        assert!(keys.current().is_none());
+
+        keys.insert(1, Key80Bit::new([0x01; 10]));
+        assert!(keys.current().is_none());
+        keys.rotate(2, Key80Bit::new([0x02; 10]));
+        keys.rotate(3, Key80Bit::new([0x03; 10]));
+        assert_eq!(keys.current().map(|(id, key)| (id, key.value)), Some((3, [0x03; 10])));
+        assert_eq!(keys.get(2).map(|key| key.value), Some([0x02; 10]));
+        assert_eq!(keys.len(), 3);
+
+        assert!(keys.remove(3).is_some());
+        assert!(keys.current().is_none());
+        assert!(keys.get(3).is_none());
+        assert_eq!(keys.len(), 2);
+    }
+}
@@ -0,0 +1,526 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use rustcrypto_aead::consts::{U5, U6, U8};
+use rustcrypto_aead::inout::InOutBuf;
+use rustcrypto_aead::{AeadCore, AeadInOut, Error, Nonce, Tag, TagPosition};
+use cipher::consts::{U1, U10, U16};
+use cipher::{
+    AlgorithmName, Block, BlockCipherDecBackend, BlockCipherDecClosure, BlockCipherDecrypt, BlockCipherEncBackend,
+    BlockCipherEncClosure, BlockCipherEncrypt, BlockSizeUser, InOut, InvalidLength, KeyInit, KeySizeUser,
+    OverflowError, ParBlocksSizeUser, SeekNum, StreamCipher, StreamCipherError, StreamCipherSeek,
+};
+use digest::{FixedOutput, FixedOutputReset, MacMarker, Output, OutputSizeUser, Reset, Update};
+
+use aead::Eax;
+use block::{encrypt_block8, decrypt_block8};
+use ccm::Ccm;
+use ctr::Ctr;
+use keys::{Key, Key80Bit, Key128Bit, RoundKeys};
+use mac::Cmac;
+use ocb::Ocb;
+use siv::Siv;
+use tag::TagLen;
+#[cfg(feature = "zeroize")]
+use keys::wipe;
+
+/// The PRESENT block cipher for use with the RustCrypto `cipher` traits.
+///
+/// This exposes the raw primitive through `BlockCipherEncrypt` and
+/// `BlockCipherDecrypt`, so that generic mode implementations, such as
+/// the `cbc` and `ctr` crates, can wrap it.
+///
+/// # Examples
+///
+/// ```
+/// extern crate cipher;
+/// extern crate present;
+///
+/// use cipher::{Block, BlockCipherEncrypt};
+/// use present::{Key80Bit, PresentCipher};
+///
+/// # fn main() {
+/// let cipher = PresentCipher::from_key(&Key80Bit::new([0u8; 10]));
+/// let mut block = Block::<PresentCipher>::default();
+/// cipher.encrypt_block(&mut block);
+/// assert_eq!(block[..], [0x55, 0x79, 0xC1, 0x38, 0x7B, 0x22, 0x84, 0x45]);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PresentCipher {
+    round_keys: RoundKeys,
+}
+
+impl PresentCipher {
+    /// Constructs the cipher from a key.
+    pub fn from_key<K: Key>(key: &K) -> Self {
+        PresentCipher { round_keys: key.generate_round_keys() }
+    }
+}
+
+impl From<RoundKeys> for PresentCipher {
+    fn from(round_keys: RoundKeys) -> Self {
+        PresentCipher { round_keys }
+    }
+}
+
+impl BlockSizeUser for PresentCipher {
+    type BlockSize = U8;
+}
+
+/// The key size is that of 128-bit keys, but `new_from_slice` also
+/// accepts 80-bit keys.
+impl KeySizeUser for PresentCipher {
+    type KeySize = U16;
+}
+
+impl KeyInit for PresentCipher {
+    fn new(key: &cipher::Key<Self>) -> Self {
+        PresentCipher::from_key(&<Key128Bit as KeyInit>::new(key))
+    }
+
+    fn new_from_slice(key: &[u8]) -> Result<Self, InvalidLength> {
+        match key.len() {
+            10 => Ok(PresentCipher::from_key(&<Key80Bit as KeyInit>::new_from_slice(key)?)),
+            _ => Ok(PresentCipher::from_key(&<Key128Bit as KeyInit>::new_from_slice(key)?)),
+        }
+    }
+}
+
+impl AlgorithmName for PresentCipher {
+    fn write_alg_name(f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("PRESENT")
+    }
+}
+
+/// Backend processing one block at a time with the round keys.
+struct Backend<'a>(&'a RoundKeys);
+
+impl<'a> BlockSizeUser for Backend<'a> {
+    type BlockSize = U8;
+}
+
+impl<'a> ParBlocksSizeUser for Backend<'a> {
+    type ParBlocksSize = U1;
+}
+
+impl<'a> BlockCipherEncBackend for Backend<'a> {
+    fn encrypt_block(&self, mut block: InOut<'_, '_, Block<Self>>) {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(block.get_in());
+        encrypt_block8(&mut bytes, self.0);
+        block.get_out().copy_from_slice(&bytes);
+    }
+}
+
+impl<'a> BlockCipherDecBackend for Backend<'a> {
+    fn decrypt_block(&self, mut block: InOut<'_, '_, Block<Self>>) {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(block.get_in());
+        decrypt_block8(&mut bytes, self.0);
+        block.get_out().copy_from_slice(&bytes);
+    }
+}
+
+impl BlockCipherEncrypt for PresentCipher {
+    fn encrypt_with_backend(&self, f: impl BlockCipherEncClosure<BlockSize = Self::BlockSize>) {
+        f.call(&Backend(&self.round_keys))
+    }
+}
+
+impl BlockCipherDecrypt for PresentCipher {
+    fn decrypt_with_backend(&self, f: impl BlockCipherDecClosure<BlockSize = Self::BlockSize>) {
+        f.call(&Backend(&self.round_keys))
+    }
+}
+
+impl KeySizeUser for Key80Bit {
+    type KeySize = U10;
+}
+
+impl KeyInit for Key80Bit {
+    fn new(key: &cipher::Key<Self>) -> Self {
+        let mut value = [0u8; 10];
+        value.copy_from_slice(key);
+        Key80Bit::new(value)
+    }
+}
+
+impl KeySizeUser for Key128Bit {
+    type KeySize = U16;
+}
+
+impl KeyInit for Key128Bit {
+    fn new(key: &cipher::Key<Self>) -> Self {
+        let mut value = [0u8; 16];
+        value.copy_from_slice(key);
+        Key128Bit::new(value)
+    }
+}
+
+impl OutputSizeUser for Cmac {
+    type OutputSize = U8;
+}
+
+impl Update for Cmac {
+    fn update(&mut self, data: &[u8]) {
+        Cmac::update(self, data);
+    }
+}
+
+impl FixedOutput for Cmac {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        out.copy_from_slice(&Cmac::finalize(self));
+    }
+}
+
+impl Reset for Cmac {
+    fn reset(&mut self) {
+        Cmac::reset(self);
+    }
+}
+
+impl FixedOutputReset for Cmac {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(&Cmac::finalize(self.clone()));
+        Cmac::reset(self);
+    }
+}
+
+impl MacMarker for Cmac {}
+
+impl StreamCipher for Ctr {
+    fn check_remaining(&self, data_len: usize) -> Result<(), StreamCipherError> {
+        if self.remaining() < data_len as u64 {
+            return Err(StreamCipherError);
+        }
+        Ok(())
+    }
+
+    fn unchecked_apply_keystream_inout(&mut self, mut buf: InOutBuf<'_, '_, u8>) {
+        for i in 0..buf.len() {
+            let mut byte = buf.get(i);
+            *byte.get_out() = *byte.get_in() ^ self.next_byte();
+        }
+    }
+
+    fn unchecked_write_keystream(&mut self, buf: &mut [u8]) {
+        for byte in buf.iter_mut() {
+            *byte = self.next_byte();
+        }
+    }
+}
+
+/// Positions are in bytes from the initial counter block.
+impl StreamCipherSeek for Ctr {
+    fn try_current_pos<T: SeekNum>(&self) -> Result<T, OverflowError> {
+        // Counted as the byte within the current block, from 1 to 8
+        let pos = Ctr::current_pos(self);
+        match pos % 8 {
+            0 => T::from_block_byte(pos / 8, 8, 8),
+            byte => T::from_block_byte(pos / 8 + 1, byte as u8, 8),
+        }
+    }
+
+    fn try_seek<T: SeekNum>(&mut self, pos: T) -> Result<(), StreamCipherError> {
+        let (block, byte) = pos.into_block_byte::<u64>(8).map_err(|_| StreamCipherError)?;
+        let pos = block.checked_mul(8).and_then(|p| p.checked_add(byte as u64)).ok_or(StreamCipherError)?;
+        Ctr::seek(self, pos);
+        Ok(())
+    }
+}
+
+/// OCB with 6 byte nonces and 8 byte tags.
+///
+/// The RustCrypto traits fix the tag size, so encryption and decryption
+/// through them fail if the `Ocb` instance was created with a truncated
+/// tag length.
+impl AeadCore for Ocb {
+    type NonceSize = U6;
+    type TagSize = U8;
+    const TAG_POSITION: TagPosition = TagPosition::Postfix;
+}
+
+impl AeadInOut for Ocb {
+    fn encrypt_inout_detached(&self, nonce: &Nonce<Self>, associated_data: &[u8], mut buffer: InOutBuf<'_, '_, u8>) -> Result<Tag<Self>, Error> {
+        if self.tag_len() != TagLen::FULL {
+            return Err(Error);
+        }
+
+        let sealed = self.encrypt(nonce, associated_data, buffer.get_in()).map_err(|_| Error)?;
+        let (ciphertext, tag) = sealed.split_at(buffer.len());
+        buffer.get_out().copy_from_slice(ciphertext);
+        Tag::<Self>::try_from(tag).map_err(|_| Error)
+    }
+
+    fn decrypt_inout_detached(&self, nonce: &Nonce<Self>, associated_data: &[u8], mut buffer: InOutBuf<'_, '_, u8>, tag: &Tag<Self>) -> Result<(), Error> {
+        if self.tag_len() != TagLen::FULL {
+            return Err(Error);
+        }
+
+        let mut sealed = buffer.get_in().to_vec();
+        sealed.extend_from_slice(tag);
+        let plaintext = self.decrypt(nonce, associated_data, &sealed).map_err(|_| Error)?;
+        buffer.get_out().copy_from_slice(&plaintext);
+        Ok(())
+    }
+}
+
+/// SIV with 8 byte nonces. The synthetic IV is the tag and precedes the
+/// ciphertext.
+impl AeadCore for Siv {
+    type NonceSize = U8;
+    type TagSize = U8;
+    const TAG_POSITION: TagPosition = TagPosition::Prefix;
+}
+
+impl AeadInOut for Siv {
+    fn encrypt_inout_detached(&self, nonce: &Nonce<Self>, associated_data: &[u8], mut buffer: InOutBuf<'_, '_, u8>) -> Result<Tag<Self>, Error> {
+        let sealed = self.encrypt(nonce, associated_data, buffer.get_in());
+        let (tag, ciphertext) = sealed.split_at(8);
+        buffer.get_out().copy_from_slice(ciphertext);
+        Tag::<Self>::try_from(tag).map_err(|_| Error)
+    }
+
+    fn decrypt_inout_detached(&self, nonce: &Nonce<Self>, associated_data: &[u8], mut buffer: InOutBuf<'_, '_, u8>, tag: &Tag<Self>) -> Result<(), Error> {
+        let mut sealed = tag.to_vec();
+        sealed.extend_from_slice(buffer.get_in());
+        let plaintext = self.decrypt(nonce, associated_data, &sealed).map_err(|_| Error)?;
+        buffer.get_out().copy_from_slice(&plaintext);
+        Ok(())
+    }
+}
+
+/// CCM with 5 byte nonces and 8 byte tags.
+///
+/// The RustCrypto traits fix the nonce and tag sizes, so encryption and
+/// decryption through them fail if the `Ccm` instance was created with
+/// other lengths. With 5 byte nonces, messages can be up to 2^16 - 1
+/// bytes long.
+impl AeadCore for Ccm {
+    type NonceSize = U5;
+    type TagSize = U8;
+    const TAG_POSITION: TagPosition = TagPosition::Postfix;
+}
+
+impl AeadInOut for Ccm {
+    fn encrypt_inout_detached(&self, nonce: &Nonce<Self>, associated_data: &[u8], mut buffer: InOutBuf<'_, '_, u8>) -> Result<Tag<Self>, Error> {
+        if self.nonce_len() != 5 || self.tag_len() != TagLen::FULL {
+            return Err(Error);
+        }
+
+        let sealed = self.encrypt(nonce, associated_data, buffer.get_in()).map_err(|_| Error)?;
+        let (ciphertext, tag) = sealed.split_at(buffer.len());
+        buffer.get_out().copy_from_slice(ciphertext);
+        Tag::<Self>::try_from(tag).map_err(|_| Error)
+    }
+
+    fn decrypt_inout_detached(&self, nonce: &Nonce<Self>, associated_data: &[u8], mut buffer: InOutBuf<'_, '_, u8>, tag: &Tag<Self>) -> Result<(), Error> {
+        if self.nonce_len() != 5 || self.tag_len() != TagLen::FULL {
+            return Err(Error);
+        }
+
+        let mut sealed = buffer.get_in().to_vec();
+        sealed.extend_from_slice(tag);
+        let plaintext = self.decrypt(nonce, associated_data, &sealed).map_err(|_| Error)?;
+        buffer.get_out().copy_from_slice(&plaintext);
+        Ok(())
+    }
+}
+
+/// EAX with 8 byte nonces and 8 byte tags.
+///
+/// EAX accepts nonces of any length, but the RustCrypto traits fix the
+/// size. As for `Ocb`, encryption and decryption through the traits
+/// fail if the `Eax` instance was created with a truncated tag length.
+impl AeadCore for Eax {
+    type NonceSize = U8;
+    type TagSize = U8;
+    const TAG_POSITION: TagPosition = TagPosition::Postfix;
+}
+
+impl AeadInOut for Eax {
+    fn encrypt_inout_detached(&self, nonce: &Nonce<Self>, associated_data: &[u8], mut buffer: InOutBuf<'_, '_, u8>) -> Result<Tag<Self>, Error> {
+        if self.tag_len() != TagLen::FULL {
+            return Err(Error);
+        }
+
+        let sealed = self.encrypt(nonce, associated_data, buffer.get_in());
+        let (ciphertext, tag) = sealed.split_at(buffer.len());
+        buffer.get_out().copy_from_slice(ciphertext);
+        Tag::<Self>::try_from(tag).map_err(|_| Error)
+    }
+
+    fn decrypt_inout_detached(&self, nonce: &Nonce<Self>, associated_data: &[u8], mut buffer: InOutBuf<'_, '_, u8>, tag: &Tag<Self>) -> Result<(), Error> {
+        if self.tag_len() != TagLen::FULL {
+            return Err(Error);
+        }
+
+        let mut sealed = buffer.get_in().to_vec();
+        sealed.extend_from_slice(tag);
+        let plaintext = self.decrypt(nonce, associated_data, &sealed).map_err(|_| Error)?;
+        buffer.get_out().copy_from_slice(&plaintext);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for PresentCipher {
+    fn drop(&mut self) {
+        wipe(&mut self.round_keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustcrypto_aead::{Aead, Payload};
+    use digest::Mac;
+    use mac::cmac;
+    use keys::Key80Bit;
+
+    #[test]
+    fn test_block_cipher_traits() {
+        let cipher = PresentCipher::from_key(&Key80Bit::new([0xFF; 10]));
+        let mut blocks = [Block::<PresentCipher>::default(), Block::<PresentCipher>::from([0xFF; 8])];
+        cipher.encrypt_blocks(&mut blocks);
+        assert_eq!(blocks[0][..], [0xE7, 0x2C, 0x46, 0xC0, 0xF5, 0x94, 0x50, 0x49]);
+        assert_eq!(blocks[1][..], [0x33, 0x33, 0xDC, 0xD3, 0x21, 0x32, 0x10, 0xD2]);
+
+        cipher.decrypt_blocks(&mut blocks);
+        assert_eq!(blocks[0][..], [0u8; 8]);
+        assert_eq!(blocks[1][..], [0xFF; 8]);
+    }
+
+    #[test]
+    fn test_key_init() {
+        let key = <Key80Bit as KeyInit>::new_from_slice(&[0xFF; 10]).unwrap();
+        let mut block = Block::<PresentCipher>::default();
+        PresentCipher::from_key(&key).encrypt_block(&mut block);
+        assert_eq!(block[..], [0xE7, 0x2C, 0x46, 0xC0, 0xF5, 0x94, 0x50, 0x49]);
+
+        assert!(<Key80Bit as KeyInit>::new_from_slice(&[0xFF; 16]).is_err());
+        assert!(<Key128Bit as KeyInit>::new_from_slice(&[0xFF; 10]).is_err());
+        let key = <Key128Bit as KeyInit>::new(&cipher::Key::<Key128Bit>::from([0x01; 16]));
+        assert_eq!(key.generate_round_keys()[0].value, Key128Bit::new([0x01; 16]).generate_round_keys()[0].value);
+    }
+
+    #[test]
+    fn test_present_cipher_key_init() {
+        fn encrypt_zero<C: KeyInit + BlockCipherEncrypt<BlockSize = U8>>(key: &[u8]) -> Option<Block<C>> {
+            let mut block = Block::<C>::default();
+            C::new_from_slice(key).ok()?.encrypt_block(&mut block);
+            Some(block)
+        }
+
+        let block = encrypt_zero::<PresentCipher>(&[0xFF; 10]).unwrap();
+        assert_eq!(block[..], [0xE7, 0x2C, 0x46, 0xC0, 0xF5, 0x94, 0x50, 0x49]);
+        let block = encrypt_zero::<PresentCipher>(&[0xFF; 16]).unwrap();
+        assert_eq!(block[..], [0x13, 0x23, 0x8C, 0x71, 0x02, 0x72, 0xA5, 0xD8]);
+        assert!(encrypt_zero::<PresentCipher>(&[0xFF; 12]).is_none());
+    }
+
+    #[test]
+    fn test_cmac_through_mac_trait() {
+        fn tag<M: Mac>(mut mac: M, data: &[u8]) -> Vec<u8> {
+            Mac::update(&mut mac, data);
+            Mac::finalize(mac).into_bytes().to_vec()
+        }
+
+        let key = Key80Bit::new([0x77; 10]);
+        let expected = cmac(&key, b"framework input");
+        assert_eq!(tag(Cmac::new(&key), b"framework input"), expected);
+
+        let mut mac = Cmac::new(&key);
+        Mac::update(&mut mac, b"framework input");
+        assert!(Mac::verify_slice_reset(&mut mac, &expected).is_ok());
+        Mac::update(&mut mac, b"framework input");
+        assert!(Mac::verify_truncated_left(mac, &expected[..4]).is_ok());
+    }
+
+    #[test]
+    fn test_ctr_through_stream_traits() {
+        let key = Key80Bit::new([0x3D; 10]);
+        let iv = ::block::Block::new(0xFFFF_FFFF_FFFF_FFFE);
+        let mut expected = [0x99u8; 29];
+        Ctr::new(&key, &iv).apply_keystream(&mut expected);
+
+        let mut ctr = Ctr::new(&key, &iv);
+        let mut data = [0x99u8; 29];
+        StreamCipher::apply_keystream(&mut ctr, &mut data[..13]);
+        assert_eq!(StreamCipherSeek::current_pos::<u64>(&ctr), 13);
+        StreamCipher::apply_keystream_b2b(&mut ctr, &[0x99; 16], &mut data[13..]);
+        assert_eq!(&data[..], &expected[..]);
+
+        StreamCipherSeek::seek(&mut ctr, 16u32);
+        assert_eq!(StreamCipherSeek::current_pos::<u32>(&ctr), 16);
+        let mut keystream = [0u8; 5];
+        StreamCipher::write_keystream(&mut ctr, &mut keystream);
+        for (k, (d, p)) in keystream.iter().zip(expected[16..21].iter().zip([0x99u8; 5].iter())) {
+            assert_eq!(*k, d ^ p);
+        }
+
+        Ctr::seek(&mut ctr, u64::MAX - 1);
+        assert!(StreamCipher::try_apply_keystream(&mut ctr, &mut [0u8; 2]).is_err());
+        assert!(StreamCipher::try_apply_keystream(&mut ctr, &mut [0u8; 1]).is_ok());
+        assert!(StreamCipherSeek::try_current_pos::<u32>(&ctr).is_err());
+    }
+
+    fn roundtrip<A: Aead>(cipher: &A, nonce: &Nonce<A>) {
+        let payload = Payload { msg: b"generic message", aad: b"header" };
+        let ciphertext = cipher.encrypt(nonce, payload).unwrap();
+        assert_eq!(ciphertext.len(), 15 + 8);
+
+        let plaintext = cipher.decrypt(nonce, Payload { msg: &ciphertext, aad: b"header" }).unwrap();
+        assert_eq!(plaintext, b"generic message");
+        assert!(cipher.decrypt(nonce, Payload { msg: &ciphertext, aad: b"other" }).is_err());
+    }
+
+    #[test]
+    fn test_ocb_through_traits() {
+        let key = Key80Bit::new([0x1F; 10]);
+        let ocb = Ocb::new(&key, TagLen::FULL);
+        let nonce = Nonce::<Ocb>::from([1, 2, 3, 4, 5, 6]);
+        roundtrip(&ocb, &nonce);
+
+        // Output is identical to the inherent API
+        let sealed = ocb.encrypt(&nonce, b"", b"same").unwrap();
+        assert_eq!(Aead::encrypt(&ocb, &nonce, &b"same"[..]).unwrap(), sealed);
+
+        let truncated = Ocb::new(&key, TagLen::MIN);
+        assert!(Aead::encrypt(&truncated, &nonce, &b"same"[..]).is_err());
+    }
+
+    #[test]
+    fn test_siv_through_traits() {
+        let siv = Siv::new(&Key80Bit::new([0x1F; 10]), &Key80Bit::new([0x2F; 10]));
+        let nonce = Nonce::<Siv>::from([9; 8]);
+        roundtrip(&siv, &nonce);
+        assert_eq!(Aead::encrypt(&siv, &nonce, &b"same"[..]).unwrap(), siv.encrypt(&nonce, b"", b"same"));
+    }
+
+    #[test]
+    fn test_ccm_through_traits() {
+        let ccm = Ccm::new(&Key80Bit::new([0x1F; 10]), 5, TagLen::FULL).unwrap();
+        let nonce = Nonce::<Ccm>::from([1, 2, 3, 4, 5]);
+        roundtrip(&ccm, &nonce);
+        assert_eq!(Aead::encrypt(&ccm, &nonce, &b"same"[..]).unwrap(), ccm.encrypt(&nonce, b"", b"same").unwrap());
+
+        let short_nonce = Ccm::new(&Key80Bit::new([0x1F; 10]), 3, TagLen::FULL).unwrap();
+        assert!(Aead::encrypt(&short_nonce, &nonce, &b"same"[..]).is_err());
+        let truncated = Ccm::new(&Key80Bit::new([0x1F; 10]), 5, TagLen::MIN).unwrap();
+        assert!(Aead::encrypt(&truncated, &nonce, &b"same"[..]).is_err());
+    }
+
+    #[test]
+    fn test_eax_through_traits() {
+        let key = Key80Bit::new([0x1F; 10]);
+        let eax = Eax::new(&key, TagLen::FULL);
+        let nonce = Nonce::<Eax>::from([9; 8]);
+        roundtrip(&eax, &nonce);
+        assert_eq!(Aead::encrypt(&eax, &nonce, &b"same"[..]).unwrap(), ::aead::seal(&key, &nonce, b"", b"same"));
+
+        let truncated = Eax::new(&key, TagLen::MIN);
+        assert!(Aead::encrypt(&truncated, &nonce, &b"same"[..]).is_err());
+    }
+}
@@ -1,120 +1,218 @@
-use std::collections::BTreeMap;
+#[cfg(any(test, feature = "ct-sbox"))]
+use bitslice;
 
-lazy_static! {
-    pub static ref S_BOX: SBox = SBox::new();
+/// PRESENT's 4-bit S-Box.
+#[cfg(any(test, not(feature = "ct-sbox")))]
+const S_ENC: [u8; 16] = [0xC, 0x5, 0x6, 0xB, 0x9, 0x0, 0xA, 0xD, 0x3, 0xE, 0xF, 0x8, 0x4, 0x7, 0x1, 0x2];
+
+/// The inverse of PRESENT's 4-bit S-Box.
+#[cfg(any(test, not(feature = "ct-sbox")))]
+const S_DEC: [u8; 16] = [0x5, 0xE, 0xF, 0x8, 0xC, 0x1, 0x2, 0xD, 0xB, 0x4, 0x6, 0x3, 0x0, 0x7, 0x9, 0xA];
+
+/// The S-Box applied to both nibbles of a byte at once.
+#[cfg(any(test, not(feature = "ct-sbox")))]
+const S_ENC_BYTE: [u8; 256] = byte_table(&S_ENC);
+
+/// The inverse S-Box applied to both nibbles of a byte at once.
+#[cfg(any(test, not(feature = "ct-sbox")))]
+const S_DEC_BYTE: [u8; 256] = byte_table(&S_DEC);
+
+/// The lowest bit of every nibble.
+#[cfg(any(test, feature = "ct-sbox"))]
+const NIBBLE_LOW_BITS: u64 = 0x1111111111111111;
+
+#[cfg(any(test, not(feature = "ct-sbox")))]
+const fn byte_table(nibble_table: &[u8; 16]) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = (nibble_table[i >> 4] << 4) | nibble_table[i & 0xF];
+        i += 1;
+    }
+    table
 }
 
-pub struct SBox {
-    s_map_enc: BTreeMap<u8, u8>,
-    s_map_dec: BTreeMap<u8, u8>,
+/// Applies the S-Box to a nibble.
+#[cfg(not(feature = "ct-sbox"))]
+pub const fn apply_enc(input: u8) -> u8 {
+    S_ENC[input as usize]
 }
 
-impl SBox {
-    fn new() -> Self {
-        let mut tmp_map_enc = BTreeMap::new();
-        tmp_map_enc.insert(0, 12);
-        tmp_map_enc.insert(1, 5);
-        tmp_map_enc.insert(2, 6);
-        tmp_map_enc.insert(3, 11);
-        tmp_map_enc.insert(4, 9);
-        tmp_map_enc.insert(5, 0);
-        tmp_map_enc.insert(6, 10);
-        tmp_map_enc.insert(7, 13);
-        tmp_map_enc.insert(8, 3);
-        tmp_map_enc.insert(9, 14);
-        tmp_map_enc.insert(10, 15);
-        tmp_map_enc.insert(11, 8);
-        tmp_map_enc.insert(12, 4);
-        tmp_map_enc.insert(13, 7);
-        tmp_map_enc.insert(14, 1);
-        tmp_map_enc.insert(15, 2);
-
-        let mut tmp_map_dec = BTreeMap::new();
-        tmp_map_dec.insert(0, 5);
-        tmp_map_dec.insert(1, 14);
-        tmp_map_dec.insert(2, 15);
-        tmp_map_dec.insert(3, 8);
-        tmp_map_dec.insert(4, 12);
-        tmp_map_dec.insert(5, 1);
-        tmp_map_dec.insert(6, 2);
-        tmp_map_dec.insert(7, 13);
-        tmp_map_dec.insert(8, 11);
-        tmp_map_dec.insert(9, 4);
-        tmp_map_dec.insert(10, 6);
-        tmp_map_dec.insert(11, 3);
-        tmp_map_dec.insert(12, 0);
-        tmp_map_dec.insert(13, 7);
-        tmp_map_dec.insert(14, 9);
-        tmp_map_dec.insert(15, 10);
-
-        SBox {
-            s_map_enc: tmp_map_enc,
-            s_map_dec: tmp_map_dec
-        }
-    }
+/// Applies the inverse S-Box to a nibble.
+#[cfg(not(feature = "ct-sbox"))]
+pub const fn apply_dec(input: u8) -> u8 {
+    S_DEC[input as usize]
+}
+
+/// Applies the S-Box to a nibble with the circuit, for the key
+/// schedule and the other callers that substitute single nibbles.
+#[cfg(feature = "ct-sbox")]
+pub fn apply_enc(input: u8) -> u8 {
+    assert!(input < 16, "Logic error! S-Box input {} is not a nibble", input);
+    (apply_enc_state(input as u64) & 0xF) as u8
+}
 
-    pub fn apply_enc(&self, input: u8) -> u8 {
-        *self.s_map_enc.get(&input).expect("Logic error! Invalid S-Box input! (enc)")
+/// Applies the inverse S-Box to a nibble with the circuit.
+#[cfg(feature = "ct-sbox")]
+pub fn apply_dec(input: u8) -> u8 {
+    assert!(input < 16, "Logic error! S-Box input {} is not a nibble", input);
+    (apply_dec_state(input as u64) & 0xF) as u8
+}
+
+/// Applies the S-Box to both nibbles of a byte.
+#[cfg(any(test, not(feature = "ct-sbox")))]
+pub const fn apply_enc_byte(input: u8) -> u8 {
+    S_ENC_BYTE[input as usize]
+}
+
+/// Applies the inverse S-Box to both nibbles of a byte.
+#[cfg(any(test, not(feature = "ct-sbox")))]
+pub const fn apply_dec_byte(input: u8) -> u8 {
+    S_DEC_BYTE[input as usize]
+}
+
+/// Applies the S-Box to all sixteen nibbles of a state.
+#[cfg(not(feature = "ct-sbox"))]
+pub fn apply_enc_state(state: u64) -> u64 {
+    apply_bytes(state, apply_enc_byte)
+}
+
+/// Applies the inverse S-Box to all sixteen nibbles of a state.
+#[cfg(not(feature = "ct-sbox"))]
+pub fn apply_dec_state(state: u64) -> u64 {
+    apply_bytes(state, apply_dec_byte)
+}
+
+/// Applies the S-Box to all sixteen nibbles of a state, without any
+/// table lookups.
+#[cfg(feature = "ct-sbox")]
+pub fn apply_enc_state(state: u64) -> u64 {
+    apply_circuit(state, bitslice::substitute_enc)
+}
+
+/// Applies the inverse S-Box to all sixteen nibbles of a state,
+/// without any table lookups.
+#[cfg(feature = "ct-sbox")]
+pub fn apply_dec_state(state: u64) -> u64 {
+    apply_circuit(state, bitslice::substitute_dec)
+}
+
+#[cfg(any(test, not(feature = "ct-sbox")))]
+fn apply_bytes<F: Fn(u8) -> u8>(state: u64, sbox: F) -> u64 {
+    let mut bytes = state.to_be_bytes();
+    for byte in bytes.iter_mut() {
+        *byte = sbox(*byte);
     }
+    u64::from_be_bytes(bytes)
+}
 
-    pub fn apply_dec(&self, input: u8) -> u8 {
-        *self.s_map_dec.get(&input).expect("Logic error! Invalid S-Box input! (dec)")
+/// Evaluates an S-Box circuit on all nibbles at once.
+///
+/// Bit `i` of every nibble is gathered into the word `planes[i]`, so
+/// the circuit from the bitsliced implementation processes the sixteen
+/// nibbles in parallel. The running time does not depend on the state.
+#[cfg(any(test, feature = "ct-sbox"))]
+fn apply_circuit<F: Fn(&mut [u64])>(state: u64, circuit: F) -> u64 {
+    let mut planes = [0u64; 4];
+    for (i, plane) in planes.iter_mut().enumerate() {
+        *plane = (state >> i) & NIBBLE_LOW_BITS;
     }
+
+    circuit(&mut planes);
+
+    // NOT in the circuit also sets the unused bits, so mask again
+    planes.iter().enumerate().fold(0, |output, (i, plane)| output | ((plane & NIBBLE_LOW_BITS) << i))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use testing::states;
 
     #[test]
     fn test_that_sbox_gives_correct_outputs() {
-        assert_eq!(S_BOX.apply_enc(0), 12);
-        assert_eq!(S_BOX.apply_enc(1), 5);
-        assert_eq!(S_BOX.apply_enc(2), 6);
-        assert_eq!(S_BOX.apply_enc(3), 11);
-        assert_eq!(S_BOX.apply_enc(4), 9);
-        assert_eq!(S_BOX.apply_enc(5), 0);
-        assert_eq!(S_BOX.apply_enc(6), 10);
-        assert_eq!(S_BOX.apply_enc(7), 13);
-        assert_eq!(S_BOX.apply_enc(8), 3);
-        assert_eq!(S_BOX.apply_enc(9), 14);
-        assert_eq!(S_BOX.apply_enc(10), 15);
-        assert_eq!(S_BOX.apply_enc(11), 8);
-        assert_eq!(S_BOX.apply_enc(12), 4);
-        assert_eq!(S_BOX.apply_enc(13), 7);
-        assert_eq!(S_BOX.apply_enc(14), 1);
-        assert_eq!(S_BOX.apply_enc(15), 2);
+        assert_eq!(apply_enc(0), 12);
+        assert_eq!(apply_enc(1), 5);
+        assert_eq!(apply_enc(2), 6);
+        assert_eq!(apply_enc(3), 11);
+        assert_eq!(apply_enc(4), 9);
+        assert_eq!(apply_enc(5), 0);
+        assert_eq!(apply_enc(6), 10);
+        assert_eq!(apply_enc(7), 13);
+        assert_eq!(apply_enc(8), 3);
+        assert_eq!(apply_enc(9), 14);
+        assert_eq!(apply_enc(10), 15);
+        assert_eq!(apply_enc(11), 8);
+        assert_eq!(apply_enc(12), 4);
+        assert_eq!(apply_enc(13), 7);
+        assert_eq!(apply_enc(14), 1);
+        assert_eq!(apply_enc(15), 2);
     }
 
     #[test]
     fn test_that_inverse_sbox_gives_correct_outputs() {
-        assert_eq!(S_BOX.apply_dec(0), 5);
-        assert_eq!(S_BOX.apply_dec(1), 14);
-        assert_eq!(S_BOX.apply_dec(2), 15);
-        assert_eq!(S_BOX.apply_dec(3), 8);
-        assert_eq!(S_BOX.apply_dec(4), 12);
-        assert_eq!(S_BOX.apply_dec(5), 1);
-        assert_eq!(S_BOX.apply_dec(6), 2);
-        assert_eq!(S_BOX.apply_dec(7), 13);
-        assert_eq!(S_BOX.apply_dec(8), 11);
-        assert_eq!(S_BOX.apply_dec(9), 4);
-        assert_eq!(S_BOX.apply_dec(10), 6);
-        assert_eq!(S_BOX.apply_dec(11), 3);
-        assert_eq!(S_BOX.apply_dec(12), 0);
-        assert_eq!(S_BOX.apply_dec(13), 7);
-        assert_eq!(S_BOX.apply_dec(14), 9);
-        assert_eq!(S_BOX.apply_dec(15), 10);
+        assert_eq!(apply_dec(0), 5);
+        assert_eq!(apply_dec(1), 14);
+        assert_eq!(apply_dec(2), 15);
+        assert_eq!(apply_dec(3), 8);
+        assert_eq!(apply_dec(4), 12);
+        assert_eq!(apply_dec(5), 1);
+        assert_eq!(apply_dec(6), 2);
+        assert_eq!(apply_dec(7), 13);
+        assert_eq!(apply_dec(8), 11);
+        assert_eq!(apply_dec(9), 4);
+        assert_eq!(apply_dec(10), 6);
+        assert_eq!(apply_dec(11), 3);
+        assert_eq!(apply_dec(12), 0);
+        assert_eq!(apply_dec(13), 7);
+        assert_eq!(apply_dec(14), 9);
+        assert_eq!(apply_dec(15), 10);
+    }
+
+    #[test]
+    #[cfg(feature = "ct-sbox")]
+    fn test_that_circuit_matches_nibble_tables() {
+        for nibble in 0..16u8 {
+            assert_eq!(apply_enc(nibble), S_ENC[nibble as usize]);
+            assert_eq!(apply_dec(nibble), S_DEC[nibble as usize]);
+        }
+    }
+
+    #[test]
+    fn test_that_byte_table_matches_nibbles() {
+        for byte in 0..=255u8 {
+            let expected = (apply_enc(byte >> 4) << 4) | apply_enc(byte & 0xF);
+            assert_eq!(apply_enc_byte(byte), expected);
+            assert_eq!(apply_dec_byte(expected), byte);
+        }
+    }
+
+    #[test]
+    fn test_that_circuit_matches_tables() {
+        for &state in states(64).iter() {
+            let substituted = apply_bytes(state, apply_enc_byte);
+            assert_eq!(apply_circuit(state, bitslice::substitute_enc), substituted);
+            assert_eq!(apply_circuit(substituted, bitslice::substitute_dec), state);
+            assert_eq!(apply_enc_state(state), substituted);
+            assert_eq!(apply_dec_state(substituted), state);
+        }
+    }
+
+    #[test]
+    fn test_that_sbox_works_in_const_context() {
+        const OUTPUT: u8 = apply_enc_byte(0x01);
+        assert_eq!(OUTPUT, 0xC5);
     }
 
     #[test]
     #[should_panic]
     fn test_that_invalid_input_panics() {
-        S_BOX.apply_enc(16);
+        apply_enc(16);
     }
 
     #[test]
     #[should_panic]
     fn test_that_invalid_input_panics_inverse() {
-        S_BOX.apply_dec(42);
+        apply_dec(42);
     }
 }
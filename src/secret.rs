@@ -0,0 +1,184 @@
+use secrecy::{ExposeSecret, SecretBox, SecretSlice};
+use subtle::{Choice, ConstantTimeEq};
+
+use errors::KeyParseError;
+use keys::{schedule_80bit, schedule_128bit, to_round_keys, wipe, Key, RoundKeys};
+
+/// An 80-bit key held in a [`SecretBox`](../secrecy/struct.SecretBox.html).
+///
+/// Unlike [`Key80Bit`](struct.Key80Bit.html), the key bytes live on the
+/// heap and are wiped when the key is dropped, the key cannot be cloned,
+/// and its `Debug` output is redacted. The bytes are only accessible
+/// through [`ExposeSecret`](../secrecy/trait.ExposeSecret.html), which
+/// makes every access easy to find in a review.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate present;
+/// # extern crate secrecy;
+/// # fn main() {
+/// use present::{Block, SecretKey80};
+/// use secrecy::SecretSlice;
+///
+/// let bytes: SecretSlice<u8> = vec![0x42; 10].into();
+/// let key = SecretKey80::from_slice(&bytes).unwrap();
+/// assert_eq!(format!("{:?}", key), "SecretKey80 { value: SecretBox<[u8; 10]>([REDACTED]) }");
+///
+/// let mut block = Block::new(0);
+/// block.encrypt(&key);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SecretKey80 {
+    value: SecretBox<[u8; 10]>,
+}
+
+impl SecretKey80 {
+    /// Constructs a new 80-bit key from the given secret bytes.
+    pub fn new(value: SecretBox<[u8; 10]>) -> Self {
+        SecretKey80 { value }
+    }
+
+    /// Copies an 80-bit key out of a secret byte slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyParseError::InvalidLength` if the slice is not 10
+    /// bytes long.
+    pub fn from_slice(bytes: &SecretSlice<u8>) -> Result<Self, KeyParseError> {
+        Ok(SecretKey80 { value: copy_secret(bytes.expose_secret())? })
+    }
+}
+
+impl From<SecretBox<[u8; 10]>> for SecretKey80 {
+    fn from(value: SecretBox<[u8; 10]>) -> Self {
+        SecretKey80::new(value)
+    }
+}
+
+impl ExposeSecret<[u8; 10]> for SecretKey80 {
+    fn expose_secret(&self) -> &[u8; 10] {
+        self.value.expose_secret()
+    }
+}
+
+impl ConstantTimeEq for SecretKey80 {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.expose_secret().ct_eq(other.value.expose_secret())
+    }
+}
+
+impl Key for SecretKey80 {
+    /// The key schedule for 80-bit keys, see
+    /// [`Key80Bit`](struct.Key80Bit.html).
+    fn generate_round_keys(&self) -> RoundKeys {
+        let mut bytes = schedule_80bit(self.value.expose_secret());
+        let round_keys = to_round_keys(&bytes);
+        wipe(&mut bytes);
+        round_keys
+    }
+}
+
+/// A 128-bit key held in a [`SecretBox`](../secrecy/struct.SecretBox.html).
+///
+/// See [`SecretKey80`](struct.SecretKey80.html) for details.
+#[derive(Debug)]
+pub struct SecretKey128 {
+    value: SecretBox<[u8; 16]>,
+}
+
+impl SecretKey128 {
+    /// Constructs a new 128-bit key from the given secret bytes.
+    pub fn new(value: SecretBox<[u8; 16]>) -> Self {
+        SecretKey128 { value }
+    }
+
+    /// Copies a 128-bit key out of a secret byte slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KeyParseError::InvalidLength` if the slice is not 16
+    /// bytes long.
+    pub fn from_slice(bytes: &SecretSlice<u8>) -> Result<Self, KeyParseError> {
+        Ok(SecretKey128 { value: copy_secret(bytes.expose_secret())? })
+    }
+}
+
+impl From<SecretBox<[u8; 16]>> for SecretKey128 {
+    fn from(value: SecretBox<[u8; 16]>) -> Self {
+        SecretKey128::new(value)
+    }
+}
+
+impl ExposeSecret<[u8; 16]> for SecretKey128 {
+    fn expose_secret(&self) -> &[u8; 16] {
+        self.value.expose_secret()
+    }
+}
+
+impl ConstantTimeEq for SecretKey128 {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.expose_secret().ct_eq(other.value.expose_secret())
+    }
+}
+
+impl Key for SecretKey128 {
+    /// The key schedule for 128-bit keys, see
+    /// [`Key128Bit`](struct.Key128Bit.html).
+    fn generate_round_keys(&self) -> RoundKeys {
+        let mut bytes = schedule_128bit(self.value.expose_secret());
+        let round_keys = to_round_keys(&bytes);
+        wipe(&mut bytes);
+        round_keys
+    }
+}
+
+/// Copies the bytes into a new secret box of the matching size, without
+/// an intermediate copy on the stack.
+fn copy_secret<T>(bytes: &[u8]) -> Result<SecretBox<T>, KeyParseError>
+    where T: AsMut<[u8]> + Default + ::zeroize::Zeroize {
+
+    let mut len_matches = true;
+    let value = SecretBox::init_with_mut(|value: &mut T| {
+        let value = value.as_mut();
+        len_matches = value.len() == bytes.len();
+        if len_matches {
+            value.copy_from_slice(bytes);
+        }
+    });
+    if !len_matches {
+        return Err(KeyParseError::InvalidLength(bytes.len()));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::{Key80Bit, Key128Bit};
+
+    #[test]
+    fn test_secret_keys_match_plain_keys() {
+        let key = SecretKey80::new(SecretBox::new(Box::new([0x3C; 10])));
+        assert_eq!(key.generate_round_keys()[31].value, Key80Bit::new([0x3C; 10]).generate_round_keys()[31].value);
+
+        let bytes: SecretSlice<u8> = vec![0xA5; 16].into();
+        let key = SecretKey128::from_slice(&bytes).unwrap();
+        assert_eq!(key.expose_secret(), &[0xA5; 16]);
+        assert_eq!(key.generate_round_keys()[31].value, Key128Bit::new([0xA5; 16]).generate_round_keys()[31].value);
+    }
+
+    #[test]
+    fn test_from_slice_checks_length() {
+        let bytes: SecretSlice<u8> = vec![0x42; 16].into();
+        assert!(matches!(SecretKey80::from_slice(&bytes), Err(KeyParseError::InvalidLength(16))));
+        assert!(SecretKey128::from_slice(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_debug_output_is_redacted() {
+        let key = SecretKey128::new(SecretBox::new(Box::new([0x42; 16])));
+        assert!(!format!("{:?}", key).contains("66"));
+    }
+}
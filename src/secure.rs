@@ -0,0 +1,119 @@
+use std::alloc::{self, Layout};
+use std::io;
+use std::mem;
+use std::ptr::NonNull;
+
+use libc;
+
+use keys::{wipe, Key, RoundKeys};
+
+/// A key with a precomputed key schedule in page-locked memory.
+///
+/// The round keys are expanded once into a page of their own, which is
+/// locked with `mlock` so it is never written to swap, and on Linux
+/// also excluded from core dumps. The page is wiped and unlocked when
+/// the key is dropped. Use this for servers that hold keys for long
+/// sessions; short-lived keys are better served by
+/// [`Key80Bit`](struct.Key80Bit.html) and the `zeroize` feature.
+///
+/// The round keys are still copied to the stack for every operation,
+/// like with any other key.
+///
+/// # Examples
+///
+/// ```
+/// use present::{Block, Key80Bit, SecureKey};
+/// let key = SecureKey::new(&Key80Bit::new([0x42; 10])).unwrap();
+///
+/// let mut block = Block::new(0x0123456789ABCDEF);
+/// block.encrypt(&key);
+/// block.decrypt(&key);
+/// assert_eq!(block.get_state(), 0x0123456789ABCDEF);
+/// ```
+pub struct SecureKey {
+    round_keys: NonNull<RoundKeys>,
+    layout: Layout,
+}
+
+// The locked page is owned by the key and never mutated after
+// construction
+unsafe impl Send for SecureKey {}
+unsafe impl Sync for SecureKey {}
+
+impl SecureKey {
+    /// Constructs a new secure key by expanding the given key into
+    /// locked memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the memory cannot be allocated or locked, e.g.
+    /// because the process exceeds its limit of locked memory
+    /// (`RLIMIT_MEMLOCK`).
+    pub fn new<K: Key>(key: &K) -> io::Result<Self> {
+        let page_size = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+            size if size > 0 => size as usize,
+            _ => 4096,
+        };
+        let size = mem::size_of::<RoundKeys>().div_ceil(page_size) * page_size;
+        let layout = Layout::from_size_align(size, page_size).map_err(io::Error::other)?;
+
+        // SAFETY: the layout has a non-zero size
+        let page = unsafe { alloc::alloc_zeroed(layout) };
+        let round_keys = match NonNull::new(page as *mut RoundKeys) {
+            Some(round_keys) => round_keys,
+            None => return Err(io::ErrorKind::OutOfMemory.into()),
+        };
+
+        // SAFETY: the page was just allocated with this size
+        if unsafe { libc::mlock(page as *const libc::c_void, size) } != 0 {
+            let err = io::Error::last_os_error();
+            unsafe { alloc::dealloc(page, layout) };
+            return Err(err);
+        }
+
+        // Failing to exclude the page from core dumps is not fatal
+        #[cfg(target_os = "linux")]
+        unsafe { libc::madvise(page as *mut libc::c_void, size, libc::MADV_DONTDUMP) };
+
+        let mut expanded = key.generate_round_keys();
+        // SAFETY: the page is aligned and large enough for the round keys
+        unsafe { round_keys.as_ptr().write(expanded) };
+        wipe(&mut expanded);
+
+        Ok(SecureKey { round_keys, layout })
+    }
+}
+
+impl Key for SecureKey {
+    /// Returns a copy of the round keys from locked memory.
+    fn generate_round_keys(&self) -> RoundKeys {
+        // SAFETY: the round keys were initialized in new()
+        unsafe { *self.round_keys.as_ref() }
+    }
+}
+
+impl Drop for SecureKey {
+    fn drop(&mut self) {
+        // SAFETY: the page is owned by this key and no longer borrowed
+        unsafe {
+            wipe(self.round_keys.as_mut());
+            let page = self.round_keys.as_ptr() as *mut u8;
+            libc::munlock(page as *const libc::c_void, self.layout.size());
+            alloc::dealloc(page, self.layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Key128Bit;
+
+    #[test]
+    fn test_secure_key_matches_plain_key() {
+        let key = Key128Bit::new([0x5A; 16]);
+        let secure = SecureKey::new(&key).unwrap();
+        assert!(secure.generate_round_keys() == key.generate_round_keys());
+        assert_eq!(secure.layout.size() % secure.layout.align(), 0);
+    }
+}
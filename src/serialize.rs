@@ -0,0 +1,291 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use block::Block;
+use ciphertext::Ciphertext;
+use errors::{KeyParseError, ParseError};
+use keys::{Key, Key80Bit, Key128Bit, KeyAny};
+
+/// Serializes a key as its [fingerprint](trait.Key.html#method.fingerprint)
+/// instead of its value.
+///
+/// Keys are serialized in full by default, so they can be stored in
+/// configuration files. For structs that are logged or returned by
+/// APIs, annotate the key field with
+/// `#[serde(serialize_with = "present::serialize_fingerprint")]`, so
+/// they show which key is used without revealing it.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate present;
+/// # extern crate serde_json;
+/// # fn main() {
+/// use present::{serialize_fingerprint, Key, Key80Bit};
+///
+/// let key = Key80Bit::new([0x42; 10]);
+/// let redacted = serialize_fingerprint(&key, serde_json::value::Serializer).unwrap();
+/// assert_eq!(redacted, key.fingerprint().to_string());
+/// # }
+/// ```
+pub fn serialize_fingerprint<K: Key, S: Serializer>(key: &K, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&key.fingerprint())
+}
+
+/// Formats bytes as lowercase hexadecimal digits without an
+/// intermediate string.
+struct Hex<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for Hex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a key as hexadecimal digits in human-readable formats and
+/// as bytes otherwise.
+fn serialize_key<K: Key, S: Serializer>(key: &K, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.collect_str(&Hex(&key.as_bytes()))
+    } else {
+        serializer.serialize_bytes(&key.as_bytes())
+    }
+}
+
+/// Deserializes hexadecimal digits from human-readable formats and bytes
+/// otherwise, the counterpart of `serialize_key()`.
+fn deserialize_hex_or_bytes<'de, D: Deserializer<'de>, V: Visitor<'de>>(deserializer: D, visitor: V) -> Result<V::Value, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_str(visitor)
+    } else {
+        deserializer.deserialize_bytes(visitor)
+    }
+}
+
+/// Key types that can be parsed from hexadecimal digits.
+trait FromHex: Key + Sized {
+    const EXPECTING: &'static str;
+
+    fn from_hex(hex: &str) -> Result<Self, KeyParseError>;
+}
+
+impl FromHex for Key80Bit {
+    const EXPECTING: &'static str = "an 80-bit key as 20 hexadecimal digits or 10 bytes";
+
+    fn from_hex(hex: &str) -> Result<Self, KeyParseError> {
+        Key80Bit::from_hex(hex)
+    }
+}
+
+impl FromHex for Key128Bit {
+    const EXPECTING: &'static str = "a 128-bit key as 32 hexadecimal digits or 16 bytes";
+
+    fn from_hex(hex: &str) -> Result<Self, KeyParseError> {
+        Key128Bit::from_hex(hex)
+    }
+}
+
+impl FromHex for KeyAny {
+    const EXPECTING: &'static str = "a key as 20 or 32 hexadecimal digits or 10 or 16 bytes";
+
+    fn from_hex(hex: &str) -> Result<Self, KeyParseError> {
+        KeyAny::from_hex(hex)
+    }
+}
+
+struct KeyVisitor<K>(PhantomData<K>);
+
+impl<'de, K: FromHex> Visitor<'de> for KeyVisitor<K> {
+    type Value = K;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(K::EXPECTING)
+    }
+
+    fn visit_str<E: de::Error>(self, hex: &str) -> Result<K, E> {
+        K::from_hex(hex).map_err(|e| key_error(e, &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<K, E> {
+        K::from_bytes(bytes).map_err(|e| key_error(e, &self))
+    }
+}
+
+/// Converts key parsing errors without including the input, which
+/// would leak the key into error messages.
+fn key_error<E: de::Error>(e: KeyParseError, expected: &dyn de::Expected) -> E {
+    match e {
+        KeyParseError::InvalidLength(len) => E::invalid_length(len, expected),
+        _ => E::custom("invalid key encoding"),
+    }
+}
+
+impl Serialize for Key80Bit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_key(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key80Bit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex_or_bytes(deserializer, KeyVisitor(PhantomData))
+    }
+}
+
+impl Serialize for Key128Bit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_key(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key128Bit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex_or_bytes(deserializer, KeyVisitor(PhantomData))
+    }
+}
+
+impl Serialize for KeyAny {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_key(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyAny {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex_or_bytes(deserializer, KeyVisitor(PhantomData))
+    }
+}
+
+impl Serialize for Block {
+    /// Serializes the block as 16 hexadecimal digits in human-readable
+    /// formats and as 8 bytes otherwise.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
+
+struct BlockVisitor;
+
+impl<'de> Visitor<'de> for BlockVisitor {
+    type Value = Block;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a block as 16 hexadecimal digits or 8 bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, hex: &str) -> Result<Block, E> {
+        hex.parse().map_err(|e| parse_error(e, &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Block, E> {
+        if bytes.len() != 8 {
+            return Err(E::invalid_length(bytes.len(), &self));
+        }
+        let mut value = [0u8; 8];
+        value.copy_from_slice(bytes);
+        Ok(Block::from_bytes(&value))
+    }
+}
+
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex_or_bytes(deserializer, BlockVisitor)
+    }
+}
+
+impl Serialize for Ciphertext {
+    /// Serializes the ciphertext in its text form, see
+    /// [`Ciphertext`](struct.Ciphertext.html).
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+struct CiphertextVisitor;
+
+impl<'de> Visitor<'de> for CiphertextVisitor {
+    type Value = Ciphertext;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a ciphertext as mode, IV and hexadecimal bytes separated by colons")
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Ciphertext, E> {
+        text.parse().map_err(|e| parse_error(e, &self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Ciphertext {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(CiphertextVisitor)
+    }
+}
+
+fn parse_error<E: de::Error>(e: ParseError, expected: &dyn de::Expected) -> E {
+    match e {
+        ParseError::InvalidLength(len) => E::invalid_length(len, expected),
+        ParseError::InvalidHex => E::custom("invalid hexadecimal digits"),
+        _ => E::custom(format_args!("invalid format, expected {}", expected)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::value::{BytesDeserializer, Error};
+    use serde_json;
+    use modes::OpMode;
+
+    #[test]
+    fn test_keys() {
+        let key = Key80Bit::new([0x42; 10]);
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, "\"42424242424242424242\"");
+        assert!(serde_json::from_str::<Key80Bit>(&json).unwrap() == key);
+        assert!(serde_json::from_str::<Key128Bit>(&json).is_err());
+        assert!(serde_json::from_str::<KeyAny>(&json).unwrap() == KeyAny::Key80(key));
+
+        let bytes = [0x17u8; 16];
+        let key = Key128Bit::deserialize(BytesDeserializer::<Error>::new(&bytes)).unwrap();
+        assert_eq!(key.value, bytes);
+        assert!(Key80Bit::deserialize(BytesDeserializer::<Error>::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn test_key_errors_do_not_leak_input() {
+        let err = serde_json::from_str::<Key80Bit>("\"4242424242424242424x\"").err().unwrap();
+        assert!(!err.to_string().contains("4242"));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let key = Key80Bit::new([0x42; 10]);
+        let json = serialize_fingerprint(&key, serde_json::value::Serializer).unwrap();
+        assert_eq!(json, serde_json::Value::String(key.fingerprint().to_string()));
+    }
+
+    #[test]
+    fn test_blocks_and_ciphertexts() {
+        let json = serde_json::to_string(&Block::new(0x0123456789ABCDEF)).unwrap();
+        assert_eq!(json, "\"0123456789abcdef\"");
+        assert_eq!(serde_json::from_str::<Block>(&json).unwrap().get_state(), 0x0123456789ABCDEF);
+        let block = Block::deserialize(BytesDeserializer::<Error>::new(&[0, 0, 0, 0, 0, 0, 0, 1])).unwrap();
+        assert_eq!(block.get_state(), 1);
+
+        let ciphertext = Ciphertext::new(vec![0xAB; 8], OpMode::CBC, Some(Block::new(1)));
+        let json = serde_json::to_string(&ciphertext).unwrap();
+        assert_eq!(json, "\"cbc:0000000000000001:abababababababab\"");
+        assert_eq!(serde_json::from_str::<Ciphertext>(&json).unwrap().to_string(), ciphertext.to_string());
+        assert!(serde_json::from_str::<Ciphertext>("\"cbc::ab\"").is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use rand::RngCore;
+
+use errors::RngError;
+use keys::RoundKeys;
+#[cfg(feature = "zeroize")]
+use keys::wipe;
+use masked::SplitMix;
+use pbox;
+use profile;
+use sbox;
+
+/// A key schedule whose rounds process the S-Box nibbles in random
+/// order.
+///
+/// Every round draws a new permutation of the sixteen nibbles, so a
+/// given nibble is substituted at a different time in every round and
+/// every block. Power traces then no longer line up with the
+/// operations on a particular nibble, which raises the number of
+/// traces an attacker needs.
+#[derive(Clone)]
+pub struct ShuffledSchedule {
+    round_keys: RoundKeys,
+    /// Generator for the order of the nibbles.
+    generator: SplitMix,
+}
+
+impl ShuffledSchedule {
+    /// Wraps the round keys, seeding the nibble order from the RNG.
+    pub fn new<R: RngCore + ?Sized>(round_keys: RoundKeys, rng: &mut R) -> Result<Self, RngError> {
+        Ok(ShuffledSchedule { round_keys, generator: SplitMix::from_rng(rng)? })
+    }
+
+    /// Encrypts a state.
+    pub fn encrypt(&self, mut state: u64) -> u64 {
+        profile::count_block();
+
+        for round_key in self.round_keys.iter().take(31) {
+            state ^= round_key.value;
+            state = self.substitute(state, sbox::apply_enc);
+            state = pbox::apply_enc(state);
+        }
+        state ^ self.round_keys[31].value
+    }
+
+    /// Decrypts a state.
+    pub fn decrypt(&self, mut state: u64) -> u64 {
+        profile::count_block();
+
+        for round in (1..32).rev() {
+            state ^= self.round_keys[round].value;
+            state = pbox::apply_dec(state);
+            state = self.substitute(state, sbox::apply_dec);
+        }
+        state ^ self.round_keys[0].value
+    }
+
+    /// Returns a copy of the round keys.
+    pub fn round_keys(&self) -> RoundKeys {
+        self.round_keys
+    }
+
+    /// Applies the S-Box to every nibble, in a random order.
+    fn substitute<F: Fn(u8) -> u8>(&self, state: u64, sbox: F) -> u64 {
+        let mut output = 0u64;
+        for nibble in self.order().iter() {
+            let shift = 4 * nibble;
+            output |= (sbox(((state >> shift) & 0xF) as u8) as u64) << shift;
+        }
+        output
+    }
+
+    /// Draws a random permutation of the nibble positions.
+    fn order(&self) -> [usize; 16] {
+        let mut order = [0usize; 16];
+        for (i, position) in order.iter_mut().enumerate() {
+            *position = i;
+        }
+
+        // Fisher-Yates shuffle
+        for i in (1..16).rev() {
+            let j = (self.generator.next() % (i as u64 + 1)) as usize;
+            order.swap(i, j);
+        }
+        order
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for ShuffledSchedule {
+    fn drop(&mut self) {
+        wipe(&mut self.round_keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+    use keys::{Key, Key80Bit};
+
+    #[test]
+    fn test_shuffled_rounds_match_test_vectors() {
+        let round_keys = Key80Bit::new([0xFF; 10]).generate_round_keys();
+        let schedule = ShuffledSchedule::new(round_keys, &mut StepRng::new(1, 1)).unwrap();
+        for _ in 0..16 {
+            assert_eq!(schedule.encrypt(0xFFFFFFFFFFFFFFFF), 0x3333DCD3213210D2);
+            assert_eq!(schedule.decrypt(0x3333DCD3213210D2), 0xFFFFFFFFFFFFFFFF);
+        }
+    }
+
+    #[test]
+    fn test_order_is_a_random_permutation() {
+        let schedule = ShuffledSchedule::new(Key80Bit::new([0; 10]).generate_round_keys(), &mut StepRng::new(7, 3)).unwrap();
+        let first = schedule.order();
+        let mut sorted = first;
+        sorted.sort();
+        assert_eq!(sorted, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        assert!((0..8).any(|_| schedule.order() != first));
+    }
+}
@@ -0,0 +1,218 @@
+use bitslice::{self, Word};
+use keys::RoundKeys;
+
+use self::lanes::Lanes;
+
+/// Encrypts many 8-byte blocks in place using SIMD registers.
+///
+/// This is the bitsliced implementation of
+/// [`encrypt_blocks_bitsliced()`](fn.encrypt_blocks_bitsliced.html) with
+/// 128-bit words, so each batch holds 128 blocks and every operation
+/// processes twice as many blocks. On x86_64 the words are SSE2
+/// registers. On other targets they are pairs of 64-bit integers, which
+/// the compiler maps to the vector unit of the target, e.g. NEON on
+/// aarch64. Like the bitsliced implementation, it runs in constant time.
+/// With the `simd` feature, [`Ctr`](struct.Ctr.html) and the
+/// `encrypt_blocks()` methods of [`Present80`](struct.Present80.html) and
+/// [`Present128`](struct.Present128.html) use it in place of the
+/// bitsliced implementation.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_blocks_simd, decrypt_blocks_simd, Key, Key80Bit};
+/// let round_keys = Key80Bit::new([0u8; 10]).generate_round_keys();
+/// let mut blocks = vec![[0u8; 8]; 200];
+/// encrypt_blocks_simd(&mut blocks, &round_keys);
+/// assert_eq!(blocks[199], [0x55, 0x79, 0xC1, 0x38, 0x7B, 0x22, 0x84, 0x45]);
+/// decrypt_blocks_simd(&mut blocks, &round_keys);
+/// assert_eq!(blocks[199], [0u8; 8]);
+/// ```
+pub fn encrypt_blocks_simd(blocks: &mut [[u8; 8]], round_keys: &RoundKeys) {
+    for batch in blocks.chunks_mut(128) {
+        let mut slices = load(batch);
+        bitslice::encrypt_slices(&mut slices, round_keys);
+        store(&slices, batch);
+    }
+}
+
+/// Decrypts many 8-byte blocks in place using SIMD registers.
+///
+/// This is the inverse of
+/// [`encrypt_blocks_simd()`](fn.encrypt_blocks_simd.html).
+pub fn decrypt_blocks_simd(blocks: &mut [[u8; 8]], round_keys: &RoundKeys) {
+    for batch in blocks.chunks_mut(128) {
+        let mut slices = load(batch);
+        bitslice::decrypt_slices(&mut slices, round_keys);
+        store(&slices, batch);
+    }
+}
+
+/// Converts up to 128 blocks into bit slices, with the first 64 blocks
+/// in the low lane and the rest in the high lane.
+fn load(batch: &[[u8; 8]]) -> [Lanes; 64] {
+    let (low_blocks, high_blocks) = batch.split_at(batch.len().min(64));
+    let low = bitslice::load(low_blocks);
+    let high = bitslice::load(high_blocks);
+
+    let mut slices = [Lanes::splat(0); 64];
+    for (i, slice) in slices.iter_mut().enumerate() {
+        *slice = Lanes::pack(low[i], high[i]);
+    }
+    slices
+}
+
+/// Converts bit slices back into blocks. This is the inverse of `load()`.
+fn store(slices: &[Lanes; 64], batch: &mut [[u8; 8]]) {
+    let mut low = [0u64; 64];
+    let mut high = [0u64; 64];
+    for (i, slice) in slices.iter().enumerate() {
+        let (l, h) = slice.unpack();
+        low[i] = l;
+        high[i] = h;
+    }
+
+    let split = batch.len().min(64);
+    let (low_blocks, high_blocks) = batch.split_at_mut(split);
+    bitslice::store(low, low_blocks);
+    bitslice::store(high, high_blocks);
+}
+
+#[cfg(target_arch = "x86_64")]
+mod lanes {
+    use std::arch::x86_64::{
+        __m128i, _mm_and_si128, _mm_cvtsi128_si64, _mm_set1_epi64x, _mm_set_epi64x, _mm_unpackhi_epi64, _mm_xor_si128,
+    };
+    use std::ops::{BitAnd, BitXor, Not};
+
+    use bitslice::Word;
+
+    /// Two 64-bit lanes in an SSE2 register.
+    ///
+    /// The intrinsics are only unsafe because they require SSE2, which is
+    /// part of the x86_64 baseline, so calling them is always sound.
+    #[derive(Clone, Copy)]
+    pub struct Lanes(__m128i);
+
+    impl Lanes {
+        pub fn pack(low: u64, high: u64) -> Self {
+            Lanes(unsafe { _mm_set_epi64x(high as i64, low as i64) })
+        }
+
+        pub fn unpack(self) -> (u64, u64) {
+            unsafe {
+                let high = _mm_unpackhi_epi64(self.0, self.0);
+                (_mm_cvtsi128_si64(self.0) as u64, _mm_cvtsi128_si64(high) as u64)
+            }
+        }
+    }
+
+    impl BitAnd for Lanes {
+        type Output = Self;
+
+        fn bitand(self, rhs: Self) -> Self {
+            Lanes(unsafe { _mm_and_si128(self.0, rhs.0) })
+        }
+    }
+
+    impl BitXor for Lanes {
+        type Output = Self;
+
+        fn bitxor(self, rhs: Self) -> Self {
+            Lanes(unsafe { _mm_xor_si128(self.0, rhs.0) })
+        }
+    }
+
+    impl Not for Lanes {
+        type Output = Self;
+
+        fn not(self) -> Self {
+            Lanes(unsafe { _mm_xor_si128(self.0, _mm_set1_epi64x(-1)) })
+        }
+    }
+
+    impl Word for Lanes {
+        fn splat(bit: u64) -> Self {
+            Lanes(unsafe { _mm_set1_epi64x(0i64.wrapping_sub((bit & 1) as i64)) })
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod lanes {
+    use std::ops::{BitAnd, BitXor, Not};
+
+    use bitslice::Word;
+
+    /// Two 64-bit lanes, left to the compiler to vectorize.
+    #[derive(Clone, Copy)]
+    pub struct Lanes([u64; 2]);
+
+    impl Lanes {
+        pub fn pack(low: u64, high: u64) -> Self {
+            Lanes([low, high])
+        }
+
+        pub fn unpack(self) -> (u64, u64) {
+            (self.0[0], self.0[1])
+        }
+    }
+
+    impl BitAnd for Lanes {
+        type Output = Self;
+
+        fn bitand(self, rhs: Self) -> Self {
+            Lanes([self.0[0] & rhs.0[0], self.0[1] & rhs.0[1]])
+        }
+    }
+
+    impl BitXor for Lanes {
+        type Output = Self;
+
+        fn bitxor(self, rhs: Self) -> Self {
+            Lanes([self.0[0] ^ rhs.0[0], self.0[1] ^ rhs.0[1]])
+        }
+    }
+
+    impl Not for Lanes {
+        type Output = Self;
+
+        fn not(self) -> Self {
+            Lanes([!self.0[0], !self.0[1]])
+        }
+    }
+
+    impl Word for Lanes {
+        fn splat(bit: u64) -> Self {
+            let word = u64::splat(bit);
+            Lanes([word, word])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitslice::encrypt_blocks_bitsliced;
+    use keys::{Key, Key128Bit};
+    use testing::blocks;
+
+    #[test]
+    fn test_matches_bitsliced_encryption() {
+        let round_keys = Key128Bit::new([0x3C; 16]).generate_round_keys();
+
+        // Covers a full batch, a batch with a partial high lane and one
+        // with a partial low lane
+        for &len in [128usize, 300, 40].iter() {
+            let original = blocks(len);
+            let mut expected = original.clone();
+            encrypt_blocks_bitsliced(&mut expected, &round_keys);
+
+            let mut data = original.clone();
+            encrypt_blocks_simd(&mut data, &round_keys);
+            assert_eq!(data, expected);
+            decrypt_blocks_simd(&mut data, &round_keys);
+            assert_eq!(data, original);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use block::encrypt_block8;
+use errors::AeadError;
+use kdf::Hkdf;
+use keys::{Key, RoundKeys};
+#[cfg(feature = "zeroize")]
+use keys::wipe;
+use mac::{double, Cmac};
+use tag::tags_equal;
+
+/// Labels for deriving the subkeys in `Siv::from_master_key()`.
+const MAC_LABEL: &[u8] = b"present siv mac";
+const ENC_LABEL: &[u8] = b"present siv enc";
+
+/// SIV nonce-misuse-resistant authenticated encryption.
+///
+/// SIV (RFC 5297) derives a synthetic IV from a CMAC over the nonce,
+/// the associated data and the plaintext, and uses it both as the
+/// authentication tag and as the starting counter for encrypting the
+/// plaintext in counter mode. If a nonce is ever repeated, an attacker
+/// only learns whether two messages were identical. The nonce may also
+/// be omitted entirely, which makes encryption deterministic.
+///
+/// The construction follows RFC 5297 with PRESENT's 64-bit block, so
+/// the synthetic IV and the overhead per message are 8 bytes. It uses
+/// separate keys for the MAC and for encryption, which can also be
+/// derived from a single key with
+/// [`from_master_key()`](#method.from_master_key).
+///
+/// # Examples
+///
+/// ```
+/// use present::{Key128Bit, Siv};
+/// let siv = Siv::new(&Key128Bit::new([0x11; 16]), &Key128Bit::new([0x22; 16]));
+///
+/// let sealed = siv.encrypt(b"", b"header", b"reading");
+/// assert_eq!(sealed.len(), 8 + 7);
+/// assert_eq!(siv.decrypt(b"", b"header", &sealed).unwrap(), b"reading");
+/// ```
+pub struct Siv {
+    mac: Cmac,
+    round_keys: RoundKeys,
+}
+
+impl Siv {
+    /// Constructs a new SIV instance from a MAC key and an encryption key.
+    pub fn new<K: Key>(mac_key: &K, enc_key: &K) -> Self {
+        Siv { mac: Cmac::new(mac_key), round_keys: enc_key.generate_round_keys() }
+    }
+
+    /// Constructs a new SIV instance with MAC and encryption keys
+    /// derived from a single key with [`Hkdf`](struct.Hkdf.html).
+    pub fn from_master_key<K: Key>(key: &K) -> Self {
+        let hkdf = Hkdf::from_prk(key);
+        Siv::new(&hkdf.expand_key128(MAC_LABEL), &hkdf.expand_key128(ENC_LABEL))
+    }
+
+    /// Encrypts and authenticates a message.
+    ///
+    /// Returns the synthetic IV followed by the ciphertext. An empty
+    /// nonce is left out of the IV derivation.
+    pub fn encrypt(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let iv = self.s2v(nonce, aad, plaintext);
+        let mut output = iv.to_vec();
+        output.extend(self.ctr(&iv, plaintext));
+        output
+    }
+
+    /// Verifies and decrypts a message produced by
+    /// [`encrypt()`](#method.encrypt).
+    ///
+    /// # Errors
+    ///
+    /// Returns `AeadError::CiphertextTooShort` if the input is shorter
+    /// than the synthetic IV, and `AeadError::AuthenticationFailed` if
+    /// the IV does not match.
+    pub fn decrypt(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AeadError> {
+        if ciphertext.len() < 8 {
+            return Err(AeadError::CiphertextTooShort(ciphertext.len()));
+        }
+        let (iv, ciphertext) = ciphertext.split_at(8);
+        let mut received = [0u8; 8];
+        received.copy_from_slice(iv);
+
+        let plaintext = self.ctr(&received, ciphertext);
+        if !tags_equal(&self.s2v(nonce, aad, &plaintext), iv) {
+            return Err(AeadError::AuthenticationFailed);
+        }
+
+        Ok(plaintext)
+    }
+
+    fn cmac(&self, data: &[u8]) -> u64 {
+        let mut mac = self.mac.clone();
+        mac.update(data);
+        u64::from_be_bytes(mac.finalize())
+    }
+
+    /// Combines the inputs into the synthetic IV, see RFC 5297, 2.4.
+    fn s2v(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> [u8; 8] {
+        let mut d = self.cmac(&[0u8; 8]);
+        d = double(d) ^ self.cmac(aad);
+        if !nonce.is_empty() {
+            d = double(d) ^ self.cmac(nonce);
+        }
+
+        let mut mac = self.mac.clone();
+        if plaintext.len() >= 8 {
+            // XOR the accumulated value into the end of the plaintext
+            let split = plaintext.len() - 8;
+            let mut last = [0u8; 8];
+            last.copy_from_slice(&plaintext[split..]);
+            mac.update(&plaintext[..split]);
+            mac.update(&(u64::from_be_bytes(last) ^ d).to_be_bytes());
+        } else {
+            let mut padded = [0u8; 8];
+            padded[..plaintext.len()].copy_from_slice(plaintext);
+            padded[plaintext.len()] = 0x80;
+            mac.update(&(double(d) ^ u64::from_be_bytes(padded)).to_be_bytes());
+        }
+        mac.finalize()
+    }
+
+    fn ctr(&self, iv: &[u8; 8], input: &[u8]) -> Vec<u8> {
+        let counter = u64::from_be_bytes(*iv);
+        let mut output = Vec::with_capacity(input.len());
+        for (i, chunk) in input.chunks(8).enumerate() {
+            let mut keystream = counter.wrapping_add(i as u64).to_be_bytes();
+            encrypt_block8(&mut keystream, &self.round_keys);
+            output.extend(chunk.iter().zip(keystream.iter()).map(|(x, k)| x ^ k));
+        }
+        output
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Siv {
+    fn drop(&mut self) {
+        wipe(&mut self.round_keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::{Key80Bit, Key128Bit};
+    use mac::cmac;
+
+    fn siv() -> Siv {
+        Siv::new(&Key80Bit::new([0x11; 10]), &Key80Bit::new([0x22; 10]))
+    }
+
+    #[test]
+    fn test_s2v_matches_definition() {
+        let mac_key = Key80Bit::new([0x11; 10]);
+        let c = |data: &[u8]| u64::from_be_bytes(cmac(&mac_key, data));
+
+        let d = double(c(&[0u8; 8])) ^ c(b"aad");
+        let short = double(d) ^ u64::from_be_bytes(*b"abc\x80\0\0\0\0");
+        assert_eq!(siv().s2v(b"", b"aad", b"abc"), cmac(&mac_key, &short.to_be_bytes()));
+
+        let d = double(d) ^ c(b"nonce");
+        let mut long = b"0123456789".to_vec();
+        let tail = u64::from_be_bytes(*b"23456789") ^ d;
+        long[2..].copy_from_slice(&tail.to_be_bytes());
+        assert_eq!(siv().s2v(b"nonce", b"aad", b"0123456789"), cmac(&mac_key, &long));
+    }
+
+    #[test]
+    fn test_roundtrip_and_tampering() {
+        let siv = siv();
+        for len in 0..24 {
+            let plaintext: Vec<u8> = (0..len as u8).collect();
+            let mut sealed = siv.encrypt(b"n", b"header", &plaintext);
+            assert_eq!(sealed.len(), len + 8);
+            assert_eq!(siv.decrypt(b"n", b"header", &sealed).unwrap(), plaintext);
+
+            assert_eq!(siv.decrypt(b"", b"header", &sealed), Err(AeadError::AuthenticationFailed));
+            assert_eq!(siv.decrypt(b"n", b"other", &sealed), Err(AeadError::AuthenticationFailed));
+            let last = sealed.len() - 1;
+            sealed[last] ^= 0x04;
+            assert_eq!(siv.decrypt(b"n", b"header", &sealed), Err(AeadError::AuthenticationFailed));
+        }
+        assert_eq!(siv.decrypt(b"", b"", &[0; 7]), Err(AeadError::CiphertextTooShort(7)));
+    }
+
+    #[test]
+    fn test_master_key_derives_separate_keys() {
+        let master = Key128Bit::new([0x33; 16]);
+        let siv = Siv::from_master_key(&master);
+        let sealed = siv.encrypt(b"n", b"header", b"reading");
+        assert_eq!(siv.decrypt(b"n", b"header", &sealed).unwrap(), b"reading");
+        assert!(Siv::new(&master, &master).decrypt(b"n", b"header", &sealed).is_err());
+    }
+
+    #[test]
+    fn test_nonce_reuse_only_leaks_equality() {
+        let siv = siv();
+        assert_eq!(siv.encrypt(b"n", b"", b"same"), siv.encrypt(b"n", b"", b"same"));
+        let a = siv.encrypt(b"n", b"", b"message a");
+        let b = siv.encrypt(b"n", b"", b"message b");
+        assert!(a[..8] != b[..8]);
+        assert!(a[8..16] != b[8..16]);
+    }
+}
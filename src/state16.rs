@@ -0,0 +1,261 @@
+#[cfg(debug_assertions)]
+use crosscheck;
+#[cfg(debug_assertions)]
+use keys::RoundKey;
+use keys::{Key80Bit, Key128Bit, RoundKeys, schedule_80bit, schedule_128bit};
+use sbox;
+
+/// Round keys for the 16-bit implementation.
+///
+/// Each round key is stored as four 16-bit words, with the most
+/// significant word first. The key schedule only uses byte
+/// operations, so no 64-bit arithmetic is required at all.
+pub struct RoundKeys16 {
+    keys: [[u16; 4]; 32],
+}
+
+impl RoundKeys16 {
+    /// Generates the round keys for an 80-bit key.
+    pub fn from_key80(key: &Key80Bit) -> Self {
+        RoundKeys16::from_bytes(&schedule_80bit(&key.value))
+    }
+
+    /// Generates the round keys for a 128-bit key.
+    pub fn from_key128(key: &Key128Bit) -> Self {
+        RoundKeys16::from_bytes(&schedule_128bit(&key.value))
+    }
+
+    fn from_bytes(bytes: &[[u8; 8]; 32]) -> Self {
+        let mut keys = [[0u16; 4]; 32];
+        for (words, round_key) in keys.iter_mut().zip(bytes.iter()) {
+            *words = to_words(round_key);
+        }
+        RoundKeys16 { keys }
+    }
+
+    /// Converts the round keys to the representation of the reference
+    /// implementation, for cross-checking in debug builds.
+    #[cfg(debug_assertions)]
+    fn to_round_keys(&self) -> RoundKeys {
+        let mut round_keys = RoundKeys::new([RoundKey { value: 0 }; 32]);
+        for (round_key, words) in round_keys.iter_mut().zip(self.keys.iter()) {
+            round_key.value = u64::from_be_bytes(to_bytes(words));
+        }
+        round_keys
+    }
+}
+
+/// Encrypts an 8-byte array in place using 16-bit operations only.
+///
+/// This produces the same result as
+/// [`encrypt_block8()`](fn.encrypt_block8.html), but represents the
+/// state as four 16-bit words. It is meant for 8-bit and 16-bit
+/// microcontrollers, on which 64-bit operations are emulated and slow.
+///
+/// # Examples
+///
+/// ```
+/// use present::{encrypt_block16, decrypt_block16, Key80Bit, RoundKeys16};
+/// let round_keys = RoundKeys16::from_key80(&Key80Bit::new([0u8; 10]));
+/// let mut bytes = [0u8; 8];
+/// encrypt_block16(&mut bytes, &round_keys);
+/// assert_eq!(bytes, [0x55, 0x79, 0xC1, 0x38, 0x7B, 0x22, 0x84, 0x45]);
+/// decrypt_block16(&mut bytes, &round_keys);
+/// assert_eq!(bytes, [0u8; 8]);
+/// ```
+pub fn encrypt_block16(bytes: &mut [u8; 8], round_keys: &RoundKeys16) {
+    #[cfg(debug_assertions)]
+    let input = *bytes;
+    *bytes = to_bytes(&encrypt_words(to_words(bytes), |round| round_keys.keys[round]));
+
+    #[cfg(debug_assertions)]
+    {
+        if crosscheck::should_sample() {
+            crosscheck::check_block("16-bit", true, &input, bytes, &round_keys.to_round_keys());
+        }
+    }
+}
+
+/// Decrypts an 8-byte array in place using 16-bit operations only.
+///
+/// This is the inverse of [`encrypt_block16()`](fn.encrypt_block16.html).
+pub fn decrypt_block16(bytes: &mut [u8; 8], round_keys: &RoundKeys16) {
+    #[cfg(debug_assertions)]
+    let input = *bytes;
+    *bytes = to_bytes(&decrypt_words(to_words(bytes), |round| round_keys.keys[round]));
+
+    #[cfg(debug_assertions)]
+    {
+        if crosscheck::should_sample() {
+            crosscheck::check_block("16-bit", false, &input, bytes, &round_keys.to_round_keys());
+        }
+    }
+}
+
+/// Encrypts a block state with the round keys of the 64-bit
+/// implementation, which is how [`Block`](struct.Block.html) encrypts
+/// with the `small-state` feature. Each round key is split into words
+/// when it is used, so the rounds only use 16-bit operations.
+pub fn encrypt_state(state: u64, round_keys: &RoundKeys) -> u64 {
+    let bytes = to_bytes(&encrypt_words(to_words(&state.to_be_bytes()), |round| split(round_keys, round)));
+
+    #[cfg(debug_assertions)]
+    {
+        if crosscheck::should_sample() {
+            crosscheck::check_block("16-bit", true, &state.to_be_bytes(), &bytes, round_keys);
+        }
+    }
+    u64::from_be_bytes(bytes)
+}
+
+/// Decrypts a block state with the round keys of the 64-bit
+/// implementation, see [`encrypt_state()`](fn.encrypt_state.html).
+pub fn decrypt_state(state: u64, round_keys: &RoundKeys) -> u64 {
+    let bytes = to_bytes(&decrypt_words(to_words(&state.to_be_bytes()), |round| split(round_keys, round)));
+
+    #[cfg(debug_assertions)]
+    {
+        if crosscheck::should_sample() {
+            crosscheck::check_block("16-bit", false, &state.to_be_bytes(), &bytes, round_keys);
+        }
+    }
+    u64::from_be_bytes(bytes)
+}
+
+/// Applies the rounds of encryption, with the round keys given by round.
+fn encrypt_words<F>(mut state: [u16; 4], round_key: F) -> [u16; 4]
+    where F: Fn(usize) -> [u16; 4] {
+
+    for round in 0..31 {
+        add_round_key(&mut state, &round_key(round));
+        substitute(&mut state, sbox::apply_enc);
+        state = permute(&state, |bit| (bit % 4) * 16 + bit / 4);
+    }
+    add_round_key(&mut state, &round_key(31));
+    state
+}
+
+/// Applies the rounds of decryption, with the round keys given by round.
+fn decrypt_words<F>(mut state: [u16; 4], round_key: F) -> [u16; 4]
+    where F: Fn(usize) -> [u16; 4] {
+
+    for round in (1..32).rev() {
+        add_round_key(&mut state, &round_key(round));
+        state = permute(&state, |bit| bit / 16 + (bit % 16) * 4);
+        substitute(&mut state, sbox::apply_dec);
+    }
+    add_round_key(&mut state, &round_key(0));
+    state
+}
+
+/// Splits the round key of the given round into words.
+fn split(round_keys: &RoundKeys, round: usize) -> [u16; 4] {
+    to_words(&round_keys[round].value.to_be_bytes())
+}
+
+fn add_round_key(state: &mut [u16; 4], round_key: &[u16; 4]) {
+    for (word, key) in state.iter_mut().zip(round_key.iter()) {
+        *word ^= *key;
+    }
+}
+
+fn substitute<F>(state: &mut [u16; 4], sbox: F)
+    where F: Fn(u8) -> u8 {
+
+    for word in state.iter_mut() {
+        let mut new_word = 0u16;
+        for split in 0..4 {
+            let shift = 4 * split;
+            new_word |= (sbox(((*word >> shift) as u8) % 16) as u16) << shift;
+        }
+        *word = new_word;
+    }
+}
+
+fn permute<F>(state: &[u16; 4], calc_bit: F) -> [u16; 4]
+    where F: Fn(usize) -> usize {
+
+    // Bit 0 is the least significant bit of the last word
+    let mut output = [0u16; 4];
+    for bit in 0..64 {
+        let new_bit = calc_bit(bit);
+        let bit_value = (state[3 - bit / 16] >> (bit % 16)) & 1;
+        output[3 - new_bit / 16] |= bit_value << (new_bit % 16);
+    }
+    output
+}
+
+fn to_words(bytes: &[u8; 8]) -> [u16; 4] {
+    let mut words = [0u16; 4];
+    for (word, pair) in words.iter_mut().zip(bytes.chunks(2)) {
+        *word = ((pair[0] as u16) << 8) | pair[1] as u16;
+    }
+    words
+}
+
+fn to_bytes(words: &[u16; 4]) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    for (pair, word) in bytes.chunks_mut(2).zip(words.iter()) {
+        pair[0] = (*word >> 8) as u8;
+        pair[1] = *word as u8;
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::Block;
+    use keys::Key;
+
+    #[test]
+    fn test_word_conversion() {
+        let bytes = [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+        let words = to_words(&bytes);
+        assert_eq!(words, [0x0123, 0x4567, 0x89AB, 0xCDEF]);
+        assert_eq!(to_bytes(&words), bytes);
+    }
+
+    #[test]
+    fn test_permutation_matches_pbox() {
+        let state = to_words(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF]);
+        let permuted = permute(&state, |bit| (bit % 4) * 16 + bit / 4);
+        assert_eq!(permuted, [0x00FF, 0x0F0F, 0x3333, 0x5555]);
+        assert_eq!(permute(&permuted, |bit| bit / 16 + (bit % 16) * 4), state);
+    }
+
+    #[test]
+    fn test_encryption_matches_block_encryption() {
+        let key = Key128Bit::new([0xA, 0xC0, 0xA6, 0xE7, 0x63, 0x26, 0xBC, 0x7E, 0x82, 0x80, 0x12, 0xAA, 0x5F, 0xDF, 0x39, 0x25]);
+        let round_keys = RoundKeys16::from_key128(&key);
+
+        let mut bytes = [0x10, 0x34, 0x52, 0xD9, 0xAF, 0x55, 0x90, 0xFC];
+        let mut block = Block::from_bytes(&bytes);
+        block.encrypt(&key);
+        encrypt_block16(&mut bytes, &round_keys);
+        assert_eq!(bytes, block.to_bytes());
+
+        decrypt_block16(&mut bytes, &round_keys);
+        assert_eq!(bytes, [0x10, 0x34, 0x52, 0xD9, 0xAF, 0x55, 0x90, 0xFC]);
+    }
+
+    #[test]
+    fn test_block_uses_words() {
+        let key = Key80Bit::new([0xFF_u8; 10]);
+        let round_keys = key.generate_round_keys();
+        assert_eq!(encrypt_state(0xFFFFFFFFFFFFFFFF, &round_keys), 0x3333DCD3213210D2);
+        assert_eq!(decrypt_state(0x3333DCD3213210D2, &round_keys), 0xFFFFFFFFFFFFFFFF);
+
+        let mut block = Block::new(0xFFFFFFFFFFFFFFFF);
+        block.encrypt(&key);
+        assert_eq!(block.get_state(), 0x3333DCD3213210D2);
+    }
+
+    #[test]
+    fn test_encryption_80bit_key_test_vectors() {
+        let round_keys = RoundKeys16::from_key80(&Key80Bit::new([0xFF_u8; 10]));
+        let mut bytes = [0xFF_u8; 8];
+        encrypt_block16(&mut bytes, &round_keys);
+        assert_eq!(bytes, [0x33, 0x33, 0xDC, 0xD3, 0x21, 0x32, 0x10, 0xD2]);
+    }
+}
@@ -0,0 +1,507 @@
+use std::io::{self, Read, Write};
+
+use block::Block;
+use context::Expanded;
+use errors::DecryptError;
+use keys::Key;
+use modes::{ModeOfOperation, OpMode};
+use keys::KeyFingerprint;
+use policy;
+use {check_padding, final_block, report_mode};
+
+/// Size of the working buffers, so memory use does not depend on the
+/// amount of data passing through.
+pub const BUFFER_SIZE: usize = 4096;
+
+/// Encrypts all data of a reader into a writer.
+///
+/// The data is processed in chunks with a working buffer of fixed
+/// size, so inputs of any size can be encrypted without holding them
+/// in memory. The ciphertext is the same as the one returned by
+/// [`try_encrypt_bytes_with_iv()`](fn.try_encrypt_bytes_with_iv.html). Returns
+/// the number of plaintext bytes read.
+///
+/// # Examples
+///
+/// ```
+/// use present::{decrypt_stream, encrypt_stream, Block, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0x42; 10]);
+/// let iv = Block::new(0x0123456789ABCDEF);
+/// let plaintext = vec![0x5A; 100_000];
+///
+/// let mut ciphertext = Vec::new();
+/// encrypt_stream(&mut &plaintext[..], &mut ciphertext, &key, &OpMode::CBC, &iv).unwrap();
+///
+/// let mut decrypted = Vec::new();
+/// decrypt_stream(&mut &ciphertext[..], &mut decrypted, &key, &OpMode::CBC, &iv).unwrap();
+/// assert_eq!(decrypted, plaintext);
+/// ```
+pub fn encrypt_stream<R, W, K>(reader: &mut R, writer: &mut W, key: &K, mode: &OpMode, init_vec: &Block) -> io::Result<u64>
+    where R: Read, W: Write, K: Key {
+
+    let mut encrypt_writer = EncryptWriter::new(writer, key, mode, init_vec);
+    let len = io::copy(reader, &mut encrypt_writer)?;
+    encrypt_writer.finish()?;
+    Ok(len)
+}
+
+/// Decrypts all data of a reader into a writer.
+///
+/// This is the inverse of [`encrypt_stream()`](fn.encrypt_stream.html).
+/// Returns the number of plaintext bytes written.
+///
+/// # Errors
+///
+/// Decryption errors, e.g. invalid padding, are returned as an
+/// `io::Error` of kind `InvalidData`, like in
+/// [`DecryptReader`](struct.DecryptReader.html).
+pub fn decrypt_stream<R, W, K>(reader: &mut R, writer: &mut W, key: &K, mode: &OpMode, init_vec: &Block) -> io::Result<u64>
+    where R: Read, W: Write, K: Key {
+
+    io::copy(&mut DecryptReader::new(reader, key, mode, init_vec), writer)
+}
+
+/// Writer that encrypts all data written to it.
+///
+/// Full blocks are encrypted and passed on to the inner writer as soon
+/// as they are complete, the rest is buffered. Since the padding can
+/// only be added once the end of the data is known,
+/// [`finish()`](#method.finish) must be called after the last write.
+/// `flush()` only flushes the inner writer and leaves an incomplete
+/// block buffered. If the writer is dropped without being finished,
+/// the final block is lost and the ciphertext is incomplete, just like
+/// an [`AsyncEncryptWriter`](struct.AsyncEncryptWriter.html) that is
+/// never shut down.
+///
+/// The ciphertext is the same as the one returned by
+/// [`try_encrypt_bytes_with_iv()`](fn.try_encrypt_bytes_with_iv.html). The IV
+/// is not written and is ignored by ECB.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{Read, Write};
+/// use present::{Block, DecryptReader, EncryptWriter, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0x42; 10]);
+/// let iv = Block::new(0x0123456789ABCDEF);
+///
+/// let mut writer = EncryptWriter::new(Vec::new(), &key, &OpMode::CBC, &iv);
+/// writer.write_all(b"streamed ").unwrap();
+/// writer.write_all(b"message").unwrap();
+/// let ciphertext = writer.finish().unwrap();
+///
+/// let mut reader = DecryptReader::new(&ciphertext[..], &key, &OpMode::CBC, &iv);
+/// let mut plaintext = String::new();
+/// reader.read_to_string(&mut plaintext).unwrap();
+/// assert_eq!(plaintext, "streamed message");
+/// ```
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    state: StreamState,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    /// Constructs a new writer that encrypts into the given writer.
+    pub fn new<K: Key>(inner: W, key: &K, mode: &OpMode, init_vec: &Block) -> Self {
+        EncryptWriter { inner, state: StreamState::for_encryption(key, mode, init_vec) }
+    }
+
+    /// Writes the final block, including the padding, flushes the
+    /// inner writer and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        let len = self.state.encrypt_final();
+        self.inner.write_all(&self.state.buffer[..len])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for chunk in buf.chunks(BUFFER_SIZE - 8) {
+            let len = self.state.encrypt_update(chunk);
+            self.inner.write_all(&self.state.buffer[..len])?;
+            self.state.consume(len);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader that decrypts all data read through it.
+///
+/// The ciphertext is read from the inner reader in chunks and
+/// decrypted as soon as possible. For modes with padding, the last
+/// block is held back until the end of the input is reached, and the
+/// padding is checked and removed then. This is the inverse of
+/// [`EncryptWriter`](struct.EncryptWriter.html).
+///
+/// Errors during decryption, e.g. invalid padding, are returned as an
+/// `io::Error` of kind `InvalidData`. Note that the data before the
+/// final block has already been returned at that point.
+pub struct DecryptReader<R: Read> {
+    inner: R,
+    state: StreamState,
+    /// Range of decrypted bytes in the buffer that were not read yet.
+    start: usize,
+    end: usize,
+    eof: bool,
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Constructs a new reader that decrypts the data of the given reader.
+    pub fn new<K: Key>(inner: R, key: &K, mode: &OpMode, init_vec: &Block) -> Self {
+        DecryptReader { inner, state: StreamState::new(key, mode, init_vec), start: 0, end: 0, eof: false }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads and decrypts more data, until at least one byte of
+    /// plaintext is available or the end of the input is reached.
+    fn fill(&mut self) -> io::Result<()> {
+        self.state.consume(self.end);
+        self.start = 0;
+        self.end = 0;
+
+        while self.end == 0 && !self.eof {
+            let pending = self.state.pending;
+            let read = match self.inner.read(&mut self.state.buffer[pending..]) {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            if read == 0 {
+                self.eof = true;
+                self.end = self.state.decrypt_final().map_err(invalid_data)?;
+            } else {
+                self.end = self.state.decrypt_update(read);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.start == self.end {
+            self.fill()?;
+        }
+
+        let len = buf.len().min(self.end - self.start);
+        buf[..len].copy_from_slice(&self.state.buffer[self.start..(self.start + len)]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+/// Incremental encryption of data that arrives in chunks.
+///
+/// Feed the plaintext to [`update()`](#method.update) in pieces of any
+/// size, e.g. as they are received from a socket, and call
+/// [`finalize()`](#method.finalize) after the last piece to get the
+/// final block with the padding. The concatenated outputs are the same
+/// as the ciphertext returned by
+/// [`try_encrypt_bytes_with_iv()`](fn.try_encrypt_bytes_with_iv.html).
+///
+/// # Examples
+///
+/// ```
+/// use present::{Block, Decryptor, Encryptor, Key128Bit, OpMode};
+/// let key = Key128Bit::new([0x42; 16]);
+/// let iv = Block::new(0x0123456789ABCDEF);
+///
+/// let mut encryptor = Encryptor::new(&key, &OpMode::PCBC, &iv);
+/// let mut ciphertext = encryptor.update(b"arrives ");
+/// ciphertext.extend(encryptor.update(b"in chunks"));
+/// ciphertext.extend(encryptor.finalize());
+///
+/// let mut decryptor = Decryptor::new(&key, &OpMode::PCBC, &iv);
+/// let mut plaintext = decryptor.update(&ciphertext[..5]);
+/// plaintext.extend(decryptor.update(&ciphertext[5..]));
+/// plaintext.extend(decryptor.finalize().unwrap());
+/// assert_eq!(plaintext, b"arrives in chunks");
+/// ```
+pub struct Encryptor {
+    state: StreamState,
+}
+
+impl Encryptor {
+    /// Constructs a new encryptor. The IV is ignored by ECB.
+    pub fn new<K: Key>(key: &K, mode: &OpMode, init_vec: &Block) -> Self {
+        Encryptor { state: StreamState::for_encryption(key, mode, init_vec) }
+    }
+
+    /// Encrypts the next piece of plaintext and returns the ciphertext
+    /// of all blocks that are complete. An incomplete block is kept
+    /// until more data arrives.
+    pub fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let mut ciphertext = Vec::with_capacity(plaintext.len() + 8);
+        for chunk in plaintext.chunks(BUFFER_SIZE - 8) {
+            let len = self.state.encrypt_update(chunk);
+            ciphertext.extend_from_slice(&self.state.buffer[..len]);
+            self.state.consume(len);
+        }
+        ciphertext
+    }
+
+    /// Encrypts the remaining data and returns the final ciphertext,
+    /// including the padding.
+    pub fn finalize(mut self) -> Vec<u8> {
+        let len = self.state.encrypt_final();
+        self.state.buffer[..len].to_vec()
+    }
+}
+
+/// Incremental decryption of data that arrives in chunks.
+///
+/// This is the inverse of [`Encryptor`](struct.Encryptor.html). For
+/// modes with padding, the last block is held back by
+/// [`update()`](#method.update), since it may be the final one, and
+/// only returned by [`finalize()`](#method.finalize) once its padding
+/// has been checked and removed.
+pub struct Decryptor {
+    state: StreamState,
+}
+
+impl Decryptor {
+    /// Constructs a new decryptor. The IV is ignored by ECB.
+    pub fn new<K: Key>(key: &K, mode: &OpMode, init_vec: &Block) -> Self {
+        Decryptor { state: StreamState::new(key, mode, init_vec) }
+    }
+
+    /// Decrypts the next piece of ciphertext and returns the plaintext
+    /// that is already known.
+    pub fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        let mut plaintext = Vec::with_capacity(ciphertext.len() + 8);
+        for chunk in ciphertext.chunks(BUFFER_SIZE - 8) {
+            let pending = self.state.pending;
+            self.state.buffer[pending..(pending + chunk.len())].copy_from_slice(chunk);
+            let len = self.state.decrypt_update(chunk.len());
+            plaintext.extend_from_slice(&self.state.buffer[..len]);
+            self.state.consume(len);
+        }
+        plaintext
+    }
+
+    /// Decrypts the remaining data and returns the final plaintext,
+    /// without the padding.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with a `DecryptError` if the total length of the
+    /// ciphertext is invalid or the padding is corrupted.
+    pub fn finalize(mut self) -> Result<Vec<u8>, DecryptError> {
+        let len = self.state.decrypt_final()?;
+        Ok(self.state.buffer[..len].to_vec())
+    }
+}
+
+/// Converts a decryption error into an I/O error.
+pub fn invalid_data(err: DecryptError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Mode state and block buffering shared by the streaming types.
+///
+/// The buffer starts with the processed bytes that were not passed on
+/// yet, followed by `pending` unprocessed bytes.
+pub struct StreamState {
+    cipher: Expanded,
+    mode: OpMode,
+    feedback: Block,
+    pub buffer: [u8; BUFFER_SIZE],
+    pub pending: usize,
+    total: usize,
+    /// Fingerprint of the key for the security policy, only set for
+    /// encryption.
+    key: Option<KeyFingerprint>,
+}
+
+impl StreamState {
+    pub fn new<K: Key>(key: &K, mode: &OpMode, init_vec: &Block) -> Self {
+        report_mode(mode);
+        StreamState {
+            cipher: Expanded::new(key),
+            mode: *mode,
+            feedback: Block::new(init_vec.get_state()),
+            buffer: [0u8; BUFFER_SIZE],
+            pending: 0,
+            total: 0,
+            key: None,
+        }
+    }
+
+    /// Constructs the state for encryption, which is tracked by the
+    /// security policy.
+    pub fn for_encryption<K: Key>(key: &K, mode: &OpMode, init_vec: &Block) -> Self {
+        let mut state = StreamState::new(key, mode, init_vec);
+        state.key = policy::fingerprint(&state.cipher);
+        let init_vec = if mode.needs_init_vec() { Some(init_vec.get_state()) } else { None };
+        policy::record_encryption(state.key, init_vec, 0);
+        state
+    }
+
+    /// Removes the first `len` bytes from the buffer, which must have
+    /// been processed.
+    pub fn consume(&mut self, len: usize) {
+        self.buffer.copy_within(len..(len + self.pending), 0);
+    }
+
+    /// Appends the input to the pending bytes and encrypts all full
+    /// blocks. Returns the number of encrypted bytes at the start of
+    /// the buffer. The input must fit into the buffer.
+    pub fn encrypt_update(&mut self, input: &[u8]) -> usize {
+        let end = self.pending + input.len();
+        self.buffer[self.pending..end].copy_from_slice(input);
+
+        let len = end - end % 8;
+        self.pending = end - len;
+        self.total += len;
+        self.mode.encrypt_in_place(&mut self.buffer[..len], &self.cipher, &mut self.feedback);
+        len
+    }
+
+    /// Encrypts the pending bytes as the final block. Returns the
+    /// number of encrypted bytes at the start of the buffer.
+    pub fn encrypt_final(&mut self) -> usize {
+        let mut len = self.pending;
+        if self.mode.is_padded() {
+            let last = final_block(&self.buffer[..len]);
+            self.buffer[..8].copy_from_slice(&last);
+            len = 8;
+        }
+
+        self.pending = 0;
+        self.total += len;
+        self.mode.encrypt_in_place(&mut self.buffer[..len], &self.cipher, &mut self.feedback);
+        policy::record_encryption(self.key, None, self.total.div_ceil(8) as u64);
+        len
+    }
+
+    /// Decrypts the blocks among the pending bytes after `read` more
+    /// bytes were appended. For modes with padding, the last block is
+    /// held back. Returns the number of decrypted bytes at the start
+    /// of the buffer.
+    pub fn decrypt_update(&mut self, read: usize) -> usize {
+        let end = self.pending + read;
+        let len = if self.mode.is_padded() {
+            end.saturating_sub(1) / 8 * 8
+        } else {
+            end - end % 8
+        };
+
+        self.pending = end - len;
+        self.total += read;
+        self.mode.decrypt_in_place(&mut self.buffer[..len], &self.cipher, &mut self.feedback);
+        len
+    }
+
+    /// Decrypts the pending bytes as the final block and removes the
+    /// padding. Returns the number of decrypted bytes at the start of
+    /// the buffer.
+    pub fn decrypt_final(&mut self) -> Result<usize, DecryptError> {
+        let len = self.pending;
+        if self.mode.is_padded() {
+            if self.total < 8 {
+                return Err(DecryptError::CiphertextTooShort { len: self.total, min_len: 8 });
+            }
+            if len != 8 {
+                return Err(DecryptError::CiphertextNotAligned { len: self.total, block_size: 8 });
+            }
+        }
+
+        self.pending = 0;
+        self.mode.decrypt_in_place(&mut self.buffer[..len], &self.cipher, &mut self.feedback);
+
+        if self.mode.is_padded() {
+            Ok(len - check_padding(&self.buffer[..8], self.total / 8 - 1)?)
+        } else {
+            Ok(len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modes::all_modes;
+    use keys::Key80Bit;
+
+    #[test]
+    fn test_matches_allocating_functions() {
+        let key = Key80Bit::new([0x42; 10]);
+        let iv = Block::new(0x0123456789ABCDEF);
+
+        // Longer than the buffer, and written in odd-sized pieces
+        let plaintext: Vec<u8> = (0..10000u32).map(|i| i as u8).collect();
+        for &len in [0usize, 7, 8, 9, 10000].iter() {
+            let plaintext = &plaintext[..len];
+            for mode in all_modes().iter() {
+                let expected = ::try_encrypt_bytes_with_iv(plaintext, &key, mode, &iv).unwrap();
+
+                let mut writer = EncryptWriter::new(Vec::new(), &key, mode, &iv);
+                for piece in plaintext.chunks(13) {
+                    writer.write_all(piece).unwrap();
+                }
+                let ciphertext = writer.finish().unwrap();
+                assert_eq!(ciphertext, expected);
+
+                let mut decrypted = Vec::new();
+                DecryptReader::new(&ciphertext[..], &key, mode, &iv).read_to_end(&mut decrypted).unwrap();
+                assert_eq!(decrypted, plaintext);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_rejects_invalid_ciphertext() {
+        let key = Key80Bit::new([0x42; 10]);
+        let iv = Block::new(0);
+        let mut ciphertext = ::try_encrypt_bytes_with_iv(b"some data", &key, &OpMode::CBC, &iv).unwrap();
+        ciphertext.pop();
+
+        let err = DecryptReader::new(&ciphertext[..], &key, &OpMode::CBC, &iv).read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = DecryptReader::new(&[0u8; 3][..], &key, &OpMode::PCBC, &iv).read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_incremental_matches_allocating_functions() {
+        let key = Key80Bit::new([0x42; 10]);
+        let iv = Block::new(0x0123456789ABCDEF);
+        let plaintext: Vec<u8> = (0..5000u32).map(|i| (i * 7) as u8).collect();
+
+        for mode in all_modes().iter() {
+            let expected = ::try_encrypt_bytes_with_iv(&plaintext, &key, mode, &iv).unwrap();
+
+            // Piece sizes that are not aligned to the block size
+            let mut encryptor = Encryptor::new(&key, mode, &iv);
+            let mut ciphertext = encryptor.update(&plaintext[..3]);
+            ciphertext.extend(encryptor.update(&plaintext[3..4500]));
+            ciphertext.extend(encryptor.update(&plaintext[4500..]));
+            ciphertext.extend(encryptor.finalize());
+            assert_eq!(ciphertext, expected);
+
+            let mut decryptor = Decryptor::new(&key, mode, &iv);
+            let mut decrypted = Vec::new();
+            for piece in ciphertext.chunks(11) {
+                decrypted.extend(decryptor.update(piece));
+            }
+            decrypted.extend(decryptor.finalize().unwrap());
+            assert_eq!(decrypted, plaintext);
+        }
+
+        let mut decryptor = Decryptor::new(&key, &OpMode::CBC, &iv);
+        decryptor.update(&[0u8; 12]);
+        assert!(matches!(decryptor.finalize(), Err(DecryptError::CiphertextNotAligned { len: 12, .. })));
+    }
+}
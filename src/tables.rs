@@ -0,0 +1,53 @@
+use pbox;
+use sbox;
+
+/// The S-Box and the permutation of the encryption round fused into one
+/// table per state byte, in the style of AES T-tables. Each entry holds
+/// the permuted substitution of one byte value at one position, so the
+/// whole layer is eight lookups combined with OR.
+///
+/// Decryption has no fused form, since the inverse S-Box is applied
+/// after the inverse permutation and the permutation mixes nibbles
+/// across bytes.
+static SP_ENC: [[u64; 256]; 8] = sp_tables();
+
+const fn sp_tables() -> [[u64; 256]; 8] {
+    let mut tables = [[0u64; 256]; 8];
+    let mut byte = 0;
+    while byte < 8 {
+        let mut value = 0;
+        while value < 256 {
+            let substituted = sbox::apply_enc_byte(value as u8) as u64;
+            tables[byte][value] = pbox::apply_enc_bitwise(substituted << (8 * byte));
+            value += 1;
+        }
+        byte += 1;
+    }
+    tables
+}
+
+/// Applies the substitution and the permutation layer of a round.
+pub fn substitute_permute(state: u64) -> u64 {
+    let mut output = 0u64;
+    for (byte, table) in SP_ENC.iter().enumerate() {
+        output |= table[((state >> (8 * byte)) & 0xFF) as usize];
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::states;
+
+    #[test]
+    fn test_fused_layer_matches_separate_layers() {
+        for &value in states(64).iter() {
+            let mut bytes = value.to_be_bytes();
+            for byte in bytes.iter_mut() {
+                *byte = sbox::apply_enc_byte(*byte);
+            }
+            assert_eq!(substitute_permute(value), pbox::apply_enc(u64::from_be_bytes(bytes)));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use subtle::ConstantTimeEq;
+
+use errors::AeadError;
+
+/// Length of an authentication tag.
+///
+/// PRESENT's 64-bit block limits tags to at most 8 bytes. Constrained
+/// protocols often require shorter tags, so tags can be truncated, but
+/// never below [`TagLen::MIN`](#associatedconstant.MIN) bytes, since
+/// shorter tags can be forged by guessing.
+///
+/// # Examples
+///
+/// ```
+/// use present::TagLen;
+/// assert_eq!(TagLen::new(6).unwrap().bytes(), 6);
+/// assert!(TagLen::new(2).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagLen(usize);
+
+impl TagLen {
+    /// Shortest allowed tag length in bytes (32 bits).
+    pub const MIN: TagLen = TagLen(4);
+    /// Full tag length in bytes (64 bits).
+    pub const FULL: TagLen = TagLen(8);
+
+    /// Creates a tag length of the given number of bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AeadError::InvalidTagLength` if `bytes` is shorter than
+    /// `TagLen::MIN` or longer than a block.
+    pub fn new(bytes: usize) -> Result<Self, AeadError> {
+        if !(TagLen::MIN.0..=TagLen::FULL.0).contains(&bytes) {
+            return Err(AeadError::InvalidTagLength(bytes));
+        }
+        Ok(TagLen(bytes))
+    }
+
+    /// Returns the tag length in bytes.
+    pub fn bytes(&self) -> usize {
+        self.0
+    }
+}
+
+impl Default for TagLen {
+    fn default() -> Self {
+        TagLen::FULL
+    }
+}
+
+/// Compares two tags in constant time.
+///
+/// Use this instead of `==` to check a received tag against the
+/// expected one: `==` returns at the first differing byte, so its
+/// timing tells an attacker how many leading bytes of a forged tag are
+/// correct. Only the lengths are compared in variable time, since they
+/// are not secret.
+///
+/// # Examples
+///
+/// ```
+/// use present::{cmac, tags_equal, Key128Bit};
+/// let key = Key128Bit::new([0x2B; 16]);
+/// let tag = cmac(&key, b"Hello, world!");
+/// assert!(tags_equal(&tag, &cmac(&key, b"Hello, world!")));
+/// assert!(!tags_equal(&tag, &cmac(&key, b"Hello, world?")));
+/// ```
+pub fn tags_equal(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_len_bounds() {
+        for bytes in 0..12 {
+            assert_eq!(TagLen::new(bytes).is_ok(), (4..=8).contains(&bytes));
+        }
+        assert_eq!(TagLen::default(), TagLen::FULL);
+    }
+
+    #[test]
+    fn test_tags_equal() {
+        assert!(tags_equal(&[1, 2, 3, 4], &[1, 2, 3, 4]));
+        assert!(!tags_equal(&[1, 2, 3, 4], &[1, 2, 3, 5]));
+        assert!(!tags_equal(&[1, 2, 3, 4], &[1, 2, 3]));
+    }
+}
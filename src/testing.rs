@@ -0,0 +1,17 @@
+/// Returns distinct states for tests that compare two implementations
+/// on arbitrary input. Consecutive states differ in about half of their
+/// bits, and the sequence is the same on every run.
+pub fn states(count: usize) -> Vec<u64> {
+    let mut state = 0x0123456789ABCDEF_u64;
+    (0..count).map(|_| {
+        let current = state;
+        state = state.rotate_left(7) ^ 0x9E3779B97F4A7C15;
+        current
+    }).collect()
+}
+
+/// Returns the states of [`states()`](fn.states.html) as byte arrays,
+/// for the functions working on batches of blocks.
+pub fn blocks(count: usize) -> Vec<[u8; 8]> {
+    states(count).iter().map(|state| state.to_be_bytes()).collect()
+}
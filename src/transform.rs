@@ -0,0 +1,202 @@
+use std::io::{self, Read, Write};
+
+use mac::Cmac;
+use stream::{invalid_data, Decryptor, Encryptor, BUFFER_SIZE};
+
+/// A stage of a processing pipeline that turns bytes into bytes.
+///
+/// Data is fed in pieces of any size with [`update()`](#tymethod.update),
+/// and [`finalize()`](#tymethod.finalize) returns the output that is
+/// only known at the end, e.g. the padding of the last block. Stages are
+/// combined with [`chain()`](#method.chain), and any stage or chain can
+/// be run over a reader and a writer with
+/// [`transform_stream()`](fn.transform_stream.html).
+///
+/// # Examples
+///
+/// ```
+/// use present::{Block, Cmac, Encryptor, Key128Bit, OpMode, Transform};
+/// let key = Key128Bit::new([0x42; 16]);
+/// let mac_key = Key128Bit::new([0x17; 16]);
+/// let iv = Block::new(0x0123456789ABCDEF);
+///
+/// let mut pipeline = Encryptor::new(&key, &OpMode::CBC, &iv).chain(Cmac::new(&mac_key));
+/// let mut output = pipeline.update(b"encrypted, ");
+/// output.extend(pipeline.update(b"then authenticated"));
+/// output.extend(pipeline.finalize().unwrap());
+///
+/// // The ciphertext is followed by its 8-byte tag
+/// assert_eq!(output.len(), 40);
+/// ```
+pub trait Transform {
+    /// Processes the next piece of input and returns the output that
+    /// is already known.
+    fn update(&mut self, input: &[u8]) -> Vec<u8>;
+
+    /// Finishes processing and returns the remaining output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` of kind `InvalidData` if the input is
+    /// invalid for the stage, e.g. a ciphertext with corrupted padding.
+    fn finalize(self) -> io::Result<Vec<u8>> where Self: Sized;
+
+    /// Feeds the output of this stage into `next`.
+    fn chain<T: Transform>(self, next: T) -> Chain<Self, T> where Self: Sized {
+        Chain { first: self, second: next }
+    }
+}
+
+/// Two stages run one after the other, constructed with
+/// [`Transform::chain()`](trait.Transform.html#method.chain).
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Transform, B: Transform> Transform for Chain<A, B> {
+    fn update(&mut self, input: &[u8]) -> Vec<u8> {
+        let output = self.first.update(input);
+        self.second.update(&output)
+    }
+
+    fn finalize(self) -> io::Result<Vec<u8>> {
+        let Chain { first, mut second } = self;
+        let mut output = second.update(&first.finalize()?);
+        output.extend(second.finalize()?);
+        Ok(output)
+    }
+}
+
+impl Transform for Encryptor {
+    fn update(&mut self, input: &[u8]) -> Vec<u8> {
+        Encryptor::update(self, input)
+    }
+
+    fn finalize(self) -> io::Result<Vec<u8>> {
+        Ok(Encryptor::finalize(self))
+    }
+}
+
+impl Transform for Decryptor {
+    fn update(&mut self, input: &[u8]) -> Vec<u8> {
+        Decryptor::update(self, input)
+    }
+
+    /// Decryption errors are returned like in
+    /// [`DecryptReader`](struct.DecryptReader.html).
+    fn finalize(self) -> io::Result<Vec<u8>> {
+        Decryptor::finalize(self).map_err(invalid_data)
+    }
+}
+
+impl Transform for Cmac {
+    /// Passes the input on unchanged.
+    fn update(&mut self, input: &[u8]) -> Vec<u8> {
+        Cmac::update(self, input);
+        input.to_vec()
+    }
+
+    /// Returns the tag, so it follows the data it was computed over.
+    fn finalize(self) -> io::Result<Vec<u8>> {
+        Ok(Cmac::finalize(self).to_vec())
+    }
+}
+
+/// Runs a stage over all data of a reader and writes the output into
+/// a writer.
+///
+/// The data is read in chunks, so the same pipeline can process files,
+/// sockets or in-memory buffers of any size. Returns the number of
+/// bytes read.
+///
+/// # Examples
+///
+/// ```
+/// use present::{transform_stream, Block, Decryptor, Encryptor, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0x42; 10]);
+/// let iv = Block::new(0x0123456789ABCDEF);
+/// let plaintext = vec![0x5A; 100_000];
+///
+/// let mut ciphertext = Vec::new();
+/// let encryptor = Encryptor::new(&key, &OpMode::CBC, &iv);
+/// transform_stream(encryptor, &mut &plaintext[..], &mut ciphertext).unwrap();
+///
+/// let mut decrypted = Vec::new();
+/// let decryptor = Decryptor::new(&key, &OpMode::CBC, &iv);
+/// transform_stream(decryptor, &mut &ciphertext[..], &mut decrypted).unwrap();
+/// assert_eq!(decrypted, plaintext);
+/// ```
+pub fn transform_stream<T, R, W>(transform: T, reader: &mut R, writer: &mut W) -> io::Result<u64>
+    where T: Transform, R: Read, W: Write {
+
+    let mut transform = transform;
+    let mut buffer = [0u8; BUFFER_SIZE];
+    let mut total = 0u64;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        total += read as u64;
+        writer.write_all(&transform.update(&buffer[..read]))?;
+    }
+    writer.write_all(&transform.finalize()?)?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::Block;
+    use keys::Key80Bit;
+    use mac::cmac;
+    use modes::{all_modes, OpMode};
+
+    #[test]
+    fn test_stages_match_one_shot_functions() {
+        let key = Key80Bit::new([0x42; 10]);
+        let iv = Block::new(0x0123456789ABCDEF);
+        let plaintext: Vec<u8> = (0..10000u32).map(|i| (i * 3) as u8).collect();
+
+        for mode in all_modes().iter() {
+            let expected = ::try_encrypt_bytes_with_iv(&plaintext, &key, mode, &iv).unwrap();
+            let mut ciphertext = Vec::new();
+            let len = transform_stream(Encryptor::new(&key, mode, &iv), &mut &plaintext[..], &mut ciphertext).unwrap();
+            assert_eq!(len, plaintext.len() as u64);
+            assert_eq!(ciphertext, expected);
+
+            let mut decrypted = Vec::new();
+            transform_stream(Decryptor::new(&key, mode, &iv), &mut &ciphertext[..], &mut decrypted).unwrap();
+            assert_eq!(decrypted, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        let key = Key80Bit::new([0x42; 10]);
+        let mac_key = Key80Bit::new([0x17; 10]);
+        let iv = Block::new(0);
+        let plaintext = b"a message that spans several PRESENT blocks";
+
+        let mut pipeline = Encryptor::new(&key, &OpMode::PCBC, &iv).chain(Cmac::new(&mac_key));
+        let mut output = Vec::new();
+        for piece in plaintext.chunks(5) {
+            output.extend(pipeline.update(piece));
+        }
+        output.extend(pipeline.finalize().unwrap());
+
+        let (ciphertext, tag) = output.split_at(output.len() - 8);
+        assert_eq!(ciphertext, &::try_encrypt_bytes_with_iv(plaintext, &key, &OpMode::PCBC, &iv).unwrap()[..]);
+        assert_eq!(tag, cmac(&mac_key, ciphertext));
+    }
+
+    #[test]
+    fn test_invalid_ciphertext() {
+        let key = Key80Bit::new([0x42; 10]);
+        let err = transform_stream(Decryptor::new(&key, &OpMode::CBC, &Block::new(0)), &mut &[0u8; 12][..], &mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,160 @@
+use std::str;
+
+use block::Block;
+use errors::DecryptError;
+use keys::Key;
+use modes::{ModeOfOperation, OpMode};
+use decrypt_raw;
+
+/// Decrypt arbitrary binary data without revealing why it is invalid.
+///
+/// Works like [`decrypt_bytes`](fn.decrypt_bytes.html), but checks the
+/// padding in constant time and reports invalid padding as
+/// `DecryptError::Rejected`. Use this whenever the outcome of
+/// decryption can be observed by an attacker, e.g. by a server that
+/// answers differently to invalid requests: telling invalid padding
+/// apart from other failures allows a padding oracle attack, which
+/// recovers the plaintext of CBC ciphertexts.
+///
+/// This only hides the reason of the failure. If the ciphertext must
+/// not be modified at all, use authenticated encryption instead.
+///
+/// # Errors
+///
+/// Returns `DecryptError::Rejected` if the padding is invalid. Errors
+/// that only depend on the length of the ciphertext or the missing IV
+/// are returned as usual.
+///
+/// # Examples
+///
+/// ```
+/// use present::{decrypt_bytes_uniform, try_encrypt_bytes_with_iv, Block, DecryptError, Key80Bit, OpMode};
+/// let key = Key80Bit::new([0x42; 10]);
+/// let mut ciphertext = try_encrypt_bytes_with_iv(b"attack at dawn", &key, &OpMode::CBC, &Block::new(42)).unwrap();
+/// let plaintext = decrypt_bytes_uniform(&ciphertext, &key, &OpMode::CBC, Some(Block::new(42)));
+/// assert_eq!(plaintext.unwrap(), b"attack at dawn");
+///
+/// ciphertext[15] ^= 0x01;
+/// let err = decrypt_bytes_uniform(&ciphertext, &key, &OpMode::CBC, Some(Block::new(42))).unwrap_err();
+/// assert!(matches!(err, DecryptError::Rejected));
+/// ```
+pub fn decrypt_bytes_uniform<K: Key>(ciphertext: &[u8], key: &K, mode: &OpMode, init_vec: Option<Block>) -> Result<Vec<u8>, DecryptError> {
+    let (buffer, valid) = decrypt_unpadded(ciphertext, key, mode, init_vec)?;
+    if valid != 0xFF {
+        return Err(DecryptError::Rejected);
+    }
+    Ok(buffer)
+}
+
+/// Decrypt a string without revealing why it is invalid.
+///
+/// Works like [`decrypt_str`](fn.decrypt_str.html), but both invalid
+/// padding and invalid UTF-8 are reported as `DecryptError::Rejected`.
+/// See [`decrypt_bytes_uniform`](fn.decrypt_bytes_uniform.html) for
+/// details.
+///
+/// Only the padding is checked in constant time. The UTF-8 check always
+/// runs, but it stops at the first invalid byte, so its timing can
+/// reveal where the decrypted data stops being valid UTF-8. Do not rely
+/// on this function to hide the content of the plaintext from an
+/// attacker who can measure it.
+///
+/// # Errors
+///
+/// Returns `DecryptError::Rejected` if the padding or the encoding of
+/// the decrypted string is invalid. Errors that only depend on the
+/// length of the ciphertext or the missing IV are returned as usual.
+pub fn decrypt_str_uniform<K: Key>(ciphertext: &[u8], key: &K, mode: &OpMode, init_vec: Option<Block>) -> Result<String, DecryptError> {
+    let (buffer, valid) = decrypt_unpadded(ciphertext, key, mode, init_vec)?;
+    let utf8_valid = str::from_utf8(&buffer).is_ok();
+    if valid != 0xFF || !utf8_valid {
+        return Err(DecryptError::Rejected);
+    }
+    Ok(String::from_utf8(buffer).expect("Logic error! The string was checked before!"))
+}
+
+/// Decrypts the ciphertext and removes the padding.
+///
+/// Returns the plaintext and a mask that is `0xFF` if the padding is
+/// valid and `0x00` otherwise. If the padding is invalid, nothing is
+/// removed.
+fn decrypt_unpadded<K: Key>(ciphertext: &[u8], key: &K, mode: &OpMode, init_vec: Option<Block>) -> Result<(Vec<u8>, u8), DecryptError> {
+    let mut buffer = decrypt_raw(ciphertext, key, mode, init_vec)?;
+    if !mode.is_padded() {
+        return Ok((buffer, 0xFF));
+    }
+
+    let len = buffer.len();
+    let (pad, valid) = padding_len(&buffer[(len - 8)..]);
+    buffer.truncate(len - (pad & valid) as usize);
+    Ok((buffer, valid))
+}
+
+/// Checks the padding of the final block without branching on its
+/// value.
+///
+/// Accepts the same padding as `check_padding()`. Returns the padding
+/// length and a mask that is `0xFF` if the padding is valid and `0x00`
+/// otherwise.
+fn padding_len(final_block: &[u8]) -> (u8, u8) {
+    let pad = final_block[7];
+
+    // The masks are all ones if 8 < pad or pad == 0, i.e. the length is
+    // invalid
+    let mut invalid = ((8u16.wrapping_sub(pad as u16)) >> 8) as u8;
+    invalid |= ((pad as u16).wrapping_sub(1) >> 8) as u8;
+    for (i, byte) in final_block.iter().rev().enumerate() {
+        // All ones if the byte is part of the padding (i < pad)
+        let in_padding = ((i as u16).wrapping_sub(pad as u16) >> 8) as u8;
+        invalid |= in_padding & (byte ^ pad);
+    }
+
+    // Collapse any set bit into a full mask
+    let valid = ((invalid as u16).wrapping_sub(1) >> 8) as u8;
+    (pad, valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use check_padding;
+    use keys::Key80Bit;
+    use {decrypt_bytes, try_encrypt_bytes_with_iv};
+
+    #[test]
+    fn test_padding_len_matches_check_padding() {
+        for last in 0..=255u8 {
+            for filler in [0x00, 0x03, 0x08, last].iter() {
+                let mut block = [*filler; 8];
+                block[7] = last;
+                let (pad, valid) = padding_len(&block);
+                match check_padding(&block, 0) {
+                    Ok(expected) => assert_eq!((pad as usize, valid), (expected, 0xFF)),
+                    Err(_) => assert_eq!(valid, 0x00),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_padding_and_content_errors_are_uniform() {
+        let key = Key80Bit::new([0x42; 10]);
+        let iv = || Some(Block::new(0x0123456789ABCDEF));
+        let ciphertext = try_encrypt_bytes_with_iv(&[0xFF; 13], &key, &OpMode::CBC, &Block::new(0x0123456789ABCDEF)).unwrap();
+
+        // Invalid padding
+        let mut tampered = ciphertext.clone();
+        tampered[15] ^= 0x01;
+        assert!(matches!(decrypt_bytes(&tampered, &key, &OpMode::CBC, iv()), Err(DecryptError::InvalidPadding { .. })));
+        assert!(matches!(decrypt_bytes_uniform(&tampered, &key, &OpMode::CBC, iv()), Err(DecryptError::Rejected)));
+        assert!(matches!(decrypt_str_uniform(&tampered, &key, &OpMode::CBC, iv()), Err(DecryptError::Rejected)));
+
+        // Valid padding, but invalid UTF-8
+        assert_eq!(decrypt_bytes_uniform(&ciphertext, &key, &OpMode::CBC, iv()).unwrap(), vec![0xFF; 13]);
+        assert!(matches!(decrypt_str_uniform(&ciphertext, &key, &OpMode::CBC, iv()), Err(DecryptError::Rejected)));
+
+        // Length errors are not hidden
+        assert!(matches!(decrypt_str_uniform(&ciphertext[..12], &key, &OpMode::CBC, iv()),
+                         Err(DecryptError::CiphertextNotAligned { len: 12, .. })));
+    }
+}
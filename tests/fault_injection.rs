@@ -0,0 +1,109 @@
+extern crate present;
+
+use present::*;
+
+#[test]
+fn test_ciphertext_faults_never_go_unnoticed_cbc() {
+    let key = Key80Bit::new([0xA, 0xC0, 0xA6, 0xE7, 0x63, 0x26, 0xBC, 0x7E, 0x82, 0x80]);
+    let to_encrypt = b"faults in the ciphertext";
+    let (encrypted, iv) = encrypt_bytes(to_encrypt, &key, &OpMode::CBC).unwrap().into_parts();
+
+    let report = flip_ciphertext_bits(&encrypted, to_encrypt, |faulty| decrypt_bytes(faulty, &key, &OpMode::CBC, iv));
+    assert_eq!(report.injected, encrypted.len() * 8);
+    assert!(report.all_detectable());
+}
+
+#[cfg(feature = "insecure-modes")]
+#[allow(deprecated)]
+#[test]
+fn test_ciphertext_faults_never_go_unnoticed_ecb() {
+    let key = Key128Bit::new([0x3C; 16]);
+    let to_encrypt = b"faults in the ciphertext";
+    let (encrypted, _) = encrypt_bytes(to_encrypt, &key, &OpMode::ECB).unwrap().into_parts();
+
+    let report = flip_ciphertext_bits(&encrypted, to_encrypt, |faulty| decrypt_bytes(faulty, &key, &OpMode::ECB, None));
+    assert_eq!(report.injected, encrypted.len() * 8);
+    assert!(report.all_detectable());
+}
+
+#[test]
+fn test_init_vec_faults_never_go_unnoticed_cbc() {
+    let key = Key80Bit::new([0x23; 10]);
+    let to_encrypt = b"faults in the IV";
+    let (encrypted, iv) = encrypt_bytes(to_encrypt, &key, &OpMode::CBC).unwrap().into_parts();
+
+    let report = flip_init_vec_bits(&iv.unwrap().to_bytes(), to_encrypt, |faulty| {
+        let mut iv = [0u8; 8];
+        iv.copy_from_slice(faulty);
+        decrypt_bytes(&encrypted, &key, &OpMode::CBC, Some(Block::from_bytes(&iv)))
+    });
+    assert_eq!(report.injected, 64);
+    assert!(report.all_detectable());
+}
+
+#[test]
+fn test_tag_faults_are_rejected_eax() {
+    let key = Key128Bit::new([0x42; 16]);
+    let sealed = aead::seal(&key, b"nonce", b"header", b"faults in the tag");
+
+    let tag = (sealed.len() - aead::TAG_LEN)..sealed.len();
+    let report = flip_tag_bits(&sealed, tag, b"faults in the tag", |faulty| aead::open(&key, b"nonce", b"header", faulty));
+    assert_eq!(report.injected, 64);
+    assert!(report.all_rejected());
+
+    let report = flip_ciphertext_bits(&sealed, b"faults in the tag", |faulty| aead::open(&key, b"nonce", b"header", faulty));
+    assert!(report.all_rejected());
+}
+
+#[test]
+fn test_tag_faults_are_rejected_ccm() {
+    let ccm = Ccm::new(&Key128Bit::new([0x42; 16]), 5, TagLen::new(6).unwrap()).unwrap();
+    let sealed = ccm.encrypt(b"nonce", b"header", b"faults in the tag").unwrap();
+
+    let report = flip_tag_bits(&sealed, (sealed.len() - 6)..sealed.len(), b"faults in the tag", |faulty| ccm.decrypt(b"nonce", b"header", faulty));
+    assert_eq!(report.injected, 48);
+    assert!(report.all_rejected());
+
+    let report = flip_ciphertext_bits(&sealed, b"faults in the tag", |faulty| ccm.decrypt(b"nonce", b"header", faulty));
+    assert!(report.all_rejected());
+}
+
+#[test]
+fn test_tag_faults_are_rejected_ocb() {
+    let ocb = Ocb::new(&Key128Bit::new([0x42; 16]), TagLen::FULL);
+    let sealed = ocb.encrypt(b"nonce", b"header", b"faults in the tag").unwrap();
+
+    let report = flip_tag_bits(&sealed, (sealed.len() - 8)..sealed.len(), b"faults in the tag", |faulty| ocb.decrypt(b"nonce", b"header", faulty));
+    assert_eq!(report.injected, 64);
+    assert!(report.all_rejected());
+
+    let report = flip_ciphertext_bits(&sealed, b"faults in the tag", |faulty| ocb.decrypt(b"nonce", b"header", faulty));
+    assert!(report.all_rejected());
+}
+
+#[test]
+fn test_tag_faults_are_rejected_siv() {
+    let siv = Siv::new(&Key128Bit::new([0x11; 16]), &Key128Bit::new([0x22; 16]));
+    let sealed = siv.encrypt(b"nonce", b"header", b"faults in the tag");
+
+    // The synthetic IV in front doubles as the tag
+    let report = flip_tag_bits(&sealed, 0..8, b"faults in the tag", |faulty| siv.decrypt(b"nonce", b"header", faulty));
+    assert_eq!(report.injected, 64);
+    assert!(report.all_rejected());
+
+    let report = flip_ciphertext_bits(&sealed, b"faults in the tag", |faulty| siv.decrypt(b"nonce", b"header", faulty));
+    assert!(report.all_rejected());
+}
+
+#[test]
+fn test_tag_faults_are_rejected_etm() {
+    let key = Key80Bit::new([0x42; 10]);
+    let framed = encrypt_then_mac(b"faults in the tag", &key).unwrap();
+
+    let report = flip_tag_bits(&framed, (framed.len() - 8)..framed.len(), b"faults in the tag", |faulty| verify_then_decrypt(faulty, &key));
+    assert_eq!(report.injected, 64);
+    assert!(report.all_rejected());
+
+    let report = flip_ciphertext_bits(&framed, b"faults in the tag", |faulty| verify_then_decrypt(faulty, &key));
+    assert!(report.all_rejected());
+}
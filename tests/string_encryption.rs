@@ -2,31 +2,35 @@ extern crate present;
 
 use present::*;
 
+#[cfg(feature = "insecure-modes")]
+#[allow(deprecated)]
 #[test]
 fn test_encryption_ecb() {
     let key = Key80Bit::new([0xA, 0xC0, 0xA6, 0xE7, 0x63, 0x26, 0xBC, 0x7E, 0x82, 0x80]);
     let op_mode = OpMode::ECB;
 
     let to_encrypt = "this is a test string →in UTF8←";
-    let (encrypted, iv) = encrypt_str(to_encrypt, &key, &op_mode);
+    let (encrypted, iv) = encrypt_str(to_encrypt, &key, &op_mode).unwrap().into_parts();
     assert_eq!(encrypted.len(), 40);
     assert!(iv.is_none());
     let decrypt_result = decrypt_str(&encrypted, &key, &op_mode, None);
     assert_eq!(decrypt_result.unwrap(), to_encrypt);
 
     let to_encrypt = "ö";
-    let (encrypted, iv) = encrypt_str(to_encrypt, &key, &op_mode);
+    let (encrypted, iv) = encrypt_str(to_encrypt, &key, &op_mode).unwrap().into_parts();
     assert_eq!(encrypted.len(), 8);
     assert!(iv.is_none());
     let decrypt_result = decrypt_str(&encrypted, &key, &op_mode, None);
     assert_eq!(decrypt_result.unwrap(), to_encrypt);
 }
 
+#[cfg(feature = "insecure-modes")]
+#[allow(deprecated)]
 #[test]
 #[should_panic]
 fn test_encryption_fails_with_differing_keys_ecb() {
     let to_encrypt = "foo bar baz ²³";
-    let (encrypted, _) = encrypt_str(to_encrypt, &Key80Bit::new([0xAB; 10]), &OpMode::ECB);
+    let (encrypted, _) = encrypt_str(to_encrypt, &Key80Bit::new([0xAB; 10]), &OpMode::ECB).unwrap().into_parts();
     let decrypt_result = decrypt_str(&encrypted, &Key80Bit::new([0xAC; 10]), &OpMode::ECB, None);
     assert_eq!(decrypt_result.unwrap(), to_encrypt);
 }
@@ -37,14 +41,14 @@ fn test_encryption_cbc() {
     let op_mode = OpMode::CBC;
 
     let to_encrypt = "this is a test string →in UTF8←";
-    let (encrypted, iv) = encrypt_str(to_encrypt, &key, &op_mode);
+    let (encrypted, iv) = encrypt_str(to_encrypt, &key, &op_mode).unwrap().into_parts();
     assert_eq!(encrypted.len(), 40);
     assert!(iv.is_some());
     let decrypt_result = decrypt_str(&encrypted, &key, &op_mode, iv);
     assert_eq!(decrypt_result.unwrap(), to_encrypt);
 
     let to_encrypt = "ö";
-    let (encrypted, iv) = encrypt_str(to_encrypt, &key, &op_mode);
+    let (encrypted, iv) = encrypt_str(to_encrypt, &key, &op_mode).unwrap().into_parts();
     assert_eq!(encrypted.len(), 8);
     assert!(iv.is_some());
     let decrypt_result = decrypt_str(&encrypted, &key, &op_mode, iv);
@@ -55,7 +59,7 @@ fn test_encryption_cbc() {
 #[should_panic]
 fn test_encryption_fails_with_differing_keys_cbc() {
     let to_encrypt = "foo bar baz ²³";
-    let (encrypted, iv) = encrypt_str(to_encrypt, &Key80Bit::new([0xAB; 10]), &OpMode::CBC);
+    let (encrypted, iv) = encrypt_str(to_encrypt, &Key80Bit::new([0xAB; 10]), &OpMode::CBC).unwrap().into_parts();
     let decrypt_result = decrypt_str(&encrypted, &Key80Bit::new([0xAC; 10]), &OpMode::CBC, iv);
     assert_eq!(decrypt_result.unwrap(), to_encrypt);
 }
@@ -65,7 +69,58 @@ fn test_encryption_fails_with_differing_keys_cbc() {
 fn test_encryption_fails_with_wrong_iv_cbc() {
     let to_encrypt = "foo bar baz ²³";
     let key = Key80Bit::new([0x23; 10]);
-    let (encrypted, _) = encrypt_str(to_encrypt, &key, &OpMode::CBC);
+    let (encrypted, _) = encrypt_str(to_encrypt, &key, &OpMode::CBC).unwrap().into_parts();
     let decrypt_result = decrypt_str(&encrypted, &key, &OpMode::CBC, Some(Block::new(0u64)));
     assert_eq!(decrypt_result.unwrap(), to_encrypt);
 }
+
+#[test]
+fn test_encryption_pcbc() {
+    let key = Key80Bit::new([0xA, 0xC0, 0xA6, 0xE7, 0x63, 0x26, 0xBC, 0x7E, 0x82, 0x80]);
+    let op_mode = OpMode::PCBC;
+    let iv = 0x0123456789ABCDEFu64;
+
+    // Two full blocks plus a block of padding
+    let to_encrypt = "pcbc mode test!!";
+    let encrypted = try_encrypt_str_with_iv(to_encrypt, &key, &op_mode, &Block::new(iv)).unwrap();
+    assert_eq!(encrypted.len(), 24);
+
+    let mut plain = [0u64; 3];
+    for (i, chunk) in to_encrypt.as_bytes().chunks(8).enumerate() {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(chunk);
+        plain[i] = u64::from_be_bytes(bytes);
+    }
+    plain[2] = 0x0808080808080808;
+
+    let mut feedback = iv;
+    for (i, p) in plain.iter().enumerate() {
+        let mut block = Block::new(p ^ feedback);
+        block.encrypt(&key);
+        assert_eq!(&encrypted[i * 8..i * 8 + 8], &block.to_bytes());
+        feedback = p ^ block.get_state();
+    }
+
+    let decrypt_result = decrypt_str(&encrypted, &key, &op_mode, Some(Block::new(iv)));
+    assert_eq!(decrypt_result.unwrap(), to_encrypt);
+}
+
+#[test]
+fn test_errors_propagate_pcbc() {
+    let key = Key80Bit::new([0x23; 10]);
+    let iv = Block::new(0xFEDCBA9876543210);
+    let plaintext = [0x5Au8; 32];
+    let mut encrypted = try_encrypt_bytes_with_iv(&plaintext, &key, &OpMode::PCBC, &iv).unwrap();
+    encrypted[0] ^= 0x01;
+
+    // The corrupted first block garbles every following block, so the
+    // padding check fails
+    let decrypt_result = decrypt_bytes(&encrypted, &key, &OpMode::PCBC, Some(Block::new(iv.get_state())));
+    assert!(decrypt_result.is_err());
+
+    // With CBC, only the first two blocks would be affected
+    let mut encrypted = try_encrypt_bytes_with_iv(&plaintext, &key, &OpMode::CBC, &iv).unwrap();
+    encrypted[0] ^= 0x01;
+    let decrypted = decrypt_bytes(&encrypted, &key, &OpMode::CBC, Some(iv)).unwrap();
+    assert_eq!(&decrypted[16..], &plaintext[16..]);
+}